@@ -0,0 +1,170 @@
+use crate::{
+    discover, download::Downloader, expression, history, pack, raw, repository::Repository,
+};
+use log::{info, warn};
+use pathbuf::pathbuf;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Largest file scanned for abbreviated object IDs.
+const MAX_SCAN: u64 = 1 << 20;
+
+/// An abbreviated object ID found in the dump and an object it expanded to.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Expansion {
+    pub abbreviation: String,
+    pub id: String,
+    /// The file the abbreviation was found in, or the commit whose message mentions it.
+    pub source: String,
+}
+
+/// Returns the abbreviated object IDs in text, leaving out runs of hex digits that are more
+/// likely numbers or words, as they lack either a digit or a letter.
+pub fn find(text: &str) -> BTreeSet<&str> {
+    expression::ABBREVIATED
+        .find_iter(text)
+        .map(|m| m.as_str())
+        .filter(|id| id.bytes().any(|b| b.is_ascii_digit()))
+        .filter(|id| id.bytes().any(|b| b.is_ascii_alphabetic()))
+        .collect()
+}
+
+/// Returns the abbreviations found in the files of the dump and the recovered commit messages,
+/// each with the first place it was found in.
+fn collect(repo: Option<&Repository>) -> BTreeMap<String, String> {
+    let mut found = BTreeMap::new();
+    let objects = Path::new(".").join(".git").join("objects");
+    let index = Path::new(".").join(".git").join("index");
+    let raw = Path::new(".").join(raw::RAW_DIR);
+    let files = WalkDir::new(".")
+        .into_iter()
+        .filter_entry(|entry| entry.path() != objects && entry.path() != raw)
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.path() != index)
+        .filter(|entry| entry.metadata().is_ok_and(|m| m.len() <= MAX_SCAN));
+    for entry in files {
+        let Ok(data) = std::fs::read(entry.path()) else {
+            continue;
+        };
+        let source = entry.path().strip_prefix(".").unwrap_or(entry.path());
+        for id in find(&String::from_utf8_lossy(&data)) {
+            found
+                .entry(id.to_string())
+                .or_insert_with(|| source.display().to_string());
+        }
+    }
+    let commits = repo.map(history::recover).transpose().unwrap_or_else(|e| {
+        warn!("Failed to read the recovered commits: {e}");
+        None
+    });
+    for (commit_id, commit) in commits.unwrap_or_default() {
+        for id in find(&commit.message) {
+            found
+                .entry(id.to_string())
+                .or_insert_with(|| format!("commit {commit_id}"));
+        }
+    }
+    found
+}
+
+/// Returns the IDs of the objects the dump holds or its pack indexes list, even those whose
+/// pack could not be downloaded.
+fn known(repo: Option<&Repository>) -> BTreeSet<String> {
+    let mut known: BTreeSet<String> = repo
+        .and_then(|repo| repo.objects().ok())
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let pack_dir = pathbuf![".git", "objects", "pack"];
+    for entry in WalkDir::new(pack_dir).into_iter().filter_map(|e| e.ok()) {
+        if entry.path().extension().is_some_and(|ext| ext == "idx") {
+            known.extend(pack::parse(entry.path()).unwrap_or_default());
+        }
+    }
+    known
+}
+
+/// Returns the IDs starting with prefix.
+fn completions<'a>(
+    known: &'a BTreeSet<String>,
+    prefix: &'a str,
+) -> impl Iterator<Item = &'a String> {
+    known
+        .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+        .take_while(move |id| id.starts_with(prefix))
+}
+
+/// Expands the abbreviated object IDs mentioned in the dump, such as those in saved CI logs,
+/// error pages and commit messages, into full IDs by matching them against the objects the dump
+/// and its pack indexes know of and against the loose objects listed in `objects/<xx>/`, which
+/// some servers list even when they refuse to list the .git directory. The objects they expand to
+/// are then downloaded along with everything they reach.
+pub async fn expand(download: &Downloader) -> Vec<Expansion> {
+    let repo = Repository::open(".").ok();
+    let found = collect(repo.as_ref());
+    if found.is_empty() {
+        return Vec::new();
+    }
+    info!("Found {} abbreviated object IDs", found.len());
+    let mut known = known(repo.as_ref());
+    let directories: BTreeSet<_> = found.keys().map(|id| &id[..2]).collect();
+    for directory in directories {
+        let href = format!(".git/objects/{directory}");
+        let links = download.collect_links(&href).await.unwrap_or_default();
+        known.extend(links.iter().filter_map(|link| {
+            let name = link.rsplit('/').next()?;
+            let id = format!("{directory}{name}");
+            (matches!(id.len(), 40 | 64) && id.bytes().all(|b| b.is_ascii_hexdigit())).then_some(id)
+        }));
+    }
+
+    let mut expansions = Vec::new();
+    for (abbreviation, source) in &found {
+        for id in completions(&known, abbreviation) {
+            expansions.push(Expansion {
+                abbreviation: abbreviation.clone(),
+                id: id.clone(),
+                source: source.clone(),
+            });
+        }
+    }
+    let ids: BTreeSet<_> = expansions.iter().map(|e| e.id.clone()).collect();
+    let expanded: BTreeSet<_> = expansions.iter().map(|e| &e.abbreviation).collect();
+    info!(
+        "Expanded {} of {} abbreviations into {} objects",
+        expanded.len(),
+        found.len(),
+        ids.len()
+    );
+    let fetched = discover::reachable(download, ids.into_iter().collect()).await;
+    info!("Downloaded {fetched} missing objects they name or reach");
+    expansions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_abbreviations() {
+        let log = "HEAD is now at 1a410ef Fix the build\nbuild 1700000000 took deadbeef ms, see 9fceb02d0ae";
+        assert_eq!(
+            find(log).into_iter().collect::<Vec<_>>(),
+            ["1a410ef", "9fceb02d0ae"]
+        );
+        assert!(find("1a410efbd13591db07496601ebc7a059dd55cfe9").is_empty());
+
+        let known: BTreeSet<String> = [
+            "1a410efbd13591db07496601ebc7a059dd55cfe9",
+            "1a410ef000000000000000000000000000000000",
+            "1a410f0000000000000000000000000000000000",
+        ]
+        .map(str::to_string)
+        .into();
+        assert_eq!(completions(&known, "1a410ef").count(), 2);
+        assert_eq!(completions(&known, "1a410efb").count(), 1);
+    }
+}
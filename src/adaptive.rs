@@ -0,0 +1,152 @@
+use crate::{
+    middleware::{Middleware, Next},
+    transport::{Request, Response},
+};
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use log::debug;
+use reqwest::StatusCode;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+
+/// A response slower than this multiple of the smoothed latency counts as a latency spike.
+const SPIKE_FACTOR: f64 = 2.0;
+
+/// Weight of the newest sample in the smoothed latency.
+const SMOOTHING: f64 = 0.2;
+
+struct Window {
+    /// Number of requests allowed in flight, fractional so it can grow by less than one at a time.
+    limit: f64,
+    in_flight: usize,
+    /// Exponentially weighted moving average of the latency.
+    latency: Option<Duration>,
+    last_decrease: Instant,
+}
+
+/// Adjusts the number of requests in flight with additive increase, multiplicative decrease:
+/// the limit grows by about one per round of healthy responses, up to the number of jobs, and
+/// halves when the target answers with 429 or 5xx, fails to answer or slows down sharply.
+pub struct AdaptiveConcurrency {
+    max: usize,
+    window: Mutex<Window>,
+    released: Notify,
+}
+
+/// Returns true if a response suggests the target is overloaded or throttling us.
+fn is_overloaded(response: &Result<Response>) -> bool {
+    match response {
+        Ok(response) => {
+            let status = response.status();
+            status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+        }
+        Err(_) => true,
+    }
+}
+
+/// Releases the slot of a request once it completes or is cancelled.
+struct Slot<'a>(&'a AdaptiveConcurrency);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.window.lock().unwrap().in_flight -= 1;
+        self.0.released.notify_waiters();
+    }
+}
+
+impl AdaptiveConcurrency {
+    /// Starts out allowing max requests in flight, which is also the most it ever allows.
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        AdaptiveConcurrency {
+            max,
+            window: Mutex::new(Window {
+                limit: max as f64,
+                in_flight: 0,
+                latency: None,
+                last_decrease: Instant::now(),
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Waits until another request may be sent.
+    async fn acquire(&self) -> Slot<'_> {
+        loop {
+            // Registered before checking so a release in between is not missed.
+            let released = self.released.notified();
+            {
+                let mut window = self.window.lock().unwrap();
+                if window.in_flight < window.limit as usize {
+                    window.in_flight += 1;
+                    return Slot(self);
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Updates the limit after a response that took latency to arrive.
+    fn record(&self, latency: Duration, overloaded: bool) {
+        let mut window = self.window.lock().unwrap();
+        let smoothed = window.latency.unwrap_or(latency);
+        let spiked = latency.as_secs_f64() > smoothed.as_secs_f64() * SPIKE_FACTOR;
+        window.latency = Some(smoothed.mul_f64(1.0 - SMOOTHING) + latency.mul_f64(SMOOTHING));
+
+        if overloaded || spiked {
+            // Responses to requests sent before the last decrease reflect the old limit.
+            if window.last_decrease.elapsed() >= smoothed {
+                window.limit = (window.limit / 2.0).max(1.0);
+                window.last_decrease = Instant::now();
+                debug!(
+                    "Backing off to {} concurrent requests",
+                    window.limit as usize
+                );
+            }
+        } else {
+            window.limit = (window.limit + 1.0 / window.limit).min(self.max as f64);
+        }
+        drop(window);
+        self.released.notify_waiters();
+    }
+}
+
+impl Middleware for AdaptiveConcurrency {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            let _slot = self.acquire().await;
+            let start = Instant::now();
+            let response = next.run(request).await;
+            self.record(start.elapsed(), is_overloaded(&response));
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(limiter: &AdaptiveConcurrency) -> usize {
+        limiter.window.lock().unwrap().limit as usize
+    }
+
+    #[test]
+    fn halves_on_overload_and_recovers() {
+        let limiter = AdaptiveConcurrency::new(8);
+        let latency = Duration::ZERO;
+        limiter.record(latency, true);
+        assert_eq!(limit(&limiter), 4);
+        limiter.record(latency, true);
+        assert_eq!(limit(&limiter), 2);
+        for _ in 0..20 {
+            limiter.record(latency, false);
+        }
+        assert!(limit(&limiter) > 2);
+        for _ in 0..1000 {
+            limiter.record(latency, false);
+        }
+        assert_eq!(limit(&limiter), 8);
+    }
+}
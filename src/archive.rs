@@ -0,0 +1,367 @@
+use crate::encoding;
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use std::path::PathBuf;
+
+/// Archives commonly left next to a .git directory, tried in order with --try-archives.
+pub const CANDIDATES: &[&str] = &[
+    ".git.zip",
+    ".git.tar.gz",
+    ".git.tgz",
+    ".git.tar",
+    "git.zip",
+    "git.tar.gz",
+    "backup.zip",
+    "backup.tar.gz",
+    "site-backup.zip",
+    "site-backup.tar.gz",
+    "site.zip",
+    "site.tar.gz",
+    "www.zip",
+    "www.tar.gz",
+    "www.rar",
+];
+
+/// Signature of the record ending the central directory of a zip archive.
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+/// Signature of an entry of the central directory of a zip archive.
+const CENTRAL_FILE_HEADER: u32 = 0x0201_4b50;
+
+/// Signature of the header preceding the data of a file in a zip archive.
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+
+/// Most bytes a file of an archive is inflated to, against archives made to exhaust memory.
+const MAX_FILE_SIZE: usize = 1 << 30;
+
+/// Most bytes all the files of an archive add up to, against archives of many large files made to
+/// fill the disk.
+const MAX_TOTAL_SIZE: u64 = 4 << 30;
+
+/// A file of an archive.
+pub struct Entry {
+    /// Path of the file in the archive.
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn slice(data: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| eyre!("it is truncated"))
+}
+
+/// Adds the len bytes of a file to the total read from an archive so far, failing once it passes
+/// max_total.
+fn add_size(total: &mut u64, len: usize, max_total: u64) -> Result<()> {
+    *total += len as u64;
+    if *total > max_total {
+        bail!("its files add up to more than {max_total} bytes");
+    }
+    Ok(())
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<usize> {
+    Ok(u16::from_le_bytes(slice(data, offset, 2)?.try_into()?) as usize)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(slice(data, offset, 4)?.try_into()?))
+}
+
+/// Returns true if data starts like a zip archive.
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(&LOCAL_FILE_HEADER.to_le_bytes())
+}
+
+/// Reads the files of a zip archive, stored or deflated, leaving out its directories.
+pub fn unzip(data: &[u8]) -> Result<Vec<Entry>> {
+    unzip_within(data, MAX_TOTAL_SIZE)
+}
+
+/// Reads the files of a zip archive as long as they add up to at most max_total bytes.
+fn unzip_within(data: &[u8], max_total: u64) -> Result<Vec<Entry>> {
+    // The record is 22 bytes long, followed by a comment of up to 64 KiB.
+    let last = data
+        .len()
+        .checked_sub(22)
+        .ok_or_else(|| eyre!("it is truncated"))?;
+    let end = (last.saturating_sub(0xffff)..=last)
+        .rev()
+        .find(|&offset| u32_at(data, offset).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| eyre!("it has no end of central directory record"))?;
+    let count = u16_at(data, end + 10)?;
+    let mut offset = u32_at(data, end + 16)? as usize;
+    if count == 0xffff || offset == u32::MAX as usize {
+        bail!("it is a ZIP64 archive, which is not supported");
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    let mut total = 0;
+    for _ in 0..count {
+        if u32_at(data, offset)? != CENTRAL_FILE_HEADER {
+            bail!("its central directory is corrupt");
+        }
+        let method = u16_at(data, offset + 10)?;
+        let compressed = u32_at(data, offset + 20)? as usize;
+        let name_len = u16_at(data, offset + 28)?;
+        let local = u32_at(data, offset + 42)? as usize;
+        let name = String::from_utf8_lossy(slice(data, offset + 46, name_len)?).into_owned();
+        offset += 46 + name_len + u16_at(data, offset + 30)? + u16_at(data, offset + 32)?;
+        if name.ends_with('/') {
+            continue;
+        }
+        if u32_at(data, local)? != LOCAL_FILE_HEADER {
+            bail!("the header of {name} is corrupt");
+        }
+        let start = local + 30 + u16_at(data, local + 26)? + u16_at(data, local + 28)?;
+        let raw = slice(data, start, compressed)?;
+        let data = match method {
+            0 => raw.to_vec(),
+            8 => miniz_oxide::inflate::decompress_to_vec_with_limit(raw, MAX_FILE_SIZE)
+                .map_err(|e| eyre!("{name} does not inflate: {e:?}"))?,
+            _ => bail!("{name} is compressed with the unsupported method {method}"),
+        };
+        add_size(&mut total, data.len(), max_total)?;
+        entries.push(Entry { name, data });
+    }
+    Ok(entries)
+}
+
+/// Returns true if data holds a tar archive, whose first header carries the ustar magic.
+pub fn is_tar(data: &[u8]) -> bool {
+    data.get(257..262) == Some(b"ustar")
+}
+
+/// Parses an octal number of a tar header, padded with spaces or NULs.
+fn octal(field: &[u8]) -> Result<usize> {
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    usize::from_str_radix(text, 8).map_err(|_| eyre!("it has an invalid size {text:?}"))
+}
+
+/// Returns the text of a NUL-terminated field of a tar header.
+fn field(data: &[u8]) -> String {
+    let end = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+/// Reads the regular files of a tar archive, with the long names of GNU and pax archives.
+pub fn untar(data: &[u8]) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut long_name = None;
+    let mut offset = 0;
+    let mut total = 0;
+    while let Some(header) = data.get(offset..offset + 512) {
+        // Two empty blocks end the archive.
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let size = octal(&header[124..136])?;
+        let content = slice(data, offset + 512, size)?;
+        offset += 512 + size.div_ceil(512) * 512;
+        let mut name = field(&header[..100]);
+        let prefix = field(&header[345..500]);
+        if header[257..262] == *b"ustar" && !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+        match header[156] {
+            b'L' => long_name = Some(field(content)),
+            b'x' => {
+                long_name = String::from_utf8_lossy(content).lines().find_map(|line| {
+                    let (_, record) = line.split_once(' ')?;
+                    record.strip_prefix("path=").map(str::to_string)
+                })
+            }
+            b'0' | 0 => {
+                add_size(&mut total, content.len(), MAX_TOTAL_SIZE)?;
+                entries.push(Entry {
+                    name: long_name.take().unwrap_or(name),
+                    data: content.to_vec(),
+                })
+            }
+            _ => long_name = None,
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns true if data starts like a RAR archive, which is recognized but cannot be read.
+fn is_rar(data: &[u8]) -> bool {
+    data.starts_with(b"Rar!\x1a\x07")
+}
+
+/// Reads the files of the archive in data, telling its format from its content: zip, tar or a
+/// gzipped tar.
+pub fn read(data: &[u8]) -> Result<Vec<Entry>> {
+    if is_zip(data) {
+        unzip(data)
+    } else if is_tar(data) {
+        untar(data)
+    } else if data.starts_with(&encoding::GZIP_MAGIC) {
+        let data = encoding::gunzip(data)?;
+        if !is_tar(&data) {
+            bail!("it is gzipped but holds no tar archive");
+        }
+        untar(&data)
+    } else if is_rar(data) {
+        bail!("it is a RAR archive, extract it with unrar to recover the .git directory")
+    } else {
+        bail!("it is not an archive opendotgit can read")
+    }
+}
+
+/// Splits the name of a file of an archive into the directory holding its .git directory and its
+/// path within, such as `site` and `HEAD` for `site/.git/HEAD`. Paths that could escape the
+/// output directory are refused.
+fn split_git_path(name: &str) -> Option<(String, String)> {
+    let components: Vec<&str> = name.split(['/', '\\']).collect();
+    let index = components
+        .iter()
+        .position(|&component| component == ".git")?;
+    let rest = &components[index + 1..];
+    if rest.is_empty()
+        || rest
+            .iter()
+            .any(|&component| matches!(component, "" | "." | "..") || component.contains(':'))
+    {
+        return None;
+    }
+    Some((components[..index].join("/"), rest.join("/")))
+}
+
+/// Writes the files of the outermost .git directory among entries into the current directory,
+/// returning how many were written. The .git directories of nested repositories are left out.
+pub fn extract_git_dir(entries: Vec<Entry>) -> Result<usize> {
+    let files: Vec<_> = entries
+        .into_iter()
+        .filter_map(|entry| split_git_path(&entry.name).map(|split| (split, entry.data)))
+        .collect();
+    let Some(root) = files
+        .iter()
+        .map(|((root, _), _)| root)
+        .min_by_key(|root| (root.matches('/').count(), root.len()))
+        .cloned()
+    else {
+        return Ok(0);
+    };
+
+    let mut written = 0;
+    for ((_, path), data) in files.into_iter().filter(|((dir, _), _)| *dir == root) {
+        let path: PathBuf = [".git", &path].iter().collect();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, data).wrap_err(format!("Failed to write {}", path.display()))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a zip archive of stored files.
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut central = Vec::new();
+        for (name, content) in files {
+            let local = data.len() as u32;
+            let mut header = Vec::new();
+            header.extend(0u16.to_le_bytes()); // compression method
+            header.extend([0; 8]); // time, date and CRC-32, which are not checked
+            header.extend((content.len() as u32).to_le_bytes());
+            header.extend((content.len() as u32).to_le_bytes());
+            header.extend((name.len() as u16).to_le_bytes());
+
+            data.extend(LOCAL_FILE_HEADER.to_le_bytes());
+            data.extend([20, 0, 0, 0]); // version and flags
+            data.extend(&header);
+            data.extend(0u16.to_le_bytes()); // extra field length
+            data.extend(name.as_bytes());
+            data.extend(*content);
+
+            central.extend(CENTRAL_FILE_HEADER.to_le_bytes());
+            central.extend([20, 0, 20, 0, 0, 0]); // versions and flags
+            central.extend(&header);
+            central.extend([0; 12]); // extra and comment lengths, disk, attributes
+            central.extend(local.to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+        let offset = data.len() as u32;
+        data.extend(&central);
+        data.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        data.extend([0; 4]); // disk numbers
+        data.extend((files.len() as u16).to_le_bytes());
+        data.extend((files.len() as u16).to_le_bytes());
+        data.extend((central.len() as u32).to_le_bytes());
+        data.extend(offset.to_le_bytes());
+        data.extend([0; 2]); // comment length
+        data
+    }
+
+    #[test]
+    fn reads_zip_archives() {
+        let data = zip(&[
+            ("site/index.html", b"<html>"),
+            ("site/.git/HEAD", b"ref: refs/heads/main\n"),
+        ]);
+        assert!(is_zip(&data));
+        let entries = read(&data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "site/.git/HEAD");
+        assert_eq!(entries[1].data, b"ref: refs/heads/main\n");
+        assert!(read(b"<html>Not Found</html>").is_err());
+        assert!(unzip_within(&data, 27).is_ok());
+        assert!(unzip_within(&data, 26).is_err());
+    }
+
+    #[test]
+    fn reads_tar_archives() {
+        let mut data = Vec::new();
+        for (name, content) in [
+            ("www/.git/HEAD", &b"ref: refs/heads/main\n"[..]),
+            ("www/a", b""),
+        ] {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+            header[156] = b'0';
+            header[257..263].copy_from_slice(b"ustar\0");
+            data.extend(header);
+            data.extend(content);
+            data.resize(data.len().div_ceil(512) * 512, 0);
+        }
+        data.extend([0; 1024]);
+        let entries = read(&data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "www/.git/HEAD");
+        assert_eq!(entries[0].data, b"ref: refs/heads/main\n");
+        assert!(read(b"Rar!\x1a\x07\x01\x00").is_err());
+    }
+
+    #[test]
+    fn finds_git_directories() {
+        assert_eq!(
+            split_git_path("site/.git/refs/heads/main"),
+            Some(("site".to_string(), "refs/heads/main".to_string()))
+        );
+        assert_eq!(
+            split_git_path(".git/HEAD"),
+            Some((String::new(), "HEAD".to_string()))
+        );
+        assert_eq!(split_git_path("site/.git/"), None);
+        assert_eq!(split_git_path(".git/../../etc/passwd"), None);
+        assert_eq!(split_git_path("site/index.html"), None);
+    }
+}
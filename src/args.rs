@@ -1,37 +1,517 @@
-use clap::{ArgAction::Count, Parser};
+use crate::{
+    config::Config, evasion::Profile, export::StreamFormat, formats::Format, hosts::Rule, listing,
+    pipeline::Stage, scope::Glob, session::PreRequest, template::PathTemplate, throttle::Delay,
+};
+use clap::{ArgAction::Count, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use color_eyre::Result;
+use reqwest::header::{HeaderName, HeaderValue};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use tokio::time::Duration;
 use url::Url;
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(
+    author,
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub dump: Option<Args>,
+
+    /// Turn debugging information on
+    #[arg(short, long, action = Count, global = true)]
+    pub verbose: u8,
+
+    /// Mask the hosts and IP addresses of the targets in logs and reports
+    #[arg(long, global = true)]
+    pub redact: bool,
+
+    /// File mapping the masks of --redact back to the hosts they stand for
+    #[arg(
+        long,
+        value_name = "FILE",
+        global = true,
+        default_value = "opendotgit-redactions.json"
+    )]
+    pub redaction_map: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Dump a repository, the default when no subcommand is given
+    Dump(Box<Args>),
+
+    /// Check whether a URL exposes a .git directory without downloading it
+    Check(Box<CheckArgs>),
+
+    /// Dump every target from the command line or a file, each into its own directory
+    Scan(Box<ScanArgs>),
+
+    /// Post-process an existing dump: recover deleted files, find secrets and write a report
+    Extract(ExtractArgs),
+
+    /// Print the objects of a pack or pack index, to debug incomplete dumps
+    Pack {
+        #[command(subcommand)]
+        command: PackCommand,
+    },
+
+    /// Inspect the loose objects of a dump, even one git refuses to open
+    Objects {
+        #[command(subcommand)]
+        command: ObjectsCommand,
+    },
+
+    /// Replace this binary with the latest release once its signature checks out
+    SelfUpdate(SelfUpdateArgs),
+
+    /// Print a completion script for the given shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the man page
+    Man,
+}
+
+#[derive(clap::Args, Debug)]
 pub struct Args {
     /// URL of the .git directory
     pub url: Url,
 
     /// Directory to output the results
+    #[arg(required_unless_present = "to_stdout", conflicts_with = "to_stdout")]
+    pub output: Option<String>,
+
+    /// Dump into a temporary directory and write it to standard output in this format instead, logging to standard error
+    #[arg(long, value_name = "FORMAT", conflicts_with = "tui")]
+    pub to_stdout: Option<StreamFormat>,
+
+    #[command(flatten)]
+    pub options: DumpOptions,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SelfUpdateArgs {
+    /// Only report whether a newer release is available
+    #[arg(long)]
+    pub check: bool,
+
+    /// Minisign public key the release must be signed with, instead of the one built in
+    #[arg(long, value_name = "KEY")]
+    pub public_key: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CheckArgs {
+    /// URL of the .git directory
+    pub url: Url,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ScanArgs {
+    /// Directory to create a subdirectory per target in
     pub output: String,
 
-    /// Number of asynchronous jobs to spawn
+    /// URLs of the .git directories
+    pub urls: Vec<Url>,
+
+    /// File listing additional targets, one per line, each a URL or a JSON object overriding the headers, proxy, user agent, rate or delay for that target
+    #[arg(short = 'i', long, value_name = "FILE", value_parser = parse_existing_path)]
+    pub input_file: Option<PathBuf>,
+
+    /// Address to publish Prometheus metrics on while scanning (e.g. "127.0.0.1:9100")
+    #[arg(long, value_name = "ADDR")]
+    pub metrics_listen: Option<SocketAddr>,
+
+    #[command(flatten)]
+    pub options: DumpOptions,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct ExtractArgs {
+    #[command(subcommand)]
+    pub command: Option<ExtractCommand>,
+
+    /// Directory of a previous dump
+    #[arg(required_unless_present = "from", conflicts_with = "from", value_parser = parse_existing_path)]
+    pub dir: Option<PathBuf>,
+
+    /// Directory holding a .git directory obtained by other means, such as a wget mirror, to check out and analyze in place
+    #[arg(long, value_name = "DIR", value_parser = parse_existing_path)]
+    pub from: Option<PathBuf>,
+
+    /// Format of the findings report
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+
+    /// Print the authors, committers and dates of the commits as a table
+    #[arg(long)]
+    pub summary: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExtractCommand {
+    /// Write every stash of a dump as patches and files under opendotgit-stash
+    Stash {
+        /// Directory of a previous dump
+        #[arg(value_parser = parse_existing_path)]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PackCommand {
+    /// Print the offset, ID and CRC-32 of every object a pack index lists
+    Idx {
+        /// Pack index file (.idx)
+        #[arg(value_parser = parse_existing_path)]
+        file: PathBuf,
+    },
+    /// Print the ID, type, size, packed size, offset and delta base of every object in a pack
+    List {
+        /// Pack file (.pack)
+        #[arg(value_parser = parse_existing_path)]
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ObjectsCommand {
+    /// Print the ID, type, size and the start of the content of every loose object
+    Ls {
+        /// Directory of a dump, a .git directory or an objects directory
+        #[arg(value_parser = parse_existing_path)]
+        dir: PathBuf,
+    },
+}
+
+/// Options shared by every command that dumps a repository.
+#[derive(clap::Args, Debug, Clone)]
+pub struct DumpOptions {
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    /// Let git fetch objects omitted by a partial clone from its HTTP(S) promisor remote
+    #[arg(long)]
+    pub fetch_promised: bool,
+
+    /// Replace Git LFS pointer files with their content from the server
+    #[arg(long)]
+    pub fetch_lfs: bool,
+
+    /// Expand the abbreviated object IDs mentioned in the dumped files and commit messages and download the objects they name
+    #[arg(long)]
+    pub expand_abbreviations: bool,
+
+    /// Probe the remotes the repository fetches from for exposed .git directories, offering to scan those within --scope or on the host of the target
+    #[arg(long)]
+    pub follow_remotes: bool,
+
+    /// Also keep the body of every successful response exactly as the server sent it under opendotgit-raw, laid out like the server paths
+    #[arg(long)]
+    pub mirror: bool,
+
+    /// Append every request, response and written file to this file as a line of JSON, for tools driving the dump
+    #[arg(long, value_name = "FILE", value_parser = parse_path)]
+    pub events: Option<PathBuf>,
+
+    /// In listing mode, also mirror sensitive items listed next to the .git directory (e.g. ".env", "backup/", "*.sql")
+    #[arg(long)]
+    pub grab_siblings: bool,
+
+    /// When the .git directory is not served, look for archives left next to it (e.g. ".git.zip", "backup.tar.gz") and extract it from them
+    #[arg(long)]
+    pub try_archives: bool,
+
+    /// Additional file to fetch in blind mode, relative to the repository root (e.g. ".git/hooks/pre-commit")
+    #[arg(long = "extra-known-file", value_name = "PATH")]
+    pub extra_known_files: Vec<String>,
+
+    /// Additional file to search for references in blind mode (e.g. ".git/refs/heads/develop")
+    #[arg(long = "extra-ref-file", value_name = "PATH")]
+    pub extra_ref_files: Vec<String>,
+
+    /// File listing additional paths to fetch in blind mode, one per line
+    #[arg(long, value_name = "FILE", value_parser = parse_existing_path)]
+    pub known_files_list: Option<PathBuf>,
+
+    /// Only fetch paths matching this pattern, relative to the repository root (e.g. ".git/**")
+    #[arg(long = "include-glob", value_name = "GLOB", value_parser = Glob::parse)]
+    pub include_globs: Vec<Glob>,
+
+    /// Never fetch paths matching this pattern (e.g. ".git/objects/pack/*.pack")
+    #[arg(long = "exclude-glob", value_name = "GLOB", value_parser = Glob::parse)]
+    pub exclude_globs: Vec<Glob>,
+
+    /// Also fetch sample hooks, info/exclude and editor backups found in listings
+    #[arg(long)]
+    pub fetch_boilerplate: bool,
+
+    /// Check that every object the refs need was recovered intact, listing the missing and corrupt ones
+    #[arg(long)]
+    pub fsck: bool,
+
+    /// Request the objects --fsck found missing or corrupt once more before checking out
+    #[arg(long, requires = "fsck")]
+    pub refetch: bool,
+
+    /// Only fetch the refs and pack indexes, then report how many objects the dump would download and their size
+    #[arg(long)]
+    pub estimate: bool,
+
+    /// Only fetch the index, or the trees HEAD points to without one, then print every file with its mode and size
+    #[arg(long, conflicts_with = "estimate")]
+    pub list_files: bool,
+
+    /// Ask before downloading from a target whose packs hold more objects than this
+    #[arg(long, value_name = "OBJECTS", default_value_t = 100_000)]
+    pub confirm_above: u64,
+
+    /// Download huge targets without asking, needed when stdin is not a terminal
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Phase of the dump to leave out, may be repeated (e.g. "objects" to only fetch the refs and packs)
+    #[arg(long = "skip-phase", value_name = "PHASE", value_enum)]
+    pub skip_phases: Vec<Stage>,
+
+    /// Print how long each phase took and how many requests per second it made, to tune --jobs and --rate
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Branch, tag or commit to check out instead of HEAD
+    #[arg(long = "ref", value_name = "REF")]
+    pub checkout_ref: Option<String>,
+
+    /// Shell command to run after a successful checkout, with {output_dir}, {url} and {report} substituted
+    #[arg(long = "post-cmd", value_name = "COMMAND")]
+    pub post_commands: Vec<String>,
+
+    /// Previous dump or clone of the target, objects it has are not downloaded again
+    #[arg(long, value_name = "DIR", value_parser = parse_existing_path)]
+    pub baseline: Option<PathBuf>,
+
+    /// Webhook to POST the report to when the run completes or fails (Slack, Discord or generic JSON)
+    #[arg(long, value_name = "URL")]
+    pub notify_url: Option<Url>,
+
+    /// SQLite database to append the outcome of the run to, useful when scanning many targets
+    #[arg(long, value_name = "FILE", value_parser = parse_path)]
+    pub db: Option<PathBuf>,
+
+    /// Format of the findings report
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+
+    /// Request paths again even if a previous run of the same dump found them missing
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Remove the files already in the output directory before dumping into it
+    #[arg(long, conflicts_with = "merge")]
+    pub force: bool,
+
+    /// Dump into an output directory that already holds other files, keeping them
+    #[arg(long)]
+    pub merge: bool,
+
+    /// Hash every file of the dump into SEAL.sha256 and write an RFC 3161 timestamp request for its digest
+    #[arg(long)]
+    pub seal: bool,
+
+    /// Timestamping authority to send the timestamp request of --seal to (e.g. "https://freetsa.org/tsr")
+    #[arg(long, value_name = "URL", requires = "seal")]
+    pub tsa_url: Option<Url>,
+
+    /// Package the recovered refs and objects into a git bundle at this path after the run
+    #[arg(long, value_name = "FILE", value_parser = parse_path)]
+    pub bundle: Option<PathBuf>,
+
+    /// Package the output directory into a gzipped tarball at this path after the run
+    #[arg(long, value_name = "FILE", value_parser = parse_path)]
+    pub archive: Option<PathBuf>,
+
+    /// Leave the .git directory and opendotgit's own files out of --archive
+    #[arg(long, requires = "archive")]
+    pub archive_worktree: bool,
+
+    /// After a successful checkout, push every recovered ref to this git remote, authenticating with the token in OPENDOTGIT_MIRROR_TOKEN
+    #[arg(long, value_name = "GIT_URL")]
+    pub mirror_to: Option<String>,
+
+    /// Show a live dashboard instead of log lines
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Print the authors, committers and dates of the recovered commits as a table
+    #[arg(long)]
+    pub summary: bool,
+}
+
+/// Options controlling how requests are made.
+#[derive(clap::Args, Debug, Clone)]
+pub struct NetworkArgs {
+    /// Maximum number of concurrent requests, fewer are made while the target is overloaded
     #[arg(short = 'j', long, default_value_t = 8)]
     pub jobs: usize,
 
-    /// Turn debugging information on
-    #[arg(short, long, action = Count)]
-    pub verbose: u8,
-
-    /// Number of times to retry a failed request
+    /// Number of times to retry a request that timed out, lost its connection or got a 5xx response
     #[arg(short, long, default_value_t = 3)]
     pub retries: usize,
 
     /// Timeout beyond which a request is no longer retried
     #[arg(short, long, default_value = "10", value_parser = parse_seconds, value_name="SECONDS")]
     pub timeout: Duration,
+
+    /// Header to send with every request, may be repeated
+    #[arg(short = 'H', long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    pub headers: Vec<String>,
+
+    /// URL to request before the dump to establish a session, optionally preceded by a method and followed by a form body, may be repeated (e.g. "POST https://example.com/login user=admin&password=admin")
+    #[arg(long = "pre-request", value_name = "REQUEST", value_parser = PreRequest::parse)]
+    pub pre_requests: Vec<PreRequest>,
+
+    /// File to load cookies from and save the cookies set by the target to, so that later runs stay in the same session
+    #[arg(long, value_name = "FILE", value_parser = parse_path)]
+    pub cookie_jar: Option<PathBuf>,
+
+    /// Domain, IP address or CIDR range requests may be sent to, every other host being refused, may be repeated (e.g. "example.com", "10.0.0.0/8")
+    #[arg(long = "scope", value_name = "HOST", value_parser = Rule::parse)]
+    pub scope: Vec<Rule>,
+
+    /// Proxy to send every request through (e.g. "http://127.0.0.1:8080")
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<Url>,
+
+    /// User agent to send with every request
+    #[arg(long)]
+    pub user_agent: Option<String>,
+
+    /// Send the headers of a browser in its order and require TLS 1.2 like it does
+    #[arg(long, value_name = "PROFILE")]
+    pub evasion: Option<Profile>,
+
+    /// curl-compatible command to make requests with instead, such as a curl-impersonate wrapper (e.g. "curl_chrome116")
+    #[arg(long, value_name = "COMMAND")]
+    pub impersonate_with: Option<PathBuf>,
+
+    /// Maximum number of requests per second
+    #[arg(long, value_name = "REQUESTS", value_parser = parse_rate)]
+    pub rate: Option<f64>,
+
+    /// Pause before each download, fixed or random within a range (e.g. "200ms" or "200ms-800ms")
+    #[arg(long, value_name = "DURATION", value_parser = Delay::parse)]
+    pub delay: Option<Delay>,
+
+    /// Wait before every request once a web application firewall blocked one, longer the more it blocks
+    #[arg(long)]
+    pub waf_slowdown: bool,
+
+    /// Ask for the size and content type of every pack with a HEAD request before downloading it, skipping those that cannot be packs
+    #[arg(long)]
+    pub head_first: bool,
+
+    /// Where the files of the repository are requested, relative to the URL, with %s standing for their path (e.g. "static/%s" or "%s.txt")
+    #[arg(long, value_name = "TEMPLATE", value_parser = PathTemplate::parse)]
+    pub path_template: Option<PathTemplate>,
+
+    /// How the HTML of directory listings is parsed
+    #[arg(long, value_name = "PARSER", default_value = "tree")]
+    pub html_parser: listing::Parser,
+
+    /// Stop after this many consecutive failed requests, or when half of the last 100 failed (0 to never stop)
+    #[arg(long, default_value_t = 50, value_name = "COUNT")]
+    pub max_failures: usize,
+
+    /// Configuration file providing defaults [default: ~/.config/opendotgit/config.toml]
+    #[arg(long, value_name = "FILE", value_parser = parse_existing_path)]
+    pub config: Option<PathBuf>,
 }
 
-pub fn parse() -> Args {
-    Args::parse()
+/// Parses the command line and fills in defaults from the configuration file.
+/// Returns the command to run along with the requested verbosity and, with --redact, the file
+/// mapping the masks to the hosts.
+pub fn parse() -> Result<(Command, u8, Option<PathBuf>)> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+    let redaction_map = cli.redact.then_some(cli.redaction_map);
+    let mut command = match cli.command {
+        Some(command) => command,
+        // clap leaves the group of a flattened Option<Args> empty when Args flattens other
+        // structs, so cli.dump is always None and the arguments are read directly instead.
+        None if matches.contains_id("url") => {
+            Command::Dump(Box::new(Args::from_arg_matches(&matches)?))
+        }
+        // Without a subcommand the positional arguments of the dump are required.
+        None => Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "the following required arguments were not provided: <URL> <OUTPUT>",
+            )
+            .exit(),
+    };
+
+    let matches = match matches.subcommand() {
+        Some((_, matches)) => matches,
+        None => &matches,
+    };
+    let network = match &mut command {
+        Command::Dump(args) => &mut args.options.network,
+        Command::Scan(args) => &mut args.options.network,
+        Command::Check(args) => &mut args.network,
+        _ => return Ok((command, cli.verbose, redaction_map)),
+    };
+    let path = network
+        .config
+        .clone()
+        .or_else(|| crate::config::default_path().filter(|path| path.exists()));
+    if let Some(path) = path {
+        Config::load(path)?.apply(network, matches);
+    }
+    Ok((command, cli.verbose, redaction_map))
 }
 
 fn parse_seconds(arg: &str) -> Result<std::time::Duration, std::num::ParseIntError> {
     let seconds = arg.parse()?;
     Ok(Duration::from_secs(seconds))
 }
+
+/// Resolves a path to an existing file or directory, since relative paths break once the output directory becomes the working directory.
+fn parse_existing_path(arg: &str) -> std::io::Result<PathBuf> {
+    std::fs::canonicalize(arg)
+}
+
+/// Resolves a path that may not exist yet against the directory opendotgit was started in.
+fn parse_path(arg: &str) -> std::io::Result<PathBuf> {
+    std::path::absolute(arg)
+}
+
+/// Checks that a header is given as "Name: value" with a valid name and value.
+pub fn parse_header(arg: &str) -> Result<String, String> {
+    let (name, value) = arg
+        .split_once(':')
+        .ok_or_else(|| "expected a header in the form \"Name: value\"".to_string())?;
+    HeaderName::from_bytes(name.trim().as_bytes()).map_err(|e| e.to_string())?;
+    HeaderValue::from_str(value.trim()).map_err(|e| e.to_string())?;
+    Ok(arg.to_string())
+}
+
+fn parse_rate(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err("expected a positive number of requests per second".to_string()),
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{diff, repository::Repository};
+use color_eyre::{eyre::WrapErr, Result};
+use pathbuf::pathbuf;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A previous dump or clone of the target whose objects need not be downloaded again.
+pub struct Baseline {
+    /// The objects directory of the baseline repository.
+    objects_dir: PathBuf,
+    /// Hashes of every object present in the baseline.
+    objects: HashSet<String>,
+    /// Commits that the references of the baseline point to.
+    tips: Vec<String>,
+    /// The tree checked out in the baseline.
+    head_tree: Option<String>,
+}
+
+/// A commit found in the dump but not in the baseline.
+#[derive(Serialize)]
+pub struct NewCommit {
+    pub id: String,
+    pub summary: String,
+}
+
+/// The differences between the dump and the baseline.
+#[derive(Serialize)]
+pub struct Diff {
+    pub new_commits: Vec<NewCommit>,
+    /// Paths that differ between the checked out trees of the baseline and the dump.
+    pub changed_files: Vec<String>,
+}
+
+impl Baseline {
+    /// Opens the repository at path and indexes its objects and references.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let repo = Repository::open(path)
+            .wrap_err(format!("Failed to open baseline {}", path.display()))?;
+
+        let objects = repo.objects()?;
+        let tips = repo.tips();
+        let head_tree = repo
+            .resolve("HEAD")
+            .and_then(|id| repo.peel_to_commit(&id))
+            .and_then(|id| repo.commit(&id))
+            .ok()
+            .map(|commit| commit.tree);
+
+        Ok(Baseline {
+            objects_dir: std::fs::canonicalize(repo.git_dir().join("objects"))?,
+            objects,
+            tips,
+            head_tree,
+        })
+    }
+
+    /// Returns true if href is a loose object that the baseline already has.
+    pub fn contains(&self, href: &str) -> bool {
+        let mut segments = href.rsplit('/').filter(|s| !s.is_empty());
+        match (segments.next(), segments.next(), segments.next()) {
+            (Some(rest), Some(prefix), Some("objects")) if prefix.len() == 2 => {
+                self.objects.contains(&format!("{prefix}{rest}"))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the number of objects in the baseline.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Registers the baseline objects as alternates of the dump, so git resolves the objects
+    /// that were skipped during the download from the baseline.
+    pub fn link(&self) -> Result<()> {
+        let info_dir = pathbuf![".git", "objects", "info"];
+        std::fs::create_dir_all(&info_dir)?;
+        let path = info_dir.join("alternates");
+        let line = self.objects_dir.display().to_string();
+        // Resumed and repeated dumps against the same baseline link it only once.
+        match std::fs::read_to_string(&path) {
+            Ok(linked) if linked.lines().any(|linked| linked == line) => return Ok(()),
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut alternates = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(alternates, "{line}")?;
+        Ok(())
+    }
+
+    /// Compares the dump in the current directory against the baseline.
+    pub fn diff(&self) -> Result<Diff> {
+        let repo = Repository::open(".")?;
+
+        // Tips that were deleted from the target are not in the dump and hide nothing.
+        let new_commits = repo
+            .walk(&repo.tips(), &self.tips)
+            .into_iter()
+            .map(|(id, commit)| NewCommit {
+                summary: commit.summary().to_string(),
+                id,
+            })
+            .collect();
+
+        let new_tree = repo
+            .resolve("HEAD")
+            .and_then(|id| repo.peel_to_commit(&id))
+            .and_then(|id| repo.commit(&id))
+            .ok()
+            .map(|commit| commit.tree);
+        let changed_files = diff::trees(&repo, self.head_tree.as_deref(), new_tree.as_deref())?
+            .into_iter()
+            .map(|change| change.path)
+            .collect();
+
+        Ok(Diff {
+            new_commits,
+            changed_files,
+        })
+    }
+}
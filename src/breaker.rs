@@ -0,0 +1,137 @@
+use crate::{
+    middleware::{Middleware, Next},
+    progress::Progress,
+    transport::{Request, Response},
+};
+use color_eyre::{eyre::bail, Result};
+use futures::future::BoxFuture;
+use log::error;
+use reqwest::StatusCode;
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+/// Number of most recent responses the error rate is measured over.
+const WINDOW: usize = 100;
+
+/// Share of failed responses within a full window that trips the breaker.
+const MAX_ERROR_RATE: f64 = 0.5;
+
+#[derive(Default)]
+struct Outcomes {
+    consecutive_failures: usize,
+    /// Whether each of the most recent responses failed, oldest first.
+    recent: VecDeque<bool>,
+}
+
+/// Stops the dump once the target appears to block it, after too many consecutive failures or
+/// a sustained error rate, instead of logging an error for every remaining request.
+///
+/// Missing files are expected while dumping, so only errors, rate limiting, forbidden and
+/// server error responses count as failures.
+pub struct CircuitBreaker {
+    max_failures: usize,
+    /// Set once tripped, the downloader makes no new requests afterwards.
+    tripped: Arc<AtomicBool>,
+    progress: Arc<Progress>,
+    outcomes: Mutex<Outcomes>,
+}
+
+/// Returns true if a response suggests the target is refusing requests.
+fn is_failure(response: &Result<Response>) -> bool {
+    match response {
+        Ok(response) => {
+            let status = response.status();
+            status == StatusCode::FORBIDDEN
+                || status == StatusCode::TOO_MANY_REQUESTS
+                || status.is_server_error()
+        }
+        Err(_) => true,
+    }
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker that trips after max_failures consecutive failures, setting tripped.
+    pub fn new(max_failures: usize, tripped: Arc<AtomicBool>, progress: Arc<Progress>) -> Self {
+        CircuitBreaker {
+            max_failures,
+            tripped,
+            progress,
+            outcomes: Mutex::default(),
+        }
+    }
+
+    /// Records the outcome of a request, returning the reason to trip if there is one.
+    fn record(&self, failed: bool) -> Option<String> {
+        let mut outcomes = self.outcomes.lock().unwrap();
+        if failed {
+            outcomes.consecutive_failures += 1;
+        } else {
+            outcomes.consecutive_failures = 0;
+        }
+        outcomes.recent.push_back(failed);
+        if outcomes.recent.len() > WINDOW {
+            outcomes.recent.pop_front();
+        }
+
+        let failures = outcomes.recent.iter().filter(|&&failed| failed).count();
+        if outcomes.consecutive_failures >= self.max_failures {
+            Some(format!("{} consecutive requests failed", self.max_failures))
+        } else if outcomes.recent.len() == WINDOW
+            && failures as f64 / WINDOW as f64 >= MAX_ERROR_RATE
+        {
+            Some(format!("{failures} of the last {WINDOW} requests failed"))
+        } else {
+            None
+        }
+    }
+}
+
+impl Middleware for CircuitBreaker {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            if self.tripped.load(Ordering::Relaxed) {
+                bail!(
+                    "Not requesting {}, the target is blocking requests",
+                    request.url
+                );
+            }
+            let response = next.run(request).await;
+            if let Some(reason) = self.record(is_failure(&response)) {
+                // Only the first request to trip the breaker reports it.
+                if !self.tripped.swap(true, Ordering::Relaxed) {
+                    let (finished, queued) = self.progress.counts();
+                    error!(
+                        "The target started blocking us at request {finished}/{queued} of the {} phase: {reason}",
+                        self.progress.phase().name()
+                    );
+                }
+            }
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trips_on_consecutive_failures_and_error_rate() {
+        let breaker = CircuitBreaker::new(3, Arc::default(), Arc::default());
+        assert!(breaker.record(true).is_none());
+        assert!(breaker.record(true).is_none());
+        assert!(breaker.record(false).is_none());
+        assert!(breaker.record(true).is_none());
+        assert!(breaker.record(true).is_none());
+        assert!(breaker.record(true).is_some());
+
+        let breaker = CircuitBreaker::new(usize::MAX, Arc::default(), Arc::default());
+        for i in 0..WINDOW - 1 {
+            assert!(breaker.record(i % 2 == 0).is_none());
+        }
+        assert!(breaker.record(true).is_some());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{extract, object::Kind, repository::Repository};
+use color_eyre::Result;
+use log::warn;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// File in the output directory listing the paths that could not be checked out.
+pub const MISSING_FILES: &str = "MISSING_FILES.txt";
+
+/// Mode of an executable file in a tree or the index.
+const EXECUTABLE: u32 = 0o100755;
+
+/// Mode of a symbolic link in a tree or the index.
+const SYMLINK: u32 = 0o120000;
+
+/// The result of materializing a worktree from the objects present in the dump.
+#[derive(Default)]
+pub struct Checkout {
+    /// Number of files written.
+    pub written: usize,
+    /// Paths whose blobs, or directories whose trees, are missing from the dump.
+    pub missing: Vec<String>,
+    /// Symbolic links to create once every regular file is written, so none is written through.
+    links: Vec<(PathBuf, Vec<u8>)>,
+    /// Folded paths already checked out, see [`fold`].
+    seen: HashSet<String>,
+}
+
+/// Folds path the way case insensitive filesystems compare names: NTFS ignores case and trailing
+/// dots and spaces, HFS+ ignores case and some zero width characters as well.
+fn fold(path: &Path) -> String {
+    path.iter()
+        .map(|name| {
+            let name: String = name
+                .to_string_lossy()
+                .chars()
+                .filter(|c| {
+                    !matches!(c, '\u{200c}'..='\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{206a}'..='\u{206f}' | '\u{feff}')
+                })
+                .collect();
+            name.trim_end_matches(['.', ' ']).to_lowercase()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether any directory above path is a symbolic link, which writing to path would follow.
+fn below_symlink(path: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .any(|parent| {
+            std::fs::symlink_metadata(parent)
+                .is_ok_and(|metadata| metadata.file_type().is_symlink())
+        })
+}
+
+/// Writes a blob to path, recording the path as missing if the blob is not in the dump. Symbolic
+/// links are only queued, see [`create_links`].
+///
+/// Paths colliding with one already checked out on a case insensitive filesystem and paths below
+/// a symbolic link are skipped.
+fn write_blob(
+    repo: &Repository,
+    path: &Path,
+    id: &str,
+    mode: u32,
+    checkout: &mut Checkout,
+) -> Result<()> {
+    if !checkout.seen.insert(fold(path)) {
+        warn!(
+            "Skipping {}, which collides with a path already checked out",
+            path.display()
+        );
+        return Ok(());
+    }
+    if below_symlink(path) {
+        warn!(
+            "Skipping {}, which lies below a symbolic link",
+            path.display()
+        );
+        return Ok(());
+    }
+    let Ok(content) = repo.blob(id) else {
+        checkout.missing.push(path.display().to_string());
+        return Ok(());
+    };
+    if mode == SYMLINK {
+        checkout.links.push((path.to_path_buf(), content));
+        return Ok(());
+    }
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Replace whatever a previous run left behind, including symbolic links.
+    let _ = std::fs::remove_file(path);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::write(path, &content)?;
+        if mode == EXECUTABLE {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = EXECUTABLE;
+        std::fs::write(path, &content)?;
+    }
+    checkout.written += 1;
+    Ok(())
+}
+
+/// Creates the symbolic links queued by [`write_blob`], skipping those that would be created
+/// through another link or in place of a directory. Where symbolic links are not supported, the
+/// target is written as the content of a regular file like git does.
+fn create_links(checkout: &mut Checkout) -> Result<()> {
+    for (path, target) in std::mem::take(&mut checkout.links) {
+        if below_symlink(&path) || path.is_dir() {
+            warn!("Skipping the symbolic link {}", path.display());
+            continue;
+        }
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let _ = std::fs::remove_file(&path);
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(&target), &path)?;
+        }
+        #[cfg(not(unix))]
+        std::fs::write(&path, &target)?;
+        checkout.written += 1;
+    }
+    Ok(())
+}
+
+/// Recursively writes every entry of a tree below prefix. Submodules are skipped.
+fn write_tree(repo: &Repository, tree: &str, prefix: &Path, checkout: &mut Checkout) -> Result<()> {
+    let Ok(entries) = repo.tree(tree) else {
+        checkout.missing.push(format!("{}/", prefix.display()));
+        return Ok(());
+    };
+    for entry in entries {
+        let Some(path) = extract::relative(&prefix.join(entry.name())) else {
+            continue;
+        };
+        match entry.kind() {
+            Some(Kind::Tree) => write_tree(repo, &entry.id, &path, checkout)?,
+            Some(Kind::Blob) => write_blob(repo, &path, &entry.id, entry.mode, checkout)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Resolves a branch, tag or commit to the ID of its tree and points HEAD at it.
+fn switch(repo: &Repository, target: &str) -> Result<String> {
+    let (id, reference) = repo.rev_parse(target)?;
+    let commit_id = repo.peel_to_commit(&id)?;
+    let commit = repo.commit(&commit_id)?;
+    match reference {
+        Some(name) if name.starts_with("refs/heads/") => repo.set_head(&name, false)?,
+        _ => repo.set_head(&commit_id, true)?,
+    }
+    // Keep the index in step with the new HEAD where the dump has every tree.
+    #[cfg(feature = "git2")]
+    if let Some(git2) = repo.git2() {
+        let mut index = git2.index()?;
+        let tree = git2::Oid::from_str(&commit.tree).and_then(|id| git2.find_tree(id));
+        if tree.and_then(|tree| index.read_tree(&tree)).is_ok() {
+            index.write()?;
+        }
+    }
+    Ok(commit.tree)
+}
+
+/// Writes every file of HEAD, or of target if given, whose blob is present in the dump into the
+/// current directory and collects the paths of those that are missing.
+///
+/// When HEAD does not resolve to a tree, e.g. because the commit was never downloaded, the files
+/// listed in the index are written instead.
+pub fn checkout(repo: &Repository, target: Option<&str>) -> Result<Checkout> {
+    let mut checkout = Checkout::default();
+    let head_tree = || -> Result<String> {
+        let id = repo.peel_to_commit(&repo.resolve("HEAD")?)?;
+        let tree = repo.commit(&id)?.tree;
+        repo.tree(&tree)?;
+        Ok(tree)
+    };
+    match target {
+        Some(target) => {
+            let tree = switch(repo, target)?;
+            if !repo.contains(&tree) {
+                warn!("The tree of {target} is missing from the dump");
+            }
+            write_tree(repo, &tree, Path::new(""), &mut checkout)?;
+        }
+        None => match head_tree() {
+            Ok(tree) => write_tree(repo, &tree, Path::new(""), &mut checkout)?,
+            Err(e) => {
+                warn!("HEAD does not resolve to a tree in the dump ({e}), checking out the index instead");
+                for entry in repo.index()? {
+                    let path = String::from_utf8_lossy(&entry.path).to_string();
+                    if let Some(path) = extract::relative(Path::new(&path)) {
+                        write_blob(repo, &path, &entry.id, entry.mode, &mut checkout)?;
+                    }
+                }
+            }
+        },
+    }
+    create_links(&mut checkout)?;
+    Ok(checkout)
+}
+
+/// Lists the missing paths in [`MISSING_FILES`], removing the list left by a previous run if nothing is missing.
+pub fn record_missing(missing: &[String]) -> Result<()> {
+    if missing.is_empty() {
+        if Path::new(MISSING_FILES).exists() {
+            std::fs::remove_file(MISSING_FILES)?;
+        }
+        return Ok(());
+    }
+    let mut text = missing.join("\n");
+    text.push('\n');
+    std::fs::write(MISSING_FILES, text)?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "git2"))]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn skips_colliding_paths_and_paths_below_links() {
+        let _guard = testing::lock_current_dir().await;
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let git = git2::Repository::init(dir.path()).unwrap();
+        let blob = git.blob(b"content\n").unwrap();
+        let mut sub = git.treebuilder(None).unwrap();
+        sub.insert("file", blob, 0o100644).unwrap();
+        let sub = sub.write().unwrap();
+        let mut root = git.treebuilder(None).unwrap();
+        root.insert("README", blob, 0o100644).unwrap();
+        root.insert("readme.", blob, 0o100644).unwrap();
+        root.insert("dir", sub, 0o040000).unwrap();
+        let tree = git.find_tree(root.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("Fixture", "fixture@example.com").unwrap();
+        git.commit(Some("HEAD"), &signature, &signature, "Collide", &tree, &[])
+            .unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("dir")).unwrap();
+
+        let current_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(dir.path()).unwrap();
+        let result = checkout(&Repository::open(dir.path()).unwrap(), None);
+        std::env::set_current_dir(current_dir).unwrap();
+
+        assert_eq!(result.unwrap().written, if cfg!(unix) { 1 } else { 2 });
+        assert!(dir.path().join("README").is_file());
+        assert!(!dir.path().join("readme.").exists());
+        assert!(!outside.path().join("file").exists());
+    }
+}
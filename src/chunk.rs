@@ -0,0 +1,59 @@
+use byteorder::{BigEndian, ReadBytesExt};
+use color_eyre::{eyre::bail, Result};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::ops::Range;
+
+/// The identifier of a chunk, e.g. `OIDL` for the object ID lookup chunk.
+pub type ChunkId = [u8; 4];
+
+/// Parses the table of contents of a Git chunk-based file format (commit-graph, multi-pack-index)
+/// starting at the given offset and returns the byte range of every chunk keyed by its identifier.
+pub fn parse_table(
+    data: &[u8],
+    offset: usize,
+    chunk_count: u8,
+) -> Result<HashMap<ChunkId, Range<usize>>> {
+    let mut reader = Cursor::new(data.get(offset..).unwrap_or_default());
+
+    // Each entry is a 4 byte identifier followed by an 8 byte offset,
+    // the table is terminated by an extra entry that marks the end of the last chunk.
+    let mut entries = Vec::with_capacity(chunk_count as usize + 1);
+    for _ in 0..=chunk_count {
+        let mut id = [0u8; 4];
+        reader.read_exact(&mut id)?;
+        let start = reader.read_u64::<BigEndian>()? as usize;
+        entries.push((id, start));
+    }
+
+    let mut chunks = HashMap::with_capacity(chunk_count as usize);
+    for window in entries.windows(2) {
+        let (id, start) = window[0];
+        let (_, end) = window[1];
+        if start > end || end > data.len() {
+            bail!(
+                "Chunk {} points outside of the file",
+                String::from_utf8_lossy(&id)
+            );
+        }
+        chunks.insert(id, start..end);
+    }
+    Ok(chunks)
+}
+
+/// Returns the length of an object ID for the hash version byte found in chunk file headers.
+pub fn hash_len(version: u8) -> Result<usize> {
+    match version {
+        1 => Ok(20),
+        2 => Ok(32),
+        _ => bail!("Unknown hash version {version}"),
+    }
+}
+
+/// Splits an object ID lookup chunk into hex encoded object IDs.
+pub fn object_ids(data: &[u8], range: Range<usize>, hash_len: usize) -> Vec<String> {
+    data[range]
+        .chunks_exact(hash_len)
+        .map(hex::encode)
+        .collect()
+}
@@ -0,0 +1,100 @@
+use crate::{
+    args::{self, NetworkArgs},
+    throttle::Delay,
+};
+use clap::{parser::ValueSource, ArgMatches};
+use color_eyre::{eyre::WrapErr, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::time::Duration;
+use url::Url;
+
+/// Defaults read from a configuration file, overridden by anything given on the command line.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub jobs: Option<usize>,
+    pub retries: Option<usize>,
+    /// Request timeout in seconds.
+    pub timeout: Option<u64>,
+    /// Headers sent with every request.
+    pub headers: BTreeMap<String, String>,
+    pub proxy: Option<Url>,
+    pub user_agent: Option<String>,
+    /// Maximum number of requests per second.
+    pub rate: Option<f64>,
+    /// Consecutive failed requests after which the dump stops.
+    pub max_failures: Option<usize>,
+    /// Pause before each download, e.g. "200ms-800ms".
+    pub delay: Option<String>,
+    pub waf_slowdown: Option<bool>,
+    pub head_first: Option<bool>,
+}
+
+/// Returns the location of the per-user configuration file, `~/.config/opendotgit/config.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(config_dir.join("opendotgit").join("config.toml"))
+}
+
+impl Config {
+    /// Reads and parses the configuration file at path.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).wrap_err(format!("Failed to read {}", path.display()))?;
+        let config: Config =
+            toml::from_str(&text).wrap_err(format!("Failed to parse {}", path.display()))?;
+        if let Some(delay) = &config.delay {
+            Delay::parse(delay)
+                .map_err(color_eyre::eyre::Report::msg)
+                .wrap_err(format!("Invalid delay in {}", path.display()))?;
+        }
+        for (name, value) in &config.headers {
+            args::parse_header(&format!("{name}: {value}"))
+                .map_err(color_eyre::eyre::Report::msg)
+                .wrap_err(format!("Invalid header in {}", path.display()))?;
+        }
+        Ok(config)
+    }
+
+    /// Fills in every setting that was not given on the command line.
+    pub fn apply(self, args: &mut NetworkArgs, matches: &ArgMatches) {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let (Some(jobs), false) = (self.jobs, from_cli("jobs")) {
+            args.jobs = jobs;
+        }
+        if let (Some(retries), false) = (self.retries, from_cli("retries")) {
+            args.retries = retries;
+        }
+        if let (Some(timeout), false) = (self.timeout, from_cli("timeout")) {
+            args.timeout = Duration::from_secs(timeout);
+        }
+        if let (Some(max_failures), false) = (self.max_failures, from_cli("max_failures")) {
+            args.max_failures = max_failures;
+        }
+        args.waf_slowdown |= self.waf_slowdown.unwrap_or_default();
+        args.head_first |= self.head_first.unwrap_or_default();
+        args.proxy = args.proxy.take().or(self.proxy);
+        args.user_agent = args.user_agent.take().or(self.user_agent);
+        args.rate = args.rate.or(self.rate);
+        // The delay was validated when loading the configuration.
+        args.delay = args
+            .delay
+            .or_else(|| self.delay.and_then(|delay| Delay::parse(&delay).ok()));
+
+        // Headers from the configuration go first so that the command line ones replace them.
+        let mut headers: Vec<String> = self
+            .headers
+            .into_iter()
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect();
+        headers.append(&mut args.headers);
+        args.headers = headers;
+    }
+}
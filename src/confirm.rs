@@ -0,0 +1,104 @@
+use crate::{download::Downloader, response::ResponseExt};
+use color_eyre::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// A line of `objects/info/packs` naming a pack.
+    static ref PACK_LINE: Regex = Regex::new(r"^P pack-([0-9a-f]{40}|[0-9a-f]{64})\.pack$").unwrap();
+}
+
+/// The description `git init` writes, which only a git directory serves.
+const DEFAULT_DESCRIPTION: &str = "Unnamed repository; edit this file 'description'";
+
+/// A file of the .git directory confirming that it is exposed when the server refuses to serve
+/// HEAD, as some block files named after the method.
+pub struct Probe {
+    /// Path of the file relative to the target.
+    pub path: &'static str,
+    /// Returns true if the content served is what git writes to the file.
+    pub matches: fn(&str) -> bool,
+}
+
+/// The files tried in order after `.git/HEAD`.
+pub const FALLBACKS: &[Probe] = &[
+    Probe {
+        path: ".git/config",
+        matches: is_config,
+    },
+    Probe {
+        path: ".git/description",
+        matches: is_description,
+    },
+    Probe {
+        path: ".git/objects/info/packs",
+        matches: is_pack_list,
+    },
+];
+
+/// Every repository configuration has a core section, web pages and error messages do not.
+fn is_config(text: &str) -> bool {
+    text.lines()
+        .any(|line| line.trim().eq_ignore_ascii_case("[core]"))
+}
+
+/// Only the description left by `git init` is told apart from any other text file.
+fn is_description(text: &str) -> bool {
+    text.trim_start().starts_with(DEFAULT_DESCRIPTION)
+}
+
+fn is_pack_list(text: &str) -> bool {
+    let lines: Vec<_> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    !lines.is_empty() && lines.iter().all(|line| PACK_LINE.is_match(line))
+}
+
+/// Tries the fallbacks in turn, returning the path of the first one served as git writes it.
+pub async fn fallback(download: &Downloader) -> Result<Option<&'static str>> {
+    for probe in FALLBACKS {
+        let uri = download.normalize_url(probe.path)?;
+        let response = download.fetch_raw_url(&uri).await?;
+        if response.verify().is_ok() && (probe.matches)(&response.text()) {
+            return Ok(Some(probe.path));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_git_files() {
+        let matches = |path: &str, text: &str| {
+            (FALLBACKS
+                .iter()
+                .find(|probe| probe.path == path)
+                .unwrap()
+                .matches)(text)
+        };
+        assert!(matches(
+            ".git/config",
+            "[core]\n\trepositoryformatversion = 0\n\tbare = false\n"
+        ));
+        assert!(!matches(".git/config", "Forbidden"));
+        assert!(matches(
+            ".git/description",
+            "Unnamed repository; edit this file 'description' to name the repository.\n"
+        ));
+        assert!(!matches(".git/description", "Not Found"));
+        assert!(matches(
+            ".git/objects/info/packs",
+            "P pack-0123456789abcdef0123456789abcdef01234567.pack\n\n"
+        ));
+        assert!(!matches(".git/objects/info/packs", "\n"));
+        assert!(!matches(
+            ".git/objects/info/packs",
+            "P pack-0123456789abcdef0123456789abcdef01234567.pack\n<html>"
+        ));
+    }
+}
@@ -1,7 +1,9 @@
 /// A slice of file paths that are commonly found in Git repositories.
 pub static KNOWN_FILES: &[&str] = &[
+    ".git-credentials",
     ".gitignore",
     ".git/COMMIT_EDITMSG",
+    ".git/credentials",
     ".git/description",
     ".git/hooks/applypatch-msg.sample",
     ".git/hooks/commit-msg.sample",
@@ -17,7 +19,10 @@ pub static KNOWN_FILES: &[&str] = &[
     ".git/hooks/update.sample",
     ".git/index",
     ".git/info/exclude",
+    ".git/objects/info/commit-graph",
+    ".git/objects/info/commit-graphs/commit-graph-chain",
     ".git/objects/info/packs",
+    ".git/objects/pack/multi-pack-index",
 ];
 
 /// A slice of file paths that reference various locations within a Git repository.
@@ -40,3 +45,40 @@ pub static REF_FILES: &[&str] = &[
     ".git/refs/wip/wtree/refs/heads/master",
     ".git/refs/wip/index/refs/heads/master",
 ];
+
+/// Patterns of commonly sensitive items in the web root, mirrored with `--grab-siblings`.
+pub static SENSITIVE_SIBLINGS: &[&str] = &[
+    ".env",
+    ".env.*",
+    ".htpasswd",
+    ".svn",
+    ".hg",
+    "backup",
+    "backups",
+    "dump",
+    "*.sql",
+    "*.sql.gz",
+    "*.bak",
+    "*.old",
+    "*.swp",
+    "*.zip",
+    "*.tar",
+    "*.tar.gz",
+    "*.tgz",
+    "*.key",
+    "*.pem",
+    "*.log",
+    "wp-config.php~",
+];
+
+/// Returns the default paths followed by the extra ones, skipping duplicates, blank lines and `#` comments.
+pub fn extend<S: AsRef<str>>(defaults: &[&str], extra: &[S]) -> Vec<String> {
+    let mut paths: Vec<String> = defaults.iter().map(|path| path.to_string()).collect();
+    for path in extra {
+        let path = path.as_ref().trim();
+        if !path.is_empty() && !path.starts_with('#') && !paths.iter().any(|known| known == path) {
+            paths.push(path.to_string());
+        }
+    }
+    paths
+}
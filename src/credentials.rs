@@ -0,0 +1,108 @@
+use crate::gitconfig::GitConfig;
+use color_eyre::Result;
+use pathbuf::pathbuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The kind of sensitive value found in a repository configuration.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// A remote URL without embedded credentials.
+    Remote,
+    /// A URL with an embedded username and/or password.
+    Url,
+    /// A username or email address.
+    Username,
+    /// An access token, password or authorization header.
+    Token,
+}
+
+/// A sensitive value along with where it was found.
+#[derive(Serialize, Debug)]
+pub struct Credential {
+    /// The file and, for configuration files, the key holding the value.
+    pub source: String,
+    pub kind: Kind,
+    pub value: String,
+}
+
+/// Returns true if the URL carries a username or password.
+fn has_userinfo(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| !url.username().is_empty() || url.password().is_some())
+}
+
+/// Classifies a configuration entry, returning None for entries that are not interesting.
+fn classify(name: &str, value: &str) -> Option<Kind> {
+    if has_userinfo(value) {
+        return Some(Kind::Url);
+    }
+    let name = name.to_lowercase();
+    if name.ends_with("extraheader") || name.contains("token") || name.contains("password") {
+        Some(Kind::Token)
+    } else if name.ends_with(".username") || name == "user.name" || name == "user.email" {
+        Some(Kind::Username)
+    } else if name.ends_with(".url") || name.ends_with(".pushurl") {
+        Some(Kind::Remote)
+    } else {
+        None
+    }
+}
+
+/// Harvests remote URLs, usernames and tokens from a git configuration file.
+fn from_config<P: AsRef<Path>>(path: P) -> Result<Vec<Credential>> {
+    let path = path.as_ref();
+    let config = GitConfig::open(path)?;
+    let mut credentials = Vec::new();
+    for (name, value) in &config.entries {
+        if let Some(kind) = classify(name, value) {
+            credentials.push(Credential {
+                source: format!("{}:{name}", path.display()),
+                kind,
+                value: value.clone(),
+            });
+        }
+    }
+    Ok(credentials)
+}
+
+/// Harvests URLs from a credential store file, which holds one URL with embedded credentials per line.
+fn from_store<P: AsRef<Path>>(path: P) -> Result<Vec<Credential>> {
+    let path = path.as_ref();
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| has_userinfo(line))
+        .map(|line| Credential {
+            source: path.display().to_string(),
+            kind: Kind::Url,
+            value: line.to_string(),
+        })
+        .collect())
+}
+
+/// Harvests credentials from every configuration and credential store file present in the dump.
+pub fn harvest() -> Vec<Credential> {
+    let mut credentials = Vec::new();
+    let config: PathBuf = pathbuf![".git", "config"];
+    if config.exists() {
+        match from_config(&config) {
+            Ok(found) => credentials.extend(found),
+            Err(e) => log::warn!("Failed to parse {}: {e}", config.display()),
+        }
+    }
+
+    for store in [
+        pathbuf![".git", "credentials"],
+        pathbuf![".git-credentials"],
+    ] {
+        if store.exists() {
+            match from_store(&store) {
+                Ok(found) => credentials.extend(found),
+                Err(e) => log::warn!("Failed to read {}: {e}", store.display()),
+            }
+        }
+    }
+    credentials
+}
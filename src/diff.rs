@@ -0,0 +1,256 @@
+use crate::{
+    object::{Kind, TreeEntry},
+    repository::Repository,
+};
+use color_eyre::Result;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Number of unchanged lines shown around every change in a patch.
+const CONTEXT: usize = 3;
+
+/// Above this many line pairs the files are shown as replaced wholesale instead of diffed line by line.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Added,
+    Deleted,
+    Modified,
+}
+
+/// A file that differs between two trees.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub path: String,
+    pub status: Status,
+    /// The blob of the file in the old tree.
+    pub old: Option<String>,
+    /// The blob of the file in the new tree.
+    pub new: Option<String>,
+}
+
+/// Lists the entries of a tree by name, or none for a missing side.
+fn entries(repo: &Repository, id: Option<&str>) -> Result<BTreeMap<String, TreeEntry>> {
+    let Some(id) = id else {
+        return Ok(BTreeMap::new());
+    };
+    Ok(repo
+        .tree(id)?
+        .into_iter()
+        .map(|entry| (entry.name(), entry))
+        .collect())
+}
+
+fn diff_into(
+    repo: &Repository,
+    old: Option<&str>,
+    new: Option<&str>,
+    prefix: &str,
+    changes: &mut Vec<Change>,
+) -> Result<()> {
+    let old_entries = entries(repo, old)?;
+    let new_entries = entries(repo, new)?;
+    let mut names: Vec<&String> = old_entries.keys().chain(new_entries.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let path = format!("{prefix}{name}");
+        let old = old_entries.get(name);
+        let new = new_entries.get(name);
+        if let (Some(old), Some(new)) = (old, new) {
+            if old.id == new.id && old.mode == new.mode {
+                continue;
+            }
+        }
+        let subtree = |entry: Option<&TreeEntry>| {
+            entry
+                .filter(|entry| entry.kind() == Some(Kind::Tree))
+                .map(|entry| entry.id.clone())
+        };
+        let blob = |entry: Option<&TreeEntry>| {
+            entry
+                .filter(|entry| entry.kind() == Some(Kind::Blob))
+                .map(|entry| entry.id.clone())
+        };
+
+        // Subtrees missing from a partial dump cannot be compared and are skipped.
+        let (old_tree, new_tree) = (subtree(old), subtree(new));
+        if old_tree.is_some() || new_tree.is_some() {
+            let _ = diff_into(
+                repo,
+                old_tree.as_deref(),
+                new_tree.as_deref(),
+                &format!("{path}/"),
+                changes,
+            );
+        }
+        let status = match (blob(old), blob(new)) {
+            (None, None) => continue,
+            (Some(_), None) => Status::Deleted,
+            (None, Some(_)) => Status::Added,
+            (Some(_), Some(_)) => Status::Modified,
+        };
+        changes.push(Change {
+            path,
+            status,
+            old: blob(old),
+            new: blob(new),
+        });
+    }
+    Ok(())
+}
+
+/// Lists the files that differ between two trees, either of which may be absent.
+pub fn trees(repo: &Repository, old: Option<&str>, new: Option<&str>) -> Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    diff_into(repo, old, new, "", &mut changes)?;
+    Ok(changes)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Aligns two sequences of lines along their longest common subsequence.
+fn line_ops(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let (n, m) = (a.len(), b.len());
+    if n.saturating_mul(m) > MAX_DIFF_CELLS {
+        let mut ops = vec![Op::Delete; n];
+        ops.extend(vec![Op::Insert; m]);
+        return ops;
+    }
+    let width = m + 1;
+    let mut lcs = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            ops.push(Op::Equal);
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            ops.push(Op::Delete);
+            i += 1;
+        } else {
+            ops.push(Op::Insert);
+            j += 1;
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Delete, n - i));
+    ops.extend(std::iter::repeat_n(Op::Insert, m - j));
+    ops
+}
+
+/// Appends a line of a hunk, marking a missing trailing newline the way git does.
+fn push_line(text: &mut String, prefix: char, line: &str) {
+    text.push(prefix);
+    text.push_str(line);
+    if !line.ends_with('\n') {
+        text.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Formats the start and length of one side of a hunk, leaving out a length of one like git does.
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{count}", start + 1),
+    }
+}
+
+/// Renders the change between two versions of a file as a unified diff in git's format.
+/// Either version may be absent for added and deleted files.
+pub fn unified(path: &str, old: Option<&[u8]>, new: Option<&[u8]>) -> String {
+    let mut text = format!("diff --git a/{path} b/{path}\n");
+    match (old, new) {
+        (None, _) => text.push_str("new file mode 100644\n"),
+        (_, None) => text.push_str("deleted file mode 100644\n"),
+        _ => {}
+    }
+    let is_binary = |content: Option<&[u8]>| content.is_some_and(|content| content.contains(&0));
+    if is_binary(old) || is_binary(new) {
+        let _ = writeln!(text, "Binary files differ");
+        return text;
+    }
+    let _ = writeln!(
+        text,
+        "--- {}",
+        old.map_or("/dev/null".to_string(), |_| format!("a/{path}"))
+    );
+    let _ = writeln!(
+        text,
+        "+++ {}",
+        new.map_or("/dev/null".to_string(), |_| format!("b/{path}"))
+    );
+
+    let old_text = String::from_utf8_lossy(old.unwrap_or_default());
+    let new_text = String::from_utf8_lossy(new.unwrap_or_default());
+    let a: Vec<&str> = old_text.split_inclusive('\n').collect();
+    let b: Vec<&str> = new_text.split_inclusive('\n').collect();
+    let ops = line_ops(&a, &b);
+
+    // Positions in both files before every operation.
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for op in &ops {
+        positions.push((i, j));
+        match op {
+            Op::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Op::Delete => i += 1,
+            Op::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    let changed: Vec<usize> = (0..ops.len()).filter(|&k| ops[k] != Op::Equal).collect();
+    let mut k = 0;
+    while k < changed.len() {
+        // Merge changes whose context would overlap into one hunk.
+        let start = changed[k].saturating_sub(CONTEXT);
+        let mut end = changed[k];
+        while k + 1 < changed.len() && changed[k + 1] <= end + 2 * CONTEXT + 1 {
+            k += 1;
+            end = changed[k];
+        }
+        let end = (end + CONTEXT + 1).min(ops.len());
+        k += 1;
+
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let (old_count, new_count) = (old_end - old_start, new_end - new_start);
+        let _ = writeln!(
+            text,
+            "@@ -{} +{} @@",
+            range(old_start, old_count),
+            range(new_start, new_count)
+        );
+        for index in start..end {
+            let (i, j) = positions[index];
+            match ops[index] {
+                Op::Equal => push_line(&mut text, ' ', a[i]),
+                Op::Delete => push_line(&mut text, '-', a[i]),
+                Op::Insert => push_line(&mut text, '+', b[j]),
+            }
+        }
+    }
+    text
+}
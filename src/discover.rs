@@ -0,0 +1,284 @@
+use crate::{
+    download::Downloader,
+    expression, fsck, graph, index,
+    mapped::Mapped,
+    pack,
+    priority::{self, Priority},
+    refs,
+    repository::{self, Repository},
+    validate,
+    visited::Visited,
+};
+use color_eyre::{eyre::WrapErr, Result};
+use futures::{stream, StreamExt};
+use log::warn;
+use pathbuf::pathbuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tokio::{sync::mpsc::Sender, task::spawn_blocking};
+use walkdir::WalkDir;
+
+/// Number of discovered objects that may wait for the downloader before discovery pauses.
+pub const QUEUE_LEN: usize = 1024;
+
+/// Number of ref and log files scanned at once.
+const PARALLEL_SCANS: usize = 64;
+
+/// Directory inside the dump that ref and log files which are not valid UTF-8 are moved to, so
+/// they cannot break git when checking out.
+pub const QUARANTINE_DIR: &str = "opendotgit-quarantine";
+
+/// The all-zero ID that reflogs use for references that did not exist yet.
+const NULL_ID: &str = "0000000000000000000000000000000000000000";
+
+/// Hands out every new object ID to the downloader as its path, returning false once the
+/// downloader stopped listening.
+struct Queue {
+    visited: Visited,
+    sender: Sender<(Priority, String)>,
+    count: usize,
+}
+
+impl Queue {
+    async fn push(&mut self, id: &str, priority: Priority) -> Result<bool> {
+        if id == NULL_ID || id.len() < 3 || !self.visited.insert(id)? {
+            return Ok(true);
+        }
+        self.count += 1;
+        let href = format!(".git/objects/{}/{}", &id[0..2], &id[2..]);
+        Ok(self.sender.send((priority, href)).await.is_ok())
+    }
+
+    async fn extend(&mut self, ids: Vec<String>) -> Result<bool> {
+        for id in ids {
+            if !self.push(&id, Priority::Normal).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Returns the object IDs mentioned in a ref or log file, scanning it in place.
+///
+/// Files that are not valid UTF-8, usually binary pages served in place of a missing file, are
+/// quarantined after the IDs they happen to contain are taken, unless they have the format of
+/// the file and only name refs that are not UTF-8.
+fn scan(path: PathBuf) -> Result<Vec<String>> {
+    let contents = Mapped::open(&path).wrap_err(format!("Failed to read {}", path.display()))?;
+    let ids = expression::OBJECT
+        .captures_iter(&contents)
+        .filter_map(|m| m.get(2))
+        // The expression only matches hex digits, which are always valid UTF-8.
+        .map(|m| String::from_utf8_lossy(m.as_bytes()).into_owned())
+        .collect();
+    let href = path.to_string_lossy().replace('\\', "/");
+    if std::str::from_utf8(&contents).is_err() && !validate::ref_file(&href, &contents) {
+        drop(contents);
+        quarantine(&path)?;
+    }
+    Ok(ids)
+}
+
+/// Moves a file out of the .git directory into [`QUARANTINE_DIR`], keeping its path.
+fn quarantine(path: &Path) -> Result<()> {
+    let target = Path::new(QUARANTINE_DIR).join(path);
+    warn!(
+        "{} is not valid UTF-8, moving it to {}",
+        path.display(),
+        target.display()
+    );
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(path, target)?;
+    Ok(())
+}
+
+/// Parses every commit-graph that exists.
+fn graphs(graph_paths: Vec<PathBuf>) -> Vec<String> {
+    graph_paths
+        .into_iter()
+        .filter(|path| path.exists())
+        .flat_map(|path| {
+            graph::parse(&path).unwrap_or_else(|e| {
+                warn!("Failed to parse {}: {e}", path.display());
+                Vec::new()
+            })
+        })
+        .collect()
+}
+
+/// Reads the IDs of the blobs staged in the index, if the dump has one, split into those of
+/// files worth fetching first and the rest.
+fn staged() -> Result<(Vec<String>, Vec<String>)> {
+    // The index may be missing if the server withholds it or the run was interrupted.
+    let index_path = pathbuf![".git", "index"];
+    if !index_path.exists() {
+        return Ok(Default::default());
+    }
+    let (valuable, rest): (Vec<_>, Vec<_>) =
+        index::open(&index_path, repository::hash_len(Path::new(".git")))?
+            .into_iter()
+            .partition(|entry| priority::is_valuable(&String::from_utf8_lossy(&entry.path)));
+    let ids = |entries: Vec<index::Entry>| entries.into_iter().map(|entry| entry.id).collect();
+    Ok((ids(valuable), ids(rest)))
+}
+
+/// Returns the files of the dump that name refs and objects: packed-refs, info/refs, FETCH_HEAD,
+/// ORIG_HEAD and everything below refs and logs.
+fn ref_files() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = vec![
+        pathbuf![".git", "packed-refs"],
+        pathbuf![".git", "info", "refs"],
+        pathbuf![".git", "FETCH_HEAD"],
+        pathbuf![".git", "ORIG_HEAD"],
+    ];
+    let search_paths = [pathbuf![".git", "refs"], pathbuf![".git", "logs"]];
+    files.extend(search_paths.iter().flat_map(|path| {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.path().to_path_buf())
+    }));
+    files.retain(|path| path.exists());
+    files
+}
+
+/// Returns the names of the refs that HEAD and the ref and log files of the dump mention.
+pub fn named_refs() -> Vec<Vec<u8>> {
+    let mut names: Vec<Vec<u8>> = ref_files()
+        .into_iter()
+        .chain([pathbuf![".git", "HEAD"]])
+        .filter_map(|path| std::fs::read(path).ok())
+        .flat_map(|text| refs::names(&text))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Returns the objects that HEAD, the ref and log files and the index of the dump point to, from
+/// which everything the repository holds is reachable.
+pub fn tips() -> Result<Vec<String>> {
+    let mut ids: Vec<String> = ref_files()
+        .into_iter()
+        .chain(
+            [pathbuf![".git", "HEAD"]]
+                .into_iter()
+                .filter(|path| path.exists()),
+        )
+        .map(scan)
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    let (valuable, staged) = staged()?;
+    ids.extend(valuable);
+    ids.extend(staged);
+    ids.retain(|id| id != NULL_ID);
+    ids.sort();
+    ids.dedup();
+    Ok(ids)
+}
+
+/// Lists the objects of every pack index in the dump.
+fn packed() -> Vec<String> {
+    let pack_file_dir = pathbuf![".git", "objects", "pack"];
+    WalkDir::new(&pack_file_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.file_type().is_file() && name.starts_with("pack-") && name.ends_with(".idx")
+        })
+        .flat_map(|entry| {
+            pack::parse(entry.path()).unwrap_or_else(|e| {
+                warn!("Failed to parse {}: {e}", entry.path().display());
+                Vec::new()
+            })
+        })
+        .collect()
+}
+
+/// Finds the objects referenced by the dumped refs, logs, index, commit-graphs and pack indexes
+/// and sends the path of each one to the downloader as soon as it is found, starting with the
+/// staged blobs of valuable files and seed. Returns the number of objects found.
+///
+/// Files are read and scanned on blocking threads, many ref and log files at a time.
+pub async fn objects(
+    seed: Vec<String>,
+    graph_paths: Vec<PathBuf>,
+    sender: Sender<(Priority, String)>,
+) -> Result<usize> {
+    let mut queue = Queue {
+        visited: Visited::default(),
+        sender,
+        count: 0,
+    };
+    let (valuable, staged) = spawn_blocking(staged).await??;
+    for id in valuable {
+        if !queue.push(&id, Priority::Valuable).await? {
+            return Ok(queue.count);
+        }
+    }
+    if !queue.extend(seed).await? {
+        return Ok(queue.count);
+    }
+    if !queue
+        .extend(spawn_blocking(move || graphs(graph_paths)).await?)
+        .await?
+    {
+        return Ok(queue.count);
+    }
+
+    // For the contents of .git/packed-refs, .git/info/refs, .git/refs/*, .git/logs/*
+    //   check if they match "(^|\s)([a-f0-9]{40})($|\s)" and get the second match group
+    let files = spawn_blocking(ref_files).await?;
+    let mut scans = stream::iter(files)
+        .map(|path| spawn_blocking(move || scan(path)))
+        .buffer_unordered(PARALLEL_SCANS);
+    while let Some(scanned) = scans.next().await {
+        let ids = match scanned? {
+            Ok(ids) => ids,
+            Err(e) => {
+                warn!("{e}");
+                continue;
+            }
+        };
+        if !queue.extend(ids).await? {
+            return Ok(queue.count);
+        }
+    }
+
+    if !queue.extend(staged).await? {
+        return Ok(queue.count);
+    }
+    queue.extend(spawn_blocking(packed).await?).await?;
+    Ok(queue.count)
+}
+
+/// Downloads the objects and everything they reach that the dump is missing, returning how many
+/// objects were downloaded.
+pub async fn reachable(download: &Downloader, ids: Vec<String>) -> usize {
+    let Ok(repo) = Repository::open(".") else {
+        return 0;
+    };
+    let mut fetched = 0;
+    let mut seen: HashSet<String> = ids.iter().cloned().collect();
+    let mut frontier = ids;
+    while !frontier.is_empty() && !download.is_interrupted() {
+        let missing: Vec<_> = frontier.iter().filter(|id| !repo.contains(id)).collect();
+        let hrefs: Vec<_> = missing.iter().map(|id| fsck::loose_href(id)).collect();
+        download.multiple(&hrefs).await;
+        fetched += missing.iter().filter(|id| repo.contains(id)).count();
+        frontier = frontier
+            .iter()
+            .filter_map(|id| repo.find(id).ok().map(|object| object.links(id.len() / 2)))
+            .flatten()
+            .filter(|id| seen.insert(id.clone()))
+            .collect();
+    }
+    fetched
+}
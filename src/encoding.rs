@@ -0,0 +1,111 @@
+use crate::transport::Response;
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use reqwest::header::CONTENT_ENCODING;
+
+/// The first two bytes of every gzip stream.
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Flags of the gzip header, RFC 1952 section 2.3.1.
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// Returns true if data starts with a zlib header using deflate, as every loose object does.
+pub fn is_zlib(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+/// Decompresses a gzip stream.
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < 10 || data[..2] != GZIP_MAGIC || data[2] != 8 {
+        bail!("not a gzip stream");
+    }
+    let flags = data[3];
+    let mut offset = 10;
+    if flags & FEXTRA != 0 {
+        let len = data
+            .get(offset..offset + 2)
+            .ok_or_else(|| eyre!("truncated gzip header"))?;
+        offset += 2 + usize::from(u16::from_le_bytes([len[0], len[1]]));
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data
+                .get(offset..)
+                .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                .ok_or_else(|| eyre!("truncated gzip header"))?;
+            offset += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        offset += 2;
+    }
+    let deflated = data
+        .get(offset..)
+        .ok_or_else(|| eyre!("truncated gzip header"))?;
+    miniz_oxide::inflate::decompress_to_vec(deflated).map_err(|e| eyre!("invalid gzip data: {e:?}"))
+}
+
+/// Returns true if href is the path of a loose object.
+fn is_loose_object(href: &str) -> bool {
+    let segments: Vec<&str> = href.split('/').filter(|s| !s.is_empty()).collect();
+    matches!(segments.as_slice(), [.., "objects", prefix, rest] if prefix.len() == 2 && matches!(rest.len(), 38 | 62))
+}
+
+/// Undoes an encoding the server applied on top of the file at href, since reqwest hands over
+/// bodies as sent. Returns the decoded body, or None if the body is the file as stored.
+///
+/// Besides honouring `Content-Encoding`, loose objects that arrive gzipped without saying so
+/// are decoded too, so double-compressed objects are never written.
+pub fn decode(href: &str, response: &Response) -> Result<Option<Vec<u8>>> {
+    let body = response.bytes();
+    let encoding = response
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    match encoding.as_str() {
+        "" | "identity" if is_loose_object(href) && body.starts_with(&GZIP_MAGIC) => {
+            Ok(Some(gunzip(body)?))
+        }
+        "" | "identity" => Ok(None),
+        "gzip" | "x-gzip" => Ok(Some(gunzip(body)?)),
+        // Deflate is meant to be zlib wrapped, but some servers send the raw stream.
+        "deflate" => miniz_oxide::inflate::decompress_to_vec_zlib(body)
+            .or_else(|_| miniz_oxide::inflate::decompress_to_vec(body))
+            .map(Some)
+            .map_err(|e| eyre!("invalid deflate data: {e:?}")),
+        encoding => bail!("unsupported content encoding {encoding}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_gzipped_loose_object() {
+        let object = miniz_oxide::deflate::compress_to_vec_zlib(b"blob 0\0", 6);
+        assert!(is_zlib(&object));
+
+        let mut gzipped = vec![0x1f, 0x8b, 8, FNAME, 0, 0, 0, 0, 0, 3];
+        gzipped.extend(b"object\0");
+        gzipped.extend(miniz_oxide::deflate::compress_to_vec(&object, 6));
+        gzipped.extend([0; 8]);
+        assert!(!is_zlib(&gzipped));
+
+        let response = Response::new(Default::default(), Default::default(), gzipped);
+        let href = ".git/objects/e6/9de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+        assert_eq!(decode(href, &response).unwrap(), Some(object));
+        assert_eq!(decode(".git/index", &response).unwrap(), None);
+    }
+}
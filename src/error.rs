@@ -0,0 +1,108 @@
+use reqwest::StatusCode;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Why a dump failed, for callers that react to the kind of failure rather than its message.
+#[derive(Debug)]
+pub enum Error {
+    /// The target does not serve a git HEAD.
+    NotAGitRepo { url: String, reason: String },
+    /// The server refused to list a directory.
+    ListingDenied { url: String, status: StatusCode },
+    /// A request failed or got an unexpected response.
+    Network { url: String, reason: String },
+    /// The target started blocking requests.
+    RateLimited { url: String },
+    /// A request was about to leave the hosts given with --scope.
+    OutOfScope { url: String },
+    /// A file of the dump could not be written.
+    WriteFailed {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A URL or a downloaded file could not be made sense of.
+    ParseFailed { what: String, reason: String },
+    /// The command was given nothing to work on.
+    Usage { reason: String },
+    /// The dump was interrupted before it was complete, and can be resumed.
+    Interrupted { before: String },
+    /// Downloading was declined, at the prompt or by --confirm-above.
+    Declined { reason: String },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Returns the process exit code for the error, following sysexits.h.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage { .. } => 64,
+            Error::ParseFailed { .. } => 65,
+            Error::NotAGitRepo { .. } => 66,
+            Error::Network { .. } => 69,
+            Error::WriteFailed { .. } => 73,
+            Error::RateLimited { .. } | Error::Interrupted { .. } => 75,
+            Error::ListingDenied { .. } | Error::OutOfScope { .. } | Error::Declined { .. } => 77,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotAGitRepo { url, reason } => write!(f, "{url} is not a git HEAD: {reason}"),
+            Error::ListingDenied { url, status } => {
+                write!(f, "{url} refused to list the directory with status code {status}")
+            }
+            Error::Network { url, reason } => write!(f, "{url} {reason}"),
+            Error::RateLimited { url } => write!(
+                f,
+                "Stopped because {url} started blocking requests, try again later or with a lower --rate"
+            ),
+            Error::OutOfScope { url } => write!(f, "Refused to request {url}, outside of --scope"),
+            Error::WriteFailed { path, source } => {
+                write!(f, "Failed to write {}: {source}", path.display())
+            }
+            Error::ParseFailed { what, reason } => write!(f, "{what} {reason}"),
+            Error::Usage { reason } | Error::Declined { reason } => write!(f, "{reason}"),
+            Error::Interrupted { before } => write!(f, "Interrupted before {before}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::WriteFailed { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the exit code for a failed run, 1 unless the failure is one of ours.
+pub fn exit_code(report: &color_eyre::Report) -> i32 {
+    report
+        .downcast_ref::<Error>()
+        .map(Error::exit_code)
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::{eyre, WrapErr};
+
+    #[test]
+    fn maps_wrapped_errors_to_exit_codes() {
+        let result: Result<(), Error> = Err(Error::RateLimited {
+            url: "http://example.com/".to_string(),
+        });
+        let report = result.wrap_err("While dumping").unwrap_err();
+        assert_eq!(exit_code(&report), 75);
+        let interrupted = Error::Interrupted {
+            before: "checkout".to_string(),
+        };
+        assert_eq!(exit_code(&interrupted.into()), 75);
+        assert_eq!(exit_code(&eyre!("Failed to read config.toml")), 1);
+    }
+}
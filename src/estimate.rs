@@ -0,0 +1,107 @@
+use crate::{download::Downloader, expression, pack, response::ResponseExt, transport::Request};
+use log::{debug, warn};
+use pathbuf::pathbuf;
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use walkdir::WalkDir;
+
+/// Size of the dump told apart from the refs and pack indexes alone, before downloading any
+/// object.
+#[derive(Serialize, Default, Debug, Clone)]
+pub struct Estimate {
+    /// Number of refs found, loose and packed.
+    pub refs: usize,
+    /// Number of packs whose index could be read.
+    pub packs: usize,
+    /// Number of objects the packs hold. Loose objects cannot be counted before they are fetched.
+    pub packed_objects: u64,
+    /// Size of the packs in bytes, as announced by the server or read from the indexes.
+    pub packed_bytes: u64,
+}
+
+/// Asks the server for the size of a pack without downloading it.
+async fn content_length(download: &Downloader, href: &str) -> Option<u64> {
+    let url = download.normalize_url(href).ok()?;
+    let request = Request {
+        method: Method::HEAD,
+        ..Request::get(url)
+    };
+    let response = download.send(request).await.ok()?;
+    if response.status() != StatusCode::OK {
+        return None;
+    }
+    response.content_length()
+}
+
+/// Counts the loose refs dumped so far and the refs listed in packed-refs.
+fn count_refs() -> usize {
+    let loose = WalkDir::new(pathbuf![".git", "refs"])
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .count();
+    let packed = std::fs::read_to_string(pathbuf![".git", "packed-refs"])
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^') && !line.is_empty())
+        .count();
+    loose + packed
+}
+
+/// Fetches the refs, the list of packs and the pack indexes, and sums up what the packs hold.
+pub async fn run(download: &Downloader, ref_files: &[String], listing: bool) -> Estimate {
+    download
+        .multiple(&[
+            ".git/packed-refs",
+            ".git/info/refs",
+            ".git/objects/info/packs",
+        ])
+        .await;
+    download.refs_recursive(ref_files).await;
+
+    let mut listed =
+        std::fs::read_to_string(pathbuf![".git", "objects", "info", "packs"]).unwrap_or_default();
+    if listing {
+        match download.collect_links(".git/objects/pack/").await {
+            Ok(links) => listed.push_str(&links.join("\n")),
+            Err(e) => debug!("Failed to list the packs: {e}"),
+        }
+    }
+    let names: BTreeSet<&str> = expression::PACK
+        .captures_iter(&listed)
+        .filter_map(|capture| capture.get(1))
+        .map(|sha1| sha1.as_str())
+        .collect();
+    let indexes: Vec<String> = names
+        .iter()
+        .map(|name| format!(".git/objects/pack/pack-{name}.idx"))
+        .collect();
+    download.multiple(&indexes).await;
+
+    let mut estimate = Estimate {
+        refs: count_refs(),
+        ..Estimate::default()
+    };
+    for name in names {
+        let path = pathbuf![".git", "objects", "pack", &format!("pack-{name}.idx")];
+        let Ok(data) = std::fs::read(path) else {
+            continue;
+        };
+        let stats = match pack::index_stats(&data) {
+            Ok(stats) => stats,
+            Err(e) => {
+                warn!("Failed to read the index of pack-{name}: {e}");
+                continue;
+            }
+        };
+        estimate.packs += 1;
+        estimate.packed_objects += u64::from(stats.objects);
+        // The last object starts where the pack nearly ends, which will do without a length.
+        let href = format!(".git/objects/pack/pack-{name}.pack");
+        estimate.packed_bytes += content_length(download, &href)
+            .await
+            .unwrap_or(stats.max_offset);
+    }
+    estimate
+}
@@ -0,0 +1,203 @@
+use crate::transport::{Request, Response, Transport};
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use futures::future::BoxFuture;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method, StatusCode,
+};
+use std::io;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+/// Browsers whose requests can be imitated to get past servers that block other clients.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Google Chrome on Windows
+    Chrome,
+    /// Mozilla Firefox on Windows
+    Firefox,
+}
+
+impl Profile {
+    /// Returns the headers the browser sends when navigating to a page, in the order it sends them.
+    pub fn headers(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Profile::Chrome => &[
+                ("sec-ch-ua", "\"Chromium\";v=\"124\", \"Google Chrome\";v=\"124\", \"Not-A.Brand\";v=\"99\""),
+                ("sec-ch-ua-mobile", "?0"),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("upgrade-insecure-requests", "1"),
+                ("user-agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36"),
+                ("accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7"),
+                ("sec-fetch-site", "none"),
+                ("sec-fetch-mode", "navigate"),
+                ("sec-fetch-user", "?1"),
+                ("sec-fetch-dest", "document"),
+                ("accept-language", "en-US,en;q=0.9"),
+            ],
+            Profile::Firefox => &[
+                ("user-agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:125.0) Gecko/20100101 Firefox/125.0"),
+                ("accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8"),
+                ("accept-language", "en-US,en;q=0.5"),
+                ("upgrade-insecure-requests", "1"),
+                ("sec-fetch-dest", "document"),
+                ("sec-fetch-mode", "navigate"),
+                ("sec-fetch-site", "none"),
+                ("sec-fetch-user", "?1"),
+            ],
+        }
+    }
+
+    /// Returns the header map of the profile, which keeps the browser's order when sent.
+    pub fn header_map(&self) -> HeaderMap {
+        self.headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Sends requests by running a curl-compatible command, such as one of the curl-impersonate
+/// wrappers, whose TLS and HTTP/2 fingerprints match a real browser where reqwest's cannot.
+pub struct CommandTransport {
+    program: PathBuf,
+    /// Headers sent with every request, before the request's own.
+    headers: HeaderMap,
+    proxy: Option<String>,
+}
+
+impl CommandTransport {
+    pub fn new(program: PathBuf, headers: HeaderMap, proxy: Option<String>) -> Self {
+        CommandTransport {
+            program,
+            headers,
+            proxy,
+        }
+    }
+}
+
+/// Parses the output of `curl --include`, skipping interim responses such as 100 Continue.
+fn parse_output(mut output: &[u8]) -> Result<Response> {
+    loop {
+        let end = output
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| eyre!("incomplete response headers"))?;
+        let head = String::from_utf8_lossy(&output[..end]);
+        output = &output[end + 4..];
+        let mut lines = head.lines();
+        let status_line = lines.next().unwrap_or_default();
+        let code = status_line
+            .split_whitespace()
+            .nth(1)
+            .ok_or_else(|| eyre!("invalid status line {status_line:?}"))?;
+        let status = StatusCode::from_bytes(code.as_bytes())?;
+        if status.is_informational() {
+            continue;
+        }
+        let mut headers = HeaderMap::new();
+        for line in lines {
+            if let Some((name, value)) = line.split_once(':') {
+                if let (Ok(name), Ok(value)) = (
+                    HeaderName::from_bytes(name.trim().as_bytes()),
+                    HeaderValue::from_str(value.trim()),
+                ) {
+                    headers.append(name, value);
+                }
+            }
+        }
+        return Ok(Response::new(status, headers, output.to_vec()));
+    }
+}
+
+/// Returns the kind of I/O failure an exit code of curl stands for, so that retries tell failures
+/// of the connection apart from the rest.
+fn failure_kind(code: Option<i32>) -> io::ErrorKind {
+    match code {
+        Some(7) => io::ErrorKind::ConnectionRefused,
+        Some(28) => io::ErrorKind::TimedOut,
+        Some(52 | 55 | 56) => io::ErrorKind::ConnectionReset,
+        _ => io::ErrorKind::Other,
+    }
+}
+
+impl Transport for CommandTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move {
+            let mut command = Command::new(&self.program);
+            command.args(["--silent", "--show-error", "--include"]);
+            // With --request HEAD, curl waits for the body the Content-Length announces.
+            if request.method == Method::HEAD {
+                command.arg("--head");
+            } else {
+                command.arg("--request").arg(request.method.as_str());
+            }
+            let mut headers = self.headers.clone();
+            headers.extend(request.headers);
+            for (name, value) in &headers {
+                command.arg("--header").arg(format!(
+                    "{}: {}",
+                    name,
+                    value.to_str().unwrap_or_default()
+                ));
+            }
+            if let Some(proxy) = &self.proxy {
+                command.arg("--proxy").arg(proxy);
+            }
+            if request.body.is_some() {
+                command.args(["--data-binary", "@-"]);
+            }
+            command
+                .arg("--")
+                .arg(request.url.as_str())
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true);
+
+            let mut child = command
+                .spawn()
+                .wrap_err(format!("Failed to run {}", self.program.display()))?;
+            let mut stdin = child.stdin.take();
+            if let (Some(stdin), Some(body)) = (&mut stdin, &request.body) {
+                stdin.write_all(body).await?;
+            }
+            drop(stdin);
+            let output = child.wait_with_output().await?;
+            if !output.status.success() {
+                let message = format!(
+                    "{} failed: {}",
+                    self.program.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                return Err(io::Error::new(failure_kind(output.status.code()), message).into());
+            }
+            parse_output(&output.stdout)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_curl_output() {
+        let output =
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/2 200\r\ncontent-type: text/plain\r\n\r\nref: refs/heads/master\n";
+        let response = parse_output(output).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert_eq!(response.bytes(), b"ref: refs/heads/master\n");
+    }
+}
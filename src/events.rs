@@ -0,0 +1,190 @@
+use crate::{
+    middleware::{Middleware, Next},
+    redact,
+    transport::{Request, Response},
+};
+use color_eyre::{eyre::WrapErr, Result};
+use futures::{
+    future::BoxFuture,
+    stream::{self, Stream, StreamExt},
+};
+use log::{debug, warn};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::{sync::broadcast, task::JoinHandle};
+
+/// Events a subscriber that falls this far behind misses the oldest of.
+const CAPACITY: usize = 1024;
+
+/// Something the downloader did, for an embedding application to render progress from.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A request is about to be sent, once per attempt.
+    FetchStarted { method: String, url: String },
+    /// A response arrived, or the request failed with error.
+    FetchFinished {
+        method: String,
+        url: String,
+        status: Option<u16>,
+        bytes: usize,
+        error: Option<String>,
+    },
+    /// A downloaded file was written to path in the output directory.
+    Written { path: String, bytes: usize },
+}
+
+/// Broadcasts the events of a downloader to every subscriber, dropping them when nobody listens.
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Events {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Events {
+    pub fn emit(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Returns the events emitted from now on, ending once the downloader is dropped.
+    pub fn subscribe(&self) -> impl Stream<Item = Event> {
+        stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        debug!("An event subscriber fell behind and missed {missed} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+impl Middleware for Arc<Events> {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            let method = request.method.to_string();
+            let url = request.url.to_string();
+            self.emit(Event::FetchStarted {
+                method: method.clone(),
+                url: url.clone(),
+            });
+            let result = next.run(request).await;
+            self.emit(match &result {
+                Ok(response) => Event::FetchFinished {
+                    method,
+                    url,
+                    status: Some(response.status().as_u16()),
+                    bytes: response.bytes().len(),
+                    error: None,
+                },
+                Err(e) => Event::FetchFinished {
+                    method,
+                    url,
+                    status: None,
+                    bytes: 0,
+                    error: Some(e.to_string()),
+                },
+            });
+            result
+        })
+    }
+}
+
+/// Appends every event of the stream to the file at path as a line of JSON, until the stream ends.
+/// The hosts are masked under --redact like in the logs.
+pub fn record(
+    path: &Path,
+    events: impl Stream<Item = Event> + Send + 'static,
+) -> Result<JoinHandle<()>> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .wrap_err(format!("Failed to open {}", path.display()))?;
+    let path = path.to_path_buf();
+    Ok(tokio::spawn(async move {
+        let mut events = std::pin::pin!(events);
+        while let Some(event) = events.next().await {
+            let line = serde_json::to_string(&event).unwrap_or_default();
+            if let Err(e) = writeln!(file, "{}", redact::text(&line)) {
+                warn!("Failed to record events to {}: {e}", path.display());
+                return;
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Transport;
+    use reqwest::{header::HeaderMap, StatusCode};
+    use url::Url;
+
+    /// Answers every request with a 404.
+    struct Missing;
+
+    impl Transport for Missing {
+        fn send(&self, _: Request) -> BoxFuture<'_, Result<Response>> {
+            Box::pin(async {
+                Ok(Response::new(
+                    StatusCode::NOT_FOUND,
+                    HeaderMap::new(),
+                    b"gone".to_vec(),
+                ))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_requests() {
+        let events = Arc::new(Events::default());
+        let stream = events.subscribe();
+        let middlewares: Vec<Box<dyn Middleware>> = vec![Box::new(events.clone())];
+        let url = Url::parse("http://example.com/.git/HEAD").unwrap();
+        Next::new(&middlewares, &Missing)
+            .run(Request::get(url))
+            .await
+            .unwrap();
+        events.emit(Event::Written {
+            path: ".git/HEAD".to_string(),
+            bytes: 4,
+        });
+        drop(middlewares);
+        drop(events);
+
+        let url = "http://example.com/.git/HEAD".to_string();
+        assert_eq!(
+            stream.collect::<Vec<_>>().await,
+            [
+                Event::FetchStarted {
+                    method: "GET".to_string(),
+                    url: url.clone()
+                },
+                Event::FetchFinished {
+                    method: "GET".to_string(),
+                    url,
+                    status: Some(404),
+                    bytes: 4,
+                    error: None
+                },
+                Event::Written {
+                    path: ".git/HEAD".to_string(),
+                    bytes: 4
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use crate::{checkout, manifest, object::Kind, repository::Repository, seal};
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use miniz_oxide::deflate::core::{
+    compress_to_output, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Size of a tar header and the unit tar pads file contents to.
+const BLOCK: usize = 512;
+
+/// Prefix shared by the files and directories opendotgit writes next to the recovered tree.
+const OWN_PREFIX: &str = "opendotgit-";
+
+/// Returns the type number of an object kind in a pack.
+fn pack_type(kind: Kind) -> u8 {
+    match kind {
+        Kind::Commit => 1,
+        Kind::Tree => 2,
+        Kind::Blob => 3,
+        Kind::Tag => 4,
+    }
+}
+
+/// Encodes the type and inflated size of a packed object.
+fn pack_header(kind: Kind, size: usize) -> Vec<u8> {
+    let mut header = vec![pack_type(kind) << 4 | (size & 0x0f) as u8];
+    let mut size = size >> 4;
+    while size > 0 {
+        *header.last_mut().unwrap() |= 0x80;
+        header.push((size & 0x7f) as u8);
+        size >>= 7;
+    }
+    header
+}
+
+/// Writes every readable object of the repository into a git bundle listing HEAD and its refs,
+/// returning the number of objects.
+///
+/// The pack is written undeltified with a placeholder count, which is filled in once it is known
+/// before the file is read back to compute the trailing checksum.
+pub fn bundle(repo: &Repository, path: &Path) -> Result<usize> {
+    let mut refs = repo.references();
+    if let Ok(head) = repo.resolve("HEAD") {
+        refs.insert(0, ("HEAD".to_string(), head));
+    }
+    if refs.is_empty() {
+        bail!("the repository has no refs to bundle");
+    }
+    let sha256 = refs[0].1.len() == 64;
+    let mut ids: Vec<String> = repo.objects()?.into_iter().collect();
+    ids.sort();
+
+    // The pack is read back for its checksum.
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .wrap_err(format!("Failed to create {}", path.display()))?;
+    let mut file = BufWriter::new(file);
+    if sha256 {
+        file.write_all(b"# v3 git bundle\n@object-format=sha256\n")?;
+    } else {
+        file.write_all(b"# v2 git bundle\n")?;
+    }
+    for (name, id) in &refs {
+        writeln!(file, "{id} {name}")?;
+    }
+    file.write_all(b"\n")?;
+    let pack_start = file.stream_position()?;
+
+    file.write_all(b"PACK\0\0\0\x02\0\0\0\0")?;
+    let mut count: u32 = 0;
+    for id in &ids {
+        let Ok(object) = repo.find(id) else {
+            continue;
+        };
+        file.write_all(&pack_header(object.kind, object.data.len()))?;
+        file.write_all(&miniz_oxide::deflate::compress_to_vec_zlib(&object.data, 6))?;
+        count += 1;
+    }
+    let mut file = file.into_inner()?;
+    file.seek(SeekFrom::Start(pack_start + 8))?;
+    file.write_all(&count.to_be_bytes())?;
+
+    file.seek(SeekFrom::Start(pack_start))?;
+    let mut pack = Vec::new();
+    file.read_to_end(&mut pack)?;
+    let checksum = if sha256 {
+        Sha256::digest(&pack).to_vec()
+    } else {
+        Sha1::digest(&pack).to_vec()
+    };
+    file.write_all(&checksum)?;
+    Ok(count as usize)
+}
+
+/// Computes the CRC-32 gzip ends with.
+#[derive(Default)]
+struct Crc32(u32);
+
+impl Crc32 {
+    fn update(&mut self, data: &[u8]) {
+        let mut crc = !self.0;
+        for &byte in data {
+            crc ^= u32::from(byte);
+            for _ in 0..8 {
+                crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+            }
+        }
+        self.0 = !crc;
+    }
+}
+
+/// Compresses everything written to it into a gzip stream.
+struct GzipWriter<W: Write> {
+    inner: W,
+    compressor: Box<CompressorOxide>,
+    crc: Crc32,
+    size: u32,
+}
+
+impl<W: Write> GzipWriter<W> {
+    fn new(mut inner: W) -> Result<Self> {
+        // No name, no modification time and an unknown operating system, RFC 1952 section 2.3.
+        inner.write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255])?;
+        Ok(GzipWriter {
+            inner,
+            compressor: Box::new(CompressorOxide::new(create_comp_flags_from_zip_params(
+                6, -15, 0,
+            ))),
+            crc: Crc32::default(),
+            size: 0,
+        })
+    }
+
+    fn deflate(&mut self, data: &[u8], flush: TDEFLFlush) -> std::io::Result<()> {
+        let mut result = Ok(());
+        let inner = &mut self.inner;
+        let (status, consumed) = compress_to_output(&mut self.compressor, data, flush, |output| {
+            result = inner.write_all(output);
+            result.is_ok()
+        });
+        result?;
+        if consumed != data.len()
+            || matches!(status, TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed)
+        {
+            return Err(std::io::Error::other("deflate failed"));
+        }
+        Ok(())
+    }
+
+    /// Ends the stream with its checksum and size, returning the inner writer.
+    fn finish(mut self) -> std::io::Result<W> {
+        self.deflate(&[], TDEFLFlush::Finish)?;
+        self.inner.write_all(&self.crc.0.to_le_bytes())?;
+        self.inner.write_all(&self.size.to_le_bytes())?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for GzipWriter<W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.deflate(data, TDEFLFlush::None)?;
+        self.crc.update(data);
+        // The size is stored modulo 2^32.
+        self.size = self.size.wrapping_add(data.len() as u32);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes a ustar header, preceded by a GNU long name entry when the path does not fit.
+fn write_header(
+    out: &mut impl Write,
+    path: &str,
+    mode: u32,
+    size: u64,
+    kind: u8,
+    link: &str,
+) -> Result<()> {
+    if path.len() > 100 {
+        let name = format!("{path}\0");
+        write_header(out, "././@LongLink", 0, name.len() as u64, b'L', "")?;
+        out.write_all(name.as_bytes())?;
+        out.write_all(&vec![0; padding(name.len() as u64)])?;
+    }
+    let mut header = [0u8; BLOCK];
+    let mut field = |offset: usize, len: usize, value: &[u8]| {
+        let len = value.len().min(len);
+        header[offset..offset + len].copy_from_slice(&value[..len]);
+    };
+    field(0, 100, path.as_bytes());
+    field(100, 8, format!("{mode:07o}\0").as_bytes());
+    field(108, 8, b"0000000\0");
+    field(116, 8, b"0000000\0");
+    field(124, 12, format!("{size:011o}\0").as_bytes());
+    field(136, 12, b"00000000000\0");
+    field(156, 1, &[kind]);
+    field(157, 100, link.as_bytes());
+    field(257, 8, b"ustar\x0000");
+    // The checksum is computed with its own field filled with spaces.
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    out.write_all(&header)?;
+    Ok(())
+}
+
+/// Returns the number of zero bytes that pad size bytes of content to a whole block.
+fn padding(size: u64) -> usize {
+    (BLOCK - (size % BLOCK as u64) as usize) % BLOCK
+}
+
+/// Returns true if a top-level entry of the output directory is not part of the worktree.
+fn is_bookkeeping(name: &str) -> bool {
+    name == ".git"
+        || name.starts_with(OWN_PREFIX)
+        || [
+            manifest::MANIFEST,
+            checkout::MISSING_FILES,
+            seal::SEAL_MANIFEST,
+            seal::TIMESTAMP_QUERY,
+            seal::TIMESTAMP_REPLY,
+        ]
+        .contains(&name)
+}
+
+/// Packages the current directory into a gzipped tarball at path, leaving out the .git directory
+/// and opendotgit's own files when only the worktree is wanted. Returns the number of files.
+pub fn archive(path: &Path, worktree_only: bool) -> Result<usize> {
+    // The archive may be written into the directory it packages.
+    let own_path: Option<PathBuf> = std::path::absolute(path).ok();
+    let file = File::create(path).wrap_err(format!("Failed to create {}", path.display()))?;
+    let mut out = GzipWriter::new(BufWriter::new(file))?;
+    let files = write_tar(&mut out, worktree_only, own_path.as_deref())?;
+    out.finish()?.flush()?;
+    Ok(files)
+}
+
+/// Formats a dump can be streamed in with --to-stdout.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// An uncompressed tar archive of the output directory
+    Tar,
+}
+
+/// Writes the current directory to out in format, returning the number of files.
+pub fn stream(out: impl Write, format: StreamFormat) -> Result<usize> {
+    let mut out = BufWriter::new(out);
+    let files = match format {
+        StreamFormat::Tar => write_tar(&mut out, false, None)?,
+    };
+    out.flush()?;
+    Ok(files)
+}
+
+/// Writes the entries of the current directory to out as a tar archive, except for the file at
+/// skipped and, when only the worktree is wanted, the bookkeeping. Returns the number of files.
+fn write_tar(out: &mut impl Write, worktree_only: bool, skipped: Option<&Path>) -> Result<usize> {
+    let mut files = 0;
+    let walker = WalkDir::new(".")
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            !(worktree_only
+                && entry.depth() == 1
+                && is_bookkeeping(&entry.file_name().to_string_lossy()))
+        });
+    for entry in walker {
+        let entry = entry?;
+        if skipped.is_some() && skipped == std::path::absolute(entry.path()).ok().as_deref() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(".")?;
+        let name = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let file_type = entry.file_type();
+        let metadata = entry.metadata()?;
+        if file_type.is_symlink() {
+            let target = std::fs::read_link(entry.path())?;
+            write_header(out, &name, 0o777, 0, b'2', &target.to_string_lossy())?;
+        } else if file_type.is_dir() {
+            write_header(out, &format!("{name}/"), 0o755, 0, b'5', "")?;
+        } else if file_type.is_file() {
+            let mode = if is_executable(&metadata) {
+                0o755
+            } else {
+                0o644
+            };
+            write_header(out, &name, mode, metadata.len(), b'0', "")?;
+            let copied = std::io::copy(&mut File::open(entry.path())?, out)?;
+            if copied != metadata.len() {
+                return Err(eyre!("{name} changed while it was archived"));
+            }
+            out.write_all(&vec![0; padding(copied)])?;
+            files += 1;
+        }
+    }
+    // Two empty blocks end the archive.
+    out.write_all(&[0; 2 * BLOCK])?;
+    Ok(files)
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_: &std::fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_pack_headers() {
+        assert_eq!(pack_header(Kind::Blob, 10), [0x3a]);
+        // 300 = 0b1_0010_1100, the low four bits first and the rest in groups of seven.
+        assert_eq!(pack_header(Kind::Commit, 300), [0x9c, 0x12]);
+    }
+
+    #[test]
+    fn gzips_what_is_written() {
+        let mut out = GzipWriter::new(Vec::new()).unwrap();
+        out.write_all(b"ref: refs/heads/master\n").unwrap();
+        let gzipped = out.finish().unwrap();
+        assert_eq!(
+            crate::encoding::gunzip(&gzipped).unwrap(),
+            b"ref: refs/heads/master\n"
+        );
+        // The CRC-32 of the content, as computed by zlib.
+        let mut crc = Crc32::default();
+        crc.update(b"123456789");
+        assert_eq!(crc.0, 0xcbf4_3926);
+    }
+}
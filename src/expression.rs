@@ -1,16 +1,28 @@
 use lazy_static::lazy_static;
-use regex::Regex;
+use regex::{bytes, Regex};
 
 lazy_static! {
-    /// A regular expression that matches references (e.g. "refs/heads/master").
-    pub static ref REFS: Regex = Regex::new(r"(refs(/[\w\-\.\*]+)*/[\w\-\.]+)").unwrap();
+    /// A regular expression that matches references (e.g. "refs/heads/master"), made of any bytes
+    /// git allows in a ref name, so names that are not ASCII or not even UTF-8 are kept whole.
+    pub static ref REFS: bytes::Regex = bytes::Regex::new(r"(?-u)refs/[^\x00-\x20\x7f~^:?*\[\\]+").unwrap();
 
-    /// A regular expression that matches a commit hash or a reference name.
-    pub static ref HEAD: Regex = Regex::new(r"^(ref:.*|[0-9a-f]{40}$)").unwrap();
+    /// A regular expression that matches a SHA-1 or SHA-256 commit hash or a reference name.
+    pub static ref HEAD: Regex = Regex::new(r"^(ref:.*|[0-9a-f]{40}$|[0-9a-f]{64}$)").unwrap();
 
     /// A regular expression that matches the name of a pack file.
     pub static ref PACK: Regex = Regex::new(r"pack-([a-f0-9]{40})\.pack").unwrap();
 
     /// A regular expression that matches object hashes (e.g. "1a410efbd13591db07496601ebc7a059dd55cfe9").
-    pub static ref OBJECT: Regex = Regex::new(r"(^|\s)([a-f0-9]{40})($|\s)").unwrap();
+    /// It runs over raw bytes so files with invalid UTF-8 can still be scanned.
+    pub static ref OBJECT: bytes::Regex = bytes::Regex::new(r"(^|\s)([a-f0-9]{40})($|\s)").unwrap();
+
+    /// A regular expression that matches abbreviated object hashes as git prints them (e.g. "1a410ef").
+    pub static ref ABBREVIATED: Regex = Regex::new(r"\b[0-9a-f]{7,12}\b").unwrap();
+
+    /// A regular expression that matches a path inside a .git directory wherever it appears
+    /// (e.g. "/var/www/app/.git/refs/heads/main"), capturing the part below the directory.
+    pub static ref GIT_PATH: Regex = Regex::new(r"\.git/([\w\-./]+)").unwrap();
+
+    /// A regular expression that matches a full SHA-1 or SHA-256 object hash anywhere in a text.
+    pub static ref EMBEDDED_OBJECT: Regex = Regex::new(r"\b([0-9a-f]{64}|[0-9a-f]{40})\b").unwrap();
 }
@@ -0,0 +1,225 @@
+use crate::{
+    diff::{self, Change, Status},
+    repository::Repository,
+};
+use color_eyre::Result;
+use log::warn;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Directory inside the dump that deleted files are restored to.
+pub const DELETED_DIR: &str = "opendotgit-deleted";
+
+/// Directory inside the dump that stashes are written to.
+pub const STASH_DIR: &str = "opendotgit-stash";
+
+/// A stash written out as patches and the files it changed.
+pub struct Stash {
+    /// Position in the stash list, `stash@{index}`.
+    pub index: usize,
+    pub commit: String,
+    pub message: String,
+    /// Paths changed by the stash, including untracked files.
+    pub files: Vec<String>,
+    /// Directory the patches and files were written to.
+    pub dir: PathBuf,
+}
+
+/// A file that was removed in the history of the repository.
+#[derive(Serialize)]
+pub struct DeletedFile {
+    pub path: String,
+    /// The commit that deleted the file.
+    pub commit: String,
+    /// The blob holding the last version of the file.
+    pub blob: String,
+    /// Whether the last version was written to the deleted files directory.
+    pub recovered: bool,
+}
+
+/// Returns path if it stays inside the directory it is joined to and does not reach into a .git directory.
+pub fn relative(path: &Path) -> Option<PathBuf> {
+    path.components()
+        .all(|component| match component {
+            Component::Normal(name) => !name.eq_ignore_ascii_case(".git"),
+            _ => false,
+        })
+        .then(|| path.to_path_buf())
+}
+
+/// Writes content to path below dir, creating the directories in between.
+fn write_below(dir: &Path, path: &Path, content: &[u8]) -> Result<()> {
+    let target = dir.join(path);
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(target, content)?;
+    Ok(())
+}
+
+/// Finds files deleted in any commit of the repository in the current directory and restores
+/// their last version under [`DELETED_DIR`]. Only the most recent deletion of each path is kept.
+pub fn deleted_files(repo: &Repository) -> Result<Vec<DeletedFile>> {
+    let mut seen = HashSet::new();
+    let mut deleted = Vec::new();
+    for (id, commit) in repo.commits() {
+        let Some(parent) = commit.parents.first() else {
+            continue;
+        };
+        let Ok(parent) = repo.commit(parent) else {
+            continue;
+        };
+        let Ok(changes) = diff::trees(repo, Some(&parent.tree), Some(&commit.tree)) else {
+            continue;
+        };
+        for change in changes {
+            if change.status != Status::Deleted {
+                continue;
+            }
+            let Some(path) = relative(Path::new(&change.path)) else {
+                continue;
+            };
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            let blob = change.old.unwrap_or_default();
+            let recovered = match repo.blob(&blob) {
+                Ok(content) => {
+                    write_below(Path::new(DELETED_DIR), &path, &content)?;
+                    true
+                }
+                Err(_) => false,
+            };
+            deleted.push(DeletedFile {
+                path: path.display().to_string(),
+                commit: id.clone(),
+                blob,
+                recovered,
+            });
+        }
+    }
+    Ok(deleted)
+}
+
+/// The old and new content of a changed file, absent for added and deleted files respectively.
+type Contents = (Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Reads both versions of a changed file, None if either is missing from the dump.
+fn contents(repo: &Repository, change: &Change) -> Option<Contents> {
+    let read = |id: &Option<String>| match id {
+        Some(id) => repo.blob(id).ok().map(Some),
+        None => Some(None),
+    };
+    Some((read(&change.old)?, read(&change.new)?))
+}
+
+/// Renders a list of changes as one patch and, given files_dir, writes the new version of every
+/// changed file under it. Returns the changed paths; those whose content is missing from the dump
+/// are left out of the patch.
+fn write_changes(
+    repo: &Repository,
+    changes: &[Change],
+    patch_path: &Path,
+    files_dir: Option<&Path>,
+) -> Result<Vec<String>> {
+    let mut patch = String::new();
+    let mut files = Vec::new();
+    for change in changes {
+        let Some(path) = relative(Path::new(&change.path)) else {
+            continue;
+        };
+        files.push(change.path.clone());
+        let Some((old, new)) = contents(repo, change) else {
+            warn!(
+                "Leaving {} out of {}, its content is missing from the dump",
+                change.path,
+                patch_path.display()
+            );
+            continue;
+        };
+        patch.push_str(&diff::unified(&change.path, old.as_deref(), new.as_deref()));
+        if let (Some(files_dir), Some(content)) = (files_dir, &new) {
+            write_below(files_dir, &path, content)?;
+        }
+    }
+    std::fs::write(patch_path, patch)?;
+    Ok(files)
+}
+
+/// Writes every stash of the repository in the current directory under [`STASH_DIR`].
+///
+/// A stash is a commit on top of the commit it was made on whose second parent records the index
+/// and whose optional third parent records untracked files. Each gets its own directory holding
+/// `worktree.patch`, `index.patch`, `untracked.patch` and the stashed versions of the changed files.
+pub fn stashes(repo: &Repository) -> Result<Vec<Stash>> {
+    // The reflog of refs/stash lists every stash, the reference itself only the latest.
+    let mut entries: Vec<(String, String)> = repo
+        .reflog("refs/stash")
+        .into_iter()
+        .map(|entry| (entry.new, entry.message))
+        .collect();
+    if entries.is_empty() {
+        if let Ok(id) = repo.resolve("refs/stash") {
+            entries.push((id, String::new()));
+        }
+    }
+
+    let mut stashes = Vec::new();
+    for (index, (id, message)) in entries.into_iter().enumerate() {
+        let commit = match repo.commit(&id) {
+            Ok(commit) => commit,
+            Err(e) => {
+                warn!("stash@{{{index}}} ({id}) is missing from the dump: {e}");
+                continue;
+            }
+        };
+        let message = if message.is_empty() {
+            commit.summary().to_string()
+        } else {
+            message
+        };
+        let dir = Path::new(STASH_DIR).join(format!("stash-{index}"));
+        let files_dir = dir.join("files");
+        std::fs::create_dir_all(&dir)?;
+
+        let tree_of = |parent: usize| {
+            commit
+                .parents
+                .get(parent)
+                .and_then(|id| repo.commit(id).ok())
+                .map(|parent| parent.tree)
+        };
+        let base = tree_of(0);
+        let changes = diff::trees(repo, base.as_deref(), Some(&commit.tree))?;
+        let mut files = write_changes(
+            repo,
+            &changes,
+            &dir.join("worktree.patch"),
+            Some(&files_dir),
+        )?;
+
+        if let Some(staged) = tree_of(1) {
+            let changes = diff::trees(repo, base.as_deref(), Some(&staged))?;
+            write_changes(repo, &changes, &dir.join("index.patch"), None)?;
+        }
+        if let Some(untracked) = tree_of(2) {
+            let changes = diff::trees(repo, None, Some(&untracked))?;
+            files.extend(write_changes(
+                repo,
+                &changes,
+                &dir.join("untracked.patch"),
+                Some(&files_dir),
+            )?);
+        }
+
+        stashes.push(Stash {
+            index,
+            commit: id,
+            message,
+            files,
+            dir,
+        });
+    }
+    Ok(stashes)
+}
@@ -0,0 +1,135 @@
+use crate::report::Report;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+/// The formats the findings report can be written in.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum Format {
+    /// The full report as JSON
+    #[default]
+    Json,
+    /// One finding per row
+    Csv,
+    /// Static Analysis Results Interchange Format, for code scanning dashboards
+    Sarif,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Sarif => "sarif",
+        }
+    }
+}
+
+/// A single finding shared by the flat export formats.
+struct Finding {
+    rule: &'static str,
+    message: String,
+    location: String,
+}
+
+/// Flattens the detection result and the harvested secrets of a report into findings.
+fn findings(report: &Report) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if report.exposed {
+        let mode = if report.listing { "listing" } else { "blind" };
+        findings.push(Finding {
+            rule: "exposed-git-directory",
+            message: format!("Git directory is publicly accessible ({mode} mode)"),
+            location: report.url.clone(),
+        });
+    }
+    for credential in &report.credentials {
+        findings.push(Finding {
+            rule: "leaked-credential",
+            message: format!("{:?}: {}", credential.kind, credential.value),
+            location: credential.source.clone(),
+        });
+    }
+    for secret in &report.secrets {
+        findings.push(Finding {
+            rule: "leaked-secret",
+            message: format!("{}: {}", secret.rule, secret.value),
+            location: format!("{}:{} ({})", secret.path, secret.line, secret.commit),
+        });
+    }
+    findings
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Renders the findings of a report as CSV with a header row.
+pub fn csv(report: &Report) -> String {
+    let mut text = String::from("target,rule,message,location\n");
+    for finding in findings(report) {
+        let row = [
+            report.url.as_str(),
+            finding.rule,
+            &finding.message,
+            &finding.location,
+        ]
+        .map(csv_field)
+        .join(",");
+        text.push_str(&row);
+        text.push('\n');
+    }
+    text
+}
+
+/// Renders the findings of a report as a SARIF 2.1.0 log.
+pub fn sarif(report: &Report) -> Value {
+    let results: Vec<Value> = findings(report)
+        .into_iter()
+        .map(|finding| {
+            json!({
+                "ruleId": finding.rule,
+                "level": "error",
+                "message": { "text": finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": finding.location }
+                    }
+                }]
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/lavafroth/opendotgit",
+                    "rules": [
+                        {
+                            "id": "exposed-git-directory",
+                            "shortDescription": { "text": "Publicly accessible .git directory" }
+                        },
+                        {
+                            "id": "leaked-credential",
+                            "shortDescription": { "text": "Credential found in the repository configuration" }
+                        },
+                        {
+                            "id": "leaked-secret",
+                            "shortDescription": { "text": "Secret found in a committed file" }
+                        }
+                    ]
+                }
+            },
+            "results": results
+        }]
+    })
+}
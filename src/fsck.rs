@@ -0,0 +1,237 @@
+use crate::{
+    object::{self, Commit, Kind},
+    repository::Repository,
+};
+use color_eyre::{eyre::WrapErr, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// What keeps an object needed by a ref from being used.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Defect {
+    Missing,
+    Corrupt,
+}
+
+impl Defect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Defect::Missing => "missing",
+            Defect::Corrupt => "corrupt",
+        }
+    }
+}
+
+/// The defect of an object found while walking from one ref, before knowing every ref needing it.
+#[derive(Clone)]
+struct Damage {
+    defect: Defect,
+    reason: Option<String>,
+}
+
+/// An object the refs need that was not recovered intact.
+#[derive(Serialize, Debug)]
+pub struct Problem {
+    pub id: String,
+    pub defect: Defect,
+    /// Why a corrupt object could not be used.
+    pub reason: Option<String>,
+    /// The refs whose history needs the object, empty if git did the check.
+    pub refs: Vec<String>,
+}
+
+/// Outcome of checking that every object reachable from the refs was recovered intact.
+#[derive(Serialize, Default, Debug)]
+pub struct Fsck {
+    /// Number of reachable objects that were read and verified.
+    pub checked: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl Fsck {
+    pub fn count(&self, defect: Defect) -> usize {
+        self.problems
+            .iter()
+            .filter(|problem| problem.defect == defect)
+            .count()
+    }
+}
+
+/// Returns the path of the loose object with the given ID, relative to the repository root.
+pub fn loose_href(id: &str) -> String {
+    format!(".git/objects/{}/{}", &id[..2], &id[2..])
+}
+
+/// Reads and verifies an object, returning the IDs of the objects it points to.
+fn inspect(repo: &Repository, id: &str, shallow: &HashSet<String>) -> Result<Vec<String>, Damage> {
+    let corrupt = |reason: String| Damage {
+        defect: Defect::Corrupt,
+        reason: Some(reason),
+    };
+    let object = match repo.find(id) {
+        Ok(object) => object,
+        Err(e) if repo.stores(id) => return Err(corrupt(e.to_string())),
+        Err(_) => {
+            return Err(Damage {
+                defect: Defect::Missing,
+                reason: None,
+            })
+        }
+    };
+    let actual = object.id(id.len() / 2);
+    if actual != id {
+        return Err(corrupt(format!("hashes to {actual}")));
+    }
+    let children = match object.kind {
+        Kind::Commit => Commit::parse(&object.data).map(|commit| {
+            let mut ids = vec![commit.tree];
+            // The parents of a shallow clone's boundary commits were never fetched.
+            if !shallow.contains(id) {
+                ids.extend(commit.parents);
+            }
+            ids
+        }),
+        Kind::Tree => object::parse_tree(&object.data, id.len() / 2).map(|entries| {
+            entries
+                .into_iter()
+                .filter(|entry| entry.kind().is_some())
+                .map(|entry| entry.id)
+                .collect()
+        }),
+        Kind::Tag => object::parse_tag(&object.data).map(|(target, _)| vec![target]),
+        Kind::Blob => Ok(Vec::new()),
+    };
+    children.map_err(|e| corrupt(e.to_string()))
+}
+
+/// Walks every object reachable from HEAD and the refs, recording which refs need each object
+/// that is missing or corrupt.
+pub fn check(repo: &Repository) -> Fsck {
+    let shallow: HashSet<String> = std::fs::read_to_string(repo.git_dir().join("shallow"))
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    let mut refs = repo.references();
+    if let Ok(head) = repo.resolve("HEAD") {
+        refs.insert(0, ("HEAD".to_string(), head));
+    }
+
+    // Objects are read once, every ref then walks the links remembered from the first read.
+    let mut graph: HashMap<String, Result<Vec<String>, Damage>> = HashMap::new();
+    let mut problems: BTreeMap<String, Problem> = BTreeMap::new();
+    for (name, tip) in refs {
+        let mut seen = HashSet::new();
+        let mut stack = vec![tip];
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            let node = graph
+                .entry(id.clone())
+                .or_insert_with(|| inspect(repo, &id, &shallow));
+            match node {
+                Ok(children) => stack.extend(children.iter().cloned()),
+                Err(damage) => problems
+                    .entry(id.clone())
+                    .or_insert_with(|| Problem {
+                        id,
+                        defect: damage.defect,
+                        reason: damage.reason.clone(),
+                        refs: Vec::new(),
+                    })
+                    .refs
+                    .push(name.clone()),
+            }
+        }
+    }
+    Fsck {
+        checked: graph.values().filter(|node| node.is_ok()).count(),
+        problems: problems.into_values().collect(),
+    }
+}
+
+/// Parses the output of `git fsck`, which names the broken objects but not the refs needing them.
+#[cfg(any(test, not(feature = "git2")))]
+fn parse_git_fsck(output: &str) -> Fsck {
+    let mut problems = BTreeMap::new();
+    for line in output.lines() {
+        let (id, defect) = if let Some(rest) = line.strip_prefix("missing ") {
+            match rest.split_whitespace().nth(1) {
+                Some(id) => (id, Defect::Missing),
+                None => continue,
+            }
+        } else if let Some((id, _)) = line
+            .strip_prefix("error: ")
+            .and_then(|rest| rest.split_once(": object corrupt or missing"))
+        {
+            (id, Defect::Corrupt)
+        } else {
+            continue;
+        };
+        problems.entry(id.to_string()).or_insert_with(|| Problem {
+            id: id.to_string(),
+            defect,
+            reason: None,
+            refs: Vec::new(),
+        });
+    }
+    Fsck {
+        checked: 0,
+        problems: problems.into_values().collect(),
+    }
+}
+
+/// Lets git check the dump, for packs that cannot be read without libgit2.
+#[cfg(not(feature = "git2"))]
+fn git_fsck() -> Result<Fsck> {
+    use color_eyre::Section;
+
+    let output = std::process::Command::new("git")
+        .args(["fsck", "--no-dangling", "--no-progress"])
+        .output()
+        .wrap_err("Failed to run git fsck")
+        .suggestion("Make sure your system has git installed")?;
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(parse_git_fsck(&text))
+}
+
+/// Checks the repository dumped into the current directory.
+pub fn run() -> Result<Fsck> {
+    let repo = Repository::open(".").wrap_err("Failed to open the dumped repository")?;
+    #[cfg(not(feature = "git2"))]
+    {
+        let has_packs = std::fs::read_dir(repo.git_dir().join("objects").join("pack"))
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .any(|entry| entry.file_name().to_string_lossy().ends_with(".pack"))
+            })
+            .unwrap_or_default();
+        if has_packs {
+            return git_fsck();
+        }
+    }
+    Ok(check(&repo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_git_fsck_output() {
+        let output = "missing blob f2ad6c76f0115a6ba5b00456a849810e7ec0af20\n\
+            error: inflate: data stream error (incorrect header check)\n\
+            error: 04a59185a0c5f4047e4fd3fa87b0c84e671b00ee: object corrupt or missing: .git/objects/04/a59185a0c5f4047e4fd3fa87b0c84e671b00ee\n";
+        let fsck = parse_git_fsck(output);
+        assert_eq!(fsck.count(Defect::Missing), 1);
+        assert_eq!(fsck.count(Defect::Corrupt), 1);
+        assert_eq!(
+            fsck.problems[0].id,
+            "04a59185a0c5f4047e4fd3fa87b0c84e671b00ee"
+        );
+    }
+}
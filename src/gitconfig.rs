@@ -0,0 +1,128 @@
+use color_eyre::Result;
+use std::path::Path;
+
+/// The entries of a git configuration file in the order they appear.
+#[derive(Default)]
+pub struct GitConfig {
+    /// Pairs of `section[.subsection].key` and value, with section and key lowercased like git does.
+    pub entries: Vec<(String, String)>,
+}
+
+/// Parses the value after the `=` of an entry, handling quotes, escapes and trailing comments.
+fn parse_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim().chars();
+    // Whitespace is kept only between non-whitespace characters or inside quotes.
+    let mut pending_space = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                value.push_str(&pending_space);
+                pending_space.clear();
+                quoted = !quoted;
+            }
+            '\\' => {
+                value.push_str(&pending_space);
+                pending_space.clear();
+                match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('b') => {
+                        value.pop();
+                    }
+                    Some(other) => value.push(other),
+                    None => {}
+                }
+            }
+            ';' | '#' if !quoted => break,
+            c if c.is_whitespace() && !quoted => pending_space.push(c),
+            c => {
+                value.push_str(&pending_space);
+                pending_space.clear();
+                value.push(c);
+            }
+        }
+    }
+    value
+}
+
+/// Parses a section header such as `[core]`, `[remote "origin"]` or the deprecated `[branch.main]`.
+fn parse_section(header: &str) -> String {
+    let header = header.trim();
+    match header.split_once(char::is_whitespace) {
+        Some((name, subsection)) => {
+            let subsection = subsection.trim().trim_matches('"');
+            format!(
+                "{}.{}",
+                name.to_lowercase(),
+                subsection.replace("\\\"", "\"").replace("\\\\", "\\")
+            )
+        }
+        None => header.to_lowercase(),
+    }
+}
+
+impl GitConfig {
+    pub fn parse(text: &str) -> Self {
+        let mut entries = Vec::new();
+        let mut section = String::new();
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let mut line = line.trim().to_string();
+            // A trailing backslash continues the value on the next line.
+            while line.ends_with('\\') && !line.ends_with("\\\\") {
+                line.pop();
+                match lines.next() {
+                    Some(next) => line.push_str(next),
+                    None => break,
+                }
+            }
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix('[') {
+                let Some((header, rest)) = rest.split_once(']') else {
+                    continue;
+                };
+                section = parse_section(header);
+                // Entries may follow the header on the same line.
+                line = rest.trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+            }
+            if section.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), parse_value(value)),
+                // A key without a value is a boolean set to true.
+                None => (line.trim(), "true".to_string()),
+            };
+            entries.push((format!("{section}.{}", key.to_lowercase()), value));
+        }
+        GitConfig { entries }
+    }
+
+    /// Reads and parses the configuration file at path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(GitConfig::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// Returns the last value of name, which is how git resolves keys set more than once.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns true if name is set to a value git reads as true.
+    pub fn get_bool(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|value| {
+            matches!(value.to_lowercase().as_str(), "true" | "yes" | "on" | "1")
+        })
+    }
+}
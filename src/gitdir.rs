@@ -0,0 +1,59 @@
+use crate::download::{join_url, Downloader};
+use color_eyre::{eyre::eyre, Result};
+use reqwest::StatusCode;
+use url::Url;
+
+/// Returns the path a `.git` file points to, as written by `git worktree` and `--separate-git-dir`.
+pub fn parse_pointer(text: &str) -> Option<&str> {
+    let path = text.lines().next()?.strip_prefix("gitdir:")?.trim();
+    (!path.is_empty()).then_some(path)
+}
+
+/// Resolves the path of a `.git` file against the root URL it was served from, returning the URL
+/// of the git directory without a trailing slash.
+pub fn resolve(root: &Url, path: &str) -> Result<Url> {
+    // An absolute path names a place on the server's disk, which says nothing about its URL.
+    if path.starts_with('/') || path.get(1..3) == Some(":\\") || path.get(1..3) == Some(":/") {
+        return Err(eyre!("the .git file points to the absolute path {path}"));
+    }
+    let mut url =
+        join_url(root, path.trim_end_matches('/')).map_err(|reason| eyre!("{path} {reason}"))?;
+    if url.path().len() > 1 && url.path().ends_with('/') {
+        let trimmed = url.path().trim_end_matches('/').to_string();
+        url.set_path(&trimmed);
+    }
+    Ok(url)
+}
+
+/// Fetches `.git` as a file and returns the URL of the git directory it points to, if it is one.
+pub async fn follow(download: &Downloader) -> Result<Option<Url>> {
+    let response = download.fetch(".git").await?;
+    if response.status() != StatusCode::OK {
+        return Ok(None);
+    }
+    match parse_pointer(&response.text()) {
+        Some(path) => resolve(&download.url, path).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_pointers() {
+        assert_eq!(
+            parse_pointer("gitdir: ../repos/app.git\n"),
+            Some("../repos/app.git")
+        );
+        assert_eq!(parse_pointer("ref: refs/heads/main\n"), None);
+
+        let root = Url::parse("http://example.com/app").unwrap();
+        assert_eq!(
+            resolve(&root, "../repos/app.git/").unwrap().as_str(),
+            "http://example.com/repos/app.git"
+        );
+        assert!(resolve(&root, "/srv/repos/app.git").is_err());
+    }
+}
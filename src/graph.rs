@@ -0,0 +1,85 @@
+use crate::chunk;
+use color_eyre::{eyre::bail, Result};
+use std::path::Path;
+
+/// The signature at the beginning of a Git commit-graph file.
+const GRAPH_SIGNATURE: &[u8; 4] = b"CGPH";
+
+/// The signature at the beginning of a Git multi-pack-index file.
+const MIDX_SIGNATURE: &[u8; 4] = b"MIDX";
+
+/// Parses a Git commit-graph file and returns the hashes of every commit
+/// along with the root tree each of them points to.
+pub fn parse<P: AsRef<Path>>(file_path: P) -> Result<Vec<String>> {
+    let data = std::fs::read(file_path)?;
+    if data.len() < 8 || data[0..4] != *GRAPH_SIGNATURE {
+        bail!("Invalid commit-graph signature");
+    }
+    let hash_len = chunk::hash_len(data[5])?;
+    let chunks = chunk::parse_table(&data, 8, data[6])?;
+
+    let Some(lookup) = chunks.get(b"OIDL") else {
+        bail!("Commit-graph is missing the object ID lookup chunk");
+    };
+    let mut hashes = chunk::object_ids(&data, lookup.clone(), hash_len);
+
+    // Every commit data entry starts with the hash of the root tree,
+    // followed by two parent positions and the generation number with the commit time.
+    if let Some(commit_data) = chunks.get(b"CDAT") {
+        let trees = data[commit_data.clone()]
+            .chunks_exact(hash_len + 16)
+            .map(|entry| hex::encode(&entry[..hash_len]));
+        hashes.extend(trees);
+    }
+    Ok(hashes)
+}
+
+/// Parses the chain file of a split commit-graph and returns the names of the graph files it lists.
+pub fn parse_chain(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|hash| format!("graph-{hash}.graph"))
+        .collect()
+}
+
+/// The contents of a Git multi-pack-index file.
+pub struct MultiPackIndex {
+    /// Names of the pack files covered by the index, without their extension.
+    pub packs: Vec<String>,
+    /// Hashes of every object stored across the covered packs.
+    pub objects: Vec<String>,
+}
+
+/// Parses a Git multi-pack-index file and extracts the pack names and object hashes it references.
+pub fn parse_midx<P: AsRef<Path>>(file_path: P) -> Result<MultiPackIndex> {
+    let data = std::fs::read(file_path)?;
+    if data.len() < 12 || data[0..4] != *MIDX_SIGNATURE {
+        bail!("Invalid multi-pack-index signature");
+    }
+    let hash_len = chunk::hash_len(data[5])?;
+    let chunks = chunk::parse_table(&data, 12, data[6])?;
+
+    // The pack names chunk holds null terminated names of the pack indexes, e.g. "pack-<hash>.idx".
+    let packs = chunks
+        .get(b"PNAM")
+        .map(|names| {
+            data[names.clone()]
+                .split(|&byte| byte == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| {
+                    String::from_utf8_lossy(name)
+                        .trim_end_matches(".idx")
+                        .to_string()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let objects = chunks
+        .get(b"OIDL")
+        .map(|lookup| chunk::object_ids(&data, lookup.clone(), hash_len))
+        .unwrap_or_default();
+
+    Ok(MultiPackIndex { packs, objects })
+}
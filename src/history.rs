@@ -0,0 +1,90 @@
+use crate::{
+    object::{Commit, Kind},
+    repository::Repository,
+};
+use chrono::DateTime;
+use color_eyre::Result;
+use std::fmt::Write;
+
+/// The file in the output directory listing every recovered commit.
+pub const HISTORY_LOG: &str = "history.log";
+
+/// Decodes every commit object in the repository, reachable or not, newest first. Commits whose
+/// trees and blobs are missing are included, since their messages are worth reading on their own.
+pub fn recover(repo: &Repository) -> Result<Vec<(String, Commit)>> {
+    let mut commits: Vec<_> = repo
+        .objects()?
+        .into_iter()
+        .filter_map(|id| {
+            let object = repo.find(&id).ok()?;
+            if object.kind != Kind::Commit {
+                return None;
+            }
+            Some((id, Commit::parse(&object.data).ok()?))
+        })
+        .collect();
+    commits.sort_by(|(a_id, a), (b_id, b)| {
+        b.committer.time.cmp(&a.committer.time).then(a_id.cmp(b_id))
+    });
+    Ok(commits)
+}
+
+/// Formats the commits the way `git log` prints them.
+pub fn format(commits: &[(String, Commit)]) -> String {
+    let mut log = String::new();
+    for (id, commit) in commits {
+        let date = DateTime::from_timestamp(commit.author.time, 0)
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default();
+        let _ = writeln!(log, "commit {id}");
+        let _ = writeln!(
+            log,
+            "Author: {} <{}>",
+            commit.author.name, commit.author.email
+        );
+        let _ = writeln!(log, "Date:   {date}");
+        let _ = writeln!(log);
+        for line in commit.message.trim_end().lines() {
+            let _ = writeln!(log, "    {line}");
+        }
+        let _ = writeln!(log);
+    }
+    log
+}
+
+/// Writes every recovered commit to [`HISTORY_LOG`] in the current directory, returning how many
+/// there were.
+pub fn write(repo: &Repository) -> Result<usize> {
+    let commits = recover(repo)?;
+    std::fs::write(HISTORY_LOG, format(&commits))?;
+    Ok(commits.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Signature;
+
+    #[test]
+    fn formats_like_git_log() {
+        let author = Signature {
+            name: "Dev".to_string(),
+            email: "dev@corp.internal".to_string(),
+            time: 1_700_000_000,
+        };
+        let commit = Commit {
+            tree: "0".repeat(40),
+            parents: Vec::new(),
+            author: author.clone(),
+            committer: author,
+            message: "Rotate the token\n\nOld one was abc123\n".to_string(),
+        };
+        assert_eq!(
+            format(&[("1".repeat(40), commit)]),
+            format!(
+                "commit {}\nAuthor: Dev <dev@corp.internal>\nDate:   2023-11-14 22:13:20 UTC\n\n    Rotate the token\n    \n    Old one was abc123\n\n",
+                "1".repeat(40)
+            )
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use color_eyre::{eyre::WrapErr, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::process::Command;
+
+/// The outcome of running a post-processing command.
+#[derive(Serialize)]
+pub struct HookResult {
+    /// The command after substituting placeholders.
+    pub command: String,
+    /// The exit code, absent when the command was killed by a signal or failed to spawn.
+    pub exit_code: Option<i32>,
+    pub success: bool,
+}
+
+/// Values substituted for the placeholders in post-processing commands.
+pub struct Context<'a> {
+    /// Absolute path of the output directory, substituted for `{output_dir}`.
+    pub output_dir: &'a str,
+    /// URL of the dumped repository, substituted for `{url}`.
+    pub url: &'a str,
+    /// Absolute path of the report, substituted for `{report}`.
+    pub report: &'a str,
+}
+
+impl Context<'_> {
+    /// Substitutes the `{output_dir}`, `{url}` and `{report}` placeholders in a command template.
+    fn render(&self, template: &str) -> String {
+        template
+            .replace("{output_dir}", self.output_dir)
+            .replace("{url}", self.url)
+            .replace("{report}", self.report)
+    }
+}
+
+/// Returns a command that runs the given command line through the platform shell.
+fn shell(command_line: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", command_line]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", command_line]);
+        command
+    }
+}
+
+/// Runs a single post-processing command in the output directory.
+fn run_one(command_line: &str) -> Result<std::process::ExitStatus> {
+    shell(command_line)
+        .status()
+        .wrap_err(format!("Failed to run {command_line}"))
+}
+
+/// Runs every post-processing command in order and records their exit statuses.
+pub fn run(templates: &[String], context: &Context) -> Vec<HookResult> {
+    templates
+        .iter()
+        .map(|template| {
+            let command = context.render(template);
+            info!("Running post command: {command}");
+            match run_one(&command) {
+                Ok(status) => {
+                    if !status.success() {
+                        warn!("Post command exited with {status}");
+                    }
+                    HookResult {
+                        command,
+                        exit_code: status.code(),
+                        success: status.success(),
+                    }
+                }
+                Err(e) => {
+                    warn!("{e}");
+                    HookResult {
+                        command,
+                        exit_code: None,
+                        success: false,
+                    }
+                }
+            }
+        })
+        .collect()
+}
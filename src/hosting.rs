@@ -0,0 +1,107 @@
+use crate::transport::Response;
+use url::Url;
+
+/// Hosting services known to expose .git directories in their own ways.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quirk {
+    /// GitHub Pages publishes the .git directory of a site whose build copies it, such as a
+    /// project committing the repository of its generated site.
+    GitHubPages,
+    /// GitLab Pages publishes whatever a job leaves in `public/`, .git included.
+    GitLabPages,
+    /// GitLab keeps the artifacts of CI jobs, which hold the .git directory of the checkout when a
+    /// job archives its whole working directory.
+    JobArtifacts,
+}
+
+impl Quirk {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quirk::GitHubPages => "GitHub Pages",
+            Quirk::GitLabPages => "GitLab Pages",
+            Quirk::JobArtifacts => "GitLab job artifacts",
+        }
+    }
+}
+
+/// Returns the segments of the path of a GitLab job artifacts URL after `artifacts`, such as
+/// `browse` and the path browsed.
+fn artifacts_path(url: &Url) -> Option<Vec<&str>> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let jobs = segments
+        .windows(2)
+        .position(|window| window == ["-", "jobs"])?;
+    match segments.get(jobs + 3) {
+        Some(&"artifacts") => Some(segments[jobs + 4..].to_vec()),
+        _ => None,
+    }
+}
+
+/// Tells the service hosting the target from the URL and the response to a request for it.
+pub fn detect(url: &Url, response: &Response) -> Option<Quirk> {
+    let host = url.host_str().unwrap_or_default();
+    let headers = response.headers();
+    let server = headers
+        .get("server")
+        .and_then(|server| server.to_str().ok())
+        .unwrap_or_default();
+    if artifacts_path(url).is_some() {
+        Some(Quirk::JobArtifacts)
+    } else if host.ends_with(".github.io") || server.eq_ignore_ascii_case("GitHub.com") {
+        Some(Quirk::GitHubPages)
+    } else if host.ends_with(".gitlab.io")
+        || headers
+            .keys()
+            .any(|name| name.as_str().starts_with("x-gitlab-"))
+    {
+        Some(Quirk::GitLabPages)
+    } else {
+        None
+    }
+}
+
+/// Returns the URL GitLab serves the files of job artifacts from, when url browses them as HTML
+/// pages instead.
+pub fn raw_artifacts(url: &Url) -> Option<Url> {
+    let path = artifacts_path(url)?;
+    if path.first() != Some(&"browse") {
+        return None;
+    }
+    let mut raw = url.clone();
+    raw.set_path(
+        &url.path()
+            .replacen("/artifacts/browse", "/artifacts/raw", 1),
+    );
+    Some(raw)
+}
+
+/// Returns true if url serves every artifact of a GitLab job at once as a zip archive, whose
+/// entries are extracted instead of crawling for them.
+pub fn is_zip_endpoint(url: &Url) -> bool {
+    artifacts_path(url).is_some_and(|path| path == ["download"])
+        || url.path().to_ascii_lowercase().ends_with(".zip")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_gitlab_artifacts() {
+        let browse =
+            Url::parse("https://gitlab.com/group/project/-/jobs/42/artifacts/browse/public/.git/")
+                .unwrap();
+        assert_eq!(
+            raw_artifacts(&browse).unwrap().as_str(),
+            "https://gitlab.com/group/project/-/jobs/42/artifacts/raw/public/.git/"
+        );
+        let download =
+            Url::parse("https://gitlab.com/group/project/-/jobs/42/artifacts/download").unwrap();
+        assert_eq!(raw_artifacts(&download), None);
+        assert!(is_zip_endpoint(&download));
+        assert!(!is_zip_endpoint(&browse));
+        assert!(is_zip_endpoint(
+            &Url::parse("https://example.com/backup.ZIP").unwrap()
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use url::{Host, Url};
+
+/// A host, domain or network requests may be sent to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    /// A domain along with its subdomains, such as "example.com".
+    Domain(String),
+    /// An IP address range in CIDR notation, such as "10.0.0.0/8", or a single address.
+    Network(IpAddr, u8),
+}
+
+impl Rule {
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim().trim_start_matches("*.").trim_end_matches('.');
+        if rule.is_empty() {
+            return Err("expected a domain, an IP address or a CIDR range".to_string());
+        }
+        let (address, prefix) = match rule.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (rule, None),
+        };
+        let Ok(address) = address.trim_matches(['[', ']']).parse::<IpAddr>() else {
+            if prefix.is_some() {
+                return Err(format!("{address} is not an IP address"));
+            }
+            // Domains go through the same normalization as the hosts of URLs.
+            return match Host::parse(rule) {
+                Ok(Host::Domain(domain)) => Ok(Rule::Domain(domain)),
+                Ok(_) => unreachable!("IP addresses were parsed above"),
+                Err(e) => Err(format!("{rule} is not a valid domain: {e}")),
+            };
+        };
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|&prefix| prefix <= bits)
+                .ok_or_else(|| format!("expected a prefix length up to {bits}, got {prefix}"))?,
+            None => bits,
+        };
+        Ok(Rule::Network(address, prefix))
+    }
+
+    /// Returns true if the rule allows the domain name.
+    fn allows_domain(&self, domain: &str) -> bool {
+        match self {
+            Rule::Domain(allowed) => {
+                domain == allowed
+                    || domain
+                        .strip_suffix(allowed.as_str())
+                        .is_some_and(|rest| rest.ends_with('.'))
+            }
+            Rule::Network(..) => false,
+        }
+    }
+
+    /// Returns true if the rule allows the address.
+    fn allows_address(&self, address: IpAddr) -> bool {
+        let Rule::Network(network, prefix) = *self else {
+            return false;
+        };
+        match (network, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The hosts, domains and networks requests may be sent to, every other host being off limits
+/// however it was reached: by a redirect, a `.git` file, an LFS endpoint or a remote URL. A scope
+/// without rules allows every host.
+#[derive(Debug, Default)]
+pub struct HostScope {
+    rules: Vec<Rule>,
+    /// Whether the addresses each domain resolves to lie within the networks of the scope.
+    resolved: Mutex<HashMap<String, bool>>,
+}
+
+impl HostScope {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        HostScope {
+            rules,
+            resolved: Mutex::default(),
+        }
+    }
+
+    /// Returns true if the scope allows every host.
+    pub fn is_unrestricted(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns true if requests may be sent to the host of url. A domain outside every allowed
+    /// domain is allowed if all the addresses it resolves to lie within the allowed networks.
+    pub async fn allows(&self, url: &Url) -> bool {
+        if self.is_unrestricted() {
+            return true;
+        }
+        let domain = match url.host() {
+            Some(Host::Domain(domain)) => domain.to_ascii_lowercase(),
+            Some(Host::Ipv4(address)) => return self.allows_address(address.into()),
+            Some(Host::Ipv6(address)) => return self.allows_address(address.into()),
+            None => return false,
+        };
+        if self.rules.iter().any(|rule| rule.allows_domain(&domain)) {
+            return true;
+        }
+        if !self
+            .rules
+            .iter()
+            .any(|rule| matches!(rule, Rule::Network(..)))
+        {
+            return false;
+        }
+        if let Some(&allowed) = self.resolved.lock().unwrap().get(&domain) {
+            return allowed;
+        }
+        let port = url.port_or_known_default().unwrap_or(80);
+        let allowed = match tokio::net::lookup_host((domain.as_str(), port)).await {
+            Ok(addresses) => {
+                let addresses: Vec<_> = addresses.map(|address| address.ip()).collect();
+                !addresses.is_empty()
+                    && addresses
+                        .into_iter()
+                        .all(|address| self.allows_address(address))
+            }
+            Err(_) => false,
+        };
+        self.resolved.lock().unwrap().insert(domain, allowed);
+        allowed
+    }
+
+    fn allows_address(&self, address: IpAddr) -> bool {
+        self.rules.iter().any(|rule| rule.allows_address(address))
+    }
+}
+
+/// Resolves the host names of the connections the transport opens to the addresses a scope with
+/// networks allows only, so that a domain cannot pass [`HostScope::allows`] and then resolve
+/// elsewhere when the connection is made. Connections through a proxy are resolved by the proxy,
+/// which the scope cannot pin.
+pub struct PinnedResolver {
+    rules: Vec<Rule>,
+}
+
+impl PinnedResolver {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        PinnedResolver { rules }
+    }
+}
+
+impl Resolve for PinnedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let domain = name.as_str().to_ascii_lowercase();
+        let by_domain = self.rules.iter().any(|rule| rule.allows_domain(&domain));
+        let networks: Vec<Rule> = self
+            .rules
+            .iter()
+            .filter(|rule| matches!(rule, Rule::Network(..)))
+            .cloned()
+            .collect();
+        Box::pin(async move {
+            let addresses: Vec<_> = tokio::net::lookup_host((domain.as_str(), 0))
+                .await?
+                .filter(|address| {
+                    by_domain
+                        || networks
+                            .iter()
+                            .any(|rule| rule.allows_address(address.ip()))
+                })
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{domain} resolves to no address within the scope").into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn allows_hosts_within_scope() {
+        let scope = HostScope::new(
+            ["*.Example.com", "10.0.0.0/8", "2001:db8::/32", "192.0.2.7"]
+                .map(|rule| Rule::parse(rule).unwrap())
+                .into(),
+        );
+        let allows = |url: &str| {
+            let url = Url::parse(url).unwrap();
+            let scope = &scope;
+            async move { scope.allows(&url).await }
+        };
+        assert!(allows("https://example.com/.git/HEAD").await);
+        assert!(allows("https://staging.example.com:8443/").await);
+        assert!(allows("http://10.1.2.3/").await);
+        assert!(allows("http://[2001:db8::1]/").await);
+        assert!(allows("http://192.0.2.7/").await);
+        assert!(!allows("http://localhost/").await);
+        assert!(!allows("http://192.0.2.8/").await);
+        assert!(!allows("http://11.0.0.1/").await);
+
+        let domain = Rule::parse("example.com").unwrap();
+        assert!(!domain.allows_domain("notexample.com"));
+        assert!(
+            HostScope::default()
+                .allows(&Url::parse("http://a.b/").unwrap())
+                .await
+        );
+        assert!(Rule::parse("10.0.0.0/33").is_err());
+        assert!(Rule::parse("example.com/8").is_err());
+    }
+
+    #[tokio::test]
+    async fn pins_addresses_within_scope() {
+        let resolve = |rule: &str| {
+            let resolver = PinnedResolver::new(vec![Rule::parse(rule).unwrap()]);
+            resolver.resolve("localhost".parse().unwrap())
+        };
+        let addresses: Vec<_> = resolve("127.0.0.0/8").await.unwrap().collect();
+        assert!(!addresses.is_empty());
+        assert!(addresses.iter().all(|address| address.ip().is_loopback()));
+        assert!(resolve("10.0.0.0/8").await.is_err());
+        assert!(resolve("localhost").await.is_ok());
+    }
+}
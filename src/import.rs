@@ -0,0 +1,109 @@
+use color_eyre::{
+    eyre::{eyre, Result},
+    Section,
+};
+use log::warn;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// How deep below the given directory a .git directory is looked for, enough for the
+/// `host/path/to/site/.git` layout of a wget mirror.
+const MAX_DEPTH: usize = 8;
+
+/// A repository obtained by other means than a dump.
+#[derive(Debug, PartialEq)]
+pub struct Import {
+    /// The directory holding the .git directory, or the repository itself when it is bare.
+    pub root: PathBuf,
+    /// Whether the repository has no worktree to check out into.
+    pub bare: bool,
+}
+
+fn is_git_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir()
+}
+
+/// Finds the repository in dir: dir itself, its .git directory, or the shallowest .git directory
+/// below it.
+pub fn locate(dir: &Path) -> Result<Import> {
+    let root = |git_dir: &Path| git_dir.parent().unwrap_or(Path::new(".")).to_path_buf();
+    if dir.file_name().is_some_and(|name| name == ".git") && is_git_dir(dir) {
+        return Ok(Import {
+            root: root(dir),
+            bare: false,
+        });
+    }
+    if is_git_dir(&dir.join(".git")) {
+        return Ok(Import {
+            root: dir.to_path_buf(),
+            bare: false,
+        });
+    }
+    if is_git_dir(dir) {
+        return Ok(Import {
+            root: dir.to_path_buf(),
+            bare: true,
+        });
+    }
+    WalkDir::new(dir)
+        .max_depth(MAX_DEPTH)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_dir() && entry.file_name() == ".git")
+        .filter(|entry| is_git_dir(entry.path()))
+        .min_by_key(|entry| entry.depth())
+        .map(|entry| Import {
+            root: root(entry.path()),
+            bare: false,
+        })
+        .ok_or_else(|| eyre!("{} holds no git repository", dir.display()))
+        .suggestion("Point --from at a directory holding a .git directory with HEAD and objects")
+}
+
+/// Returns true if the file is a directory listing saved by a mirroring tool, such as wget's
+/// `index.html` and `index.html?C=M;O=D`, rather than a file of the repository.
+fn is_listing(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name == "index.html" || name.starts_with("index.html?"))
+}
+
+/// Removes the directory listings a mirroring tool saved inside the git directory, returning how
+/// many were removed.
+pub fn clean(git_dir: &Path) -> usize {
+    WalkDir::new(git_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file() && is_listing(entry.path()))
+        .filter(|entry| {
+            std::fs::remove_file(entry.path())
+                .map_err(|e| warn!("Failed to remove {}: {e}", entry.path().display()))
+                .is_ok()
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_mirrored_repositories() {
+        let mirror = tempfile::tempdir().unwrap();
+        let git_dir = mirror.path().join("example.com/app/.git");
+        std::fs::create_dir_all(git_dir.join("objects/ab")).unwrap();
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(git_dir.join("index.html"), "<pre>").unwrap();
+        std::fs::write(git_dir.join("objects/ab/index.html?C=M;O=D"), "<pre>").unwrap();
+
+        let found = locate(mirror.path()).unwrap();
+        assert_eq!(found.root, mirror.path().join("example.com/app"));
+        assert!(!found.bare);
+        assert_eq!(locate(&git_dir).unwrap(), found);
+        assert!(locate(&git_dir.join("objects")).is_err());
+
+        assert_eq!(clean(&git_dir), 2);
+        assert!(git_dir.join("HEAD").exists());
+    }
+}
@@ -0,0 +1,107 @@
+use byteorder::{BigEndian, ReadBytesExt};
+use color_eyre::{eyre::bail, Result};
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+/// The signature at the beginning of a Git index file.
+const SIGNATURE: &[u8; 4] = b"DIRC";
+
+/// Size of the stat data preceding the object ID of every entry.
+const STAT_LEN: usize = 40;
+
+/// Flag marking an entry with a second, extended flags field (version 3 and later).
+const EXTENDED: u16 = 0x4000;
+
+/// A file staged in the index.
+pub struct Entry {
+    pub path: Vec<u8>,
+    pub id: String,
+    pub mode: u32,
+    /// Size of the file when it was staged, truncated to 32 bits.
+    pub size: u32,
+}
+
+/// Reads the variable length integer used to compress paths in version 4 indexes.
+fn read_offset(reader: &mut Cursor<&[u8]>) -> Result<usize> {
+    let mut byte = reader.read_u8()?;
+    let mut value = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        byte = reader.read_u8()?;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+    }
+    Ok(value)
+}
+
+/// Reads bytes up to and including a NUL terminator, returning them without it.
+fn read_until_nul(reader: &mut Cursor<&[u8]>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    loop {
+        match reader.read_u8()? {
+            0 => return Ok(bytes),
+            byte => bytes.push(byte),
+        }
+    }
+}
+
+/// Parses the entries of an index file of version 2, 3 or 4 with object IDs of hash_len bytes.
+pub fn parse(data: &[u8], hash_len: usize) -> Result<Vec<Entry>> {
+    let mut reader = Cursor::new(data);
+    let mut signature = [0u8; 4];
+    reader.read_exact(&mut signature)?;
+    if signature != *SIGNATURE {
+        bail!("Invalid index signature");
+    }
+    let version = reader.read_u32::<BigEndian>()?;
+    if !(2..=4).contains(&version) {
+        bail!("Unsupported index version {version}");
+    }
+    let count = reader.read_u32::<BigEndian>()?;
+
+    let mut entries = Vec::with_capacity(count as usize);
+    let mut previous: Vec<u8> = Vec::new();
+    for _ in 0..count {
+        let start = reader.position() as usize;
+        let mut stat = [0u8; STAT_LEN];
+        reader.read_exact(&mut stat)?;
+        let mode = u32::from_be_bytes([stat[24], stat[25], stat[26], stat[27]]);
+        let size = u32::from_be_bytes([stat[36], stat[37], stat[38], stat[39]]);
+        let mut id = vec![0u8; hash_len];
+        reader.read_exact(&mut id)?;
+        let flags = reader.read_u16::<BigEndian>()?;
+        if version >= 3 && flags & EXTENDED != 0 {
+            reader.read_u16::<BigEndian>()?;
+        }
+
+        let path = if version == 4 {
+            // The path replaces the last N bytes of the previous path with a new suffix.
+            let strip = read_offset(&mut reader)?;
+            if strip > previous.len() {
+                bail!("Index entry strips more of the previous path than it has");
+            }
+            let mut path = previous[..previous.len() - strip].to_vec();
+            path.extend(read_until_nul(&mut reader)?);
+            path
+        } else {
+            let path = read_until_nul(&mut reader)?;
+            // Entries are padded with NUL bytes to a multiple of eight bytes.
+            let len = reader.position() as usize - start;
+            let padded = len.div_ceil(8) * 8;
+            reader.set_position((start + padded) as u64);
+            path
+        };
+
+        previous.clone_from(&path);
+        entries.push(Entry {
+            path,
+            id: hex::encode(id),
+            mode,
+            size,
+        });
+    }
+    Ok(entries)
+}
+
+/// Reads and parses the index file at path.
+pub fn open<P: AsRef<Path>>(path: P, hash_len: usize) -> Result<Vec<Entry>> {
+    parse(&std::fs::read(path)?, hash_len)
+}
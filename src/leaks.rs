@@ -0,0 +1,192 @@
+use crate::{
+    discover,
+    download::Downloader,
+    expression,
+    middleware::{Middleware, Next},
+    response::ResponseExt,
+    transport::{Request, Response},
+};
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use log::info;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// Largest page scanned for leaks.
+const MAX_PAGE: usize = 1 << 20;
+
+/// Rounds of following leaks, as the pages requested while following them may leak more.
+const ROUNDS: usize = 3;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum LeakKind {
+    /// A path below the git directory, such as a ref named in a stack trace.
+    Path,
+    /// A full object ID.
+    Object,
+}
+
+/// Something an HTML page served by the target gave away about the repository.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Leak {
+    pub kind: LeakKind,
+    /// The path relative to the repository root, or the object ID.
+    pub value: String,
+    /// URL of the page it was found in.
+    pub url: String,
+}
+
+/// Returns the paths below the git directory and the object IDs mentioned in a page served for
+/// url, leaving out those taken from url itself, which error pages often repeat.
+pub fn find(url: &Url, page: &str) -> Vec<(LeakKind, String)> {
+    let requested = url.path();
+    let mut found = Vec::new();
+    for capture in expression::GIT_PATH.captures_iter(page) {
+        let path = capture[1].trim_end_matches('.');
+        let href = format!(".git/{path}");
+        if path.is_empty()
+            || path.ends_with('/')
+            || path.contains("..")
+            || requested.ends_with(&href)
+        {
+            continue;
+        }
+        found.push((LeakKind::Path, href));
+    }
+    for id in expression::EMBEDDED_OBJECT
+        .find_iter(page)
+        .map(|m| m.as_str())
+    {
+        if id.bytes().all(|b| b == b'0') || requested.contains(id) {
+            continue;
+        }
+        found.push((LeakKind::Object, id.to_string()));
+    }
+    found
+}
+
+/// The leaks found in the pages served so far, collected by every request as it completes.
+#[derive(Default)]
+pub struct Leaks {
+    found: Mutex<Vec<Leak>>,
+    /// Number of leaks already followed.
+    followed: Mutex<usize>,
+}
+
+impl Leaks {
+    /// Records the leaks in a page served for url that were not found before.
+    pub fn scan(&self, url: &Url, page: &str) {
+        let mut found = self.found.lock().unwrap();
+        for (kind, value) in find(url, page) {
+            if !found
+                .iter()
+                .any(|leak| leak.kind == kind && leak.value == value)
+            {
+                found.push(Leak {
+                    kind,
+                    value,
+                    url: url.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Returns every leak found so far.
+    pub fn all(&self) -> Vec<Leak> {
+        self.found.lock().unwrap().clone()
+    }
+
+    /// Returns the leaks found since the last call.
+    fn take_new(&self) -> Vec<Leak> {
+        let found = self.found.lock().unwrap();
+        let mut followed = self.followed.lock().unwrap();
+        let new = found[*followed..].to_vec();
+        *followed = found.len();
+        new
+    }
+}
+
+impl Middleware for Arc<Leaks> {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            let url = request.url.clone();
+            let response = next.run(request).await?;
+            if response.is_html() && response.bytes().len() <= MAX_PAGE {
+                self.scan(&url, &response.text());
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Downloads the paths and objects that pages served during the dump leaked, along with the
+/// objects they reach, returning how many objects were downloaded.
+pub async fn follow(download: &Downloader) -> usize {
+    let mut fetched = 0;
+    for _ in 0..ROUNDS {
+        let leaks = download.leaks.take_new();
+        if leaks.is_empty() || download.is_interrupted() {
+            break;
+        }
+        let values = |kind| {
+            leaks
+                .iter()
+                .filter(move |leak| leak.kind == kind)
+                .map(|leak| leak.value.clone())
+        };
+        let paths: Vec<_> = values(LeakKind::Path).collect();
+        let mut ids: Vec<_> = values(LeakKind::Object).collect();
+        info!(
+            "Following {} paths and {} objects leaked by HTML pages",
+            paths.len(),
+            ids.len()
+        );
+        download.refs_recursive(&paths).await;
+        for path in &paths {
+            if let Ok(contents) = std::fs::read(path) {
+                ids.extend(
+                    expression::OBJECT
+                        .captures_iter(&contents)
+                        .filter_map(|m| m.get(2))
+                        .map(|m| String::from_utf8_lossy(m.as_bytes()).into_owned()),
+                );
+            }
+        }
+        fetched += discover::reachable(download, ids).await;
+    }
+    fetched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_leaks_in_error_pages() {
+        let url = Url::parse("http://example.com/.git/refs/heads/main").unwrap();
+        let page = concat!(
+            "<h1>500</h1><pre>GitError: cannot read /var/www/app/.git/refs/heads/release-2.1.\n",
+            "  at /var/www/app/.git/refs/heads/main (HEAD 1a410efbd13591db07496601ebc7a059dd55cfe9)\n",
+            "  parent 0000000000000000000000000000000000000000 in /var/www/app/.git/</pre>"
+        );
+        assert_eq!(
+            find(&url, page),
+            [
+                (LeakKind::Path, ".git/refs/heads/release-2.1".to_string()),
+                (
+                    LeakKind::Object,
+                    "1a410efbd13591db07496601ebc7a059dd55cfe9".to_string()
+                ),
+            ]
+        );
+
+        let leaks = Leaks::default();
+        leaks.scan(&url, page);
+        leaks.scan(&url, page);
+        assert_eq!(leaks.take_new().len(), 2);
+        assert!(leaks.take_new().is_empty());
+        assert_eq!(leaks.all().len(), 2);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    download::Downloader,
+    error::Error,
+    gitconfig::GitConfig,
+    response::ResponseExt,
+    transport::{Request, Response},
+};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use log::{info, warn};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use url::Url;
+use walkdir::WalkDir;
+
+/// The first line of every Git LFS pointer file.
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Pointer files are tiny, anything larger is real content.
+const MAX_POINTER_SIZE: u64 = 1024;
+
+/// The media type spoken by the Git LFS batch API.
+const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+
+/// A Git LFS pointer file found in the checked out tree.
+#[derive(Serialize)]
+pub struct Pointer {
+    /// Path of the pointer file relative to the output directory.
+    pub path: String,
+    /// SHA-256 hash of the actual content.
+    pub oid: String,
+    /// Size of the actual content in bytes.
+    pub size: u64,
+    /// Whether the pointer was replaced with the actual content.
+    pub fetched: bool,
+}
+
+/// Parses the object ID and size out of the text of a Git LFS pointer file.
+fn parse_pointer(text: &str) -> Option<(String, u64)> {
+    let mut lines = text.lines();
+    if lines.next()? != POINTER_VERSION {
+        return None;
+    }
+    let mut oid = None;
+    let mut size = None;
+    for line in lines {
+        if let Some(hash) = line.strip_prefix("oid sha256:") {
+            if hash.len() != 64 || !hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                return None;
+            }
+            oid = Some(hash.to_string());
+        } else if let Some(length) = line.strip_prefix("size ") {
+            size = length.parse().ok();
+        }
+    }
+    Some((oid?, size?))
+}
+
+/// Finds every Git LFS pointer file in the working tree of the current directory.
+pub fn find() -> Vec<Pointer> {
+    WalkDir::new(".")
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(|e| e.ok())
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && entry
+                    .metadata()
+                    .is_ok_and(|metadata| metadata.len() <= MAX_POINTER_SIZE)
+        })
+        .filter_map(|entry| {
+            let text = std::fs::read_to_string(entry.path()).ok()?;
+            let (oid, size) = parse_pointer(&text)?;
+            Some(Pointer {
+                path: entry.path().display().to_string(),
+                oid,
+                size,
+                fetched: false,
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct BatchObject<'a> {
+    oid: &'a str,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct BatchRequest<'a> {
+    operation: &'a str,
+    transfers: &'a [&'a str],
+    objects: Vec<BatchObject<'a>>,
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    objects: Vec<BatchResponseObject>,
+}
+
+#[derive(Deserialize)]
+struct BatchResponseObject {
+    oid: String,
+    actions: Option<BatchActions>,
+}
+
+#[derive(Deserialize)]
+struct BatchActions {
+    download: Option<BatchAction>,
+}
+
+#[derive(Deserialize)]
+struct BatchAction {
+    href: String,
+    #[serde(default)]
+    header: HashMap<String, String>,
+}
+
+/// Returns the LFS server URL configured for the repository, or the one derived from its origin remote.
+pub fn endpoint<P: AsRef<Path>>(config_path: P) -> Option<Url> {
+    let config = GitConfig::open(config_path).ok()?;
+    if let Some(url) = config.get("lfs.url") {
+        return Url::parse(url).ok();
+    }
+    let remote = config.get("remote.origin.url")?;
+    if !remote.starts_with("http://") && !remote.starts_with("https://") {
+        return None;
+    }
+    let remote = remote.trim_end_matches('/');
+    let remote = if remote.ends_with(".git") {
+        remote.to_string()
+    } else {
+        format!("{remote}.git")
+    };
+    Url::parse(&format!("{remote}/info/lfs")).ok()
+}
+
+/// Verifies the content against the pointer and replaces the pointer file with it.
+fn replace(pointer: &mut Pointer, content: &[u8]) -> Result<()> {
+    let hash = hex::encode(Sha256::digest(content));
+    if hash != pointer.oid {
+        bail!("content for {} hashes to {hash}", pointer.oid);
+    }
+    std::fs::write(&pointer.path, content)?;
+    pointer.fetched = true;
+    Ok(())
+}
+
+/// Tries to download the content of a pointer from the `.git/lfs/objects` directory on the server.
+async fn fetch_exposed(download: &Downloader, pointer: &mut Pointer) -> Result<()> {
+    let oid = &pointer.oid;
+    let href = format!(".git/lfs/objects/{}/{}/{oid}", &oid[0..2], &oid[2..4]);
+    let response = download.fetch(&href).await?;
+    response.verify()?;
+    replace(pointer, response.bytes())
+}
+
+/// Sends a request to the LFS server or the storage it points to, bypassing the middlewares meant
+/// for the target but not its scope.
+async fn send(download: &Downloader, request: Request) -> Result<Response> {
+    if !download.hosts.allows(&request.url).await {
+        return Err(Error::OutOfScope {
+            url: request.url.to_string(),
+        }
+        .into());
+    }
+    download.transport.send(request).await
+}
+
+/// Asks the LFS batch API for download locations of the given pointers and fetches their content.
+async fn fetch_batch(
+    download: &Downloader,
+    pointers: &mut [&mut Pointer],
+    lfs: &Url,
+) -> Result<()> {
+    let request = BatchRequest {
+        operation: "download",
+        transfers: &["basic"],
+        objects: pointers
+            .iter()
+            .map(|pointer| BatchObject {
+                oid: &pointer.oid,
+                size: pointer.size,
+            })
+            .collect(),
+    };
+    let batch_url: Url = format!("{}/objects/batch", lfs.as_str().trim_end_matches('/')).parse()?;
+    let request = Request::post(batch_url.clone(), serde_json::to_vec(&request)?)
+        .header(ACCEPT, LFS_MEDIA_TYPE)
+        .header(CONTENT_TYPE, LFS_MEDIA_TYPE);
+    let response = send(download, request).await?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("{batch_url} responded with status code {status}");
+    }
+    let batch: BatchResponse = serde_json::from_slice(response.bytes())?;
+
+    for object in batch.objects {
+        let Some(action) = object.actions.and_then(|actions| actions.download) else {
+            warn!("LFS server offered no download for {}", object.oid);
+            continue;
+        };
+        let Some(pointer) = pointers
+            .iter_mut()
+            .find(|pointer| pointer.oid == object.oid)
+        else {
+            continue;
+        };
+        let result = async {
+            let mut request = Request::get(action.href.parse()?);
+            for (name, value) in &action.header {
+                request = request.header(name, value);
+            }
+            let response = send(download, request).await?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(eyre!("responded with status code {status}"));
+            }
+            replace(pointer, response.bytes())
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to download LFS object {}: {e}", object.oid);
+        }
+    }
+    Ok(())
+}
+
+/// Replaces pointer files with their actual content, trying the exposed `.git/lfs/objects`
+/// directory first and falling back to the LFS batch API.
+pub async fn fetch(download: &Downloader, pointers: &mut [Pointer], lfs: Option<Url>) {
+    for pointer in pointers.iter_mut() {
+        if let Err(e) = fetch_exposed(download, pointer).await {
+            info!("LFS object {} is not exposed: {e}", pointer.oid);
+        }
+    }
+
+    let mut missing: Vec<_> = pointers.iter_mut().filter(|p| !p.fetched).collect();
+    if missing.is_empty() {
+        return;
+    }
+    let Some(lfs) = lfs else {
+        warn!(
+            "No LFS server is configured, {} objects remain pointers",
+            missing.len()
+        );
+        return;
+    };
+    info!(
+        "Requesting {} objects from the LFS server at {lfs}",
+        missing.len()
+    );
+    if let Err(e) = fetch_batch(download, &mut missing, &lfs).await {
+        warn!("LFS batch request failed: {e}");
+    }
+}
@@ -0,0 +1,509 @@
+//! Directory listings, read from a response by [`read`] and [`list`] or from a body as it arrives
+//! by [`Reader`]. New listing formats are added to [`FORMATS`].
+
+use crate::transport::Response;
+use clap::ValueEnum;
+use html5ever::{
+    rcdom::{Handle, NodeData, RcDom},
+    tendril::TendrilSink,
+    tokenizer::{
+        BufferQueue, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+    },
+    ParseOpts,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashSet;
+use url_path::UrlPath;
+
+/// Listings larger than this many bytes are read by the tokenizer in chunks as they arrive instead
+/// of being parsed whole, so that a directory of a hundred thousand objects neither builds a
+/// document tree of as many elements nor is held in memory.
+pub const STREAMED_LEN: usize = 1 << 20;
+
+/// Bytes of a large listing handed to the tokenizer at once.
+const CHUNK_LEN: usize = 64 << 10;
+
+lazy_static! {
+    /// An href assigned or given as a property in a script, such as `a.href = "x"` or the items of
+    /// the JSON index scripts like h5ai load, whose slashes may be escaped.
+    static ref SCRIPT_HREF: Regex =
+        Regex::new(r#"(?i)\bhref["']?\s*[:=]\s*["']((?:[^"'\\<>\s]|\\/)+)["']"#).unwrap();
+}
+
+/// How the HTML of directory listings is parsed.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Parser {
+    /// Build the document tree as a browser does
+    #[default]
+    Tree,
+    /// Read the tags one by one, which also finds those written inside scripts and mangled markup
+    Tokens,
+}
+
+/// A link or anchor element of a page.
+#[derive(Debug, Default, PartialEq)]
+struct Link {
+    href: String,
+    rel: Option<String>,
+    /// The text inside the element.
+    text: String,
+}
+
+impl Link {
+    /// Returns the link element with the given tag name and attributes, if it has an href.
+    fn from_tag<'a>(name: &str, attrs: impl Iterator<Item = (&'a str, &'a str)>) -> Option<Self> {
+        if name != "a" && name != "link" {
+            return None;
+        }
+        let mut href = None;
+        let mut rel = None;
+        for (name, value) in attrs {
+            match name {
+                "href" => href = href.or(Some(value.trim().to_string())),
+                "rel" => rel = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Some(Link {
+            href: href?,
+            rel,
+            text: String::new(),
+        })
+    }
+}
+
+/// Returns the links of the document tree built from text.
+fn tree_links(text: &str) -> Vec<Link> {
+    let dom = html5ever::parse_document(RcDom::default(), ParseOpts::default()).one(text);
+    let mut links = Vec::new();
+    // Walked without recursion, since malformed pages can nest elements arbitrarily deep.
+    let mut stack: Vec<Handle> = vec![dom.document];
+    while let Some(node) = stack.pop() {
+        if let NodeData::Element { name, attrs, .. } = &node.data {
+            let attrs = attrs.borrow();
+            let link = Link::from_tag(
+                &name.local,
+                attrs.iter().map(|attr| (&*attr.name.local, &*attr.value)),
+            );
+            if let Some(mut link) = link {
+                link.text = text_of(&node);
+                links.push(link);
+            }
+        }
+        stack.extend(node.children.borrow().iter().rev().cloned());
+    }
+    links
+}
+
+/// Returns the text inside a node of the document tree.
+fn text_of(node: &Handle) -> String {
+    let mut text = String::new();
+    let mut stack = vec![node.clone()];
+    while let Some(node) = stack.pop() {
+        if let NodeData::Text { contents } = &node.data {
+            text.push_str(&contents.borrow());
+        }
+        stack.extend(node.children.borrow().iter().rev().cloned());
+    }
+    text
+}
+
+/// Collects the links of a page as they are tokenized.
+#[derive(Default)]
+struct LinkSink {
+    links: Vec<Link>,
+    /// Whether the last link is an anchor whose text is still being read.
+    open: bool,
+}
+
+impl TokenSink for LinkSink {
+    type Handle = ();
+
+    fn process_token(&mut self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            Token::TagToken(tag) if tag.kind == TagKind::StartTag => {
+                let attrs = tag
+                    .attrs
+                    .iter()
+                    .map(|attr| (&*attr.name.local, &*attr.value));
+                if let Some(link) = Link::from_tag(&tag.name, attrs) {
+                    self.open = &*tag.name == "a" && !tag.self_closing;
+                    self.links.push(link);
+                }
+            }
+            Token::TagToken(tag) if &*tag.name == "a" => self.open = false,
+            Token::CharacterTokens(text) if self.open => {
+                if let Some(link) = self.links.last_mut() {
+                    link.text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+/// Returns the links of the tags in text, without building a document tree.
+fn token_links(text: &str) -> Vec<Link> {
+    let mut tokenizer = Tokenizer::new(LinkSink::default(), TokenizerOpts::default());
+    let mut input = BufferQueue::new();
+    input.push_back(text.into());
+    let _ = tokenizer.feed(&mut input);
+    tokenizer.end();
+    tokenizer.sink.links
+}
+
+/// Returns the hrefs written in the scripts of a page or served by an index script as JSON.
+fn script_links(text: &str) -> Vec<Link> {
+    SCRIPT_HREF
+        .captures_iter(text)
+        .map(|captures| Link {
+            href: captures[1].replace("\\/", "/"),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Formats of listings besides the links of the HTML, tried on every page in turn. A new format
+/// is added here as a function returning the links it finds in the text of a page.
+const FORMATS: &[fn(&str) -> Vec<Link>] = &[script_links];
+
+/// Returns the links of text parsed with parser, followed by the new ones of every other format.
+fn links(text: &str, parser: Parser) -> Vec<Link> {
+    let mut links = match parser {
+        Parser::Tree => tree_links(text),
+        Parser::Tokens => token_links(text),
+    };
+    for link in FORMATS.iter().flat_map(|format| format(text)) {
+        if !links.iter().any(|known| known.href == link.href) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// Returns the length of the next chunk of a large page, ending at a line break where possible
+/// so that the scripts the formats read are rarely cut, and never inside a UTF-8 character.
+fn chunk_end(body: &[u8], len: usize) -> usize {
+    if body.len() <= len {
+        return body.len();
+    }
+    if let Some(newline) = body[..len].iter().rposition(|&byte| byte == b'\n') {
+        return newline + 1;
+    }
+    let mut end = len;
+    while end > 0 && body[end] & 0xc0 == 0x80 {
+        end -= 1;
+    }
+    if end == 0 {
+        len
+    } else {
+        end
+    }
+}
+
+/// Returns the file an href of the listing of directory stands for. Absolute links into the
+/// directory are made relative to it, other absolute links are dropped.
+fn entry(href: &str, directory: &str) -> Option<String> {
+    let href = match href.strip_prefix(directory) {
+        Some(child) => child,
+        None if href.starts_with(['/', '?']) => return None,
+        None => href,
+    };
+    let href = UrlPath::new(href).normalize();
+    (!href.is_empty() && !href.starts_with(['/', '?'])).then_some(href)
+}
+
+/// Returns true if a link leads to the next page of a listing: it is marked with rel="next", or
+/// reads "Next" or is an arrow such as "»".
+fn is_next(rel: Option<&str>, text: &str) -> bool {
+    if rel.is_some_and(|rel| {
+        rel.split_whitespace()
+            .any(|rel| rel.eq_ignore_ascii_case("next"))
+    }) {
+        return true;
+    }
+    let text = text.trim();
+    let words: String = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    match words.as_str() {
+        "next" | "nextpage" => true,
+        "" => matches!(text, ">" | ">>" | "»" | "›" | "→"),
+        _ => false,
+    }
+}
+
+/// Returns true if a link of a listing leads to its next page rather than to an entry.
+fn leads_next(link: &Link) -> bool {
+    // Entries of the listing read like their href, even one named "next".
+    let is_entry = link.href.trim_end_matches('/') == link.text.trim().trim_end_matches('/');
+    !is_entry && is_next(link.rel.as_deref(), &link.text)
+}
+
+/// The entries of a directory listing and the link to its next page, if the server splits it
+/// into pages as autoindex modules do for large directories.
+#[derive(Debug, Default, PartialEq)]
+pub struct Page {
+    pub entries: Vec<String>,
+    pub next: Option<String>,
+}
+
+/// Sorts the links of the listing of a directory into its entries and its next page as they are
+/// read.
+struct Collector {
+    directory: String,
+    entries: Vec<String>,
+    /// The first link element marked with rel="next", trusted over the anchors of the body.
+    marked: Option<String>,
+    anchor: Option<String>,
+}
+
+impl Collector {
+    fn new(path: &str) -> Self {
+        Collector {
+            directory: format!("{}/", path.trim_end_matches('/')),
+            entries: Vec::new(),
+            marked: None,
+            anchor: None,
+        }
+    }
+
+    fn add(&mut self, link: Link) {
+        self.entries.extend(entry(&link.href, &self.directory));
+        if leads_next(&link) {
+            let next = if link.rel.is_some() {
+                &mut self.marked
+            } else {
+                &mut self.anchor
+            };
+            next.get_or_insert(link.href);
+        }
+    }
+
+    fn finish(self) -> Page {
+        Page {
+            entries: self.entries,
+            next: self.marked.or(self.anchor),
+        }
+    }
+}
+
+/// Returns the page parsed from the HTML text of the listing of the directory at path.
+fn page(text: &str, path: &str, parser: Parser) -> Page {
+    let mut collector = Collector::new(path);
+    for link in links(text, parser) {
+        collector.add(link);
+    }
+    collector.finish()
+}
+
+/// Reads a large listing with the tokenizer as its body arrives, in chunks of about chunk_len
+/// bytes, so that a directory of a hundred thousand objects is never held in memory whole. The
+/// new links the other formats find in every chunk follow those of the HTML.
+pub struct Reader {
+    tokenizer: Tokenizer<LinkSink>,
+    input: BufferQueue,
+    hrefs: HashSet<String>,
+    formatted: Vec<Link>,
+    /// Bytes received but not read yet, at most a chunk and the piece that arrived last.
+    pending: Vec<u8>,
+    chunk_len: usize,
+    collector: Collector,
+}
+
+impl Reader {
+    /// Creates a reader for the listing of the directory at the URL path.
+    pub fn new(path: &str) -> Self {
+        Self::with_chunk_len(path, CHUNK_LEN)
+    }
+
+    fn with_chunk_len(path: &str, chunk_len: usize) -> Self {
+        Reader {
+            tokenizer: Tokenizer::new(LinkSink::default(), TokenizerOpts::default()),
+            input: BufferQueue::new(),
+            hrefs: HashSet::new(),
+            formatted: Vec::new(),
+            pending: Vec::new(),
+            chunk_len,
+            collector: Collector::new(path),
+        }
+    }
+
+    /// Reads the next bytes of the body.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        while self.pending.len() > self.chunk_len {
+            self.read_chunk();
+        }
+    }
+
+    /// Tokenizes the next chunk of the pending bytes.
+    fn read_chunk(&mut self) {
+        let end = chunk_end(&self.pending, self.chunk_len);
+        let text = String::from_utf8_lossy(&self.pending[..end]).into_owned();
+        self.pending.drain(..end);
+        self.formatted
+            .extend(FORMATS.iter().flat_map(|format| format(&text)));
+        self.input.push_back(text.into());
+        let _ = self.tokenizer.feed(&mut self.input);
+        // The last anchor is kept until its text is read in full.
+        let keep = usize::from(self.tokenizer.sink.open);
+        self.hand_over(keep);
+    }
+
+    /// Hands the links the tokenizer read to the collector, but for the last keep of them.
+    fn hand_over(&mut self, keep: usize) {
+        let links = &mut self.tokenizer.sink.links;
+        for link in links.drain(..links.len().saturating_sub(keep)) {
+            self.hrefs.insert(link.href.clone());
+            self.collector.add(link);
+        }
+    }
+
+    /// Reads the rest of the body and returns the page.
+    pub fn finish(mut self) -> Page {
+        while !self.pending.is_empty() {
+            self.read_chunk();
+        }
+        self.tokenizer.end();
+        self.hand_over(0);
+        for link in std::mem::take(&mut self.formatted) {
+            if self.hrefs.insert(link.href.clone()) {
+                self.collector.add(link);
+            }
+        }
+        self.collector.finish()
+    }
+}
+
+/// Returns the page parsed from the HTML in a response to a request for the listing of the
+/// directory at the URL path. Listings larger than [`STREAMED_LEN`] are tokenized in chunks
+/// whatever the parser.
+pub fn read(res: &Response, path: &str, parser: Parser) -> Page {
+    if res.bytes().len() > STREAMED_LEN {
+        let mut reader = Reader::new(path);
+        for chunk in res.bytes().chunks(CHUNK_LEN) {
+            reader.feed(chunk);
+        }
+        return reader.finish();
+    }
+    page(&res.text(), path, parser)
+}
+
+/// Returns a list of files parsed from the HTML in a response to a request for the listing of the
+/// directory at the URL path.
+pub fn list(res: &Response, path: &str, parser: Parser) -> Vec<String> {
+    read(res, path, parser).entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(text: &str, path: &str, parser: Parser) -> Vec<String> {
+        page(text, path, parser).entries
+    }
+
+    fn next_link(text: &str, parser: Parser) -> Option<String> {
+        page(text, "/", parser).next
+    }
+
+    #[test]
+    fn finds_next_pages() {
+        let page = r#"<html><body>
+            <a href="?C=M;O=A">Last modified</a>
+            <a href="00/">00/</a>
+            <a href="01/">01/</a>
+            <a href="?page=1">&laquo; Previous</a>
+            <a href="?page=3">Next &raquo;</a>
+        </body></html>"#;
+        for parser in [Parser::Tree, Parser::Tokens] {
+            assert_eq!(entries(page, "/.git/objects/", parser), ["00", "01"]);
+            assert_eq!(next_link(page, parser).as_deref(), Some("?page=3"));
+            assert_eq!(
+                next_link(
+                    r#"<head><link rel="next" href="?offset=500"></head>"#,
+                    parser
+                )
+                .as_deref(),
+                Some("?offset=500")
+            );
+            assert_eq!(
+                next_link(r#"<a href="?p=2">&rsaquo;</a>"#, parser).as_deref(),
+                Some("?p=2")
+            );
+            assert_eq!(next_link(r#"<a href="next/">next/</a>"#, parser), None);
+        }
+    }
+
+    #[test]
+    fn tolerates_malformed_listings() {
+        // Unquoted and unclosed attributes, stray closing tags and a page cut short.
+        let page = "<table><tr><td><a href=HEAD>HEAD</td></a></b><a href='config'>config\
+                    <a href=\"refs/\"><img src=x alt=[DIR]</a><a href=\"objects/";
+        for parser in [Parser::Tree, Parser::Tokens] {
+            let mut files = entries(page, "/.git", parser);
+            files.sort();
+            assert_eq!(files, ["HEAD", "config", "refs"]);
+        }
+
+        let script = r#"<script>document.write('<a href="packed-refs">packed-refs</a>');
+            link.href = "logs/";</script>"#;
+        assert_eq!(
+            entries(script, "/.git", Parser::Tokens),
+            ["packed-refs", "logs"]
+        );
+        assert!(entries(script, "/.git", Parser::Tree).contains(&"logs".to_string()));
+    }
+
+    #[test]
+    fn streams_large_listings() {
+        let mut page = String::from("<html><body><pre><a href=\"../\">../</a>\n");
+        for i in 0..50 {
+            page.push_str(&format!(
+                "<a href=\"caf%C3%A9-{i}\">caf\u{e9}-{i}</a> 01-Jan-2024 00:00 42\n"
+            ));
+        }
+        page.push_str("<a href=\"?page=2\">Next</a></pre></body></html>\n");
+        // Chunks this small cut tags and characters in half, whatever pieces the body arrives in.
+        let streamed = |page: &str, chunk_len, piece_len| {
+            let mut reader = Reader::with_chunk_len("/.git/objects", chunk_len);
+            for piece in page.as_bytes().chunks(piece_len) {
+                reader.feed(piece);
+            }
+            reader.finish()
+        };
+        for (chunk_len, piece_len) in [(1, 1), (7, 3), (7, 100), (4096, 5000)] {
+            let read = streamed(&page, chunk_len, piece_len);
+            assert_eq!(
+                read.entries,
+                entries(&page, "/.git/objects", Parser::Tokens)
+            );
+            assert_eq!(read.next.as_deref(), Some("?page=2"));
+        }
+        assert_eq!(chunk_end("\u{e9}\u{e9}".as_bytes(), 3), 2);
+
+        page.push_str("<script>\nlink.href = \"packed-refs\";\n</script>\n");
+        let files = streamed(&page, 64, 64).entries;
+        assert_eq!(files.len(), 51);
+        assert_eq!(files.last().map(String::as_str), Some("packed-refs"));
+    }
+
+    #[test]
+    fn reads_index_scripts() {
+        let json = r#"{"items":[
+            {"href":"\/site\/.git\/","time":1700000000000,"size":null,"managed":false,"fetched":true},
+            {"href":"\/site\/.git\/objects\/","time":1700000000000,"size":null},
+            {"href":"\/site\/.git\/HEAD","time":1700000000000,"size":23},
+            {"href":"\/site\/","time":1700000000000,"size":null}
+        ]}"#;
+        assert_eq!(
+            entries(json, "/site/.git/", Parser::Tree),
+            ["objects", "HEAD"]
+        );
+    }
+}
@@ -1,12 +1,111 @@
+use crate::{progress::Progress, redact};
 use color_eyre::{eyre::bail, Result};
-pub fn init(verbosity: u8) -> Result<()> {
-    simple_logger::init_with_level(match verbosity {
-        0 => log::Level::Info,
-        1 => log::Level::Debug,
-        2 => log::Level::Trace,
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::sync::Arc;
+
+fn level(verbosity: u8) -> Result<Level> {
+    Ok(match verbosity {
+        0 => Level::Info,
+        1 => Level::Debug,
+        2 => Level::Trace,
         _ => {
             bail!("I'm sorry, but revealing too much information might wake the real Elliot. For now, let's focus on Dark Army, shall we?")
         }
-    })?;
+    })
+}
+
+pub fn init(verbosity: u8) -> Result<()> {
+    let logger = simple_logger::SimpleLogger::new().with_level(level(verbosity)?.to_level_filter());
+    log::set_max_level(logger.max_level());
+    log::set_boxed_logger(Box::new(Redacted(logger)))?;
+    Ok(())
+}
+
+/// Logs to standard error when standard output carries the dump, as it does with --to-stdout.
+pub fn init_stderr(verbosity: u8) -> Result<()> {
+    let level = level(verbosity)?.to_level_filter();
+    log::set_max_level(level);
+    log::set_boxed_logger(Box::new(Redacted(StderrLogger { level })))?;
+    Ok(())
+}
+
+/// Prints messages to standard error the way the plain logger prints them to standard output.
+struct StderrLogger {
+    level: LevelFilter,
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{:<5} [{}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Masks the hosts and IP addresses in the messages passed on to the inner logger under --redact.
+struct Redacted<L>(L);
+
+impl<L: Log> Log for Redacted<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !redact::is_enabled() || !self.enabled(record.metadata()) {
+            return self.0.log(record);
+        }
+        let message = record.args().to_string();
+        let message = redact::text(&message);
+        self.0.log(
+            &Record::builder()
+                .metadata(record.metadata().clone())
+                .args(format_args!("{message}"))
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.0.flush()
+    }
+}
+
+/// A logger that keeps warnings and errors for the dashboard instead of printing them.
+struct ProgressLogger {
+    progress: Arc<Progress>,
+}
+
+impl Log for ProgressLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.progress
+                .message(format!("{:<5} {}", record.level(), record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Routes warnings and errors to the dashboard, anything printed would garble it.
+pub fn init_dashboard(progress: Arc<Progress>) -> Result<()> {
+    log::set_boxed_logger(Box::new(Redacted(ProgressLogger { progress })))?;
+    log::set_max_level(Level::Warn.to_level_filter());
     Ok(())
 }
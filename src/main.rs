@@ -1,35 +1,161 @@
-use color_eyre::{
-    eyre::{Result, WrapErr},
-    Section,
-};
+use args::{Cli, Command};
+use clap::CommandFactory;
+use color_eyre::eyre::Result;
+use progress::Progress;
+use std::io::IsTerminal;
+use std::sync::Arc;
+mod abbrev;
+mod adaptive;
+mod archive;
 mod args;
+mod baseline;
+mod breaker;
+mod checkout;
+mod chunk;
+mod config;
+mod confirm;
 mod constants;
+mod credentials;
+mod diff;
+mod discover;
 mod download;
+mod encoding;
+mod error;
+mod estimate;
+mod evasion;
+mod events;
+mod export;
 mod expression;
+mod extract;
+mod formats;
+mod fsck;
+mod gitconfig;
+mod gitdir;
+mod graph;
+mod history;
+mod hooks;
+mod hosting;
+mod hosts;
+mod import;
+mod index;
+mod leaks;
+mod lfs;
+mod listing;
 mod logging;
+mod manifest;
+mod mapped;
+mod metrics;
+mod middleware;
+mod mirror;
+mod notify;
+mod object;
+mod output;
 mod pack;
+mod pipeline;
+mod priority;
+mod progress;
+mod promisor;
+mod prompt;
+mod raw;
+mod recon;
+mod redact;
+mod refs;
+mod remotes;
+mod report;
+mod repository;
 mod response;
+mod retry;
 mod runner;
-mod webpage;
+mod safepath;
+mod scope;
+mod seal;
+mod secrets;
+mod session;
+mod siblings;
+mod smart;
+mod soft404;
+mod state;
+mod statistics;
+mod storage;
+mod summary;
+mod targets;
+mod template;
+#[cfg(all(test, feature = "git2"))]
+mod testing;
+mod throttle;
+mod transport;
+mod tui;
+mod update;
+mod validate;
+mod visited;
+mod waf;
+mod writer;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
-    let cli = args::parse();
-    logging::init(cli.verbose)?;
+    let (command, verbose, redaction_map) = args::parse()?;
+    // Enabled before anything is logged, and before the runner changes directory.
+    if let Some(map_file) = redaction_map {
+        redact::enable(&map_file)?;
+    }
+    let progress = Arc::new(Progress::default());
+    let to_stdout = matches!(&command, Command::Dump(args) if args.to_stdout.is_some());
+    let tui = match &command {
+        Command::Dump(args) => args.options.tui,
+        Command::Scan(args) => args.options.tui,
+        Command::Check(_)
+        | Command::Extract(_)
+        | Command::Pack { .. }
+        | Command::Objects { .. }
+        | Command::SelfUpdate(_) => false,
+        Command::Completions { shell } => {
+            clap_complete::generate(
+                *shell,
+                &mut Cli::command(),
+                "opendotgit",
+                &mut std::io::stdout(),
+            );
+            return Ok(());
+        }
+        Command::Man => {
+            clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout())?;
+            return Ok(());
+        }
+    };
 
-    // Create the output directory specified in the command line arguments
-    // and ensure that all parent directories exist.
-    std::fs::create_dir_all(&cli.output)
-        .wrap_err("Failed to create output directory")
-        .suggestion("Try supplying a location you can write to")?;
-
-    // Set the current working directory to the output directory.
-    log::info!("Changing current directory to \"{}\"", &cli.output);
-    std::env::set_current_dir(&cli.output)?;
-
-    // Spawn a new `Runner` instance with the specified URL and tasks.
-    runner::run(cli).await?;
+    // The dashboard needs a terminal to draw on, plain logging is used otherwise.
+    let dashboard = if tui && std::io::stdout().is_terminal() {
+        logging::init_dashboard(progress.clone())?;
+        Some(tui::start(progress.clone())?)
+    } else if to_stdout {
+        logging::init_stderr(verbose)?;
+        None
+    } else {
+        logging::init(verbose)?;
+        if tui {
+            log::warn!("Standard output is not a terminal, falling back to plain logging");
+        }
+        None
+    };
 
+    let result = match command {
+        Command::Dump(args) => runner::dump(*args, progress).await,
+        Command::Scan(args) => runner::scan(*args, progress).await,
+        Command::Check(args) => runner::check(*args).await,
+        Command::Extract(args) => runner::extract(args),
+        Command::Pack { command } => runner::pack(command),
+        Command::Objects { command } => runner::objects(command),
+        Command::SelfUpdate(args) => update::run(args).await,
+        Command::Completions { .. } | Command::Man => Ok(()),
+    };
+    if let Some(dashboard) = dashboard {
+        dashboard.stop()?;
+    }
+    // Failures of our own kind exit with a code telling them apart.
+    if let Err(e) = result {
+        eprintln!("Error: {}", redact::text(&format!("{e:?}")));
+        std::process::exit(error::exit_code(&e));
+    }
     Ok(())
 }
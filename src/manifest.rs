@@ -0,0 +1,98 @@
+use crate::{
+    middleware::{Middleware, Next},
+    transport::{Request, Response},
+};
+use chrono::DateTime;
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use log::warn;
+use reqwest::{
+    header::{HeaderMap, HeaderName, ETAG, LAST_MODIFIED},
+    Method,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File in the output directory recording every response received from the target.
+pub const MANIFEST: &str = "manifest.jsonl";
+
+/// A line of the manifest.
+#[derive(Serialize)]
+struct Entry<'a> {
+    url: &'a str,
+    status: u16,
+    size: usize,
+    sha256: String,
+    etag: Option<&'a str>,
+    last_modified: Option<&'a str>,
+    fetched_at: String,
+}
+
+/// Records the URL, status, content hash and caching headers of every response to [`MANIFEST`],
+/// so every recovered file can be traced back to the request it came from.
+///
+/// Lines are appended, so a resumed dump keeps the entries of the previous runs.
+pub struct Manifest {
+    file: Mutex<File>,
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+impl Manifest {
+    /// Opens the manifest in the current directory for appending.
+    pub fn open() -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(MANIFEST)?;
+        Ok(Manifest {
+            file: Mutex::new(file),
+        })
+    }
+
+    fn record(&self, url: &str, response: &Response) -> Result<()> {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let entry = Entry {
+            url,
+            status: response.status().as_u16(),
+            size: response.bytes().len(),
+            sha256: hex::encode(Sha256::digest(response.bytes())),
+            etag: header(response.headers(), &ETAG),
+            last_modified: header(response.headers(), &LAST_MODIFIED),
+            fetched_at: DateTime::from_timestamp(secs as i64, 0)
+                .map(|time| time.to_rfc3339())
+                .unwrap_or_default(),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        // Each entry is written at once so concurrent requests never interleave lines.
+        self.file.lock().unwrap().write_all(&line)?;
+        Ok(())
+    }
+}
+
+impl Middleware for Manifest {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            // HEAD responses carry no body any file could be traced back to.
+            if request.method == Method::HEAD {
+                return next.run(request).await;
+            }
+            let url = request.url.to_string();
+            let response = next.run(request).await?;
+            if let Err(e) = self.record(&url, &response) {
+                warn!("Failed to record {url} in {MANIFEST}: {e}");
+            }
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use color_eyre::Result;
+use std::{fs::File, ops::Deref, path::Path};
+
+/// The contents of a file, memory mapped where the platform allows it so large files are scanned
+/// in place instead of being copied into memory first.
+pub enum Mapped {
+    #[cfg(unix)]
+    Map {
+        pointer: *mut libc::c_void,
+        len: usize,
+    },
+    Buffer(Vec<u8>),
+}
+
+impl Mapped {
+    /// Maps a file read-only, reading it into a buffer if it cannot be mapped.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(&path)?;
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+            let len = file.metadata()?.len() as usize;
+            // Empty files cannot be mapped.
+            if len > 0 {
+                // SAFETY: the mapping is private and read-only, and it is unmapped on drop. Reading
+                // a page past the end of a file truncated while mapped raises SIGBUS, but only files
+                // of the dump are mapped and the writer replaces those by renaming a new file over
+                // them, which leaves the mapped one whole until it is unmapped.
+                let pointer = unsafe {
+                    libc::mmap(
+                        std::ptr::null_mut(),
+                        len,
+                        libc::PROT_READ,
+                        libc::MAP_PRIVATE,
+                        file.as_raw_fd(),
+                        0,
+                    )
+                };
+                if pointer != libc::MAP_FAILED {
+                    return Ok(Mapped::Map { pointer, len });
+                }
+            }
+        }
+        drop(file);
+        Ok(Mapped::Buffer(std::fs::read(path)?))
+    }
+}
+
+impl Deref for Mapped {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            // SAFETY: the mapping spans len readable bytes for as long as self lives.
+            #[cfg(unix)]
+            Mapped::Map { pointer, len } => unsafe {
+                std::slice::from_raw_parts(*pointer as *const u8, *len)
+            },
+            Mapped::Buffer(buffer) => buffer,
+        }
+    }
+}
+
+impl Drop for Mapped {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Mapped::Map { pointer, len } = self {
+            // SAFETY: the pointer and length came from a successful mmap.
+            unsafe {
+                libc::munmap(*pointer, *len);
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{
+    progress::Progress,
+    statistics::{PhaseStatistics, Statistics},
+};
+use color_eyre::{eyre::WrapErr, Result};
+use log::debug;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+/// Content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Name, help and value of a counter published per phase.
+type Counter = (&'static str, &'static str, fn(&PhaseStatistics) -> u64);
+
+const COUNTERS: [Counter; 4] = [
+    (
+        "retries_total",
+        "Requests attempted again after failing.",
+        |s| s.retries as u64,
+    ),
+    (
+        "timeouts_total",
+        "Requests that did not complete within the timeout.",
+        |s| s.timeouts as u64,
+    ),
+    (
+        "errors_total",
+        "Requests that failed without a response, after retrying.",
+        |s| s.errors as u64,
+    ),
+    (
+        "received_bytes_total",
+        "Size of the response bodies received.",
+        |s| s.bytes,
+    ),
+];
+
+#[derive(Default)]
+struct State {
+    dumped: usize,
+    failed: usize,
+    /// The target being dumped and the statistics of its requests.
+    current: Option<(Url, Arc<Statistics>)>,
+    /// Requests of the targets already dumped, per phase.
+    finished: BTreeMap<&'static str, PhaseStatistics>,
+}
+
+/// Progress of a scan, published for Prometheus to scrape.
+pub struct Metrics {
+    targets: AtomicUsize,
+    progress: Arc<Progress>,
+    state: Mutex<State>,
+}
+
+/// Adds the counts of from to into.
+fn merge(into: &mut PhaseStatistics, from: &PhaseStatistics) {
+    for (status, count) in &from.statuses {
+        *into.statuses.entry(*status).or_default() += count;
+    }
+    into.bytes += from.bytes;
+    into.retries += from.retries;
+    into.timeouts += from.timeouts;
+    into.errors += from.errors;
+}
+
+/// Escapes a label value as the exposition format expects it.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Writes the help and type lines introducing a metric.
+fn header(text: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP opendotgit_{name} {help}");
+    let _ = writeln!(text, "# TYPE opendotgit_{name} {kind}");
+}
+
+impl Metrics {
+    pub fn new(targets: usize, progress: Arc<Progress>) -> Self {
+        Metrics {
+            targets: AtomicUsize::new(targets),
+            progress,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Records that a target was queued during the scan.
+    pub fn add_target(&self) {
+        self.targets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that the dump of url started, counting its requests with statistics.
+    pub fn start(&self, url: &Url, statistics: Arc<Statistics>) {
+        self.state.lock().unwrap().current = Some((url.clone(), statistics));
+    }
+
+    /// Records that the current dump ended, successfully or not.
+    pub fn finish(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            state.dumped += 1;
+        } else {
+            state.failed += 1;
+        }
+        if let Some((_, statistics)) = state.current.take() {
+            for phase in statistics.phases() {
+                merge(state.finished.entry(phase.phase).or_default(), &phase);
+            }
+        }
+    }
+
+    /// Formats the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut phases = state.finished.clone();
+        if let Some((_, statistics)) = &state.current {
+            for phase in statistics.phases() {
+                merge(phases.entry(phase.phase).or_default(), &phase);
+            }
+        }
+
+        let mut text = String::new();
+        header(
+            &mut text,
+            "targets",
+            "gauge",
+            "Targets of the scan by state.",
+        );
+        let pending = self.targets.load(Ordering::Relaxed) - state.dumped - state.failed;
+        for (label, value) in [
+            ("dumped", state.dumped),
+            ("failed", state.failed),
+            ("pending", pending),
+        ] {
+            let _ = writeln!(text, "opendotgit_targets{{state=\"{label}\"}} {value}");
+        }
+
+        for (name, help, value) in COUNTERS {
+            header(&mut text, name, "counter", help);
+            for (phase, statistics) in &phases {
+                let _ = writeln!(
+                    text,
+                    "opendotgit_{name}{{phase=\"{}\"}} {}",
+                    escape(phase),
+                    value(statistics)
+                );
+            }
+        }
+
+        header(
+            &mut text,
+            "responses_total",
+            "counter",
+            "Responses received by status code.",
+        );
+        for (phase, statistics) in &phases {
+            for (status, count) in &statistics.statuses {
+                let _ = writeln!(
+                    text,
+                    "opendotgit_responses_total{{phase=\"{}\",status=\"{status}\"}} {count}",
+                    escape(phase)
+                );
+            }
+        }
+
+        if let Some((url, _)) = &state.current {
+            let (finished, queued) = self.progress.counts();
+            header(
+                &mut text,
+                "current_target",
+                "gauge",
+                "The target being dumped and its phase.",
+            );
+            let _ = writeln!(
+                text,
+                "opendotgit_current_target{{url=\"{}\",phase=\"{}\"}} 1",
+                escape(url.as_str()),
+                escape(self.progress.phase().name())
+            );
+            header(
+                &mut text,
+                "current_phase_requests",
+                "gauge",
+                "Requests of the current phase by state.",
+            );
+            for (label, value) in [("finished", finished), ("queued", queued)] {
+                let _ = writeln!(
+                    text,
+                    "opendotgit_current_phase_requests{{state=\"{label}\"}} {value}"
+                );
+            }
+        }
+        text
+    }
+}
+
+/// Answers a single scrape, with the metrics on /metrics and nothing anywhere else.
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await? {
+            0 => return Ok(()),
+            n => head.extend_from_slice(&buffer[..n]),
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let path = head.split(' ').nth(1).unwrap_or("/");
+    let (status, body) = if path.split('?').next().unwrap_or_default() == "/metrics" {
+        ("200 OK", metrics.render())
+    } else {
+        ("404 Not Found", "not found\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Publishes the metrics on /metrics at addr until the process exits, returning the address
+/// listened on.
+pub async fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .wrap_err(format!("Failed to listen on {addr}"))?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((stream, peer)) = listener.accept().await {
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) = respond(stream, &metrics).await {
+                    debug!("Failed to answer the metrics scrape from {peer}: {e}");
+                }
+            });
+        }
+    });
+    Ok(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Phase;
+    use reqwest::StatusCode;
+
+    #[tokio::test]
+    async fn publishes_scan_progress() {
+        let progress = Arc::new(Progress::default());
+        let metrics = Arc::new(Metrics::new(3, progress.clone()));
+        let statistics = Arc::new(Statistics::default());
+        metrics.start(&Url::parse("http://a.test/").unwrap(), statistics.clone());
+        statistics.response(Phase::Refs, StatusCode::OK, 41);
+        metrics.finish(true);
+        let statistics = Arc::new(Statistics::default());
+        metrics.start(&Url::parse("http://b.test/").unwrap(), statistics.clone());
+        progress.enter(Phase::Objects);
+        statistics.response(Phase::Refs, StatusCode::NOT_FOUND, 9);
+
+        let addr = serve("127.0.0.1:0".parse().unwrap(), metrics)
+            .await
+            .unwrap();
+        let body = reqwest::get(format!("http://{addr}/metrics"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        for line in [
+            "opendotgit_targets{state=\"dumped\"} 1",
+            "opendotgit_targets{state=\"pending\"} 2",
+            "opendotgit_received_bytes_total{phase=\"Refs\"} 50",
+            "opendotgit_responses_total{phase=\"Refs\",status=\"404\"} 1",
+            "opendotgit_current_target{url=\"http://b.test/\",phase=\"Objects\"} 1",
+        ] {
+            assert!(
+                body.lines().any(|l| l == line),
+                "{line} missing from {body}"
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::transport::{Request, Response, Transport};
+use color_eyre::Result;
+use futures::future::BoxFuture;
+
+/// A step every request of the [`Downloader`](crate::download::Downloader) passes through on its
+/// way to the transport.
+///
+/// A middleware may rewrite the request, wait before sending it, inspect or replace the response,
+/// or veto the request by returning an error without calling `next`.
+pub trait Middleware: Send + Sync {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>>;
+}
+
+/// The rest of the chain after the current middleware, ending in the transport.
+pub struct Next<'a> {
+    middlewares: &'a [Box<dyn Middleware>],
+    transport: &'a dyn Transport,
+}
+
+impl<'a> Next<'a> {
+    pub fn new(middlewares: &'a [Box<dyn Middleware>], transport: &'a dyn Transport) -> Self {
+        Next {
+            middlewares,
+            transport,
+        }
+    }
+
+    /// Passes the request to the next middleware, or sends it if none are left.
+    pub fn run(self, request: Request) -> BoxFuture<'a, Result<Response>> {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.handle(request, Next::new(rest, self.transport)),
+            None => self.transport.send(request),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::HeaderMap, StatusCode};
+
+    /// Answers every request with the value of its X-Trace header.
+    struct Echo;
+
+    impl Transport for Echo {
+        fn send(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+            let trace = request.headers.get("x-trace").cloned();
+            Box::pin(async move {
+                let body = trace.map(|value| value.as_bytes().to_vec());
+                Ok(Response::new(
+                    StatusCode::OK,
+                    HeaderMap::new(),
+                    body.unwrap_or_default(),
+                ))
+            })
+        }
+    }
+
+    /// Appends its name to the X-Trace header.
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        fn handle<'a>(
+            &'a self,
+            request: Request,
+            next: Next<'a>,
+        ) -> BoxFuture<'a, Result<Response>> {
+            Box::pin(async move {
+                let trace = request
+                    .headers
+                    .get("x-trace")
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                next.run(request.header("x-trace", format!("{trace}{}", self.0)))
+                    .await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_middlewares_in_order() {
+        let middlewares: Vec<Box<dyn Middleware>> = vec![Box::new(Tag("a")), Box::new(Tag("b"))];
+        let request = Request::get("http://localhost/".parse().unwrap());
+        let response = Next::new(&middlewares, &Echo).run(request).await.unwrap();
+        assert_eq!(response.text(), "ab");
+    }
+}
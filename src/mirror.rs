@@ -0,0 +1,101 @@
+use crate::repository::Repository;
+use color_eyre::{eyre::bail, Result};
+
+/// Environment variable holding the token sent as the password when pushing over HTTP(S).
+pub const TOKEN_VAR: &str = "OPENDOTGIT_MIRROR_TOKEN";
+
+/// User name sent along with the token when the remote URL names none.
+const DEFAULT_USER: &str = "git";
+
+/// Returns a forced refspec for every ref of the dump, pushing it under the same name.
+fn refspecs(repo: &Repository) -> Vec<String> {
+    repo.references()
+        .into_iter()
+        .map(|(name, _)| format!("+{name}:{name}"))
+        .collect()
+}
+
+/// Pushes every ref of the repository in the current directory to url, returning the number of refs.
+pub fn push(url: &str) -> Result<usize> {
+    let repo = Repository::open(".")?;
+    let refspecs = refspecs(&repo);
+    if refspecs.is_empty() {
+        bail!("the dump has no refs to push");
+    }
+    let token = std::env::var(TOKEN_VAR).ok();
+    push_refs(url, &refspecs, token)?;
+    Ok(refspecs.len())
+}
+
+#[cfg(feature = "git2")]
+fn push_refs(url: &str, refspecs: &[String], token: Option<String>) -> Result<()> {
+    use git2::{Cred, CredentialType, PushOptions, RemoteCallbacks};
+    use std::cell::RefCell;
+
+    let repo = git2::Repository::open(".")?;
+    let mut remote = repo.remote_anonymous(url)?;
+    let rejected = RefCell::new(Vec::new());
+    let mut attempts = 0;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|_, username, allowed| {
+        // libgit2 asks again for as long as the remote refuses the credentials.
+        attempts += 1;
+        if attempts > 1 {
+            return Err(git2::Error::from_str("the remote refused the credentials"));
+        }
+        let username = username.unwrap_or(DEFAULT_USER);
+        match &token {
+            Some(token) if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) => {
+                Cred::userpass_plaintext(username, token)
+            }
+            _ if allowed.contains(CredentialType::SSH_KEY) => Cred::ssh_key_from_agent(username),
+            _ => Cred::default(),
+        }
+    });
+    callbacks.push_update_reference(|name, status| {
+        if let Some(status) = status {
+            rejected.borrow_mut().push(format!("{name} ({status})"));
+        }
+        Ok(())
+    });
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+    remote.push(refspecs, Some(&mut options))?;
+    // The callbacks borrow the rejected refs.
+    drop(options);
+
+    let rejected = rejected.into_inner();
+    if !rejected.is_empty() {
+        bail!("the remote rejected {}", rejected.join(", "));
+    }
+    Ok(())
+}
+
+/// Pushes with the git binary, handing the token over in the URL.
+#[cfg(not(feature = "git2"))]
+fn push_refs(url: &str, refspecs: &[String], token: Option<String>) -> Result<()> {
+    use color_eyre::{eyre::WrapErr, Section};
+
+    let mut url = url.to_string();
+    if let (Some(token), Ok(mut parsed)) = (token, url::Url::parse(&url)) {
+        if parsed.scheme().starts_with("http") {
+            if parsed.username().is_empty() {
+                let _ = parsed.set_username(DEFAULT_USER);
+            }
+            let _ = parsed.set_password(Some(&token));
+            url = parsed.to_string();
+        }
+    }
+    let status = std::process::Command::new("git")
+        .arg("push")
+        .arg("--quiet")
+        .arg(&url)
+        .args(refspecs)
+        .status()
+        .wrap_err("Failed to run git push")
+        .suggestion("Make sure your system has git installed")?;
+    if !status.success() {
+        bail!("git push exited with {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use crate::report::Report;
+use color_eyre::{eyre::bail, Result};
+use serde_json::json;
+use url::Url;
+
+/// Returns a one line human readable summary of the report for chat webhooks.
+fn summary(report: &Report) -> String {
+    match &report.error {
+        Some(error) => format!("opendotgit failed to dump {}: {error}", report.url),
+        None => format!(
+            "opendotgit finished dumping {} with {} credentials and {} LFS pointers found",
+            report.url,
+            report.credentials.len(),
+            report.lfs_pointers.len()
+        ),
+    }
+}
+
+/// Posts the report to a webhook. Slack and Discord webhooks receive a text summary
+/// in the shape they expect, any other URL receives the full JSON report.
+pub async fn send(url: &Url, report: &Report) -> Result<()> {
+    let host = url.host_str().unwrap_or_default();
+    let body = if host == "hooks.slack.com" {
+        json!({ "text": summary(report) })
+    } else if host.ends_with("discord.com") && url.path().starts_with("/api/webhooks") {
+        json!({ "content": summary(report) })
+    } else {
+        serde_json::to_value(report)?
+    };
+
+    let response = reqwest::Client::new()
+        .post(url.clone())
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&body)?)
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Responded with status code {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// The type of a Git object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl Kind {
+    fn parse(name: &[u8]) -> Option<Self> {
+        match name {
+            b"commit" => Some(Kind::Commit),
+            b"tree" => Some(Kind::Tree),
+            b"blob" => Some(Kind::Blob),
+            b"tag" => Some(Kind::Tag),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Commit => "commit",
+            Kind::Tree => "tree",
+            Kind::Blob => "blob",
+            Kind::Tag => "tag",
+        }
+    }
+}
+
+/// A Git object with its header stripped.
+pub struct Object {
+    pub kind: Kind,
+    pub data: Vec<u8>,
+}
+
+impl Object {
+    /// Inflates a loose object and splits off its `<kind> <size>\0` header.
+    pub fn decode(compressed: &[u8]) -> Result<Self> {
+        let raw = miniz_oxide::inflate::decompress_to_vec_zlib(compressed)
+            .map_err(|e| eyre!("Failed to inflate object: {e:?}"))?;
+        let Some(nul) = raw.iter().position(|&byte| byte == 0) else {
+            bail!("Object header is not terminated");
+        };
+        let (header, data) = (&raw[..nul], &raw[nul + 1..]);
+        let Some(space) = header.iter().position(|&byte| byte == b' ') else {
+            bail!("Object header has no size");
+        };
+        let kind =
+            Kind::parse(&header[..space]).ok_or_else(|| eyre!("Unknown object type in header"))?;
+        let size: usize = std::str::from_utf8(&header[space + 1..])?.parse()?;
+        if size != data.len() {
+            bail!("Object is {} bytes long, header says {size}", data.len());
+        }
+        Ok(Object {
+            kind,
+            data: data.to_vec(),
+        })
+    }
+
+    /// Returns the hex encoded ID of the object, hashed with SHA-256 for 64 character IDs and SHA-1 otherwise.
+    pub fn id(&self, hash_len: usize) -> String {
+        let header = format!("{} {}\0", self.kind.name(), self.data.len());
+        if hash_len == 32 {
+            hex::encode(
+                Sha256::new()
+                    .chain_update(header)
+                    .chain_update(&self.data)
+                    .finalize(),
+            )
+        } else {
+            hex::encode(
+                Sha1::new()
+                    .chain_update(header)
+                    .chain_update(&self.data)
+                    .finalize(),
+            )
+        }
+    }
+
+    /// Returns the IDs of the objects this one points to: the tree and parents of a commit, the
+    /// entries of a tree other than submodules and the target of a tag. Unparsable objects point
+    /// nowhere.
+    pub fn links(&self, hash_len: usize) -> Vec<String> {
+        match self.kind {
+            Kind::Commit => Commit::parse(&self.data)
+                .map(|commit| [vec![commit.tree], commit.parents].concat())
+                .unwrap_or_default(),
+            Kind::Tree => parse_tree(&self.data, hash_len)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|entry| entry.kind().is_some())
+                .map(|entry| entry.id)
+                .collect(),
+            Kind::Tag => parse_tag(&self.data)
+                .map(|(target, _)| vec![target])
+                .unwrap_or_default(),
+            Kind::Blob => Vec::new(),
+        }
+    }
+
+    /// Returns the start of the object on a single line, the names of the entries for trees and a
+    /// placeholder for binary blobs.
+    pub fn preview(&self, hash_len: usize, max_chars: usize) -> String {
+        let text = match self.kind {
+            Kind::Tree => match parse_tree(&self.data, hash_len) {
+                Ok(entries) => entries
+                    .iter()
+                    .map(TreeEntry::name)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                Err(e) => format!("<malformed tree: {e}>"),
+            },
+            _ if self.data.contains(&0) => return "<binary>".to_string(),
+            _ => String::from_utf8_lossy(&self.data).to_string(),
+        };
+        let text = text.trim();
+        let mut preview: String = text
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .take(max_chars)
+            .collect();
+        if text.chars().count() > max_chars {
+            preview.push('…');
+        }
+        preview
+    }
+
+    /// Inflates a loose object and checks that it hashes to the ID it is stored under.
+    pub fn verify(compressed: &[u8], id: &str) -> Result<Self> {
+        let object = Object::decode(compressed)?;
+        let actual = object.id(id.len() / 2);
+        if actual != id {
+            bail!("Object {id} hashes to {actual}");
+        }
+        Ok(object)
+    }
+}
+
+/// The name, email and time recorded for the author or committer of a commit.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// Seconds since the Unix epoch.
+    pub time: i64,
+}
+
+impl Signature {
+    /// Parses `Name <email> 1700000000 +0100`.
+    fn parse(text: &str) -> Self {
+        let (name, rest) = text.split_once('<').unwrap_or((text, ""));
+        let (email, rest) = rest.split_once('>').unwrap_or((rest, ""));
+        Signature {
+            name: name.trim().to_string(),
+            email: email.trim().to_string(),
+            time: rest
+                .split_whitespace()
+                .next()
+                .and_then(|seconds| seconds.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The parts of a commit object used by the analysis phases.
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: Signature,
+    pub committer: Signature,
+    pub message: String,
+}
+
+impl Commit {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let text = String::from_utf8_lossy(data);
+        let (headers, message) = text.split_once("\n\n").unwrap_or((&text, ""));
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+        // Continuation lines of multi-line headers such as gpgsig start with a space and are skipped.
+        for line in headers.lines() {
+            if let Some(id) = line.strip_prefix("tree ") {
+                tree = Some(id.trim().to_string());
+            } else if let Some(id) = line.strip_prefix("parent ") {
+                parents.push(id.trim().to_string());
+            } else if let Some(signature) = line.strip_prefix("author ") {
+                author = Some(Signature::parse(signature));
+            } else if let Some(signature) = line.strip_prefix("committer ") {
+                committer = Some(Signature::parse(signature));
+            }
+        }
+        let author = author.ok_or_else(|| eyre!("Commit has no author"))?;
+        Ok(Commit {
+            tree: tree.ok_or_else(|| eyre!("Commit has no tree"))?,
+            parents,
+            committer: committer.unwrap_or_else(|| author.clone()),
+            author,
+            message: message.to_string(),
+        })
+    }
+
+    /// Returns the first line of the commit message.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default().trim()
+    }
+}
+
+/// Returns the ID and type of the object an annotated tag points to.
+pub fn parse_tag(data: &[u8]) -> Result<(String, Kind)> {
+    let text = String::from_utf8_lossy(data);
+    let mut object = None;
+    let mut kind = None;
+    for line in text.lines().take_while(|line| !line.is_empty()) {
+        if let Some(id) = line.strip_prefix("object ") {
+            object = Some(id.trim().to_string());
+        } else if let Some(name) = line.strip_prefix("type ") {
+            kind = Kind::parse(name.trim().as_bytes());
+        }
+    }
+    match (object, kind) {
+        (Some(object), Some(kind)) => Ok((object, kind)),
+        _ => bail!("Tag has no target"),
+    }
+}
+
+/// Mode of a directory entry in a tree.
+pub const MODE_TREE: u32 = 0o040000;
+
+/// Mode of a submodule entry in a tree.
+pub const MODE_SUBMODULE: u32 = 0o160000;
+
+/// An entry of a tree object.
+#[derive(Debug, Clone)]
+pub struct TreeEntry {
+    pub mode: u32,
+    pub name: Vec<u8>,
+    pub id: String,
+}
+
+impl TreeEntry {
+    /// Returns the type of object the entry points to, None for submodules.
+    pub fn kind(&self) -> Option<Kind> {
+        match self.mode {
+            MODE_TREE => Some(Kind::Tree),
+            MODE_SUBMODULE => None,
+            _ => Some(Kind::Blob),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.name).to_string()
+    }
+}
+
+/// Parses the `<mode> <name>\0<binary id>` entries of a tree object.
+pub fn parse_tree(data: &[u8], hash_len: usize) -> Result<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|&byte| byte == b' ')
+            .ok_or_else(|| eyre!("Tree entry has no mode"))?;
+        let mode = u32::from_str_radix(std::str::from_utf8(&rest[..space])?, 8)?;
+        rest = &rest[space + 1..];
+        let nul = rest
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| eyre!("Tree entry name is not terminated"))?;
+        let name = rest[..nul].to_vec();
+        rest = &rest[nul + 1..];
+        if rest.len() < hash_len {
+            bail!("Tree entry is truncated");
+        }
+        entries.push(TreeEntry {
+            mode,
+            name,
+            id: hex::encode(&rest[..hash_len]),
+        });
+        rest = &rest[hash_len..];
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_objects() {
+        let blob = Object {
+            kind: Kind::Blob,
+            data: b"DB_PASSWORD=hunter2\nDB_HOST=10.0.0.5\n".to_vec(),
+        };
+        assert_eq!(blob.preview(20, 22), "DB_PASSWORD=hunter2 DB…");
+        let binary = Object {
+            kind: Kind::Blob,
+            data: vec![0x89, b'P', b'N', b'G', 0],
+        };
+        assert_eq!(binary.preview(20, 22), "<binary>");
+        let mut tree = b"100644 .env\0".to_vec();
+        tree.extend([0xab; 20]);
+        let tree = Object {
+            kind: Kind::Tree,
+            data: tree,
+        };
+        assert_eq!(tree.preview(20, 22), ".env");
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    formats::Format,
+    report,
+    state::{self, State},
+};
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{bail, eyre, Result, WrapErr},
+    Section,
+};
+use log::{info, warn};
+use std::path::Path;
+use url::Url;
+
+/// What to do with the files already in the output directory.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Policy {
+    /// Refuse to dump into the directory unless it holds an interrupted dump of the same target.
+    Refuse,
+    /// Dump next to the files, keeping those the dump does not replace.
+    Merge,
+    /// Remove the files before dumping.
+    Force,
+}
+
+impl Policy {
+    /// Returns the policy chosen with `--force` or `--merge`.
+    pub fn new(force: bool, merge: bool) -> Self {
+        match (force, merge) {
+            (true, _) => Policy::Force,
+            (false, true) => Policy::Merge,
+            (false, false) => Policy::Refuse,
+        }
+    }
+}
+
+/// Returns the target of the dump whose resume state is in dir, or None if there is none.
+/// A state written before targets were recorded yields an empty target, which matches no URL.
+fn resumable_target(dir: &Path) -> Option<String> {
+    let data = std::fs::read(dir.join(state::STATE_FILE)).ok()?;
+    let state: State = serde_json::from_slice(&data).ok()?;
+    Some(state.target.unwrap_or_default())
+}
+
+/// Returns true if dir holds the state or the report of a dump, which only opendotgit writes.
+fn holds_dump(dir: &Path) -> bool {
+    std::iter::once(state::STATE_FILE.to_string())
+        .chain(
+            Format::value_variants()
+                .iter()
+                .map(|&format| report::file_name(format)),
+        )
+        .any(|name| dir.join(name).is_file())
+}
+
+/// Returns true if dir is the current directory or the home directory, which are never emptied
+/// however they were named.
+fn is_precious(dir: &Path) -> bool {
+    let Ok(dir) = dir.canonicalize() else {
+        return false;
+    };
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    std::env::current_dir()
+        .into_iter()
+        .chain(home.map(Into::into))
+        .filter_map(|precious| precious.canonicalize().ok())
+        .any(|precious| precious == dir)
+}
+
+/// Makes sure dumping url into dir does not silently mix with unrelated files, applying policy
+/// to whatever the directory already holds. A missing or empty directory is always fine.
+pub fn claim(dir: &Path, url: &Url, policy: Policy) -> Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.collect::<std::io::Result<Vec<_>>>()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(e).wrap_err(format!("Failed to read {}", dir.display()));
+        }
+    };
+    if entries.is_empty() {
+        return Ok(());
+    }
+    match policy {
+        Policy::Merge => {
+            warn!(
+                "Dumping into {}, which already holds {} entries",
+                dir.display(),
+                entries.len()
+            );
+            Ok(())
+        }
+        Policy::Force => {
+            if std::path::absolute(dir)?.parent().is_none() {
+                bail!("Refusing to empty the root directory {}", dir.display());
+            }
+            if is_precious(dir) {
+                bail!(
+                    "Refusing to empty {}, the current or home directory",
+                    dir.display()
+                );
+            }
+            if !holds_dump(dir) {
+                return Err(eyre!(
+                    "Refusing to empty {}, which holds no previous dump",
+                    dir.display()
+                ))
+                .suggestion(
+                    "Pick another output directory, or pass --merge to dump next to its files",
+                );
+            }
+            info!(
+                "Removing the {} entries already in {}",
+                entries.len(),
+                dir.display()
+            );
+            for entry in entries {
+                let path = entry.path();
+                let removed = if entry.file_type()?.is_dir() {
+                    std::fs::remove_dir_all(&path)
+                } else {
+                    std::fs::remove_file(&path)
+                };
+                removed.wrap_err(format!("Failed to remove {}", path.display()))?;
+            }
+            Ok(())
+        }
+        Policy::Refuse => match resumable_target(dir) {
+            Some(target) if target == url.as_str() => {
+                info!("Resuming the previous dump in {}", dir.display());
+                Ok(())
+            }
+            Some(target) if target.is_empty() => Err(eyre!(
+                "{} holds a dump whose target was not recorded",
+                dir.display()
+            ))
+            .suggestion("Pass --merge to resume it anyway, or --force to replace it"),
+            Some(target) => Err(eyre!(
+                "{} holds a dump of {target}, not of {url}",
+                dir.display()
+            ))
+            .suggestion("Pick another output directory, or pass --force to replace that dump"),
+            None => Err(eyre!("{} is not empty", dir.display())).suggestion(
+                "Pass --merge to dump next to its files, or --force to remove them first",
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protects_non_empty_directories() {
+        let url = Url::parse("http://example.com/").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("out");
+        assert!(claim(&output, &url, Policy::Refuse).is_ok());
+        std::fs::create_dir(&output).unwrap();
+        assert!(claim(&output, &url, Policy::Refuse).is_ok());
+
+        std::fs::write(output.join("notes.txt"), "mine").unwrap();
+        assert!(claim(&output, &url, Policy::Refuse).is_err());
+        assert!(claim(&output, &url, Policy::Merge).is_ok());
+        assert!(output.join("notes.txt").exists());
+
+        let mut state = State::default();
+        state.bind(url.as_str(), true);
+        std::fs::write(
+            output.join(state::STATE_FILE),
+            serde_json::to_vec(&state).unwrap(),
+        )
+        .unwrap();
+        assert!(claim(&output, &url, Policy::Refuse).is_ok());
+        let other = Url::parse("http://example.org/").unwrap();
+        assert!(claim(&output, &other, Policy::Refuse).is_err());
+
+        std::fs::write(
+            output.join(state::STATE_FILE),
+            serde_json::to_vec(&State::default()).unwrap(),
+        )
+        .unwrap();
+        assert!(claim(&output, &url, Policy::Refuse).is_err());
+
+        std::fs::create_dir(output.join(".git")).unwrap();
+        assert!(claim(&output, &other, Policy::Force).is_ok());
+        assert_eq!(std::fs::read_dir(&output).unwrap().count(), 0);
+
+        std::fs::write(output.join("notes.txt"), "mine").unwrap();
+        assert!(claim(&output, &url, Policy::Force).is_err());
+        assert!(output.join("notes.txt").exists());
+    }
+}
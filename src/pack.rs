@@ -1,5 +1,10 @@
+use crate::object::{Kind, Object};
 use byteorder::{BigEndian, ReadBytesExt};
-use color_eyre::{eyre::bail, Result};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -61,3 +66,349 @@ pub fn parse<P: AsRef<Path>>(file_path: P) -> Result<Vec<String>> {
 
     Ok(hashes)
 }
+
+/// Number of objects in a pack and the offset of the last one, read from the pack's index.
+#[derive(Debug, PartialEq)]
+pub struct IndexStats {
+    pub objects: u32,
+    pub max_offset: u64,
+}
+
+/// An object listed in a pack index.
+#[derive(Debug, PartialEq)]
+pub struct IndexEntry {
+    pub id: String,
+    /// Where the object starts in the pack.
+    pub offset: u64,
+    /// Checksum of the packed object, only recorded by version 2 indexes.
+    pub crc32: Option<u32>,
+}
+
+/// Reads the objects listed in a version 1 or 2 pack index, in the order of their IDs.
+pub fn index_entries(data: &[u8]) -> Result<Vec<IndexEntry>> {
+    let slice = |offset: usize, len: usize| -> Result<&[u8]> {
+        match data.get(offset..offset + len) {
+            Some(bytes) => Ok(bytes),
+            None => bail!("Truncated packfile index"),
+        }
+    };
+    let be32 =
+        |offset: usize| -> Result<u32> { Ok(u32::from_be_bytes(slice(offset, 4)?.try_into()?)) };
+    // Version 1 indexes start with the fanout table right away.
+    let (fanout, version) = if data.starts_with(IDX_SIGNATURE) {
+        (8, be32(4)?)
+    } else {
+        (0, 1)
+    };
+    let count = be32(fanout + 255 * 4)? as usize;
+    let entries = fanout + 256 * 4;
+    let mut listed = Vec::with_capacity(count.min(data.len() / 24));
+    match version {
+        1 => {
+            for i in 0..count {
+                listed.push(IndexEntry {
+                    id: hex::encode(slice(entries + i * 24 + 4, 20)?),
+                    offset: u64::from(be32(entries + i * 24)?),
+                    crc32: None,
+                });
+            }
+        }
+        2 => {
+            let crcs = entries + count * 20;
+            let offsets = crcs + count * 4;
+            let large_offsets = offsets + count * 4;
+            for i in 0..count {
+                let offset = be32(offsets + i * 4)?;
+                let offset = if offset & 0x8000_0000 == 0 {
+                    u64::from(offset)
+                } else {
+                    let at = large_offsets + (offset & 0x7fff_ffff) as usize * 8;
+                    u64::from(be32(at)?) << 32 | u64::from(be32(at + 4)?)
+                };
+                listed.push(IndexEntry {
+                    id: hex::encode(slice(entries + i * 20, 20)?),
+                    offset,
+                    crc32: Some(be32(crcs + i * 4)?),
+                });
+            }
+        }
+        version => bail!("Unsupported packfile index version {version}"),
+    }
+    Ok(listed)
+}
+
+/// Reads the object count and the largest object offset from a version 1 or 2 pack index.
+pub fn index_stats(data: &[u8]) -> Result<IndexStats> {
+    let entries = index_entries(data)?;
+    Ok(IndexStats {
+        objects: entries.len() as u32,
+        max_offset: entries.iter().map(|entry| entry.offset).max().unwrap_or(0),
+    })
+}
+
+/// An object stored in a pack, as found by walking the pack from the start.
+#[derive(Debug)]
+pub struct PackEntry {
+    /// Where the object starts in the pack.
+    pub offset: u64,
+    /// ID of the object, unknown when it is a delta against an object missing from the pack.
+    pub id: Option<String>,
+    /// Type of the object, the type of its base for deltas.
+    pub kind: Option<Kind>,
+    /// Size of the object once inflated, of the delta itself for deltas.
+    pub size: u64,
+    /// Bytes the object takes up in the pack, header included.
+    pub packed_size: u64,
+    /// ID of the object a delta applies to.
+    pub base: Option<String>,
+}
+
+/// Type numbers of the objects in a pack.
+const OBJ_OFS_DELTA: u8 = 6;
+const OBJ_REF_DELTA: u8 = 7;
+
+fn kind_of(number: u8) -> Option<Kind> {
+    match number {
+        1 => Some(Kind::Commit),
+        2 => Some(Kind::Tree),
+        3 => Some(Kind::Blob),
+        4 => Some(Kind::Tag),
+        _ => None,
+    }
+}
+
+/// Reads a delta's size header, a little endian base-128 number.
+fn delta_size(delta: &[u8], at: &mut usize) -> Result<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        let Some(&byte) = delta.get(*at) else {
+            bail!("Truncated delta header");
+        };
+        *at += 1;
+        size |= usize::from(byte & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 || shift > 63 {
+            return Ok(size);
+        }
+    }
+}
+
+/// Rebuilds an object from its base and a delta against it.
+fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut at = 0;
+    if delta_size(delta, &mut at)? != base.len() {
+        bail!("Delta does not apply to a base of {} bytes", base.len());
+    }
+    let target_size = delta_size(delta, &mut at)?;
+    let mut target = Vec::with_capacity(target_size);
+    while let Some(&op) = delta.get(at) {
+        at += 1;
+        if op & 0x80 != 0 {
+            let mut read = |bits: u8, shifts: std::ops::Range<usize>| -> Result<usize> {
+                let mut value = 0;
+                for (bit, shift) in shifts.enumerate() {
+                    if op & (bits << bit) != 0 {
+                        let Some(&byte) = delta.get(at) else {
+                            bail!("Truncated delta copy instruction");
+                        };
+                        at += 1;
+                        value |= usize::from(byte) << (shift * 8);
+                    }
+                }
+                Ok(value)
+            };
+            let offset = read(0x01, 0..4)?;
+            let size = match read(0x10, 0..3)? {
+                0 => 0x10000,
+                size => size,
+            };
+            match base.get(offset..offset + size) {
+                Some(copied) => target.extend_from_slice(copied),
+                None => bail!("Delta copies past the end of its base"),
+            }
+        } else if op != 0 {
+            match delta.get(at..at + usize::from(op)) {
+                Some(inserted) => target.extend_from_slice(inserted),
+                None => bail!("Truncated delta insert instruction"),
+            }
+            at += usize::from(op);
+        } else {
+            bail!("Reserved delta instruction");
+        }
+    }
+    if target.len() != target_size {
+        bail!(
+            "Delta produced {} bytes instead of {target_size}",
+            target.len()
+        );
+    }
+    Ok(target)
+}
+
+/// Inflates the zlib stream at the start of data, which holds size bytes, returning them along
+/// with the length of the stream.
+fn inflate(data: &[u8], size: usize) -> Result<(Vec<u8>, usize)> {
+    use miniz_oxide::inflate::core::{decompress, inflate_flags, DecompressorOxide};
+    use miniz_oxide::inflate::TINFLStatus;
+
+    // One spare byte lets the decompressor see the end of the stream for empty objects.
+    let mut out = vec![0; size + 1];
+    let flags = inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER
+        | inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+    let (status, consumed, written) =
+        decompress(&mut DecompressorOxide::new(), data, &mut out, 0, flags);
+    if status != TINFLStatus::Done || written != size {
+        bail!("Failed to inflate a packed object ({status:?})");
+    }
+    out.truncate(size);
+    Ok((out, consumed))
+}
+
+/// Walks a version 2 or 3 pack from the start, inflating every object and resolving deltas to
+/// find the IDs of the objects.
+pub fn entries(data: &[u8]) -> Result<Vec<PackEntry>> {
+    if data.len() < 12 || &data[..4] != b"PACK" {
+        bail!("Invalid packfile signature");
+    }
+    let version = u32::from_be_bytes(data[4..8].try_into()?);
+    if version != 2 && version != 3 {
+        bail!("Unsupported packfile version {version}");
+    }
+    let count = u32::from_be_bytes(data[8..12].try_into()?) as usize;
+
+    // Objects resolved so far by offset and by ID, to resolve the deltas against them.
+    let mut by_offset: HashMap<u64, Object> = HashMap::new();
+    let mut by_id: HashMap<String, u64> = HashMap::new();
+    let mut listed = Vec::with_capacity(count.min(data.len() / 2));
+    let mut at = 12;
+    for _ in 0..count {
+        let start = at;
+        let Some(&first) = data.get(at) else {
+            bail!(
+                "Truncated packfile, {} of {count} objects read",
+                listed.len()
+            );
+        };
+        at += 1;
+        let number = (first >> 4) & 0b111;
+        let mut size = u64::from(first & 0x0f);
+        let mut shift = 4;
+        let mut byte = first;
+        while byte & 0x80 != 0 {
+            let Some(&next) = data.get(at) else {
+                bail!("Truncated object header at offset {start}");
+            };
+            at += 1;
+            byte = next;
+            size |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+        }
+
+        let base = match number {
+            OBJ_OFS_DELTA => {
+                let mut byte = *data
+                    .get(at)
+                    .ok_or_else(|| eyre!("Truncated delta offset"))?;
+                at += 1;
+                let mut distance = u64::from(byte & 0x7f);
+                while byte & 0x80 != 0 {
+                    byte = *data
+                        .get(at)
+                        .ok_or_else(|| eyre!("Truncated delta offset"))?;
+                    at += 1;
+                    distance = ((distance + 1) << 7) | u64::from(byte & 0x7f);
+                }
+                match (start as u64).checked_sub(distance) {
+                    Some(offset) => Some(offset),
+                    None => bail!("Delta at offset {start} points before the pack"),
+                }
+            }
+            OBJ_REF_DELTA => {
+                let Some(id) = data.get(at..at + 20) else {
+                    bail!("Truncated delta base at offset {start}");
+                };
+                at += 20;
+                Some(by_id.get(&hex::encode(id)).copied().unwrap_or(u64::MAX))
+            }
+            _ => None,
+        };
+        let base_id = match number {
+            OBJ_REF_DELTA => Some(hex::encode(&data[at - 20..at])),
+            _ => base
+                .and_then(|offset| by_offset.get(&offset))
+                .map(|base| base.id(20)),
+        };
+
+        let (inflated, consumed) =
+            inflate(&data[at..], size as usize).map_err(|e| eyre!("{e} at offset {start}"))?;
+        at += consumed;
+        let object = match base {
+            None => kind_of(number).map(|kind| Object {
+                kind,
+                data: inflated,
+            }),
+            Some(offset) => match by_offset.get(&offset) {
+                Some(base) => Some(Object {
+                    kind: base.kind,
+                    data: apply_delta(&base.data, &inflated)?,
+                }),
+                None => None,
+            },
+        };
+        let id = object.as_ref().map(|object| object.id(20));
+        let kind = object.as_ref().map(|object| object.kind);
+        if let (Some(object), Some(id)) = (object, &id) {
+            by_id.insert(id.clone(), start as u64);
+            by_offset.insert(start as u64, object);
+        }
+        listed.push(PackEntry {
+            offset: start as u64,
+            id,
+            kind,
+            size,
+            packed_size: (at - start) as u64,
+            base: base_id,
+        });
+    }
+    Ok(listed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_index_stats() {
+        let mut index = IDX_SIGNATURE.to_vec();
+        index.extend(2u32.to_be_bytes());
+        // Both objects start with byte 0xff, so only the last fanout entry counts them.
+        for bucket in 0..256 {
+            index.extend(if bucket == 255 { 2u32 } else { 0 }.to_be_bytes());
+        }
+        index.extend([0xff; 2 * 20]);
+        index.extend([0; 2 * 4]);
+        index.extend(12u32.to_be_bytes());
+        index.extend(0x8000_0000u32.to_be_bytes());
+        index.extend((5u64 << 32).to_be_bytes());
+        assert_eq!(
+            index_stats(&index).unwrap(),
+            IndexStats {
+                objects: 2,
+                max_offset: 5 << 32,
+            }
+        );
+        assert!(index_stats(&index[..100]).is_err());
+    }
+
+    #[test]
+    fn applies_deltas() {
+        let base = b"hello world";
+        // Sizes 11 and 12, copy 6 bytes from offset 0, insert "there", copy the final "d".
+        let mut delta = vec![11, 12, 0x90, 6, 5];
+        delta.extend(b"there");
+        delta.extend([0x91, 10, 1]);
+        assert_eq!(apply_delta(base, &delta).unwrap(), b"hello thered");
+        assert!(apply_delta(b"short", &delta).is_err());
+    }
+}
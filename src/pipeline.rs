@@ -0,0 +1,129 @@
+use clap::ValueEnum;
+use color_eyre::Result;
+use futures::future::LocalBoxFuture;
+use log::info;
+
+/// The steps a dump is made of, as named by `--skip-phase`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Check that the target serves a git HEAD and whether it lists the .git directory
+    Detect,
+    /// Report how many objects the dump would download, with --estimate
+    Estimate,
+    /// Print the files of the repository, with --list-files
+    ListFiles,
+    /// Fetch the advertised refs and the pack the smart protocol offers
+    Negotiate,
+    /// Fetch the well-known files of a .git directory
+    KnownFiles,
+    /// Follow the refs from the files fetched so far
+    Refs,
+    /// Fetch the packs, their indexes and the commit graphs
+    Packs,
+    /// Fetch the loose objects the refs reach, or mirror the listed .git directory
+    Objects,
+    /// Fetch the refs, packs and objects a listed .git directory names but its listing left out
+    FillGaps,
+    /// Follow the paths and objects leaked by HTML pages
+    Leaks,
+    /// Recreate the deleted branches the reflog of HEAD records checking out
+    DeletedBranches,
+    /// Mirror the sensitive items next to the .git directory, with --grab-siblings
+    Siblings,
+    /// Check the integrity of the dump, with --fsck
+    Fsck,
+    /// Record the refs, commits, credentials and remotes in the report
+    Findings,
+    /// Check out the files present in the dump
+    Checkout,
+    /// Replace LFS pointers and expand abbreviated object IDs
+    Extract,
+    /// Run the --post-cmd commands
+    Hooks,
+    /// Seal the output directory, with --seal
+    Seal,
+    /// Write the bundle and archive and push the refs to the mirror
+    Export,
+}
+
+impl Stage {
+    pub fn name(&self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// A step of a pipeline, working on the state C the steps share.
+pub trait Step<C> {
+    fn stage(&self) -> Stage;
+
+    fn run<'a>(&'a self, context: &'a mut C) -> LocalBoxFuture<'a, Result<()>>;
+}
+
+/// Steps run one after another, stopping at the first one that fails.
+pub struct Pipeline<C> {
+    steps: Vec<Box<dyn Step<C>>>,
+}
+
+impl<C> Pipeline<C> {
+    pub fn new(steps: Vec<Box<dyn Step<C>>>) -> Self {
+        Pipeline { steps }
+    }
+
+    /// Runs every step whose stage is not skipped.
+    pub async fn run(&self, context: &mut C, skip: &[Stage]) -> Result<()> {
+        for step in &self.steps {
+            let stage = step.stage();
+            if skip.contains(&stage) {
+                info!("Skipping the {} phase", stage.name());
+                continue;
+            }
+            step.run(context).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::bail;
+
+    /// Records its stage, failing if told to.
+    struct Record(Stage, bool);
+
+    impl Step<Vec<Stage>> for Record {
+        fn stage(&self) -> Stage {
+            self.0
+        }
+
+        fn run<'a>(&'a self, ran: &'a mut Vec<Stage>) -> LocalBoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                ran.push(self.0);
+                if self.1 {
+                    bail!("{} failed", self.0.name());
+                }
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_steps_in_order() {
+        let pipeline = Pipeline::new(vec![
+            Box::new(Record(Stage::Detect, false)),
+            Box::new(Record(Stage::KnownFiles, false)),
+            Box::new(Record(Stage::Objects, true)),
+            Box::new(Record(Stage::Checkout, false)),
+        ]);
+        let mut ran = Vec::new();
+        assert!(pipeline.run(&mut ran, &[]).await.is_err());
+        assert_eq!(ran, [Stage::Detect, Stage::KnownFiles, Stage::Objects]);
+
+        let mut ran = Vec::new();
+        assert!(pipeline.run(&mut ran, &[Stage::Objects]).await.is_ok());
+        assert_eq!(ran, [Stage::Detect, Stage::KnownFiles, Stage::Checkout]);
+        assert_eq!(Stage::KnownFiles.name(), "known-files");
+    }
+}
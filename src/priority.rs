@@ -0,0 +1,145 @@
+use crate::scope::Glob;
+use lazy_static::lazy_static;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Names of the files whose blobs are fetched before any other object, as they most often hold
+/// credentials and keys.
+pub const VALUABLE: [&str; 24] = [
+    ".env",
+    ".env.*",
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "*.kdbx",
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    ".htpasswd",
+    ".netrc",
+    ".npmrc",
+    ".pypirc",
+    ".git-credentials",
+    "credentials.json",
+    "settings.py",
+    "local_settings.py",
+    "wp-config.php",
+    "database.yml",
+    "secrets.yml",
+    "*.tfvars",
+    "*.tfstate",
+];
+
+lazy_static! {
+    static ref VALUABLE_GLOBS: Vec<Glob> = VALUABLE
+        .iter()
+        .map(|pattern| Glob::parse(pattern).expect("valuable patterns are valid"))
+        .collect();
+}
+
+/// Returns true if path, relative to the worktree, names a file worth fetching first.
+pub fn is_valuable(path: &str) -> bool {
+    VALUABLE_GLOBS.iter().any(|glob| glob.matches(path))
+}
+
+/// How early a path is fetched.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Priority {
+    /// Blobs of files matching [`VALUABLE`], so that even an interrupted dump yields them.
+    Valuable,
+    /// Packs, refs and the directories leading to them, which make up a usable repository even
+    /// if the crawl is cut short.
+    First,
+    Normal,
+    /// Hooks, samples and other files that never end up in the worktree.
+    Last,
+}
+
+/// Returns the priority of href, a path below the web root such as ".git/objects/pack/".
+pub fn of(href: &str) -> Priority {
+    let segments: Vec<&str> = href.split('/').filter(|s| !s.is_empty()).collect();
+    let Some(git) = segments.iter().position(|&segment| segment == ".git") else {
+        return Priority::Normal;
+    };
+    match &segments[git + 1..] {
+        [] | ["HEAD" | "packed-refs" | "refs" | "objects"] => Priority::First,
+        ["refs", ..] | ["objects", "pack" | "info", ..] => Priority::First,
+        ["hooks", ..] | ["description"] => Priority::Last,
+        [.., name] if name.ends_with(".sample") => Priority::Last,
+        _ => Priority::Normal,
+    }
+}
+
+/// Paths waiting to be crawled, handed out by priority and in the order they were found.
+#[derive(Default)]
+pub struct Queue {
+    heap: BinaryHeap<Reverse<(Priority, u64, String)>>,
+    pushed: u64,
+}
+
+impl Queue {
+    pub fn push(&mut self, href: String) {
+        self.push_with(of(&href), href);
+    }
+
+    /// Queues href with a priority known from elsewhere than its path.
+    pub fn push_with(&mut self, priority: Priority, href: String) {
+        self.heap.push(Reverse((priority, self.pushed, href)));
+        self.pushed += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<String> {
+        self.heap.pop().map(|Reverse((_, _, href))| href)
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_and_refs_come_first() {
+        let mut queue = Queue::default();
+        for href in [
+            ".git/hooks/",
+            ".git/objects//ab/",
+            ".git/config",
+            ".git/objects//pack/pack-1.pack",
+            ".git/refs//heads/",
+            ".git/description",
+        ] {
+            queue.push(href.to_string());
+        }
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(
+            order,
+            [
+                ".git/objects//pack/pack-1.pack",
+                ".git/refs//heads/",
+                ".git/objects//ab/",
+                ".git/config",
+                ".git/hooks/",
+                ".git/description",
+            ]
+        );
+    }
+
+    #[test]
+    fn valuable_blobs_jump_the_queue() {
+        assert!(is_valuable("config/.env.production"));
+        assert!(is_valuable("deploy/id_rsa"));
+        assert!(is_valuable("app/settings.py"));
+        assert!(!is_valuable("src/main.rs"));
+
+        let mut queue = Queue::default();
+        queue.push(".git/refs/heads/main".to_string());
+        queue.push_with(Priority::Valuable, ".git/objects/ab/cdef".to_string());
+        assert_eq!(queue.pop().as_deref(), Some(".git/objects/ab/cdef"));
+    }
+}
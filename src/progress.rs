@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Mutex,
+};
+use std::time::{Duration, Instant};
+
+/// The maximum number of messages kept for display.
+const MAX_MESSAGES: usize = 200;
+
+/// The phases a dump goes through, in order.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Phase {
+    Head,
+    KnownFiles,
+    Refs,
+    Packs,
+    Objects,
+    Fsck,
+    Branches,
+    Checkout,
+    Finished,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 8] = [
+        Phase::Head,
+        Phase::KnownFiles,
+        Phase::Refs,
+        Phase::Packs,
+        Phase::Objects,
+        Phase::Fsck,
+        Phase::Branches,
+        Phase::Checkout,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Head => "HEAD check",
+            Phase::KnownFiles => "Known files",
+            Phase::Refs => "Refs",
+            Phase::Packs => "Packs",
+            Phase::Objects => "Objects",
+            Phase::Fsck => "Integrity check",
+            Phase::Branches => "Branches and tags",
+            Phase::Checkout => "Checkout",
+            Phase::Finished => "Finished",
+        }
+    }
+}
+
+/// Live progress of a dump, shared between the downloader and whatever displays it.
+pub struct Progress {
+    phase: Mutex<Phase>,
+    /// When the current phase was entered.
+    entered: Mutex<Instant>,
+    /// Time spent in each phase before the current one.
+    durations: Mutex<BTreeMap<Phase, Duration>>,
+    /// Requests issued in the current phase, including those still in flight.
+    queued: AtomicUsize,
+    /// Requests finished in the current phase.
+    finished: AtomicUsize,
+    /// Bytes received over the whole run.
+    bytes: AtomicU64,
+    /// The most recent warnings and errors.
+    messages: Mutex<VecDeque<String>>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress {
+            phase: Mutex::new(Phase::Head),
+            entered: Mutex::new(Instant::now()),
+            durations: Mutex::new(BTreeMap::new()),
+            queued: AtomicUsize::new(0),
+            finished: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+            messages: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl Progress {
+    /// Moves on to the given phase and resets the request counters.
+    pub fn enter(&self, phase: Phase) {
+        let now = Instant::now();
+        let previous = std::mem::replace(&mut *self.phase.lock().unwrap(), phase);
+        let entered = std::mem::replace(&mut *self.entered.lock().unwrap(), now);
+        // Time spent finished between two dumps is not counted.
+        if previous != Phase::Finished {
+            *self.durations.lock().unwrap().entry(previous).or_default() += now - entered;
+        }
+        self.queued.store(0, Ordering::Relaxed);
+        self.finished.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the time spent in every phase entered since the last reset, in the order of the
+    /// phases. Phases entered more than once add up.
+    pub fn durations(&self) -> Vec<(Phase, Duration)> {
+        let mut durations = self.durations.lock().unwrap().clone();
+        let phase = self.phase();
+        if phase != Phase::Finished {
+            *durations.entry(phase).or_default() += self.entered.lock().unwrap().elapsed();
+        }
+        durations.into_iter().collect()
+    }
+
+    /// Forgets the durations, as a new dump begins.
+    pub fn reset_durations(&self) {
+        self.durations.lock().unwrap().clear();
+        *self.entered.lock().unwrap() = Instant::now();
+    }
+
+    pub fn phase(&self) -> Phase {
+        *self.phase.lock().unwrap()
+    }
+
+    /// Records that requests were queued in the current phase.
+    pub fn queue(&self, count: usize) {
+        self.queued.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Records that a request finished.
+    pub fn finish(&self) {
+        self.finished.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that a response body was received.
+    pub fn receive(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the number of finished and queued requests in the current phase.
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.finished.load(Ordering::Relaxed),
+            self.queued.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Records a message, dropping the oldest one once the buffer is full.
+    pub fn message(&self, message: String) {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() == MAX_MESSAGES {
+            messages.pop_front();
+        }
+        messages.push_back(message);
+    }
+
+    /// Returns up to count of the most recent messages, oldest first.
+    pub fn recent_messages(&self, count: usize) -> Vec<String> {
+        let messages = self.messages.lock().unwrap();
+        messages
+            .iter()
+            .skip(messages.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+}
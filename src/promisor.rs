@@ -0,0 +1,57 @@
+use crate::gitconfig::GitConfig;
+use color_eyre::Result;
+use std::path::Path;
+
+/// A remote that a partial clone lazily fetches omitted objects from.
+pub struct Promisor {
+    pub remote: String,
+    pub url: Option<String>,
+    /// The object filter the clone was made with, e.g. "blob:none".
+    pub filter: Option<String>,
+}
+
+impl Promisor {
+    /// Returns true if the promisor URL can be fetched from over HTTP(S).
+    pub fn is_http(&self) -> bool {
+        self.url
+            .as_deref()
+            .is_some_and(|url| url.starts_with("http://") || url.starts_with("https://"))
+    }
+}
+
+/// Finds the promisor remotes declared in a repository configuration file.
+pub fn detect<P: AsRef<Path>>(config_path: P) -> Result<Vec<Promisor>> {
+    let config = GitConfig::open(config_path)?;
+    let mut remotes = Vec::new();
+
+    // Older partial clones only record the remote name under extensions.partialClone.
+    if let Some(remote) = config.get("extensions.partialclone") {
+        remotes.push(remote.to_string());
+    }
+
+    for (name, _) in &config.entries {
+        if let Some(remote) = name
+            .strip_prefix("remote.")
+            .and_then(|name| name.strip_suffix(".promisor"))
+        {
+            if config.get_bool(name) {
+                remotes.push(remote.to_string());
+            }
+        }
+    }
+    remotes.sort();
+    remotes.dedup();
+
+    Ok(remotes
+        .into_iter()
+        .map(|remote| Promisor {
+            url: config
+                .get(&format!("remote.{remote}.url"))
+                .map(str::to_string),
+            filter: config
+                .get(&format!("remote.{remote}.partialclonefilter"))
+                .map(str::to_string),
+            remote,
+        })
+        .collect())
+}
@@ -0,0 +1,47 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Asks a yes or no question on the terminal and waits for the answer as long as it takes.
+///
+/// Returns None without asking when nobody can answer, i.e. stdin is not a terminal.
+pub fn confirm(question: &str) -> Option<bool> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return None;
+    }
+    ask(question, stdin.lock(), std::io::stderr()).ok()
+}
+
+/// Writes the question to output and reads answers from input until one is yes or no, an empty
+/// answer or the end of input counting as no.
+fn ask(question: &str, mut input: impl BufRead, mut output: impl Write) -> std::io::Result<bool> {
+    loop {
+        write!(output, "{question} [y/N] ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(false);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "" | "n" | "no" => return Ok(false),
+            _ => writeln!(output, "Please answer yes or no.")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_answers_until_yes_or_no() {
+        let mut output = Vec::new();
+        assert!(ask("Proceed?", &b"maybe\nYes\n"[..], &mut output).unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Proceed? [y/N] Please answer yes or no.\nProceed? [y/N] "
+        );
+        assert!(!ask("Proceed?", &b"\n"[..], std::io::sink()).unwrap());
+        assert!(!ask("Proceed?", &b""[..], std::io::sink()).unwrap());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    middleware::{Middleware, Next},
+    transport::{Request, Response},
+};
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use log::warn;
+use reqwest::Method;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Directory inside the output directory the responses are mirrored into, apart from the
+/// reconstructed repository.
+pub const RAW_DIR: &str = "opendotgit-raw";
+
+/// Name the body served for a URL ending in a slash, such as a directory listing, is stored under
+/// inside that directory, so it never clashes with the files below it.
+const LISTING: &str = "%2F";
+
+/// Returns where the body served for url is mirrored, relative to [`RAW_DIR`]: the host and port,
+/// then the path exactly as requested, percent-encoding included, and the query if any.
+pub fn path(url: &Url) -> Option<PathBuf> {
+    let mut path = PathBuf::from(match url.port() {
+        Some(port) => format!("{}_{port}", url.host_str()?),
+        None => url.host_str()?.to_string(),
+    });
+    let segments: Vec<_> = url.path_segments()?.collect();
+    let (last, parents) = segments.split_last()?;
+    for segment in parents {
+        path.push(segment);
+    }
+    let mut name = last.to_string();
+    if name.is_empty() {
+        name = LISTING.to_string();
+    }
+    if let Some(query) = url.query() {
+        name.push_str("%3F");
+        name.push_str(query);
+    }
+    path.push(name);
+    Some(path)
+}
+
+/// Mirrors the body of every successful response into [`RAW_DIR`] exactly as the server sent it,
+/// before anything decodes, validates or renames it, so the dump can be compared byte for byte
+/// with later snapshots of the target.
+pub struct RawMirror {
+    root: PathBuf,
+}
+
+impl RawMirror {
+    pub fn new(root: &Path) -> Self {
+        RawMirror {
+            root: root.to_path_buf(),
+        }
+    }
+
+    fn write(&self, url: &Url, response: &Response) -> std::io::Result<()> {
+        let Some(path) = path(url) else {
+            return Ok(());
+        };
+        let path = self.root.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, response.bytes())
+    }
+}
+
+impl Middleware for RawMirror {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            let url = request.url.clone();
+            let has_body = request.method != Method::HEAD;
+            let response = next.run(request).await?;
+            if has_body && response.status().is_success() {
+                if let Err(e) = self.write(&url, &response) {
+                    warn!("Failed to mirror {url} into {RAW_DIR}: {e}");
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_server_layout() {
+        let path = |url: &str| {
+            path(&Url::parse(url).unwrap())
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        assert_eq!(
+            path("http://example.com/.git/HEAD"),
+            "example.com/.git/HEAD"
+        );
+        assert_eq!(
+            path("http://example.com:8080/app/.git/objects/"),
+            "example.com_8080/app/.git/objects/%2F"
+        );
+        assert_eq!(
+            path("http://example.com/.git/refs/heads/feature%23x"),
+            "example.com/.git/refs/heads/feature%23x"
+        );
+        assert_eq!(
+            path("http://example.com/.git/HEAD?token=1"),
+            "example.com/.git/HEAD%3Ftoken=1"
+        );
+        assert_eq!(path("http://example.com/"), "example.com/%2F");
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{
+    download::Downloader,
+    index,
+    object::{self, Commit, Kind},
+    repository::{self, Repository},
+};
+use color_eyre::{eyre::eyre, Result};
+use log::{info, warn};
+use pathbuf::pathbuf;
+use serde::Serialize;
+use std::path::Path;
+
+/// A file of the worktree, listed without downloading its content.
+#[derive(Serialize, Clone, Debug)]
+pub struct ListedFile {
+    pub path: String,
+    /// File mode in octal, e.g. "100755" for executables.
+    pub mode: String,
+    /// Size in bytes as staged in the index, unknown when listed from the trees.
+    pub size: Option<u64>,
+    /// ID of the blob holding the content.
+    pub id: String,
+}
+
+/// Returns the path of a loose object below the output directory.
+fn object_href(id: &str) -> String {
+    format!(".git/objects/{}/{}", &id[..2], &id[2..])
+}
+
+/// Lists the files staged in the dumped index.
+fn from_index() -> Result<Vec<ListedFile>> {
+    Ok(index::open(
+        pathbuf![".git", "index"],
+        repository::hash_len(Path::new(".git")),
+    )?
+    .into_iter()
+    .map(|entry| ListedFile {
+        path: String::from_utf8_lossy(&entry.path).into_owned(),
+        mode: format!("{:06o}", entry.mode),
+        size: Some(u64::from(entry.size)),
+        id: entry.id,
+    })
+    .collect())
+}
+
+/// Downloads the commit HEAD points to and the trees below it, but none of the blobs, and lists
+/// the files of those trees.
+async fn from_trees(download: &Downloader) -> Result<Vec<ListedFile>> {
+    download.multiple(&[".git/HEAD", ".git/packed-refs"]).await;
+    let head = std::fs::read_to_string(pathbuf![".git", "HEAD"])
+        .map_err(|e| eyre!("HEAD could not be downloaded: {e}"))?;
+    if let Some(reference) = head.trim().strip_prefix("ref:") {
+        download
+            .multiple(&[format!(".git/{}", reference.trim())])
+            .await;
+    }
+    std::fs::create_dir_all(pathbuf![".git", "objects"])?;
+    let repo = Repository::open(".")?;
+    let commit_id = repo.resolve("HEAD")?;
+
+    download.multiple(&[object_href(&commit_id)]).await;
+    let commit = repo.find(&commit_id)?;
+    if commit.kind != Kind::Commit {
+        return Err(eyre!(
+            "HEAD points to a {}, not a commit",
+            commit.kind.name()
+        ));
+    }
+    let mut trees = vec![(String::new(), Commit::parse(&commit.data)?.tree)];
+    let mut files = Vec::new();
+    while let Some((prefix, id)) = trees.pop() {
+        download.multiple(&[object_href(&id)]).await;
+        let tree = match repo.find(&id) {
+            Ok(tree) => tree,
+            Err(e) => {
+                warn!("Skipping {prefix}/: {e}");
+                continue;
+            }
+        };
+        for entry in object::parse_tree(&tree.data, 20)? {
+            let path = format!("{prefix}{}", entry.name());
+            match entry.kind() {
+                Some(Kind::Tree) => trees.push((format!("{path}/"), entry.id)),
+                _ => files.push(ListedFile {
+                    path,
+                    mode: format!("{:06o}", entry.mode),
+                    size: None,
+                    id: entry.id,
+                }),
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Lists the files of the worktree from the index, or from the trees HEAD points to when the
+/// index cannot be downloaded, without downloading any blob.
+pub async fn list_files(download: &Downloader) -> Result<Vec<ListedFile>> {
+    download.multiple(&[".git/index"]).await;
+    match from_index() {
+        Ok(files) => return Ok(files),
+        Err(e) => info!("The index is unavailable ({e}), listing the trees HEAD points to"),
+    }
+    from_trees(download).await
+}
@@ -0,0 +1,149 @@
+use color_eyre::{eyre::WrapErr, Result};
+use lazy_static::lazy_static;
+use log::warn;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use url::Url;
+
+lazy_static! {
+    /// The host of a URL, after its scheme and userinfo.
+    static ref URL_HOST: Regex =
+        Regex::new(r"(?i)\b([a-z][a-z0-9+.-]*://(?:[^@/\s]*@)?)(\[[0-9a-f:.]+\]|[^/\s:?#@'\x22<>\\]+)")
+            .unwrap();
+    /// An IPv4 address anywhere in the text.
+    static ref IPV4: Regex = Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b").unwrap();
+}
+
+/// Masks standing for hosts end in the reserved `.invalid` top-level domain.
+const MASK_DOMAIN: &str = "invalid";
+
+/// The masks handed out so far, kept in a file so the same host gets the same mask across runs.
+struct Redactor {
+    map_file: PathBuf,
+    /// Masks by the host they stand for.
+    masks: Mutex<BTreeMap<String, String>>,
+}
+
+static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+
+/// Masks the hosts and IP addresses of everything logged or reported from now on, loading the
+/// masks handed out by earlier runs from map_file and saving the new ones to it.
+pub fn enable(map_file: &Path) -> Result<()> {
+    let map_file = std::path::absolute(map_file)?;
+    // The file maps each mask to its host, the way someone reading a shared report looks it up.
+    let masks: BTreeMap<String, String> = match std::fs::read(&map_file) {
+        Ok(data) => serde_json::from_slice::<BTreeMap<String, String>>(&data)
+            .wrap_err(format!("Failed to parse {}", map_file.display()))?
+            .into_iter()
+            .map(|(mask, host)| (host, mask))
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e).wrap_err(format!("Failed to read {}", map_file.display())),
+    };
+    let _ = REDACTOR.set(Redactor {
+        map_file,
+        masks: Mutex::new(masks),
+    });
+    Ok(())
+}
+
+/// Returns true if hosts are masked.
+pub fn is_enabled() -> bool {
+    REDACTOR.get().is_some()
+}
+
+impl Redactor {
+    /// Returns the mask of host, handing out a new one if it has none yet.
+    fn mask(&self, host: &str) -> String {
+        let host = host.to_ascii_lowercase();
+        if host.ends_with(&format!(".{MASK_DOMAIN}")) {
+            return host;
+        }
+        let mut masks = self.masks.lock().unwrap();
+        if let Some(mask) = masks.get(&host) {
+            return mask.clone();
+        }
+        let mask = format!("host-{}.{MASK_DOMAIN}", masks.len() + 1);
+        masks.insert(host, mask.clone());
+        let map: BTreeMap<_, _> = masks.iter().map(|(host, mask)| (mask, host)).collect();
+        if let Err(e) = serde_json::to_vec_pretty(&map)
+            .map_err(std::io::Error::from)
+            .and_then(|data| std::fs::write(&self.map_file, data))
+        {
+            warn!("Failed to save {}: {e}", self.map_file.display());
+        }
+        mask
+    }
+
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = URL_HOST.replace_all(text, |captures: &Captures| {
+            format!("{}{}", &captures[1], self.mask(&captures[2]))
+        });
+        let text = match IPV4.replace_all(&text, |captures: &Captures| self.mask(&captures[0])) {
+            Cow::Borrowed(_) => text,
+            Cow::Owned(replaced) => Cow::Owned(replaced),
+        };
+        // Hosts already masked may also appear outside of URLs.
+        let masks = self.masks.lock().unwrap().clone();
+        let mut hosts: Vec<_> = masks.keys().filter(|host| text.contains(*host)).collect();
+        if hosts.is_empty() {
+            return text;
+        }
+        hosts.sort_by_key(|host| std::cmp::Reverse(host.len()));
+        let mut text = text.into_owned();
+        for host in hosts {
+            text = text.replace(host.as_str(), &masks[host]);
+        }
+        Cow::Owned(text)
+    }
+}
+
+/// Masks the hosts and IP addresses in text if redaction is enabled.
+pub fn text(text: &str) -> Cow<'_, str> {
+    match REDACTOR.get() {
+        Some(redactor) => redactor.redact(text),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Returns url with its host masked if redaction is enabled.
+pub fn url(url: &Url) -> Url {
+    let mut url = url.clone();
+    if let (Some(redactor), Some(host)) = (REDACTOR.get(), url.host_str()) {
+        let mask = redactor.mask(host);
+        let _ = url.set_host(Some(&mask));
+    }
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_hosts_and_addresses() {
+        let dir = tempfile::tempdir().unwrap();
+        let redactor = Redactor {
+            map_file: dir.path().join("map.json"),
+            masks: Mutex::default(),
+        };
+        let line = "Failed to fetch https://deploy@Staging.example.com:8443/.git/HEAD from 10.0.0.7, staging.example.com is down";
+        assert_eq!(
+            redactor.redact(line),
+            "Failed to fetch https://deploy@host-1.invalid:8443/.git/HEAD from host-2.invalid, host-1.invalid is down"
+        );
+        assert_eq!(
+            redactor.redact("http://[2001:db8::1]/ and http://host-1.invalid/"),
+            "http://host-3.invalid/ and http://host-1.invalid/"
+        );
+        assert_eq!(redactor.redact("Found 12 objects"), "Found 12 objects");
+
+        let map: BTreeMap<String, String> =
+            serde_json::from_slice(&std::fs::read(&redactor.map_file).unwrap()).unwrap();
+        assert_eq!(map["host-1.invalid"], "staging.example.com");
+        assert_eq!(map["host-3.invalid"], "[2001:db8::1]");
+    }
+}
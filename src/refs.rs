@@ -0,0 +1,310 @@
+use crate::{
+    expression,
+    repository::{ReflogEntry, Repository},
+};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+/// What a reference names, going by its namespace.
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Branch,
+    Tag,
+    Remote,
+    Other,
+}
+
+impl Kind {
+    fn of(name: &str) -> Self {
+        if name.starts_with("refs/heads/") {
+            Kind::Branch
+        } else if name.starts_with("refs/tags/") {
+            Kind::Tag
+        } else if name.starts_with("refs/remotes/") {
+            Kind::Remote
+        } else {
+            Kind::Other
+        }
+    }
+}
+
+/// A recovered reference and the commit at its tip.
+#[derive(Serialize, Debug)]
+pub struct Tip {
+    pub name: String,
+    pub kind: Kind,
+    /// The object the reference points to, an annotated tag for some tags.
+    pub target: Option<String>,
+    /// The commit the reference resolves to, if it is in the dump.
+    pub commit: Option<String>,
+    /// First line of the commit message.
+    pub subject: Option<String>,
+}
+
+/// Resolves every reference in the repository to its tip commit, including loose and packed refs
+/// whose objects are missing from the dump.
+pub fn enumerate(repo: &Repository) -> Vec<Tip> {
+    repo.references()
+        .into_iter()
+        .map(|(name, target)| {
+            let commit = repo
+                .peel_to_commit(&target)
+                .ok()
+                .and_then(|id| Some((repo.commit(&id).ok()?, id)));
+            Tip {
+                kind: Kind::of(&name),
+                name,
+                target: Some(target),
+                subject: commit
+                    .as_ref()
+                    .map(|(commit, _)| commit.summary().to_string()),
+                commit: commit.map(|(_, id)| id),
+            }
+        })
+        .collect()
+}
+
+/// Returns the names of the references mentioned in the contents of a ref file, as the bytes
+/// they are made of. A trailing dot or slash, which git refuses in names, is taken for the
+/// punctuation around them.
+pub fn names(text: &[u8]) -> Vec<Vec<u8>> {
+    expression::REFS
+        .find_iter(text)
+        .map(|matched| {
+            let mut name = matched.as_bytes();
+            while let [rest @ .., b'.' | b'/'] = name {
+                name = rest;
+            }
+            name.to_vec()
+        })
+        .collect()
+}
+
+/// Returns the href a reference name is requested at, relative to the .git directory. Bytes
+/// outside of printable ASCII and the percent sign are percent-encoded, so that names that are
+/// not UTF-8 survive the request.
+pub fn href(name: &[u8]) -> String {
+    let mut href = String::with_capacity(name.len());
+    for &byte in name {
+        if byte.is_ascii_graphic() && byte != b'%' {
+            href.push(byte as char);
+        } else {
+            href.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    href
+}
+
+/// Returns true if name is a reference name git check-ref-format accepts: made of non-empty
+/// components that do not start with a dot or end with ".lock", without "..", "@{", control
+/// characters, spaces or any of ~^:?*[\ and not ending with a dot.
+pub fn is_valid(name: &[u8]) -> bool {
+    !name.ends_with(b".")
+        && !name.windows(2).any(|pair| pair == b".." || pair == b"@{")
+        && !name
+            .iter()
+            .any(|&byte| byte <= b' ' || byte == 0x7f || b"~^:?*[\\".contains(&byte))
+        && name.split(|&byte| byte == b'/').all(|component| {
+            !component.is_empty() && !component.starts_with(b".") && !component.ends_with(b".lock")
+        })
+}
+
+/// Returns the bytes of the file name an href of a reference or its log stands for, the
+/// inverse of [`href`]. Other hrefs are returned as they are.
+///
+/// Returns None for a reference whose decoded name is not [valid](is_valid), which could
+/// otherwise be written outside of the refs directory.
+pub fn unescape(href: &str) -> Option<Cow<'_, [u8]>> {
+    let Some(prefix) = [".git/", ".git/logs/"].into_iter().find(|prefix| {
+        href.strip_prefix(prefix)
+            .is_some_and(|name| name.starts_with("refs/"))
+    }) else {
+        return Some(Cow::Borrowed(href.as_bytes()));
+    };
+    let bytes = href.as_bytes();
+    let mut name = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                name.push(byte);
+                i += 3;
+            }
+            None => {
+                name.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    is_valid(&name[prefix.len()..]).then_some(Cow::Owned(name))
+}
+
+/// Returns the path a reference whose name is not UTF-8 is written to, made of its original
+/// bytes.
+#[cfg(unix)]
+pub fn raw_path(name: &[u8], _href: &str) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(name))
+}
+
+/// Returns the path a reference whose name is not UTF-8 is written to, which stays
+/// percent-encoded on platforms whose file names are not bytes.
+#[cfg(not(unix))]
+pub fn raw_path(_name: &[u8], href: &str) -> PathBuf {
+    PathBuf::from(href)
+}
+
+/// A branch the reflog of HEAD records checking out or leaving that no ref names anymore.
+#[derive(Serialize, Debug)]
+pub struct DeletedBranch {
+    pub name: String,
+    /// The commit the branch was at when HEAD last moved to or from it.
+    pub commit: String,
+    /// Whether the commit was recovered and the branch recreated under refs/heads.
+    pub recreated: bool,
+}
+
+/// Returns true if name may be the name of a branch at commit, and not the object ID HEAD was
+/// detached at or a name git refuses. Detached checkouts name a full object ID or an abbreviation
+/// of the commit, so short hex names such as "cafe" are still branches elsewhere.
+fn is_branch_name(name: &str, commit: &str) -> bool {
+    let hex = name.bytes().all(|b| b.is_ascii_hexdigit());
+    let detached = hex
+        && (matches!(name.len(), 40 | 64)
+            || name.len() >= 4 && commit.starts_with(&name.to_ascii_lowercase()));
+    !detached
+        && name != "HEAD"
+        && !name.starts_with('-')
+        && !name.chars().any(|c| c.is_control() || c.is_whitespace())
+        && is_valid(name.as_bytes())
+}
+
+/// Returns the branches that "checkout: moving from X to Y" entries of a reflog, newest first,
+/// mention, each with the commit of its newest mention: the old ID for X, the new one for Y.
+fn checkouts(entries: &[ReflogEntry]) -> Vec<(String, String)> {
+    let mut branches: Vec<(String, String)> = Vec::new();
+    for entry in entries {
+        let Some((from, to)) = entry
+            .message
+            .strip_prefix("checkout: moving from ")
+            .and_then(|moves| moves.split_once(" to "))
+        else {
+            continue;
+        };
+        for (name, commit) in [(to, &entry.new), (from, &entry.old)] {
+            if is_branch_name(name, commit) && !branches.iter().any(|(known, _)| known == name) {
+                branches.push((name.to_string(), commit.clone()));
+            }
+        }
+    }
+    branches
+}
+
+/// Finds the branches the reflog of HEAD records checking out that are missing from the refs,
+/// usually because they were deleted after being merged.
+pub fn deleted_branches(repo: &Repository) -> Vec<DeletedBranch> {
+    let heads: Vec<String> = repo
+        .references()
+        .into_iter()
+        .filter_map(|(name, _)| Some(name.strip_prefix("refs/heads/")?.to_string()))
+        .collect();
+    checkouts(&repo.reflog("HEAD"))
+        .into_iter()
+        .filter(|(name, commit)| !heads.contains(name) && commit.bytes().any(|b| b != b'0'))
+        .map(|(name, commit)| DeletedBranch {
+            name,
+            commit,
+            recreated: false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(old: &str, new: &str, message: &str) -> ReflogEntry {
+        ReflogEntry {
+            old: old.repeat(40),
+            new: new.repeat(40),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn keeps_ref_name_bytes() {
+        let text =
+            b"ref: refs/heads/caf\xc3\xa9\n1a41 refs/tags/v1.0^{}\nsee refs/heads/fix+crash.";
+        let found = names(text);
+        assert_eq!(
+            found,
+            [
+                "refs/heads/caf\u{e9}".as_bytes(),
+                b"refs/tags/v1.0",
+                b"refs/heads/fix+crash",
+            ]
+        );
+        let latin1 = b"refs/heads/caf\xe9";
+        assert_eq!(href(&found[0]), "refs/heads/caf%C3%A9");
+        assert_eq!(href(latin1), "refs/heads/caf%E9");
+        assert_eq!(href(b"refs/heads/100%"), "refs/heads/100%25");
+        let encoded = format!(".git/logs/{}", href(latin1));
+        assert_eq!(
+            unescape(&encoded).unwrap(),
+            &b".git/logs/refs/heads/caf\xe9"[..]
+        );
+        assert_eq!(
+            unescape(".git/objects/ab/%E9").unwrap(),
+            &b".git/objects/ab/%E9"[..]
+        );
+    }
+
+    #[test]
+    fn refuses_names_git_refuses() {
+        for name in [
+            ".git/refs/heads/%2E%2E/%2E%2E/config",
+            ".git/refs/heads/a%2F%2E%2E%2Fb",
+            ".git/refs/heads/..",
+            ".git/logs/refs/%2Fetc",
+            ".git/refs//heads",
+            ".git/refs/heads/.hidden",
+            ".git/refs/heads/main.lock",
+            ".git/refs/heads/main.",
+            ".git/refs/heads/a%00b",
+        ] {
+            assert!(unescape(name).is_none(), "{name} was accepted");
+        }
+        assert!(is_valid(b"refs/heads/feature/login"));
+        assert!(!is_valid(b"/refs/heads/main"));
+    }
+
+    #[test]
+    fn finds_checked_out_branches() {
+        // Newest first, as Repository::reflog returns them.
+        let entries = [
+            entry("c", "a", "checkout: moving from feature/login to master"),
+            entry("e", "c", "checkout: moving from cafe to feature/login"),
+            entry("b", "e", "checkout: moving from bbbbbbb to cafe"),
+            entry("a", "b", "checkout: moving from master to bbbbbbb"),
+            entry("0", "a", "clone: from https://example.com/app.git"),
+        ];
+        assert_eq!(
+            checkouts(&entries),
+            [
+                ("master".to_string(), "a".repeat(40)),
+                ("feature/login".to_string(), "c".repeat(40)),
+                ("cafe".to_string(), "e".repeat(40)),
+            ]
+        );
+        assert!(!is_branch_name(&"deadbeef".repeat(5), &"a".repeat(40)));
+        assert!(is_branch_name("deadbeef", &"a".repeat(40)));
+        assert!(!is_branch_name("fix..crash", &"a".repeat(40)));
+        assert!(!is_branch_name("HEAD", &"a".repeat(40)));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    abbrev::Expansion,
+    baseline::Diff,
+    credentials::Credential,
+    estimate::Estimate,
+    extract::DeletedFile,
+    formats::{self, Format},
+    fsck::Fsck,
+    hooks::HookResult,
+    leaks::Leak,
+    lfs::Pointer,
+    recon::ListedFile,
+    refs::{DeletedBranch, Tip},
+    remotes::Remote,
+    safepath::Collision,
+    secrets::Secret,
+    statistics::{PhaseStatistics, PhaseTiming},
+    summary::Summary,
+};
+use color_eyre::Result;
+use serde::Serialize;
+
+/// Returns the name of a report file written in format.
+pub fn file_name(format: Format) -> String {
+    format!("opendotgit-report.{}", format.extension())
+}
+
+/// Findings gathered while dumping a repository, written next to the recovered tree.
+#[derive(Serialize, Default)]
+pub struct Report {
+    /// The format the report is written in.
+    #[serde(skip)]
+    pub format: Format,
+    /// URL of the dumped repository.
+    pub url: String,
+    /// Whether the target serves a git HEAD, i.e. the repository is exposed.
+    pub exposed: bool,
+    /// The file that confirmed the exposure: `.git/HEAD`, or a fallback when the server refused it.
+    pub confirmed_by: Option<String>,
+    /// Whether the server lists the contents of the .git directory.
+    pub listing: bool,
+    /// Subdirectories of the .git directory, such as `objects`, that the server lists although it
+    /// does not list the directory itself. They are mirrored while the rest is dumped blind.
+    pub listed_subtrees: Vec<String>,
+    /// URL of the git directory a `.git` file at the target pointed to, if it served one.
+    pub git_dir: Option<String>,
+    /// The hosting service the target was recognized as, such as GitHub Pages.
+    pub hosting: Option<String>,
+    /// URL of the archive the .git directory was extracted from instead of being crawled for.
+    pub archive: Option<String>,
+    /// Whether the server speaks the smart HTTP protocol, which yielded the objects as a single pack.
+    pub smart_protocol: bool,
+    /// Objects and bytes the packs hold, estimated before downloading them.
+    pub estimate: Option<Estimate>,
+    /// Files of the worktree listed with `--list-files`, without their content.
+    pub listed_files: Vec<ListedFile>,
+    /// The error that ended the run early, if any.
+    pub error: Option<String>,
+    /// Branches, tags and other references with the commits at their tips.
+    pub refs: Vec<Tip>,
+    /// Branches the reflog of HEAD records checking out that no ref names anymore, recreated
+    /// under refs/heads when their commits were recovered.
+    pub deleted_branches: Vec<DeletedBranch>,
+    /// Remote URLs, usernames and tokens harvested from the repository configuration.
+    pub credentials: Vec<Credential>,
+    /// Remotes the repository records and whether they expose their .git directory, with
+    /// `--follow-remotes`.
+    pub remotes: Vec<Remote>,
+    /// Paths that could not be checked out because their objects are missing from the dump.
+    pub missing_files: Vec<String>,
+    /// Paths from the server the filesystem would have stored as the same file.
+    pub path_collisions: Vec<Collision>,
+    /// Paths and object IDs given away by error and debug pages the target served.
+    pub leaks: Vec<Leak>,
+    /// Git LFS pointer files found in the checked out tree.
+    pub lfs_pointers: Vec<Pointer>,
+    /// Abbreviated object IDs mentioned in the dump and the objects they expanded to, with
+    /// `--expand-abbreviations`.
+    pub abbreviations: Vec<Expansion>,
+    /// Post-processing commands run after the checkout and their exit statuses.
+    pub hooks: Vec<HookResult>,
+    /// New commits and changed files compared to the `--baseline` repository.
+    pub baseline: Option<Diff>,
+    /// Authors, committers and dates of the recovered commits.
+    pub summary: Option<Summary>,
+    /// Files removed in the history of the repository, filled in by `extract`.
+    pub deleted_files: Vec<DeletedFile>,
+    /// Secrets found in committed files, filled in by `extract`.
+    pub secrets: Vec<Secret>,
+    /// Missing and corrupt objects the refs need, when checked with `--fsck`.
+    pub fsck: Option<Fsck>,
+    /// Status codes, retries, timeouts and bytes received per phase of the dump.
+    pub requests: Vec<PhaseStatistics>,
+    /// Time spent in each phase of the dump and the requests made per second.
+    pub timings: Vec<PhaseTiming>,
+}
+
+impl Report {
+    pub fn new(url: &str, format: Format) -> Self {
+        Report {
+            format,
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the name of the report file in the output directory.
+    pub fn file_name(&self) -> String {
+        file_name(self.format)
+    }
+
+    /// Writes the report to the current directory, replacing any previous one, with the hosts
+    /// masked under --redact.
+    pub fn write(&self) -> Result<()> {
+        let contents = match self.format {
+            Format::Json => serde_json::to_vec_pretty(self)?,
+            Format::Csv => formats::csv(self).into_bytes(),
+            Format::Sarif => serde_json::to_vec_pretty(&formats::sarif(self))?,
+        };
+        let contents = if crate::redact::is_enabled() {
+            crate::redact::text(&String::from_utf8_lossy(&contents))
+                .into_owned()
+                .into_bytes()
+        } else {
+            contents
+        };
+        std::fs::write(self.file_name(), contents)?;
+        Ok(())
+    }
+}
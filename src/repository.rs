@@ -0,0 +1,425 @@
+use crate::{
+    gitconfig::GitConfig,
+    index,
+    object::{self, Commit, Kind, Object, TreeEntry},
+    safepath,
+};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+/// How many symbolic references are followed before giving up on a loop.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+/// An entry of a reflog.
+pub struct ReflogEntry {
+    pub old: String,
+    pub new: String,
+    pub message: String,
+}
+
+/// A repository read directly from its .git directory.
+///
+/// Loose objects are inflated in-process, so the analysis phases work without libgit2 or a git
+/// binary. Objects in packs are read through libgit2 when the `git2` feature is enabled and are
+/// treated as missing otherwise.
+pub struct Repository {
+    git_dir: PathBuf,
+    /// Object directories to search, the repository's own followed by its alternates.
+    object_dirs: Vec<PathBuf>,
+    /// Length of object IDs in bytes, 32 for SHA-256 repositories.
+    hash_len: usize,
+    /// Files of the dump written under another name, relative to the worktree, mapped to the
+    /// original.
+    renamed: BTreeMap<String, String>,
+    #[cfg(feature = "git2")]
+    packed: Option<git2::Repository>,
+}
+
+/// Reads the object directories listed in objects/info/alternates, recursively.
+fn alternates(objects_dir: &Path, dirs: &mut Vec<PathBuf>) {
+    let Ok(text) = std::fs::read_to_string(objects_dir.join("info").join("alternates")) else {
+        return;
+    };
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Relative alternates are relative to the objects directory.
+        let dir = objects_dir.join(line);
+        if dir.is_dir() && !dirs.contains(&dir) {
+            dirs.push(dir.clone());
+            alternates(&dir, dirs);
+        }
+    }
+}
+
+/// Returns the length in bytes of the object IDs of the repository in git_dir: 32 if its config
+/// sets extensions.objectformat to sha256, 20 otherwise.
+pub fn hash_len(git_dir: &Path) -> usize {
+    match GitConfig::open(git_dir.join("config")) {
+        Ok(config)
+            if config
+                .get("extensions.objectformat")
+                .is_some_and(|format| format.eq_ignore_ascii_case("sha256")) =>
+        {
+            32
+        }
+        _ => 20,
+    }
+}
+
+impl Repository {
+    /// Opens the repository of a worktree, or a bare repository if path is one.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let git_dir = if path.join(".git").is_dir() {
+            path.join(".git")
+        } else if path.join("objects").is_dir() && path.join("HEAD").is_file() {
+            path.to_path_buf()
+        } else {
+            bail!("{} is not a git repository", path.display());
+        };
+
+        let objects_dir = git_dir.join("objects");
+        let mut object_dirs = vec![objects_dir.clone()];
+        alternates(&objects_dir, &mut object_dirs);
+
+        let hash_len = hash_len(&git_dir);
+
+        Ok(Repository {
+            #[cfg(feature = "git2")]
+            packed: git2::Repository::open(&git_dir).ok(),
+            renamed: safepath::load(path),
+            git_dir,
+            object_dirs,
+            hash_len,
+        })
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    /// Returns the path an object is stored at when loose.
+    fn loose_path(dir: &Path, id: &str) -> Option<PathBuf> {
+        (id.len() > 2 && id.is_ascii()).then(|| dir.join(&id[..2]).join(&id[2..]))
+    }
+
+    /// Reads an object from the loose object directories, falling back to packs when possible.
+    pub fn find(&self, id: &str) -> Result<Object> {
+        for dir in &self.object_dirs {
+            if let Some(path) = Self::loose_path(dir, id) {
+                if let Ok(compressed) = std::fs::read(&path) {
+                    return Object::decode(&compressed);
+                }
+            }
+        }
+        #[cfg(feature = "git2")]
+        if let Some(object) = self.find_packed(id) {
+            return Ok(object);
+        }
+        bail!("Object {id} is missing from the repository")
+    }
+
+    /// Returns the IDs of every object in the repository's own object directory, including packed
+    /// objects when they can be read.
+    pub fn objects(&self) -> Result<HashSet<String>> {
+        let mut objects = HashSet::new();
+        let objects_dir = &self.object_dirs[0];
+        for entry in std::fs::read_dir(objects_dir)?.filter_map(|e| e.ok()) {
+            let prefix = entry.file_name().to_string_lossy().to_string();
+            if prefix.len() != 2 || !prefix.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                continue;
+            }
+            for object in std::fs::read_dir(entry.path())?.filter_map(|e| e.ok()) {
+                objects.insert(format!("{prefix}{}", object.file_name().to_string_lossy()));
+            }
+        }
+        #[cfg(feature = "git2")]
+        if let Some(repo) = &self.packed {
+            repo.odb()?.foreach(|oid| {
+                objects.insert(oid.to_string());
+                true
+            })?;
+        }
+        Ok(objects)
+    }
+
+    /// Reads an object through libgit2, which also searches packs.
+    #[cfg(feature = "git2")]
+    fn find_packed(&self, id: &str) -> Option<Object> {
+        let odb = self.packed.as_ref()?.odb().ok()?;
+        let object = odb.read(git2::Oid::from_str(id).ok()?).ok()?;
+        let kind = match object.kind() {
+            git2::ObjectType::Commit => Kind::Commit,
+            git2::ObjectType::Tree => Kind::Tree,
+            git2::ObjectType::Tag => Kind::Tag,
+            _ => Kind::Blob,
+        };
+        Some(Object {
+            kind,
+            data: object.data().to_vec(),
+        })
+    }
+
+    /// Returns true if the object can be read.
+    pub fn contains(&self, id: &str) -> bool {
+        self.find(id).is_ok()
+    }
+
+    /// Returns true if the object is stored in the repository, whether or not it can be read.
+    pub fn stores(&self, id: &str) -> bool {
+        if self
+            .object_dirs
+            .iter()
+            .filter_map(|dir| Self::loose_path(dir, id))
+            .any(|path| path.is_file())
+        {
+            return true;
+        }
+        #[cfg(feature = "git2")]
+        if let (Some(repo), Ok(oid)) = (&self.packed, git2::Oid::from_str(id)) {
+            return repo.odb().is_ok_and(|odb| odb.exists(oid));
+        }
+        false
+    }
+
+    /// Reads an object and checks that it has the expected type.
+    fn find_kind(&self, id: &str, kind: Kind) -> Result<Object> {
+        let object = self.find(id)?;
+        if object.kind != kind {
+            bail!(
+                "Object {id} is a {}, not a {}",
+                object.kind.name(),
+                kind.name()
+            );
+        }
+        Ok(object)
+    }
+
+    pub fn commit(&self, id: &str) -> Result<Commit> {
+        Commit::parse(&self.find_kind(id, Kind::Commit)?.data)
+    }
+
+    pub fn tree(&self, id: &str) -> Result<Vec<TreeEntry>> {
+        object::parse_tree(&self.find_kind(id, Kind::Tree)?.data, self.hash_len)
+    }
+
+    pub fn blob(&self, id: &str) -> Result<Vec<u8>> {
+        Ok(self.find_kind(id, Kind::Blob)?.data)
+    }
+
+    /// Follows annotated tags until reaching a commit, returning its ID.
+    pub fn peel_to_commit(&self, id: &str) -> Result<String> {
+        let mut id = id.to_string();
+        for _ in 0..MAX_SYMREF_DEPTH {
+            let object = self.find(&id)?;
+            match object.kind {
+                Kind::Commit => return Ok(id),
+                Kind::Tag => id = object::parse_tag(&object.data)?.0,
+                kind => bail!("{id} is a {}, not a commit", kind.name()),
+            }
+        }
+        bail!("Too many nested tags at {id}")
+    }
+
+    /// Reads packed-refs into a map from reference name to object ID.
+    fn packed_refs(&self) -> BTreeMap<String, String> {
+        let text = std::fs::read_to_string(self.git_dir.join("packed-refs")).unwrap_or_default();
+        text.lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .filter_map(|line| {
+                let (id, name) = line.split_once(' ')?;
+                Some((name.trim().to_string(), id.to_string()))
+            })
+            .collect()
+    }
+
+    /// Returns the path a file of the git directory was written to by the dump.
+    fn stored_path(&self, relative: &str) -> PathBuf {
+        let original = format!(".git/{relative}");
+        let local = self
+            .renamed
+            .iter()
+            .find(|(_, name)| **name == original)
+            .and_then(|(local, _)| local.strip_prefix(".git/"))
+            .map_or_else(|| safepath::local(relative).into_owned(), str::to_string);
+        safepath::extended(&self.git_dir.join(local)).into_owned()
+    }
+
+    /// Returns the raw contents of a loose reference, or its packed value.
+    fn read_ref(&self, name: &str) -> Option<String> {
+        let path = self.stored_path(name);
+        if path.is_file() {
+            return std::fs::read_to_string(path)
+                .ok()
+                .map(|text| text.trim().to_string());
+        }
+        self.packed_refs().remove(name)
+    }
+
+    /// Resolves a reference name such as "HEAD" or "refs/heads/main" to an object ID.
+    pub fn resolve(&self, name: &str) -> Result<String> {
+        let mut name = name.to_string();
+        for _ in 0..MAX_SYMREF_DEPTH {
+            let value = self
+                .read_ref(&name)
+                .ok_or_else(|| eyre!("Reference {name} does not exist"))?;
+            match value.strip_prefix("ref:") {
+                Some(target) => name = target.trim().to_string(),
+                None => return Ok(value),
+            }
+        }
+        bail!("Reference {name} is part of a symbolic reference loop")
+    }
+
+    /// Returns every loose and packed reference under refs/ with the object it resolves to.
+    pub fn references(&self) -> Vec<(String, String)> {
+        let mut names: BTreeSet<String> = self.packed_refs().into_keys().collect();
+        let refs_dir = self.git_dir.join("refs");
+        for entry in walkdir::WalkDir::new(&refs_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.file_type().is_file())
+        {
+            if let Ok(relative) = entry.path().strip_prefix(&self.git_dir) {
+                let name = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                // References the filesystem cannot hold under their own name were renamed.
+                let name = self
+                    .renamed
+                    .get(&format!(".git/{name}"))
+                    .and_then(|original| original.strip_prefix(".git/"))
+                    .map_or(name, str::to_string);
+                names.insert(name);
+            }
+        }
+        names
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), self.resolve(&name).ok()?)))
+            .collect()
+    }
+
+    /// Returns the entries of a reference's reflog, newest first.
+    pub fn reflog(&self, name: &str) -> Vec<ReflogEntry> {
+        let path = self.stored_path(&format!("logs/{name}"));
+        let text = std::fs::read_to_string(path).unwrap_or_default();
+        let mut entries: Vec<_> = text
+            .lines()
+            .filter_map(|line| {
+                let (line, message) = line.split_once('\t').unwrap_or((line, ""));
+                let mut fields = line.split(' ');
+                Some(ReflogEntry {
+                    old: fields.next()?.to_string(),
+                    new: fields.next()?.to_string(),
+                    message: message.to_string(),
+                })
+            })
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    /// Returns the entries of the index, or none if the dump has no index.
+    pub fn index(&self) -> Result<Vec<index::Entry>> {
+        let path = self.git_dir.join("index");
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        index::open(path, self.hash_len)
+    }
+
+    /// Returns the commits reachable from the given commits, newest first, skipping those missing
+    /// from the repository and everything reachable from hidden.
+    pub fn walk(&self, tips: &[String], hidden: &[String]) -> Vec<(String, Commit)> {
+        let mut hidden_set = HashSet::new();
+        let mut stack = hidden.to_vec();
+        while let Some(id) = stack.pop() {
+            if hidden_set.insert(id.clone()) {
+                if let Ok(commit) = self.commit(&id) {
+                    stack.extend(commit.parents);
+                }
+            }
+        }
+
+        let mut seen = hidden_set;
+        let mut commits = Vec::new();
+        let mut stack = tips.to_vec();
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            if let Ok(commit) = self.commit(&id) {
+                stack.extend(commit.parents.iter().cloned());
+                commits.push((id, commit));
+            }
+        }
+        commits.sort_by_key(|(_, commit)| std::cmp::Reverse(commit.committer.time));
+        commits
+    }
+
+    /// Returns the commits at the tips of HEAD and every reference.
+    pub fn tips(&self) -> Vec<String> {
+        let mut tips: Vec<String> = self
+            .references()
+            .into_iter()
+            .filter_map(|(_, id)| self.peel_to_commit(&id).ok())
+            .collect();
+        if let Ok(head) = self.resolve("HEAD").and_then(|id| self.peel_to_commit(&id)) {
+            tips.push(head);
+        }
+        tips.sort();
+        tips.dedup();
+        tips
+    }
+
+    /// Returns the commits reachable from HEAD or any reference, newest first.
+    pub fn commits(&self) -> Vec<(String, Commit)> {
+        self.walk(&self.tips(), &[])
+    }
+
+    /// Resolves a branch, tag, other reference or full object ID, returning the ID it points to
+    /// and the full name of the reference it was found through.
+    pub fn rev_parse(&self, spec: &str) -> Result<(String, Option<String>)> {
+        for name in [
+            spec.to_string(),
+            format!("refs/{spec}"),
+            format!("refs/heads/{spec}"),
+            format!("refs/tags/{spec}"),
+            format!("refs/remotes/{spec}"),
+        ] {
+            if let Ok(id) = self.resolve(&name) {
+                let reference = name.starts_with("refs/").then_some(name);
+                return Ok((id, reference));
+            }
+        }
+        if spec.len() == self.hash_len * 2 && spec.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Ok((spec.to_lowercase(), None));
+        }
+        bail!("{spec} is not a branch, tag or commit in the dump")
+    }
+
+    /// Points HEAD at a reference, or directly at a commit when detached.
+    pub fn set_head(&self, target: &str, detached: bool) -> Result<()> {
+        let contents = if detached {
+            format!("{target}\n")
+        } else {
+            format!("ref: {target}\n")
+        };
+        std::fs::write(self.git_dir.join("HEAD"), contents)?;
+        Ok(())
+    }
+
+    /// Returns the libgit2 handle for features that still need it.
+    #[cfg(feature = "git2")]
+    pub fn git2(&self) -> Option<&git2::Repository> {
+        self.packed.as_ref()
+    }
+}
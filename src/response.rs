@@ -1,29 +1,58 @@
+use crate::transport::Response;
 use color_eyre::{eyre::bail, Result};
 use reqwest::{
     header::{CONTENT_LENGTH, CONTENT_TYPE},
-    Response, StatusCode,
+    StatusCode,
 };
-/// Adds extra functionality to `hyper::Response<Body>`.
+
+/// Largest body a file of the repository is taken to have, 8 GiB. A Content-Length beyond it is
+/// bogus or a trap, either way not worth holding in memory.
+pub const MAX_CONTENT_LENGTH: u64 = 8 << 30;
+
+/// The start of an HTML document, after any byte order mark and whitespace.
+const HTML_STARTS: &[&str] = &["<!doctype html", "<html", "<head", "<body"];
+
+/// Returns true if the media type, stripped of its parameters such as the charset, is HTML or XHTML.
+fn is_html_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    media_type.eq_ignore_ascii_case("text/html")
+        || media_type.eq_ignore_ascii_case("application/xhtml+xml")
+}
+
+/// Returns true if body starts like an HTML document.
+fn looks_like_html(body: &[u8]) -> bool {
+    let body = body.strip_prefix(b"\xef\xbb\xbf").unwrap_or(body);
+    let start = body
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(body.len());
+    let body = &body[start..];
+    HTML_STARTS.iter().any(|prefix| {
+        body.get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix.as_bytes()))
+    })
+}
+
+/// Adds extra functionality to a [`Response`].
 pub trait ResponseExt {
-    /// Returns true if the response has a `Content-Type` header indicating it is HTML.
+    /// Returns true if the response is an HTML page, by its `Content-Type` or, without one, its body.
     fn is_html(&self) -> bool;
 
     /// Verifies that the response is valid according to various criteria.
     fn verify(&self) -> Result<()>;
+
+    /// Returns the size of the body announced in the `Content-Length` header.
+    fn content_length(&self) -> Option<u64>;
 }
 
 impl ResponseExt for Response {
-    /// Returns true if the response has a `Content-Type` header indicating it is HTML.
+    /// Returns true if the response has a `Content-Type` header indicating it is HTML or XHTML,
+    /// whatever its parameters, or has none and the body starts like an HTML document.
     fn is_html(&self) -> bool {
-        self.headers()
-            .get(CONTENT_TYPE)
-            .map(|content_type| {
-                content_type
-                    .to_str()
-                    .map(|t| t == "text/html")
-                    .unwrap_or(false)
-            })
-            .unwrap_or(false)
+        match self.headers().get(CONTENT_TYPE) {
+            Some(content_type) => content_type.to_str().map(is_html_type).unwrap_or(false),
+            None => looks_like_html(self.bytes()),
+        }
     }
 
     /// Verifies that the response is valid according to various criteria.
@@ -32,10 +61,12 @@ impl ResponseExt for Response {
         if status != StatusCode::OK {
             bail!("Responded with status code {status}");
         }
-        if let Some(content_length) = self.headers().get(CONTENT_LENGTH) {
-            if content_length.to_str()?.parse::<u16>() == Ok(0) {
-                bail!("Responded with content-length equal to zero");
+        match self.content_length() {
+            Some(0) => bail!("Responded with content-length equal to zero"),
+            Some(length) if length > MAX_CONTENT_LENGTH => {
+                bail!("Responded with content-length {length}, more than {MAX_CONTENT_LENGTH}")
             }
+            _ => {}
         }
 
         if self.is_html() {
@@ -43,4 +74,55 @@ impl ResponseExt for Response {
         }
         Ok(())
     }
+
+    fn content_length(&self) -> Option<u64> {
+        self.headers()
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    #[test]
+    fn verifies_content_length() {
+        let response = |length: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_LENGTH, HeaderValue::from_str(length).unwrap());
+            Response::new(StatusCode::OK, headers, b"ref: refs/heads/main\n".to_vec())
+        };
+        assert!(response("21").verify().is_ok());
+        assert!(response("70000").verify().is_ok());
+        assert!(response("0").verify().is_err());
+        assert!(response(&(MAX_CONTENT_LENGTH + 1).to_string())
+            .verify()
+            .is_err());
+        assert_eq!(response("70000").content_length(), Some(70000));
+    }
+
+    #[test]
+    fn recognizes_html() {
+        let response = |content_type: Option<&str>, body: &[u8]| {
+            let mut headers = HeaderMap::new();
+            if let Some(content_type) = content_type {
+                headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+            }
+            Response::new(StatusCode::OK, headers, body.to_vec())
+        };
+        assert!(response(Some("text/html"), b"").is_html());
+        assert!(response(Some("Text/HTML; charset=utf-8"), b"").is_html());
+        assert!(response(Some("application/xhtml+xml"), b"").is_html());
+        assert!(!response(Some("text/plain"), b"<html>").is_html());
+        assert!(!response(Some("application/octet-stream"), b"").is_html());
+        assert!(response(None, b"\xef\xbb\xbf\n  <!DOCTYPE html><html>").is_html());
+        assert!(response(None, b"<HTML><body>Not Found").is_html());
+        assert!(!response(None, b"ref: refs/heads/main\n").is_html());
+        assert!(!response(None, b"<h").is_html());
+    }
 }
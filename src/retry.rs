@@ -0,0 +1,66 @@
+use color_eyre::Report;
+use reqwest::StatusCode;
+use std::io;
+
+/// Kinds of I/O failures that come from the connection rather than the target, and may pass.
+const TRANSIENT_KINDS: [io::ErrorKind; 7] = [
+    io::ErrorKind::ConnectionRefused,
+    io::ErrorKind::ConnectionReset,
+    io::ErrorKind::ConnectionAborted,
+    io::ErrorKind::NotConnected,
+    io::ErrorKind::BrokenPipe,
+    io::ErrorKind::TimedOut,
+    io::ErrorKind::UnexpectedEof,
+];
+
+/// Returns true if a request that failed without a response may succeed when repeated, as after
+/// a timeout or a dropped connection. Failures of any other kind, such as missing hosts and
+/// untrusted certificates, are not retried.
+pub fn is_transient(error: &Report) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_timeout() || e.is_body();
+        }
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| TRANSIENT_KINDS.contains(&e.kind()))
+    })
+}
+
+/// Returns true if a response says the server failed in a way that may pass, so that the request
+/// is worth repeating. Missing files and other client errors are final.
+pub fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::WrapErr;
+
+    #[test]
+    fn retries_only_transient_failures() {
+        let lookup =
+            io::Error::other("failed to lookup address information: Name or service not known");
+        let nxdomain = Err::<(), _>(lookup)
+            .wrap_err("error sending request")
+            .unwrap_err();
+        assert!(!is_transient(&nxdomain));
+        let reset = Err::<(), _>(io::Error::from(io::ErrorKind::ConnectionReset))
+            .wrap_err("error sending request")
+            .unwrap_err();
+        assert!(is_transient(&reset));
+        let timeout = io::Error::from(io::ErrorKind::TimedOut);
+        assert!(is_transient(&Report::new(timeout)));
+
+        assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+        assert!(!is_transient_status(StatusCode::NOT_IMPLEMENTED));
+    }
+}
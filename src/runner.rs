@@ -1,148 +1,2159 @@
 use crate::{
-    args::Args, constants, download::Downloader, expression, pack, response::ResponseExt, webpage,
+    abbrev, archive,
+    args::{
+        Args, CheckArgs, DumpOptions, ExtractArgs, ExtractCommand, ObjectsCommand, PackCommand,
+        ScanArgs,
+    },
+    baseline::Baseline,
+    breaker::CircuitBreaker,
+    checkout, confirm, constants, credentials, discover,
+    download::{self, Downloader},
+    error::Error,
+    estimate, events, export, expression, extract,
+    formats::Format,
+    fsck::{self, Defect, Fsck},
+    gitdir, graph, history, hooks, hosting,
+    hosts::{HostScope, Rule},
+    import::{self, Import},
+    leaks, lfs, listing,
+    manifest::Manifest,
+    metrics::{self, Metrics},
+    mirror, notify,
+    object::Object,
+    output, pack,
+    pipeline::{Pipeline, Stage, Step},
+    progress::{Phase, Progress},
+    promisor, prompt,
+    raw::{self, RawMirror},
+    recon, refs, remotes,
+    report::{self, Report},
+    repository::Repository,
+    response::ResponseExt,
+    scope::{self, Scope},
+    seal, secrets, session, siblings, smart,
+    statistics::{self, Statistics},
+    storage, summary,
+    targets::{self, Target},
+    transport::Response,
+    tui,
 };
 
 use color_eyre::{
-    eyre::{bail, eyre, Result, WrapErr},
+    eyre::{Result, WrapErr},
     Section,
 };
+use futures::future::LocalBoxFuture;
 use log::{info, warn};
 use pathbuf::pathbuf;
-use std::{collections::HashSet, path::PathBuf};
-use tokio::fs;
-use walkdir::WalkDir;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Instant, SystemTime},
+};
+use tokio::{fs, sync::mpsc};
+use url::Url;
+
+/// Dumps a single repository into the output directory.
+pub async fn dump(args: Args, progress: Arc<Progress>) -> Result<()> {
+    let interrupted = handle_interrupts();
+    // Without an output directory, the dump is spooled to a temporary one it is streamed from.
+    let spool = std::env::temp_dir().join(format!("opendotgit-spool-{}", std::process::id()));
+    let output = args.output.as_deref().map_or(spool.as_path(), Path::new);
+    let policy = output::Policy::new(args.options.force, args.options.merge);
+    output::claim(output, &args.url, policy)?;
+    enter(output)?;
+    let statistics = Arc::new(Statistics::default());
+    let (report, result) =
+        dump_target(&args.url, &args.options, interrupted, progress, statistics).await;
+    for remote in report.remotes.iter().filter(|r| r.exposed) {
+        if let Some(target) = &remote.target {
+            info!("Scan {target} along with this target to dump the exposed remote too");
+        }
+    }
+    if let Some(format) = args.to_stdout {
+        let streamed = export::stream(std::io::stdout().lock(), format);
+        std::env::set_current_dir(std::env::temp_dir())?;
+        if let Err(e) = std::fs::remove_dir_all(&spool) {
+            warn!("Failed to remove {}: {e}", spool.display());
+        }
+        info!("Wrote {} files to standard output", streamed?);
+    }
+    result
+}
+
+/// Dumps every target into its own subdirectory of the output directory, one after another.
+pub async fn scan(args: ScanArgs, progress: Arc<Progress>) -> Result<()> {
+    let mut targets: Vec<_> = args.urls.iter().cloned().map(Target::new).collect();
+    if let Some(path) = &args.input_file {
+        let text =
+            std::fs::read_to_string(path).wrap_err(format!("Failed to read {}", path.display()))?;
+        targets.extend(targets::parse(&text));
+    }
+    if targets.is_empty() {
+        return Err(Error::Usage {
+            reason: "No targets to scan".to_string(),
+        }
+        .into());
+    }
+
+    // The working directory changes for every target.
+    let root = std::path::absolute(&args.output)?;
+    let interrupted = handle_interrupts();
+    let metrics = Arc::new(Metrics::new(targets.len(), progress.clone()));
+    if let Some(addr) = args.metrics_listen {
+        let addr = metrics::serve(addr, metrics.clone()).await?;
+        info!("Publishing metrics on http://{addr}/metrics");
+    }
+    let mut dumped = 0;
+    let mut index = 0;
+    while index < targets.len() {
+        let target = targets[index].clone();
+        index += 1;
+        let url = &target.url;
+        if interrupted.load(Ordering::Relaxed) {
+            warn!(
+                "Skipping the remaining {} targets",
+                targets.len() - index + 1
+            );
+            break;
+        }
+        info!("Scanning target {index} of {}: {url}", targets.len());
+        let output = root.join(target_dir(url));
+        let statistics = Arc::new(Statistics::default());
+        metrics.start(url, statistics.clone());
+        let policy = output::Policy::new(args.options.force, args.options.merge);
+        let mut options = args.options.clone();
+        options.network = target.network(&args.options.network);
+        let result = match output::claim(&output, url, policy).and_then(|()| enter(&output)) {
+            Ok(()) => {
+                let (report, result) = dump_target(
+                    url,
+                    &options,
+                    interrupted.clone(),
+                    progress.clone(),
+                    statistics,
+                )
+                .await;
+                for target in queue_remotes(&report, &targets, &options) {
+                    metrics.add_target();
+                    targets.push(target);
+                }
+                result
+            }
+            Err(e) => Err(e),
+        };
+        metrics.finish(result.is_ok());
+        match result {
+            Ok(()) => dumped += 1,
+            Err(e) => warn!("Failed to dump {url}: {e:#}"),
+        }
+    }
+    info!("Dumped {dumped} of {} targets", targets.len());
+    Ok(())
+}
+
+/// Returns the exposed remotes of a dumped target to queue as additional targets of the scan,
+/// asking about each one unless --yes was passed.
+fn queue_remotes(report: &Report, targets: &[Target], options: &DumpOptions) -> Vec<Target> {
+    let mut queued: Vec<Target> = Vec::new();
+    for remote in report.remotes.iter().filter(|r| r.exposed) {
+        let Some(url) = remote.target.as_ref().and_then(|t| Url::parse(t).ok()) else {
+            continue;
+        };
+        if targets.iter().chain(&queued).any(|t| t.url == url) {
+            continue;
+        }
+        // The dashboard owns the terminal, leaving nowhere to ask.
+        let answer = if options.yes {
+            Some(true)
+        } else if options.tui {
+            None
+        } else {
+            prompt::confirm(&format!(
+                "Remote {url} exposes its .git directory. Scan it too?"
+            ))
+        };
+        match answer {
+            Some(true) => {
+                info!("Queued {url}");
+                queued.push(Target::new(url));
+            }
+            Some(false) => {}
+            None => info!("Not queueing the exposed remote {url}, pass --yes to scan it too"),
+        }
+    }
+    queued
+}
+
+/// Checks whether the target exposes its .git directory without downloading anything else.
+pub async fn check(args: CheckArgs) -> Result<()> {
+    let mut download = Downloader::new(&args.url, &args.network);
+    let mut report = Report::new(args.url.as_str(), Format::default());
+    session::establish(&download, &args.network.pre_requests).await;
+    locate(&mut download, &mut report, false).await?;
+    let mode = if report.listing { "listing" } else { "blind" };
+    println!("{} exposes a git repository ({mode} mode)", args.url);
+    Ok(())
+}
+
+/// Searches an existing dump for deleted files and secrets and rewrites its report.
+pub fn extract(args: ExtractArgs) -> Result<()> {
+    let (dir, import) = match (args.command, args.dir, args.from) {
+        (Some(ExtractCommand::Stash { dir }), ..) => return extract_stashes(&dir),
+        (None, _, Some(from)) => {
+            let import = import::locate(&from)?;
+            (import.root.clone(), Some(import))
+        }
+        (None, Some(dir), None) => (dir, None),
+        (None, None, None) => {
+            return Err(Error::Usage {
+                reason: "No dump directory given".to_string(),
+            }
+            .into())
+        }
+    };
+    let repo = open_dump(&dir)?;
+
+    // Detection results are only known to the dump, carry them over from its JSON report.
+    let previous: serde_json::Value = std::fs::read(report::file_name(Format::Json))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+    let url = previous["url"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| dir.display().to_string());
+    let mut report = Report::new(&url, args.format);
+    report.exposed = previous["exposed"].as_bool().unwrap_or_default();
+    report.listing = previous["listing"].as_bool().unwrap_or_default();
+    report.confirmed_by = previous["confirmed_by"].as_str().map(str::to_string);
+    report.hosting = previous["hosting"].as_str().map(str::to_string);
+    report.archive = previous["archive"].as_str().map(str::to_string);
+
+    if let Some(import) = &import {
+        import_repository(&repo, import, &mut report);
+    }
+    enumerate_refs(&repo, &mut report);
+    summarize(&repo, &mut report, args.summary);
+    write_history(&repo);
+    info!("Harvesting credentials");
+    report.credentials = credentials::harvest();
+    info!("Finding LFS pointers");
+    report.lfs_pointers = lfs::find();
+
+    info!("Recovering deleted files");
+    report.deleted_files = extract::deleted_files(&repo)?;
+    let recovered = report
+        .deleted_files
+        .iter()
+        .filter(|file| file.recovered)
+        .count();
+    info!(
+        "Found {} deleted files, recovered {recovered} into {}",
+        report.deleted_files.len(),
+        extract::DELETED_DIR
+    );
+
+    info!("Searching the history for secrets");
+    report.secrets = secrets::scan(&repo);
+    for secret in &report.secrets {
+        info!(
+            "Found {} in {}:{}: {}",
+            secret.rule, secret.path, secret.line, secret.value
+        );
+    }
+
+    report.write()?;
+    info!("Wrote findings to {}", report.file_name());
+    Ok(())
+}
+
+/// Prepares a repository obtained by other means for analysis: removes the directory listings
+/// mirrored along with it, checks which objects the refs need are missing and checks out its files.
+fn import_repository(repo: &Repository, import: &Import, report: &mut Report) {
+    let removed = import::clean(repo.git_dir());
+    if removed > 0 {
+        info!("Removed {removed} mirrored directory listings");
+    }
+    info!("Checking which objects the refs reach");
+    report.fsck = fsck::run()
+        .map_err(|e| warn!("Failed to check the integrity of the repository: {e}"))
+        .ok();
+    if let Some(fsck) = &report.fsck {
+        log_fsck(fsck);
+    }
+    if import.bare {
+        warn!(
+            "{} is a bare repository, not checking it out",
+            import.root.display()
+        );
+        return;
+    }
+    info!("Checking out the files present in the repository");
+    if let Err(e) = checkout(false, None, report) {
+        warn!("Failed to check out the repository: {e}");
+    }
+}
+
+/// Lists every recovered branch and tag with its tip, so partial dumps are useful even when checkout fails.
+fn enumerate_refs(repo: &Repository, report: &mut Report) {
+    info!("Resolving branches and tags");
+    let tips = refs::enumerate(repo);
+    for tip in &tips {
+        let commit = tip.commit.as_deref().unwrap_or("missing");
+        let subject = tip.subject.as_deref().unwrap_or_default();
+        info!("{:?} {} at {commit} {subject}", tip.kind, tip.name);
+    }
+    report.refs = tips;
+}
+
+/// Summarizes the recovered commits into the report, printing the table if asked to.
+fn summarize(repo: &Repository, report: &mut Report, print: bool) {
+    info!("Summarizing commit metadata");
+    let summary = summary::summarize(repo);
+    if print {
+        print!("{}", summary::table(&summary));
+    }
+    report.summary = Some(summary);
+}
+
+/// Writes the message of every recovered commit to the history log, whether or not the checkout
+/// will succeed.
+fn write_history(repo: &Repository) {
+    info!("Recovering commit messages");
+    match history::write(repo) {
+        Ok(commits) => info!(
+            "Wrote {commits} commit messages to {}",
+            history::HISTORY_LOG
+        ),
+        Err(e) => warn!("Failed to write {}: {e}", history::HISTORY_LOG),
+    }
+}
+
+/// Writes the stashes of an existing dump as patches and files.
+fn extract_stashes(dir: &Path) -> Result<()> {
+    let repo = open_dump(dir)?;
+    let stashes = extract::stashes(&repo)?;
+    if stashes.is_empty() {
+        info!("The repository has no stashes");
+        return Ok(());
+    }
+    for stash in &stashes {
+        info!(
+            "Wrote stash@{{{}}} ({}) \"{}\" with {} changed files to {}",
+            stash.index,
+            stash.commit,
+            stash.message,
+            stash.files.len(),
+            stash.dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Writes a listing to stdout, which is usually piped into other tools that may stop reading early.
+fn print_listing(lines: &str) -> Result<()> {
+    match std::io::stdout().write_all(lines.as_bytes()) {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Prints the objects of a pack or a pack index, one per line.
+pub fn pack(command: PackCommand) -> Result<()> {
+    let (PackCommand::Idx { file } | PackCommand::List { file }) = &command;
+    let data = std::fs::read(file).wrap_err(format!("Failed to read {}", file.display()))?;
+    let mut lines = String::new();
+    match command {
+        PackCommand::Idx { .. } => {
+            for entry in pack::index_entries(&data)? {
+                lines.push_str(&format!("{} {}", entry.offset, entry.id));
+                if let Some(crc32) = entry.crc32 {
+                    lines.push_str(&format!(" ({crc32:08x})"));
+                }
+                lines.push('\n');
+            }
+        }
+        PackCommand::List { .. } => {
+            for entry in pack::entries(&data)? {
+                lines.push_str(&format!(
+                    "{} {} {} {} {}",
+                    entry.id.as_deref().unwrap_or("-"),
+                    entry.kind.map_or("-", |kind| kind.name()),
+                    entry.size,
+                    entry.packed_size,
+                    entry.offset
+                ));
+                if let Some(base) = entry.base {
+                    lines.push(' ');
+                    lines.push_str(&base);
+                }
+                lines.push('\n');
+            }
+        }
+    }
+    print_listing(&lines)
+}
+
+/// Prints the loose objects of a dump, one per line, marking those that cannot be inflated or do
+/// not hash to their name.
+pub fn objects(command: ObjectsCommand) -> Result<()> {
+    let ObjectsCommand::Ls { dir } = command;
+    let objects_dir = [pathbuf![&dir, ".git", "objects"], dir.join("objects")]
+        .into_iter()
+        .find(|candidate| candidate.is_dir())
+        .unwrap_or(dir);
+    let mut lines = String::new();
+    for entry in walkdir::WalkDir::new(&objects_dir)
+        .min_depth(2)
+        .max_depth(2)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+    {
+        let (Some(prefix), Some(rest)) = (
+            entry.path().parent().and_then(Path::file_name),
+            entry.path().file_name(),
+        ) else {
+            continue;
+        };
+        let id = format!("{}{}", prefix.to_string_lossy(), rest.to_string_lossy());
+        if !id.chars().all(|c| c.is_ascii_hexdigit()) || (id.len() != 40 && id.len() != 64) {
+            continue;
+        }
+        let object = std::fs::read(entry.path())
+            .map_err(Into::into)
+            .and_then(|compressed| Object::decode(&compressed));
+        match object {
+            Ok(object) => {
+                let hash_len = id.len() / 2;
+                let corrupt = if object.id(hash_len) == id {
+                    ""
+                } else {
+                    " (hash mismatch)"
+                };
+                lines.push_str(&format!(
+                    "{id} {} {}{corrupt} {}\n",
+                    object.kind.name(),
+                    object.data.len(),
+                    object.preview(hash_len, 60)
+                ));
+            }
+            Err(e) => lines.push_str(&format!("{id} - - <{e}>\n")),
+        }
+    }
+    print_listing(&lines)
+}
+
+/// Makes the output directory of a dump the current directory and opens its repository.
+fn open_dump(dir: &Path) -> Result<Repository> {
+    std::env::set_current_dir(dir)?;
+    Repository::open(".")
+        .wrap_err(format!("{} is not a dump", dir.display()))
+        .suggestion("Point extract at the output directory of a dump")
+}
+
+/// Stops making new requests on the first Ctrl-C so in-flight writes can finish, aborts on the second.
+fn handle_interrupts() -> Arc<AtomicBool> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            warn!("Interrupted, letting in-flight requests finish (press Ctrl-C again to abort)");
+            flag.store(true, Ordering::Relaxed);
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    interrupted
+}
+
+/// Creates the output directory and all of its parents and makes it the current directory.
+fn enter(output: &Path) -> Result<()> {
+    std::fs::create_dir_all(output)
+        .wrap_err("Failed to create output directory")
+        .suggestion("Try supplying a location you can write to")?;
+    info!("Changing current directory to \"{}\"", output.display());
+    std::env::set_current_dir(output)?;
+    Ok(())
+}
+
+/// Names the directory a scanned target is dumped into after its host and path, the host masked
+/// under --redact.
+fn target_dir(url: &Url) -> String {
+    let url = crate::redact::url(url);
+    let name: String = url[url::Position::BeforeHost..url::Position::AfterPath]
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    name.trim_matches('_').to_string()
+}
+
+/// Dumps a target into the current directory, then records and announces the outcome, returning
+/// the report along with it.
+async fn dump_target(
+    url: &Url,
+    options: &DumpOptions,
+    interrupted: Arc<AtomicBool>,
+    progress: Arc<Progress>,
+    statistics: Arc<Statistics>,
+) -> (Report, Result<()>) {
+    let started_at = SystemTime::now();
+    let start = Instant::now();
+    let mut report = Report::new(url.as_str(), options.format);
+    progress.reset_durations();
+    let result = dump_repository(
+        url,
+        options,
+        interrupted,
+        &mut report,
+        progress.clone(),
+        statistics.clone(),
+    )
+    .await;
+    progress.enter(Phase::Finished);
+    report.error = result.as_ref().err().map(|e| format!("{e:#}"));
+
+    report.requests = statistics.phases();
+    if !report.requests.is_empty() {
+        info!("Requests per phase:");
+        for line in statistics::table(&report.requests).lines() {
+            info!("{line}");
+        }
+    }
+    report.timings = statistics::timings(&progress.durations(), &report.requests);
+    if options.profile {
+        info!("Time per phase:");
+        for line in statistics::timing_table(&report.timings).lines() {
+            info!("{line}");
+        }
+    }
+    // A dump that stopped early leaves a report without the requests and the error, while a
+    // target that is not exposed gets no report at all.
+    if result.is_err() && Path::new(&report.file_name()).exists() {
+        if let Err(e) = report.write() {
+            warn!("Failed to write {}: {e}", report.file_name());
+        }
+    }
+
+    if let Some(db) = &options.db {
+        let outcome = storage::Outcome {
+            report: &report,
+            started_at,
+            duration: start.elapsed(),
+        };
+        if let Err(e) = storage::record(db, &outcome) {
+            warn!("Failed to record the results in {}: {e}", db.display());
+        }
+    }
+
+    if let Some(notify_url) = &options.notify_url {
+        info!("Notifying {notify_url}");
+        if let Err(e) = notify::send(notify_url, &report).await {
+            warn!("Failed to notify {notify_url}: {e}");
+        }
+    }
+    (report, result)
+}
 
-pub async fn run(args: Args) -> Result<()> {
-    let url = args.url.clone();
-    let download: Downloader = args.into();
+/// Detects the repository, following a `.git` file to the directory it points to if the target
+/// serves one instead of a .git directory, and with --try-archives extracting it from an archive
+/// left next to it when neither is served.
+async fn locate(download: &mut Downloader, report: &mut Report, try_archives: bool) -> Result<()> {
+    if hosting::is_zip_endpoint(&download.url) {
+        return from_archive(download, report).await;
+    }
+    let Err(e) = detect(download, report).await else {
+        return Ok(());
+    };
+    let git_dir = match gitdir::follow(download).await {
+        Ok(Some(git_dir)) => git_dir,
+        Ok(None) if try_archives && find_archive(download, report).await? => return Ok(()),
+        Ok(None) => return Err(e),
+        Err(pointer) => return Err(e.wrap_err(format!("Failed to follow .git: {pointer}"))),
+    };
+    info!(
+        "{}.git is a file pointing to {git_dir}, dumping that instead",
+        download.url
+    );
+    report.git_dir = Some(git_dir.to_string());
+    download.git_dir = Some(git_dir);
+    detect(download, report).await
+}
 
+/// Verifies that the target serves a git HEAD, or another file only a git directory serves when it
+/// refuses to, and whether it lists the .git directory, recording the outcome in the report.
+async fn detect(download: &Downloader, report: &mut Report) -> Result<()> {
     let uri = download.normalize_url(".git/HEAD")?;
     let response = download.fetch_raw_url(&uri).await?;
-    response
-        .verify()
-        .wrap_err(format!("While fetching {uri}"))?;
-
-    let text = response.text().await?;
-    if !expression::HEAD.is_match(text.trim()) {
-        bail!("{url} is not a git HEAD");
+    let head = match response.verify() {
+        Err(e) => Err(e.to_string()),
+        Ok(()) if !expression::HEAD.is_match(response.text().trim()) => {
+            Err("it holds neither a ref nor an object ID".to_string())
+        }
+        Ok(()) => Ok(".git/HEAD"),
+    };
+    let probe = match head {
+        Ok(probe) => probe,
+        Err(reason) => match confirm::fallback(download).await? {
+            Some(probe) => {
+                warn!("{uri} is not a git HEAD: {reason}, but {probe} confirms the repository");
+                probe
+            }
+            None => {
+                return Err(Error::NotAGitRepo {
+                    url: uri.to_string(),
+                    reason,
+                }
+                .into())
+            }
+        },
+    };
+    report.confirmed_by = Some(probe.to_string());
+    report.exposed = true;
+    if let Some(quirk) = hosting::detect(&uri, &response) {
+        info!("The target is served by {}", quirk.name());
+        report.hosting = Some(quirk.name().to_string());
     }
-    let uri = download.normalize_url(".git")?;
+
+    let mut uri = download.normalize_url(".git")?;
     info!("Testing {uri}");
 
-    let response = download.fetch_raw_url(&uri).await?;
+    let mut response = download.fetch_raw_url(&uri).await?;
+    // Servers redirect directories to their trailing slash, where the listing is served.
+    if response.status().is_redirection() {
+        uri = download.normalize_url(&download.redirect_target(".git", &response)?)?;
+        response = download.fetch_raw_url(&uri).await?;
+    }
     if !response.is_html() {
         warn!("{uri} responded without content type text/html")
     }
 
-    let is_webpage_listing = webpage::list(response)
-        .await?
+    report.listing = listing::list(&response, uri.path(), download.parser)
         .iter()
         .any(|filename| filename == "HEAD");
-    if is_webpage_listing {
-        info!("Recursively downloading {uri}");
-        download.recursive(&[".git", ".gitignore"]).await?;
+    if !report.listing {
+        report.listed_subtrees = listed_subtrees(download).await?;
+    }
+    Ok(())
+}
+
+/// Subdirectories of the .git directory that some servers list even when they refuse to list the
+/// directory itself, with the entries that tell their listing apart from any other page.
+const SUBTREES: &[(&str, &[&str])] =
+    &[("objects", &["info", "pack"]), ("refs", &["heads", "tags"])];
+
+/// Returns the subdirectories of the .git directory the server lists although it does not list
+/// the directory itself.
+async fn listed_subtrees(download: &Downloader) -> Result<Vec<String>> {
+    let mut listed = Vec::new();
+    for (subtree, markers) in SUBTREES {
+        let uri = download.normalize_url(&format!(".git/{subtree}/"))?;
+        let response = download.fetch_raw_url(&uri).await?;
+        if !response.status().is_success() {
+            continue;
+        }
+        let entries = listing::list(&response, uri.path(), download.parser);
+        if entries
+            .iter()
+            .any(|entry| markers.contains(&entry.as_str()))
+        {
+            info!("{uri} is listed, mirroring it while dumping the rest blind");
+            listed.push(subtree.to_string());
+        }
+    }
+    Ok(listed)
+}
+
+/// Returns the hrefs of the reflogs of the loose refs in the dump.
+fn loose_ref_logs() -> Vec<String> {
+    walkdir::WalkDir::new(pathbuf![".git", "refs"])
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(".git").ok()?;
+            let segments: Vec<_> = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect();
+            Some(format!(".git/logs/{}", segments.join("/")))
+        })
+        .collect()
+}
+
+/// Extracts the .git directory from an archive served at url into the current directory, recording
+/// the archive in the report. Returns the number of files extracted.
+fn extract_archive(url: &Url, response: &Response, report: &mut Report) -> Result<usize> {
+    response.verify()?;
+    let files = archive::extract_git_dir(archive::read(response.bytes())?)?;
+    if files > 0 {
+        info!("Extracted {files} files of the .git directory from {url}");
+        report.hosting = hosting::detect(url, response).map(|quirk| quirk.name().to_string());
+        report.archive = Some(url.to_string());
+        report.confirmed_by = Some(url.to_string());
+        report.exposed = true;
+    }
+    Ok(files)
+}
+
+/// Extracts the .git directory from the zip archive the target serves, such as the artifacts of a
+/// GitLab job, instead of crawling the server for it.
+async fn from_archive(download: &Downloader, report: &mut Report) -> Result<()> {
+    let url = &download.url;
+    info!("Extracting the .git directory from the archive at {url}");
+    let response = download.fetch_raw_url(url).await?;
+    let reason = match extract_archive(url, &response, report) {
+        Ok(0) => "the archive holds no .git directory".to_string(),
+        Ok(_) => return Ok(()),
+        Err(e) => format!("it is not an archive holding a .git directory: {e}"),
+    };
+    Err(Error::NotAGitRepo {
+        url: url.to_string(),
+        reason,
+    }
+    .into())
+}
+
+/// Looks for the archives commonly left next to a .git directory, extracting it from the first
+/// one holding it. Returns false if none does.
+async fn find_archive(download: &Downloader, report: &mut Report) -> Result<bool> {
+    info!("Looking for archives of the .git directory");
+    for name in archive::CANDIDATES {
+        let url = download.normalize_url(name)?;
+        let response = download.fetch_raw_url(&url).await?;
+        if response.verify().is_err() || response.is_html() {
+            continue;
+        }
+        match extract_archive(&url, &response, report) {
+            Ok(0) => info!("{url} holds no .git directory"),
+            Ok(_) => return Ok(true),
+            Err(e) => warn!("{url} was found but could not be extracted: {e}"),
+        }
+    }
+    Ok(false)
+}
+
+/// Dumps the repository into the current directory, recording findings in the report as they are made.
+async fn dump_repository(
+    url: &Url,
+    options: &DumpOptions,
+    interrupted: Arc<AtomicBool>,
+    report: &mut Report,
+    progress: Arc<Progress>,
+    statistics: Arc<Statistics>,
+) -> Result<()> {
+    let mut extra_known_files = options.extra_known_files.clone();
+    if let Some(list) = &options.known_files_list {
+        let text =
+            std::fs::read_to_string(list).wrap_err(format!("Failed to read {}", list.display()))?;
+        extra_known_files.extend(text.lines().map(str::to_string));
+    }
+    let known_files = constants::extend(constants::KNOWN_FILES, &extra_known_files);
+    let mut extra_ref_files = options.extra_ref_files.clone();
+    if let Some(name) = &options.checkout_ref {
+        // A branch or tag missing from the well-known refs would otherwise never be fetched in blind mode.
+        for namespace in ["heads", "tags", "remotes/origin"] {
+            extra_ref_files.push(format!(".git/refs/{namespace}/{name}"));
+            extra_ref_files.push(format!(".git/logs/refs/{namespace}/{name}"));
+        }
+    }
+    let ref_files = constants::extend(constants::REF_FILES, &extra_ref_files);
+    let mut download = Downloader::new(url, &options.network);
+    download.scope = Scope {
+        include: options.include_globs.clone(),
+        exclude: options.exclude_globs.clone(),
+        skip: if options.fetch_boilerplate {
+            Vec::new()
+        } else {
+            scope::boilerplate()
+        },
+    };
+    download.progress = progress.clone();
+    download.statistics = statistics;
+    download.interrupted = interrupted;
+    download
+        .state
+        .lock()
+        .unwrap()
+        .bind(url.as_str(), !options.no_cache);
+    download.middlewares.push(Box::new(Manifest::open()?));
+    if options.mirror {
+        download
+            .middlewares
+            .push(Box::new(RawMirror::new(Path::new(raw::RAW_DIR))));
+    }
+    // The breaker goes last so it sees the responses before anything else changes them.
+    if options.network.max_failures > 0 {
+        download.middlewares.push(Box::new(CircuitBreaker::new(
+            options.network.max_failures,
+            download.blocked.clone(),
+            progress.clone(),
+        )));
+    }
+    if let Some(path) = &options.baseline {
+        let baseline = Baseline::open(path)?;
+        info!(
+            "Skipping the {} objects already present in {}",
+            baseline.len(),
+            path.display()
+        );
+        download.baseline = Some(baseline);
+    }
+
+    let leftovers = download::remove_partial_writes();
+    if leftovers > 0 {
+        info!("Removed {leftovers} partially written files from a previous run");
+    }
+
+    let mut dump = Dump {
+        url,
+        options,
+        download,
+        report,
+        known_files,
+        ref_files,
+        seed_objects: Vec::new(),
+        graph_paths: Vec::new(),
+        lazy_fetch: false,
+        checked_out: Ok(()),
+    };
+    let recorder = match &options.events {
+        Some(path) => Some(events::record(path, dump.download.events())?),
+        None => None,
+    };
+    let result = pipeline(options).run(&mut dump, &options.skip_phases).await;
+    // Saved however the pipeline ended, so that the next run resumes where this one stopped.
+    dump.download.flush().await;
+    let saved = dump
+        .download
+        .save_renamed()
+        .and_then(|()| dump.download.save_state());
+    // The stream of events ends with the downloader, once the recorder has written all of them.
+    drop(dump.download);
+    if let Some(recorder) = recorder {
+        let _ = recorder.await;
+    }
+    result?;
+    saved?;
+    dump.checked_out
+}
+
+/// The state the steps of a dump share.
+struct Dump<'a> {
+    url: &'a Url,
+    options: &'a DumpOptions,
+    download: Downloader,
+    report: &'a mut Report,
+    known_files: Vec<String>,
+    ref_files: Vec<String>,
+    /// Objects the multi-pack-index lists, to start discovery from.
+    seed_objects: Vec<String>,
+    /// Commit graphs to start discovery from.
+    graph_paths: Vec<PathBuf>,
+    /// Whether git may fetch the objects a partial clone omitted while checking out.
+    lazy_fetch: bool,
+    checked_out: Result<()>,
+}
+
+impl Dump<'_> {
+    /// Returns true if the server speaks the smart protocol or lists the .git directory, either
+    /// of which yields every object without crawling for them.
+    fn is_blind(&self) -> bool {
+        !self.report.smart_protocol && !self.report.listing && !self.is_extracted()
+    }
+
+    /// Returns true if the .git directory was extracted from an archive, leaving nothing to fetch.
+    fn is_extracted(&self) -> bool {
+        self.report.archive.is_some()
+    }
+
+    /// Returns true if the server lists the subdirectory of the .git directory although it does
+    /// not list the directory itself.
+    fn lists(&self, subtree: &str) -> bool {
+        self.report
+            .listed_subtrees
+            .iter()
+            .any(|listed| listed == subtree)
+    }
+
+    /// Records the requests made so far in the report.
+    fn record_requests(&mut self) {
+        self.report.requests = self.download.statistics.phases();
+        self.report.timings =
+            statistics::timings(&self.download.progress.durations(), &self.report.requests);
+    }
+}
+
+/// Returns the steps of a dump with the given options, in order.
+fn pipeline<'a>(options: &DumpOptions) -> Pipeline<Dump<'a>> {
+    let steps: Vec<Box<dyn Step<Dump<'a>>>> = if options.estimate {
+        vec![Box::new(Detect), Box::new(EstimateSize)]
+    } else if options.list_files {
+        vec![Box::new(Detect), Box::new(ListFiles)]
     } else {
-        info!("Fetching common files");
-        download.multiple(constants::KNOWN_FILES).await;
-        info!("Finding refs");
-        download.refs_recursive(constants::REF_FILES).await;
-
-        // read .git/objects/info/packs if exists
-        //   for every sha1 hash, download .git/objects/pack/pack-%s.{idx,pack}
-        info!("Finding packs");
-
-        let pack_path: PathBuf = pathbuf![".git", "objects", "info", "packs"];
-        if pack_path.exists() {
-            let jobs: Vec<_> = expression::PACK
-                .captures_iter(&fs::read_to_string(pack_path).await?)
+        vec![
+            Box::new(Detect),
+            Box::new(Negotiate),
+            Box::new(FetchKnown),
+            Box::new(Refs),
+            Box::new(Packs),
+            Box::new(Objects),
+            Box::new(FillGaps),
+            Box::new(Leaks),
+            Box::new(DeletedBranches),
+            Box::new(Siblings),
+            Box::new(Integrity),
+            Box::new(Findings),
+            Box::new(Checkout),
+            Box::new(Extract),
+            Box::new(Hooks),
+            Box::new(Seal),
+            Box::new(Export),
+        ]
+    };
+    Pipeline::new(steps)
+}
+
+/// Establishes the session and finds the repository.
+struct Detect;
+
+impl<'d> Step<Dump<'d>> for Detect {
+    fn stage(&self) -> Stage {
+        Stage::Detect
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            dump.download.progress.enter(Phase::Head);
+            session::establish(&dump.download, &dump.options.network.pre_requests).await;
+            locate(&mut dump.download, dump.report, dump.options.try_archives).await?;
+
+            info!("Calibrating soft-404 detection");
+            if let Err(e) = dump.download.calibrate().await {
+                warn!("Failed to calibrate soft-404 detection: {e}");
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Estimates the size of the dump without downloading it.
+struct EstimateSize;
+
+impl<'d> Step<Dump<'d>> for EstimateSize {
+    fn stage(&self) -> Stage {
+        Stage::Estimate
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            info!("Estimating the size of the dump");
+            dump.download.progress.enter(Phase::Refs);
+            let estimate =
+                estimate::run(&dump.download, &dump.ref_files, dump.report.listing).await;
+            info!(
+                "Found {} refs and {} packs holding {} objects, about {}",
+                estimate.refs,
+                estimate.packs,
+                estimate.packed_objects,
+                tui::human_bytes(estimate.packed_bytes as f64)
+            );
+            info!("Loose objects cannot be counted before they are downloaded");
+            dump.report.estimate = Some(estimate);
+            dump.record_requests();
+            dump.report.write()?;
+            Ok(())
+        })
+    }
+}
+
+/// Prints the files of the repository without downloading their content.
+struct ListFiles;
+
+impl<'d> Step<Dump<'d>> for ListFiles {
+    fn stage(&self) -> Stage {
+        Stage::ListFiles
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            info!("Listing the files of the repository");
+            dump.download.progress.enter(Phase::KnownFiles);
+            let files = recon::list_files(&dump.download).await?;
+            let mut lines = String::new();
+            for file in &files {
+                let size = file.size.map_or("-".to_string(), |size| size.to_string());
+                lines.push_str(&format!("{} {size:>10} {}\n", file.mode, file.path));
+            }
+            print_listing(&lines)?;
+            info!("Listed {} files", files.len());
+            if files.iter().any(|file| file.size.is_some()) {
+                let total: u64 = files.iter().filter_map(|file| file.size).sum();
+                info!("The index stages {}", tui::human_bytes(total as f64));
+            }
+            dump.report.listed_files = files;
+            dump.record_requests();
+            dump.report.write()?;
+            Ok(())
+        })
+    }
+}
+
+/// Fetches the advertised refs and, if the server speaks the smart protocol, a pack of every
+/// object they reach, asking before huge dumps otherwise.
+struct Negotiate;
+
+impl<'d> Step<Dump<'d>> for Negotiate {
+    fn stage(&self) -> Stage {
+        Stage::Negotiate
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if dump.is_extracted() {
+                return Ok(());
+            }
+            // The refs are asked for before any crawling, even with a listing, since a server
+            // speaking the smart protocol hands over every object in one pack instead of thousands
+            // of loose files.
+            info!("Fetching the advertised refs");
+            dump.download.progress.enter(Phase::Refs);
+            dump.download
+                .multiple(&[".git/info/refs", ".git/packed-refs"])
+                .await;
+            dump.report.smart_protocol = negotiate_pack(&dump.download).await;
+            if !dump.report.smart_protocol && !dump.options.yes {
+                confirm_size(&dump.download, &dump.ref_files, dump.options, dump.report).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Fetches the well-known files, which a listing yields along with the rest.
+struct FetchKnown;
+
+impl<'d> Step<Dump<'d>> for FetchKnown {
+    fn stage(&self) -> Stage {
+        Stage::KnownFiles
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if dump.is_extracted() || (dump.report.listing && !dump.report.smart_protocol) {
+                return Ok(());
+            }
+            info!("Fetching common files");
+            dump.download.progress.enter(Phase::KnownFiles);
+            dump.download.multiple(&dump.known_files).await;
+            Ok(())
+        })
+    }
+}
+
+/// Follows the refs named in the files fetched so far.
+struct Refs;
+
+impl<'d> Step<Dump<'d>> for Refs {
+    fn stage(&self) -> Stage {
+        Stage::Refs
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if dump.is_extracted() || (dump.report.listing && !dump.report.smart_protocol) {
+                return Ok(());
+            }
+            info!("Finding refs");
+            dump.download.progress.enter(Phase::Refs);
+            let mut ref_files = dump.ref_files.clone();
+            if dump.lists("refs") {
+                info!(
+                    "Recursively downloading {}",
+                    dump.download.normalize_url(".git/refs")?
+                );
+                dump.download.recursive(&[".git/refs"]).await?;
+                // Loose refs hold object IDs only, their reflogs are not named anywhere else.
+                ref_files.extend(loose_ref_logs());
+            }
+            dump.download.refs_recursive(&ref_files).await;
+            Ok(())
+        })
+    }
+}
+
+/// Fetches the packs and commit graphs in blind mode.
+struct Packs;
+
+impl<'d> Step<Dump<'d>> for Packs {
+    fn stage(&self) -> Stage {
+        Stage::Packs
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if !dump.is_blind() {
+                return Ok(());
+            }
+            fetch_packs(dump).await
+        })
+    }
+}
+
+/// Fetches the packs that objects/info/packs and the multi-pack-index list, and the commit
+/// graphs.
+async fn fetch_packs(dump: &mut Dump<'_>) -> Result<()> {
+    // read .git/objects/info/packs if exists
+    //   for every sha1 hash, download .git/objects/pack/pack-%s.{idx,pack}
+    info!("Finding packs");
+    dump.download.progress.enter(Phase::Packs);
+
+    let mut pack_names = Vec::new();
+    let pack_path: PathBuf = pathbuf![".git", "objects", "info", "packs"];
+    if pack_path.exists() {
+        pack_names.extend(
+            expression::PACK
+                .captures_iter(&String::from_utf8_lossy(&fs::read(pack_path).await?))
                 .filter_map(|capture| capture.get(1))
-                .map(|sha1| sha1.as_str())
-                .flat_map(|sha1| {
-                    vec![
-                        format!(".git/objects/pack/pack-{sha1}.idx"),
-                        format!(".git/objects/pack/pack-{sha1}.pack"),
-                    ]
-                })
+                .map(|sha1| format!("pack-{}", sha1.as_str())),
+        );
+    }
+
+    // The multi-pack-index lists every pack it covers, including ones missing from info/packs.
+    let midx_path = pathbuf![".git", "objects", "pack", "multi-pack-index"];
+    if midx_path.exists() {
+        match graph::parse_midx(&midx_path) {
+            Ok(midx) => {
+                pack_names.extend(midx.packs);
+                dump.seed_objects.extend(midx.objects);
+            }
+            Err(e) => warn!("Failed to parse multi-pack-index: {e}"),
+        }
+    }
+
+    pack_names.sort();
+    pack_names.dedup();
+    let jobs: Vec<_> = pack_names
+        .iter()
+        .flat_map(|name| {
+            vec![
+                format!(".git/objects/pack/{name}.idx"),
+                format!(".git/objects/pack/{name}.pack"),
+            ]
+        })
+        .collect();
+    dump.download.multiple(&jobs).await;
+
+    // Split commit-graphs are listed in a chain file, one graph file per line.
+    info!("Finding commit graphs");
+    dump.graph_paths
+        .push(pathbuf![".git", "objects", "info", "commit-graph"]);
+    let chain_path = pathbuf![
+        ".git",
+        "objects",
+        "info",
+        "commit-graphs",
+        "commit-graph-chain"
+    ];
+    if chain_path.exists() {
+        let chain = graph::parse_chain(&String::from_utf8_lossy(&fs::read(chain_path).await?));
+        let jobs: Vec<_> = chain
+            .iter()
+            .map(|name| format!(".git/objects/info/commit-graphs/{name}"))
+            .collect();
+        dump.download.multiple(&jobs).await;
+        dump.graph_paths.extend(
+            chain
+                .iter()
+                .map(|name| pathbuf![".git", "objects", "info", "commit-graphs", name]),
+        );
+    }
+    Ok(())
+}
+
+/// Mirrors the listed .git directory, or finds and downloads the loose objects in blind mode.
+struct Objects;
+
+impl<'d> Step<Dump<'d>> for Objects {
+    fn stage(&self) -> Stage {
+        Stage::Objects
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if dump.report.smart_protocol || dump.is_extracted() {
+                return Ok(());
+            }
+            let download = &dump.download;
+            if dump.report.listing {
+                info!(
+                    "Recursively downloading {}",
+                    download.normalize_url(".git")?
+                );
+                download.progress.enter(Phase::Objects);
+                download.recursive(&[".git", ".gitignore"]).await?;
+                return Ok(());
+            }
+
+            if dump.lists("objects") {
+                info!(
+                    "Recursively downloading {}",
+                    download.normalize_url(".git/objects")?
+                );
+                download.progress.enter(Phase::Objects);
+                download.recursive(&[".git/objects"]).await?;
+                return Ok(());
+            }
+
+            // Objects are downloaded while discovery is still walking the refs, logs, index and
+            // pack indexes, so neither side ever holds the full list of object IDs.
+            info!("Finding objects");
+            download.progress.enter(Phase::Objects);
+            let (sender, receiver) = mpsc::channel(discover::QUEUE_LEN);
+            let producer = tokio::spawn(discover::objects(
+                std::mem::take(&mut dump.seed_objects),
+                std::mem::take(&mut dump.graph_paths),
+                sender,
+            ));
+            download.stream(receiver).await;
+            let found = producer.await??;
+            info!("Found {found} objects");
+            Ok(())
+        })
+    }
+}
+
+/// Runs the ref, pack and object closure of blind mode over the files a listing yielded, fetching
+/// what they name that the listing left out, such as the entries of pages cut short and of
+/// subdirectories the server refused to list.
+struct FillGaps;
+
+impl<'d> Step<Dump<'d>> for FillGaps {
+    fn stage(&self) -> Stage {
+        Stage::FillGaps
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if !dump.report.listing || dump.report.smart_protocol || dump.is_extracted() {
+                return Ok(());
+            }
+            info!("Filling the gaps of the listing");
+            dump.download.progress.enter(Phase::Refs);
+            let named = tokio::task::spawn_blocking(discover::named_refs).await?;
+            let hrefs: Vec<String> = named
+                .iter()
+                .map(|name| refs::href(name))
+                .flat_map(|href| [format!(".git/{href}"), format!(".git/logs/{href}")])
+                .filter(|href| !dump.download.is_known(href))
                 .collect();
-            download.multiple(&jobs).await;
-        }
-
-        // For the contents of .git/packed-refs, .git/info/refs, .git/refs/*, .git/logs/*
-        //   check if they match "(^|\s)([a-f0-9]{40})($|\s)" and get the second match group
-        info!("Finding objects");
-        let mut files: Vec<PathBuf> = vec![
-            pathbuf![".git", "packed-refs"],
-            pathbuf![".git", "info", "refs"],
-            pathbuf![".git", "FETCH_HEAD"],
-            pathbuf![".git", "ORIG_HEAD"],
-        ];
-
-        let search_paths = [pathbuf![".git", "refs"], pathbuf![".git", "logs"]];
-        let refs_and_logs = search_paths.iter().flat_map(|path| {
-            WalkDir::new(path)
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .filter(|entry| entry.file_type().is_file())
-                .map(|entry| entry.path().into())
-        });
-
-        files.extend(refs_and_logs);
-
-        let mut objs = HashSet::new();
-        for filepath in files {
-            if filepath.exists() {
-                let text = fs::read_to_string(filepath).await?;
-                let matches = expression::OBJECT
-                    .captures_iter(&text)
-                    .filter_map(|m| m.get(2))
-                    .map(|m| m.as_str().to_string());
-                objs.extend(matches);
-            }
-        }
-
-        let index = git2::Index::open(&pathbuf![".git", "index"])?;
-        objs.extend(index.iter().map(|entry| entry.id.to_string()));
-
-        let pack_file_dir = pathbuf![".git", "objects", "pack"];
-        if pack_file_dir.is_dir() {
-            let packs = WalkDir::new(&pack_file_dir)
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .filter(|entry| {
-                    let name = entry.file_name().to_string_lossy();
-                    entry.file_type().is_file()
-                        && name.starts_with("pack-")
-                        && name.ends_with(".idx")
-                })
-                .flat_map(|entry| pack::parse(entry.path()).unwrap());
-            objs.extend(packs);
-        }
-
-        objs.take("0000000000000000000000000000000000000000");
-
-        let object_paths = objs
-            .into_iter()
-            .map(|obj| format!(".git/objects/{}/{}", &obj[0..2], &obj[2..]))
-            .collect::<Vec<_>>();
-
-        download.multiple(&object_paths).await;
-    }
-    info!("Performing a git checkout");
-    checkout(!is_webpage_listing)
-}
-
-/// Checks out the Git repository and returns a Result indicating success or failure of the operation.
-fn checkout(ignore_errors: bool) -> Result<()> {
-    let status = std::process::Command::new("git")
-        .arg("checkout")
-        .status()
-        .wrap_err("Failed to run git checkout")
-        .suggestion("Make sure your system has git installed")?;
-    if ignore_errors && !status.success() {
-        Err(eyre!(
-            "Checkout command did not exit cleanly, exit status: {status}"
+            dump.download.refs_recursive(&hrefs).await;
+
+            fetch_packs(dump).await?;
+
+            dump.download.progress.enter(Phase::Objects);
+            let tips = tokio::task::spawn_blocking(discover::tips).await??;
+            let fetched = discover::reachable(&dump.download, tips).await;
+            if fetched > 0 {
+                info!("Fetched {fetched} objects the listing left out");
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Follows what the HTML pages served during the dump leaked.
+struct Leaks;
+
+impl<'d> Step<Dump<'d>> for Leaks {
+    fn stage(&self) -> Stage {
+        Stage::Leaks
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let leaked = leaks::follow(&dump.download).await;
+            dump.report.leaks = dump.download.leaks.all();
+            if !dump.report.leaks.is_empty() {
+                info!(
+                    "HTML pages leaked {} paths and object IDs, yielding {leaked} objects",
+                    dump.report.leaks.len()
+                );
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Recreates the branches that were deleted after HEAD last moved to or from them, fetching
+/// everything their commits reach.
+struct DeletedBranches;
+
+impl<'d> Step<Dump<'d>> for DeletedBranches {
+    fn stage(&self) -> Stage {
+        Stage::DeletedBranches
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let Ok(repo) = Repository::open(".") else {
+                return Ok(());
+            };
+            let mut deleted = refs::deleted_branches(&repo);
+            if deleted.is_empty() {
+                return Ok(());
+            }
+            info!("Recovering {} deleted branches", deleted.len());
+            dump.download.progress.enter(Phase::Branches);
+            let commits = deleted.iter().map(|branch| branch.commit.clone()).collect();
+            discover::reachable(&dump.download, commits).await;
+            // Reopened, since the repository does not see the objects downloaded since.
+            let repo = Repository::open(".")?;
+            for branch in &mut deleted {
+                if !repo.contains(&branch.commit) {
+                    warn!(
+                        "Deleted branch {} at {} is missing from the dump",
+                        branch.name, branch.commit
+                    );
+                    continue;
+                }
+                let path = Path::new(".git")
+                    .join("refs")
+                    .join("heads")
+                    .join(&branch.name);
+                let written = match path.parent() {
+                    Some(parent) => std::fs::create_dir_all(parent),
+                    None => Ok(()),
+                }
+                .and_then(|()| std::fs::write(&path, format!("{}\n", branch.commit)));
+                if let Err(e) = written {
+                    warn!("Failed to recreate deleted branch {}: {e}", branch.name);
+                    continue;
+                }
+                branch.recreated = true;
+                info!(
+                    "Recreated deleted branch {} at {}",
+                    branch.name, branch.commit
+                );
+            }
+            dump.report.deleted_branches = deleted;
+            Ok(())
+        })
+    }
+}
+
+/// Mirrors the sensitive items next to the .git directory with --grab-siblings.
+struct Siblings;
+
+impl<'d> Step<Dump<'d>> for Siblings {
+    fn stage(&self) -> Stage {
+        Stage::Siblings
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if !dump.options.grab_siblings {
+                return Ok(());
+            }
+            if !dump.report.listing {
+                warn!("The web root cannot be listed in blind mode, not grabbing siblings");
+                return Ok(());
+            }
+            info!("Grabbing sensitive items next to the .git directory");
+            match siblings::grab(&dump.download).await {
+                Ok(siblings) => info!(
+                    "Mirrored {} siblings into {}",
+                    siblings.len(),
+                    siblings::SIBLINGS_DIR
+                ),
+                Err(e) => warn!("Failed to grab the siblings: {e}"),
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Checks the integrity of the dump with --fsck.
+struct Integrity;
+
+impl<'d> Step<Dump<'d>> for Integrity {
+    fn stage(&self) -> Stage {
+        Stage::Fsck
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if dump.options.fsck {
+                info!("Checking the integrity of the dump");
+                dump.download.progress.enter(Phase::Fsck);
+                dump.report.fsck = check_integrity(&dump.download, dump.options.refetch).await;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Records the refs, commits, credentials and remotes of the dump in the report, which is written
+/// before checking out so the findings survive a failed checkout.
+struct Findings;
+
+impl<'d> Step<Dump<'d>> for Findings {
+    fn stage(&self) -> Stage {
+        Stage::Findings
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let Dump {
+                url,
+                options,
+                download,
+                report,
+                lazy_fetch,
+                ..
+            } = dump;
+            *lazy_fetch = check_promisors(download, options.fetch_promised).await;
+
+            if let Some(baseline) = &download.baseline {
+                baseline.link()?;
+                match baseline.diff() {
+                    Ok(diff) => {
+                        info!(
+                            "Found {} new commits and {} changed files since the baseline",
+                            diff.new_commits.len(),
+                            diff.changed_files.len()
+                        );
+                        report.baseline = Some(diff);
+                    }
+                    Err(e) => warn!("Failed to compare the dump against the baseline: {e}"),
+                }
+            }
+            download.progress.enter(Phase::Branches);
+            // Saved again after the pipeline, but needed now to read the renamed refs back.
+            download.save_renamed()?;
+            report.path_collisions = download.paths.lock().unwrap().collisions.clone();
+            for collision in &report.path_collisions {
+                warn!(
+                    "{} differs from {} only by case, wrote it as {}",
+                    collision.colliding, collision.path, collision.written_as
+                );
+            }
+            // Listings omit empty directories, yet git only recognizes a .git directory with refs
+            // in it, which stays empty when every ref is packed.
+            if Path::new(".git").is_dir() {
+                std::fs::create_dir_all(pathbuf![".git", "refs"])?;
+            }
+            match Repository::open(".") {
+                Ok(repo) => {
+                    enumerate_refs(&repo, report);
+                    summarize(&repo, report, options.summary);
+                    write_history(&repo);
+                }
+                Err(e) => warn!("Failed to open the dumped repository: {e}"),
+            }
+            info!("Harvesting credentials");
+            report.credentials = credentials::harvest();
+            for credential in &report.credentials {
+                info!(
+                    "Found {:?} in {}: {}",
+                    credential.kind, credential.source, credential.value
+                );
+            }
+            if options.follow_remotes {
+                info!("Probing the remotes of the repository");
+                let target_host = url.host_str().and_then(|host| Rule::parse(host).ok());
+                let scope = if download.hosts.is_unrestricted() {
+                    &HostScope::new(target_host.into_iter().collect())
+                } else {
+                    &download.hosts
+                };
+                report.remotes = remotes::discover(scope, &options.network).await;
+                let exposed = report.remotes.iter().filter(|r| r.exposed).count();
+                info!(
+                    "Found {} remotes, {exposed} of them exposed",
+                    report.remotes.len()
+                );
+            }
+            report.write()?;
+            info!("Wrote findings to {}", report.file_name());
+            Ok(())
+        })
+    }
+}
+
+/// Checks out the files present in the dump, unless the dump was interrupted.
+struct Checkout;
+
+impl<'d> Step<Dump<'d>> for Checkout {
+    fn stage(&self) -> Stage {
+        Stage::Checkout
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let download = &dump.download;
+            if download.is_interrupted() {
+                warn!(
+                    "Downloaded {} files before the interruption, run the same command again to resume",
+                    download.completed()
+                );
+                if download.is_blocked() {
+                    return Err(Error::RateLimited {
+                        url: dump.url.to_string(),
+                    }
+                    .into());
+                }
+                return Err(Error::Interrupted {
+                    before: "checkout".to_string(),
+                }
+                .into());
+            }
+
+            info!("Checking out the files present in the dump");
+            download.progress.enter(Phase::Checkout);
+            dump.checked_out = checkout(
+                dump.lazy_fetch,
+                dump.options.checkout_ref.as_deref(),
+                dump.report,
+            );
+            Ok(())
+        })
+    }
+}
+
+/// Replaces the LFS pointers checked out with their content and expands abbreviated object IDs.
+struct Extract;
+
+impl<'d> Step<Dump<'d>> for Extract {
+    fn stage(&self) -> Stage {
+        Stage::Extract
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            info!("Finding LFS pointers");
+            dump.report.lfs_pointers = lfs::find();
+            if !dump.report.lfs_pointers.is_empty() {
+                warn!(
+                    "Found {} LFS pointer files in place of their content",
+                    dump.report.lfs_pointers.len()
+                );
+                if dump.options.fetch_lfs {
+                    let endpoint = lfs::endpoint(pathbuf![".git", "config"]);
+                    lfs::fetch(&dump.download, &mut dump.report.lfs_pointers, endpoint).await;
+                }
+            }
+            if dump.options.expand_abbreviations {
+                info!("Expanding abbreviated object IDs");
+                dump.report.abbreviations = abbrev::expand(&dump.download).await;
+            }
+            dump.record_requests();
+            dump.report.write()?;
+            Ok(())
+        })
+    }
+}
+
+/// Runs the --post-cmd commands after a successful checkout.
+struct Hooks;
+
+impl<'d> Step<Dump<'d>> for Hooks {
+    fn stage(&self) -> Stage {
+        Stage::Hooks
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if dump.checked_out.is_err() || dump.options.post_commands.is_empty() {
+                return Ok(());
+            }
+            let output_dir = std::env::current_dir()?;
+            let report_path = output_dir.join(dump.report.file_name());
+            let context = hooks::Context {
+                output_dir: &output_dir.to_string_lossy(),
+                url: dump.url.as_str(),
+                report: &report_path.to_string_lossy(),
+            };
+            dump.report.hooks = hooks::run(&dump.options.post_commands, &context);
+            dump.report.write()?;
+            Ok(())
+        })
+    }
+}
+
+/// Seals the output directory with --seal. It comes last so the manifest covers everything the
+/// run wrote.
+struct Seal;
+
+impl<'d> Step<Dump<'d>> for Seal {
+    fn stage(&self) -> Stage {
+        Stage::Seal
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if !dump.options.seal {
+                return Ok(());
+            }
+            let seal = seal::seal()?;
+            info!(
+                "Sealed {} files into {}, digest {}",
+                seal.files,
+                seal::SEAL_MANIFEST,
+                seal.digest
+            );
+            if let Some(tsa) = &dump.options.tsa_url {
+                match seal::timestamp(tsa).await {
+                    Ok(()) => info!(
+                        "Wrote the timestamp from {tsa} to {}",
+                        seal::TIMESTAMP_REPLY
+                    ),
+                    Err(e) => warn!("Failed to timestamp the seal: {e}"),
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Writes the bundle and the archive, and pushes the recovered refs to the mirror.
+struct Export;
+
+impl<'d> Step<Dump<'d>> for Export {
+    fn stage(&self) -> Stage {
+        Stage::Export
+    }
+
+    fn run<'a>(&'a self, dump: &'a mut Dump<'d>) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let options = dump.options;
+            if let Some(path) = &options.bundle {
+                match Repository::open(".").and_then(|repo| export::bundle(&repo, path)) {
+                    Ok(objects) => info!("Bundled {objects} objects into {}", path.display()),
+                    Err(e) => warn!("Failed to write the bundle: {e}"),
+                }
+            }
+            if let Some(path) = &options.archive {
+                match export::archive(path, options.archive_worktree) {
+                    Ok(files) => info!("Archived {files} files into {}", path.display()),
+                    Err(e) => warn!("Failed to write the archive: {e}"),
+                }
+            }
+
+            if let (Some(remote), Ok(())) = (&options.mirror_to, &dump.checked_out) {
+                info!("Pushing the recovered refs to {remote}");
+                match mirror::push(remote) {
+                    Ok(refs) => info!("Pushed {refs} refs to {remote}"),
+                    Err(e) => warn!("Failed to push to {remote}: {e}"),
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Estimates the size of the dump and asks before downloading more objects than the threshold.
+async fn confirm_size(
+    download: &Downloader,
+    ref_files: &[String],
+    options: &DumpOptions,
+    report: &mut Report,
+) -> Result<()> {
+    let estimate = estimate::run(download, ref_files, report.listing).await;
+    let objects = estimate.packed_objects;
+    let size = tui::human_bytes(estimate.packed_bytes as f64);
+    report.estimate = Some(estimate);
+    if objects <= options.confirm_above {
+        return Ok(());
+    }
+    // The dashboard owns the terminal, leaving nowhere to ask.
+    let answer = if options.tui {
+        None
+    } else {
+        prompt::confirm(&format!(
+            "The packs hold {objects} objects, about {size}. Download them?"
         ))
-        .note("Some files from the repository's tree may be missing")?
+    };
+    match answer {
+        Some(true) => Ok(()),
+        Some(false) => Err(Error::Declined {
+            reason: format!("Declined to download {objects} objects"),
+        }
+        .into()),
+        None => Err(color_eyre::Report::from(Error::Declined {
+            reason: format!(
+                "The packs hold {objects} objects, more than --confirm-above {}",
+                options.confirm_above
+            ),
+        }))
+        .suggestion("Pass --yes to download them anyway"),
+    }
+}
+
+/// Fetches every object the advertised refs reach as a single pack if the server speaks the
+/// smart HTTP protocol, returning whether it did.
+async fn negotiate_pack(download: &Downloader) -> bool {
+    let advertisement = match smart::probe(download).await {
+        Ok(Some(advertisement)) => advertisement,
+        Ok(None) => return false,
+        Err(e) => {
+            warn!("Failed to probe the smart HTTP protocol: {e}");
+            return false;
+        }
+    };
+    info!(
+        "The server speaks the smart HTTP protocol and advertised {} refs, negotiating a pack",
+        advertisement.refs.len()
+    );
+    download.progress.enter(Phase::Packs);
+    let stored = async {
+        let pack = smart::fetch_pack(download, &advertisement).await?;
+        smart::store(&advertisement, &pack)
+    };
+    match stored.await {
+        Ok(path) => {
+            info!("Stored the negotiated pack in {}", path.display());
+            true
+        }
+        Err(e) => {
+            warn!("Failed to negotiate a pack, crawling instead: {e}");
+            false
+        }
+    }
+}
+
+/// Logs the missing and corrupt objects found by a check.
+fn log_fsck(fsck: &Fsck) {
+    for problem in &fsck.problems {
+        let mut message = format!("Object {} is {}", problem.id, problem.defect.name());
+        if let Some(reason) = &problem.reason {
+            message.push_str(&format!(": {reason}"));
+        }
+        if !problem.refs.is_empty() {
+            message.push_str(&format!(", needed by {}", problem.refs.join(", ")));
+        }
+        warn!("{message}");
+    }
+    info!(
+        "Checked {} objects, {} missing and {} corrupt",
+        fsck.checked,
+        fsck.count(Defect::Missing),
+        fsck.count(Defect::Corrupt)
+    );
+}
+
+/// Checks that every object the refs need was recovered intact, requesting the broken ones once
+/// more when refetching.
+async fn check_integrity(download: &Downloader, refetch: bool) -> Option<Fsck> {
+    let mut result = fsck::run()
+        .map_err(|e| warn!("Failed to check the integrity of the dump: {e}"))
+        .ok()?;
+    log_fsck(&result);
+    if !refetch || result.problems.is_empty() || download.is_interrupted() {
+        return Some(result);
+    }
+
+    let hrefs: Vec<String> = result
+        .problems
+        .iter()
+        .map(|problem| fsck::loose_href(&problem.id))
+        .collect();
+    {
+        let mut state = download.state.lock().unwrap();
+        for (problem, href) in result.problems.iter().zip(&hrefs) {
+            state.completed.remove(href);
+            state.missing.remove(href);
+            if problem.defect == Defect::Corrupt {
+                let _ = std::fs::remove_file(href);
+            }
+        }
+    }
+    info!("Requesting {} broken objects again", hrefs.len());
+    download.multiple(&hrefs).await;
+    result = fsck::run()
+        .map_err(|e| warn!("Failed to check the integrity of the dump: {e}"))
+        .ok()?;
+    log_fsck(&result);
+    Some(result)
+}
+
+/// Warns about partial clone promisor remotes in the dumped configuration and returns
+/// whether git may lazily fetch the objects they omitted during checkout.
+async fn check_promisors(download: &Downloader, fetch_promised: bool) -> bool {
+    let config_path = pathbuf![".git", "config"];
+    if !config_path.exists() {
+        return false;
+    }
+    let promisors = match promisor::detect(&config_path) {
+        Ok(promisors) => promisors,
+        Err(e) => {
+            warn!("Failed to read the repository configuration: {e}");
+            return false;
+        }
+    };
+
+    let mut lazy_fetch = false;
+    for promisor in promisors {
+        let url = promisor.url.as_deref().unwrap_or("an unknown URL");
+        let filter = promisor.filter.as_deref().unwrap_or("an unknown filter");
+        warn!(
+            "The repository is a partial clone of remote \"{}\" ({url}) with {filter}, objects omitted by the filter were never stored in the dumped .git",
+            promisor.remote
+        );
+        if !fetch_promised {
+            warn!("Checkout may fail with missing objects, pass --fetch-promised to fetch them from the promisor remote");
+            continue;
+        }
+        if !promisor.is_http() {
+            warn!("Promisor remote {url} is not reachable over HTTP(S), skipping");
+            continue;
+        }
+        let Ok(parsed) = url.parse::<Url>() else {
+            warn!("Promisor remote {url} is not a valid URL, skipping");
+            continue;
+        };
+        // git fetches from the promisor without any check, so it must lie within --scope.
+        if !download.hosts.allows(&parsed).await {
+            warn!("Promisor remote {url} is out of scope, skipping");
+            continue;
+        }
+        match download.fetch_raw_url(&parsed).await {
+            Ok(_) => {
+                info!("Allowing git to fetch missing objects from {url}");
+                lazy_fetch = true;
+            }
+            Err(e) => warn!("Promisor remote {url} is unreachable: {e}"),
+        }
     }
+    lazy_fetch
+}
+
+/// Writes every file whose blob was recovered, records the rest in MISSING_FILES.txt and the report,
+/// and lets git fetch the missing ones from a promisor remote when lazy fetching is allowed.
+fn checkout(lazy_fetch: bool, checkout_ref: Option<&str>, report: &mut Report) -> Result<()> {
+    let repo = Repository::open(".").wrap_err("Failed to open the dumped repository")?;
+    let mut outcome = checkout::checkout(&repo, checkout_ref)?;
+
+    if lazy_fetch && !outcome.missing.is_empty() {
+        // Only git knows how to fetch from promisor remotes.
+        info!(
+            "Letting git fetch {} missing files from the promisor remote",
+            outcome.missing.len()
+        );
+        let status = std::process::Command::new("git")
+            .arg("checkout")
+            .arg(checkout_ref.unwrap_or("HEAD"))
+            .arg("--")
+            .args(&outcome.missing)
+            .status()
+            .wrap_err("Failed to run git checkout")
+            .suggestion("Make sure your system has git installed")?;
+        if !status.success() {
+            warn!("git checkout did not exit cleanly, exit status: {status}");
+        }
+        outcome.missing.retain(|path| !Path::new(path).exists());
+    }
+
+    info!("Checked out {} files", outcome.written);
+    if !outcome.missing.is_empty() {
+        warn!(
+            "{} files are missing from the dump, see {}",
+            outcome.missing.len(),
+            checkout::MISSING_FILES
+        );
+    }
+    checkout::record_missing(&outcome.missing)?;
+    report.missing_files = outcome.missing;
     Ok(())
 }
+
+#[cfg(all(test, feature = "git2"))]
+mod tests {
+    use super::*;
+    use crate::{
+        args::{Cli, Command},
+        manifest, testing,
+    };
+    use clap::Parser;
+    use sha2::Digest;
+
+    /// Dumps a fixture repository served with or without directory listing and returns the
+    /// output directory along with the JSON report.
+    async fn dump_fixture(
+        listing: bool,
+        extra_args: &[&str],
+    ) -> (tempfile::TempDir, serde_json::Value) {
+        dump_changed_fixture(listing, extra_args, |_| {}).await
+    }
+
+    /// Like [`dump_fixture`], letting change rearrange the served site first.
+    async fn dump_changed_fixture(
+        listing: bool,
+        extra_args: &[&str],
+        change: impl FnOnce(&Path),
+    ) -> (tempfile::TempDir, serde_json::Value) {
+        let listed: fn(&Path) -> bool = if listing { |_| true } else { |_| false };
+        dump_listed_fixture(listed, extra_args, change).await
+    }
+
+    /// Like [`dump_changed_fixture`], listing only the directories listed accepts.
+    async fn dump_listed_fixture(
+        listed: fn(&Path) -> bool,
+        extra_args: &[&str],
+        change: impl FnOnce(&Path),
+    ) -> (tempfile::TempDir, serde_json::Value) {
+        let _guard = testing::lock_current_dir().await;
+        let site = tempfile::tempdir().unwrap();
+        testing::fixture(site.path());
+        change(site.path());
+        let url = testing::serve_listing(site.path(), listed).await;
+        let output = tempfile::tempdir().unwrap();
+
+        let output_arg = output.path().display().to_string();
+        let mut cli_args = vec!["opendotgit", "dump", url.as_str(), &output_arg];
+        cli_args.extend(extra_args);
+        let cli = Cli::try_parse_from(cli_args).unwrap();
+        let Some(Command::Dump(args)) = cli.command else {
+            panic!("expected the dump command");
+        };
+        let format = args.options.format;
+        let current_dir = std::env::current_dir().unwrap();
+        let result = dump(*args, Arc::default()).await;
+        std::env::set_current_dir(current_dir).unwrap();
+        result.unwrap();
+
+        let report = std::fs::read(output.path().join(report::file_name(format))).unwrap();
+        (output, serde_json::from_slice(&report).unwrap())
+    }
+
+    fn assert_checked_out(output: &Path) {
+        for (path, content) in testing::FILES {
+            assert_eq!(
+                std::fs::read_to_string(output.join(path)).unwrap(),
+                *content,
+                "{path} was not checked out"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn dumps_listed_repository() {
+        let (output, report) = dump_fixture(true, &[]).await;
+        assert_eq!(report["exposed"], true);
+        assert_eq!(report["listing"], true);
+        assert_checked_out(output.path());
+        assert!(!output.path().join(checkout::MISSING_FILES).exists());
+
+        let repo = Repository::open(output.path()).unwrap();
+        assert_eq!(repo.commits().len(), 2);
+
+        let manifest = std::fs::read_to_string(output.path().join(manifest::MANIFEST)).unwrap();
+        let head = manifest
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .find(|entry| entry["url"].as_str().unwrap().ends_with("/.git/HEAD"))
+            .unwrap();
+        assert_eq!(head["status"], 200);
+        assert_eq!(head["size"], "ref: refs/heads/master\n".len());
+    }
+
+    #[tokio::test]
+    async fn dumps_unlisted_repository() {
+        let (output, report) = dump_fixture(false, &[]).await;
+        assert_eq!(report["exposed"], true);
+        assert_eq!(report["listing"], false);
+        assert_eq!(report["requests"][0]["phase"], "HEAD check");
+        assert_eq!(report["requests"][0]["statuses"]["200"], 1);
+        assert_eq!(report["refs"][0]["name"], "refs/heads/master");
+        // Blind mode recovers the files staged in the index even though trees are not fetched.
+        assert_checked_out(output.path());
+    }
+
+    #[tokio::test]
+    async fn mirrors_listed_subtrees() {
+        let listed =
+            |path: &Path| path.starts_with(".git/objects") || path.starts_with(".git/refs");
+        let (output, report) = dump_listed_fixture(listed, &[], |site| {
+            // A loose ref only the listing of refs/ gives away.
+            let repo = git2::Repository::open(site).unwrap();
+            let head = repo.head().unwrap().target().unwrap();
+            repo.reference("refs/heads/unlinked", head, false, "")
+                .unwrap();
+        })
+        .await;
+        assert_eq!(report["listing"], false);
+        assert_eq!(
+            report["listed_subtrees"],
+            serde_json::json!(["objects", "refs"])
+        );
+        assert_checked_out(output.path());
+        let repo = Repository::open(output.path()).unwrap();
+        assert_eq!(repo.commits().len(), 2);
+        assert!(repo.resolve("refs/heads/unlinked").is_ok());
+    }
+
+    #[tokio::test]
+    async fn fills_gaps_of_listing() {
+        // The listing of .git leaves out its refs and objects, which the closure finds anyway.
+        let listed =
+            |path: &Path| !path.starts_with(".git/refs") && !path.starts_with(".git/objects");
+        let (output, report) = dump_listed_fixture(listed, &[], |_| {}).await;
+        assert_eq!(report["listing"], true);
+        assert_checked_out(output.path());
+        let repo = Repository::open(output.path()).unwrap();
+        assert_eq!(repo.commits().len(), 2);
+        assert!(repo.resolve("refs/heads/master").is_ok());
+    }
+
+    #[tokio::test]
+    async fn follows_git_file() {
+        for listing in [false, true] {
+            let (output, report) = dump_changed_fixture(listing, &[], |site| {
+                std::fs::create_dir(site.join("repos")).unwrap();
+                std::fs::rename(site.join(".git"), site.join("repos/app.git")).unwrap();
+                std::fs::write(site.join(".git"), "gitdir: repos/app.git\n").unwrap();
+            })
+            .await;
+            assert!(report["git_dir"]
+                .as_str()
+                .unwrap()
+                .ends_with("/repos/app.git"));
+            assert_eq!(report["refs"][0]["name"], "refs/heads/master");
+            assert_checked_out(output.path());
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_files_without_blobs() {
+        for staged in [true, false] {
+            let (output, report) = dump_changed_fixture(false, &["--list-files"], |site| {
+                if !staged {
+                    std::fs::remove_file(site.join(".git/index")).unwrap();
+                }
+            })
+            .await;
+            let files = report["listed_files"].as_array().unwrap();
+            let paths: Vec<_> = files.iter().map(|file| &file["path"]).collect();
+            assert_eq!(paths, ["README.md", "src/main.rs"]);
+            assert_eq!(files[0]["mode"], "100644");
+            assert_eq!(
+                files[0]["size"].as_u64(),
+                staged.then_some(testing::FILES[0].1.len() as u64)
+            );
+            assert!(!output.path().join("README.md").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_phases() {
+        let args = ["--skip-phase", "objects", "--skip-phase", "checkout"];
+        let (output, report) = dump_fixture(false, &args).await;
+        assert!(!report["refs"].as_array().unwrap().is_empty());
+        assert!(report["missing_files"].as_array().unwrap().is_empty());
+        assert!(output.path().join(".git/HEAD").exists());
+        assert!(!output.path().join("README.md").exists());
+    }
+
+    #[tokio::test]
+    async fn recreates_deleted_branches() {
+        use std::io::Write;
+        let (output, report) = dump_changed_fixture(false, &[], |site| {
+            let repo = git2::Repository::open(site).unwrap();
+            let head = repo.head().unwrap().peel_to_commit().unwrap();
+            let signature = git2::Signature::now("Fixture", "fixture@example.com").unwrap();
+            let feature = repo
+                .commit(None, &signature, &signature, "Add feature", &head.tree().unwrap(), &[&head])
+                .unwrap();
+            let mut log = std::fs::OpenOptions::new()
+                .append(true)
+                .open(site.join(".git/logs/HEAD"))
+                .unwrap();
+            let head = head.id();
+            writeln!(log, "{head} {feature} Dev <dev@example.com> 1700000000 +0000\tcheckout: moving from master to feature").unwrap();
+            writeln!(log, "{feature} {head} Dev <dev@example.com> 1700000001 +0000\tcheckout: moving from feature to master").unwrap();
+        })
+        .await;
+        let deleted = &report["deleted_branches"][0];
+        assert_eq!(deleted["name"], "feature");
+        assert_eq!(deleted["recreated"], true);
+        let repo = Repository::open(output.path()).unwrap();
+        let commit = repo.resolve("refs/heads/feature").unwrap();
+        assert_eq!(deleted["commit"], commit.as_str());
+        assert!(repo.commit(&commit).is_ok());
+    }
+
+    #[tokio::test]
+    async fn keeps_ref_names_that_are_not_ascii() {
+        let (output, _) = dump_changed_fixture(false, &[], |site| {
+            let repo = git2::Repository::open(site).unwrap();
+            let head = repo.head().unwrap().target().unwrap();
+            repo.reference("refs/heads/caf\u{e9}+cr\u{e8}me", head, false, "")
+                .unwrap();
+            let refs = format!("{head}\trefs/heads/caf\u{e9}+cr\u{e8}me\n");
+            std::fs::write(site.join(".git/info/refs"), refs).unwrap();
+        })
+        .await;
+        let repo = Repository::open(output.path()).unwrap();
+        assert!(repo.resolve("refs/heads/caf\u{e9}+cr\u{e8}me").is_ok());
+    }
+
+    #[tokio::test]
+    async fn checks_integrity() {
+        let (_, report) = dump_fixture(true, &["--fsck"]).await;
+        assert!(report["fsck"]["checked"].as_u64().unwrap() > 0);
+        assert_eq!(report["fsck"]["problems"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn mirrors_refs() {
+        let remote = tempfile::tempdir().unwrap();
+        git2::Repository::init_bare(remote.path()).unwrap();
+        let remote_arg = remote.path().display().to_string();
+        dump_fixture(true, &["--mirror-to", &remote_arg]).await;
+        let remote = Repository::open(remote.path()).unwrap();
+        assert_eq!(remote.commits().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn grabs_siblings() {
+        let (output, _) = dump_fixture(true, &["--grab-siblings"]).await;
+        let siblings = output.path().join(siblings::SIBLINGS_DIR);
+        for (path, content) in testing::SIBLINGS {
+            assert_eq!(
+                std::fs::read_to_string(siblings.join(path)).unwrap(),
+                *content
+            );
+        }
+        assert!(!siblings.join("index.html").exists());
+        assert!(siblings.join(siblings::MANIFEST).exists());
+    }
+
+    #[tokio::test]
+    async fn seals_dump() {
+        let (output, _) = dump_fixture(true, &["--seal"]).await;
+        let manifest = std::fs::read_to_string(output.path().join(seal::SEAL_MANIFEST)).unwrap();
+        let mut paths = Vec::new();
+        for line in manifest.lines() {
+            let (hash, path) = line.split_once("  ").unwrap();
+            let content = std::fs::read(output.path().join(path)).unwrap();
+            assert_eq!(hash, hex::encode(sha2::Sha256::digest(content)), "{path}");
+            paths.push(path);
+        }
+        assert!(paths.contains(&"README.md"));
+        assert!(paths.contains(&".git/HEAD"));
+        assert!(output.path().join(seal::TIMESTAMP_QUERY).exists());
+    }
+}
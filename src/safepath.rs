@@ -0,0 +1,298 @@
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// The file in the output directory mapping the paths written under another name to the path
+/// on the server.
+pub const PATH_MAP: &str = "opendotgit-paths.json";
+
+/// Longest file name most filesystems accept, in bytes.
+const MAX_NAME: usize = 255;
+
+/// Longest path Windows accepts without the extended-length prefix, including the terminator.
+const MAX_PATH: usize = 260;
+
+/// Whether the filesystems of the platform usually treat names differing only by case as the same.
+const CASE_INSENSITIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+/// Characters NTFS does not allow in file names.
+const ILLEGAL: [char; 8] = ['<', '>', ':', '"', '|', '?', '*', '\\'];
+
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn escape(encoded: &mut String, c: char) {
+    let mut buffer = [0; 4];
+    for byte in c.encode_utf8(&mut buffer).bytes() {
+        encoded.push_str(&format!("%{byte:02X}"));
+    }
+}
+
+/// Encodes a segment of a path so that Windows accepts it as a file name. Percent signs are
+/// encoded as well, so that no two segments are encoded alike.
+fn encode_segment(segment: &str) -> String {
+    let stem = segment.split('.').next().unwrap_or_default();
+    let reserved = RESERVED.iter().any(|name| name.eq_ignore_ascii_case(stem));
+    let last = segment.char_indices().last().map(|(i, _)| i);
+    let mut encoded = String::with_capacity(segment.len());
+    for (i, c) in segment.char_indices() {
+        let trailing = Some(i) == last && (c == '.' || c == ' ');
+        if c == '%'
+            || ILLEGAL.contains(&c)
+            || c.is_ascii_control()
+            || trailing
+            || (reserved && i == 0)
+        {
+            escape(&mut encoded, c);
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+/// Returns the path, slash separated and relative to the output directory, encoded so that every
+/// segment is a valid Windows file name, or None if it already is one.
+pub fn encode(path: &str) -> Option<String> {
+    let encoded = path
+        .split('/')
+        .map(encode_segment)
+        .collect::<Vec<_>>()
+        .join("/");
+    (encoded != path).then_some(encoded)
+}
+
+/// Returns the path with every segment too long for a file name cut short and suffixed with a
+/// hash of the whole segment, or None if no segment is too long.
+pub fn shorten(path: &str) -> Option<String> {
+    if path.split('/').all(|segment| segment.len() <= MAX_NAME) {
+        return None;
+    }
+    let shortened = path
+        .split('/')
+        .map(|segment| {
+            if segment.len() <= MAX_NAME {
+                return segment.to_string();
+            }
+            let digest = hex::encode(Sha1::digest(segment.as_bytes()));
+            let mut end = MAX_NAME - 41;
+            while !segment.is_char_boundary(end) {
+                end -= 1;
+            }
+            format!("{}~{digest}", &segment[..end])
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    Some(shortened)
+}
+
+/// Returns the path with its uppercase letters and percent signs percent-encoded, so that it no
+/// longer collides with a path differing only by case on a case-insensitive filesystem.
+fn fold_case(path: &str) -> String {
+    let mut folded = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_uppercase() || c == '%' {
+            escape(&mut folded, c);
+        } else {
+            folded.push(c);
+        }
+    }
+    folded
+}
+
+/// Returns the path a file from the server is written to on this platform.
+pub fn local(path: &str) -> Cow<'_, str> {
+    let mut local = Cow::Borrowed(path);
+    if let Some(encoded) = encode(&local).filter(|_| cfg!(windows)) {
+        local = Cow::Owned(encoded);
+    }
+    if let Some(shortened) = shorten(&local) {
+        local = Cow::Owned(shortened);
+    }
+    local
+}
+
+/// Returns the path with the extended-length prefix when it is too long for Windows to open
+/// otherwise.
+pub fn extended(path: &Path) -> Cow<'_, Path> {
+    if !cfg!(windows) {
+        return Cow::Borrowed(path);
+    }
+    match std::path::absolute(path) {
+        Ok(absolute) if absolute.as_os_str().len() >= MAX_PATH => {
+            let mut prefixed = std::ffi::OsString::from(r"\\?\");
+            prefixed.push(absolute.as_os_str());
+            Cow::Owned(PathBuf::from(prefixed))
+        }
+        _ => Cow::Borrowed(path),
+    }
+}
+
+/// Two paths from the server that the filesystem would store as the same file.
+#[derive(Serialize, Clone)]
+pub struct Collision {
+    /// The path written first, under its own name.
+    pub path: String,
+    /// The path differing only by case.
+    pub colliding: String,
+    /// Where the colliding path was written instead.
+    pub written_as: String,
+}
+
+/// Decides where the files from the server are written, keeping track of those written under
+/// another name.
+pub struct Paths {
+    case_insensitive: bool,
+    /// Paths written under an encoded name, mapped to the original.
+    pub renamed: BTreeMap<String, String>,
+    /// Paths written so far by their lowercase form, mapped to the original.
+    written: HashMap<String, String>,
+    /// Colliding paths mapped to the name they are written as, decided on their first write.
+    folded: HashMap<String, String>,
+    pub collisions: Vec<Collision>,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Paths::new(CASE_INSENSITIVE)
+    }
+}
+
+impl Paths {
+    pub fn new(case_insensitive: bool) -> Self {
+        Paths {
+            case_insensitive,
+            renamed: BTreeMap::new(),
+            written: HashMap::new(),
+            folded: HashMap::new(),
+            collisions: Vec::new(),
+        }
+    }
+
+    /// Returns the path to write href to, encoding it when the platform rejects its name or when
+    /// it differs only by case from a path written before. A folded name that is itself taken
+    /// gets a numbered suffix.
+    pub fn local(&mut self, href: &str) -> String {
+        if let Some(folded) = self.folded.get(href) {
+            return folded.clone();
+        }
+        let mut local = local(href).into_owned();
+        if self.case_insensitive {
+            let key = local.to_lowercase();
+            match self.written.get(&key).cloned() {
+                Some(existing) if existing != href => {
+                    let mut folded = fold_case(&local);
+                    let mut suffix = 1;
+                    while self.written.contains_key(&folded.to_lowercase()) {
+                        suffix += 1;
+                        folded = format!("{}~{suffix}", fold_case(&local));
+                    }
+                    self.written.insert(folded.to_lowercase(), href.to_string());
+                    self.folded.insert(href.to_string(), folded.clone());
+                    self.collisions.push(Collision {
+                        path: existing,
+                        colliding: href.to_string(),
+                        written_as: folded.clone(),
+                    });
+                    local = folded;
+                }
+                Some(_) => {}
+                None => {
+                    self.written.insert(key, href.to_string());
+                }
+            }
+        }
+        if local != href {
+            self.renamed.insert(local.clone(), href.to_string());
+        }
+        local
+    }
+}
+
+/// Reads the mapping from encoded paths to paths on the server kept in dir, empty if there is none.
+pub fn load(dir: &Path) -> BTreeMap<String, String> {
+    std::fs::read(dir.join(PATH_MAP))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Adds the mapping to the one kept in the current directory.
+pub fn save(renamed: &BTreeMap<String, String>) -> std::io::Result<()> {
+    let mut map = load(Path::new("."));
+    map.extend(renamed.iter().map(|(k, v)| (k.clone(), v.clone())));
+    std::fs::write(PATH_MAP, serde_json::to_vec_pretty(&map)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_windows_names() {
+        assert_eq!(encode(".git/refs/heads/main"), None);
+        assert_eq!(
+            encode(".git/refs/heads/fix:it?").as_deref(),
+            Some(".git/refs/heads/fix%3Ait%3F")
+        );
+        assert_eq!(
+            encode(".git/refs/tags/v1.").as_deref(),
+            Some(".git/refs/tags/v1%2E")
+        );
+        assert_eq!(
+            encode(".git/refs/heads/con.txt/aux").as_deref(),
+            Some(".git/refs/heads/%63on.txt/%61ux")
+        );
+        assert_eq!(encode(".git/refs/heads/console"), None);
+        assert_eq!(
+            encode(".git/refs/heads/fix%3Ait").as_deref(),
+            Some(".git/refs/heads/fix%253Ait")
+        );
+    }
+
+    #[test]
+    fn separates_colliding_paths() {
+        let mut paths = Paths::new(true);
+        assert_eq!(paths.local(".git/refs/heads/main"), ".git/refs/heads/main");
+        assert_eq!(paths.local(".git/refs/heads/main"), ".git/refs/heads/main");
+        assert_eq!(
+            paths.local(".git/refs/heads/Main"),
+            ".git/refs/heads/%4Dain"
+        );
+        assert_eq!(
+            paths.local(".git/refs/heads/Main"),
+            ".git/refs/heads/%4Dain"
+        );
+        assert_eq!(paths.collisions.len(), 1);
+        // The folded name of Main is taken once the server has a file of that name too.
+        assert_eq!(
+            paths.local(".git/refs/heads/%4dain"),
+            ".git/refs/heads/%254dain"
+        );
+        assert_eq!(
+            paths.local(".git/refs/heads/MAIN"),
+            ".git/refs/heads/%4D%41%49%4E"
+        );
+        assert_eq!(paths.collisions.len(), 3);
+        assert_eq!(paths.local(".git/refs/tags/%561"), ".git/refs/tags/%561");
+        assert_eq!(paths.local(".git/refs/tags/v1"), ".git/refs/tags/v1");
+        assert_eq!(paths.local(".git/refs/tags/V1"), ".git/refs/tags/%561~2");
+        assert_eq!(
+            paths
+                .renamed
+                .get(".git/refs/heads/%4Dain")
+                .map(String::as_str),
+            Some(".git/refs/heads/Main")
+        );
+
+        let long = format!(".git/refs/heads/{}", "x".repeat(300));
+        let local = paths.local(&long);
+        assert!(local.rsplit('/').next().unwrap().len() <= MAX_NAME);
+        assert_eq!(paths.renamed.get(&local), Some(&long));
+    }
+}
@@ -0,0 +1,201 @@
+use regex::Regex;
+
+/// A shell-style pattern matched against paths relative to the repository root, such as
+/// ".git/objects/pack/*.pack".
+///
+/// `*` and `?` do not match slashes while `**` matches any number of directories. A pattern without
+/// a slash is matched against the file name alone.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    expression: Regex,
+}
+
+impl Glob {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            return Err("expected a non-empty pattern".to_string());
+        }
+        let mut expression = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        expression.push_str("(?:.*/)?");
+                    } else {
+                        expression.push_str(".*");
+                    }
+                }
+                '*' => expression.push_str("[^/]*"),
+                '?' => expression.push_str("[^/]"),
+                c => expression.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        expression.push('$');
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            expression: Regex::new(&expression).map_err(|e| e.to_string())?,
+        })
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        if self.pattern.contains('/') {
+            self.expression.is_match(path)
+        } else {
+            self.expression
+                .is_match(path.rsplit('/').next().unwrap_or_default())
+        }
+    }
+
+    /// Returns true if the pattern may match a path below the directory.
+    fn may_match_below(&self, dir: &str) -> bool {
+        if !self.pattern.contains('/') {
+            return true;
+        }
+        // Compare against the part of the pattern before its first wildcard.
+        let literal = &self.pattern[..self.pattern.find(['*', '?']).unwrap_or(self.pattern.len())];
+        let dir = format!("{dir}/");
+        literal.starts_with(&dir) || dir.starts_with(literal)
+    }
+}
+
+/// Files git creates in every repository or editors leave behind, which listings show but which
+/// never tell anything about the target.
+pub const BOILERPLATE: [&str; 6] = [
+    ".git/hooks/*.sample",
+    ".git/info/exclude",
+    "*~",
+    "*.swp",
+    "*.swo",
+    "#*#",
+];
+
+/// Returns the globs matching boilerplate files.
+pub fn boilerplate() -> Vec<Glob> {
+    BOILERPLATE
+        .iter()
+        .map(|pattern| Glob::parse(pattern).expect("boilerplate patterns are valid"))
+        .collect()
+}
+
+/// Decides which paths are fetched from the target.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    /// Only paths matching one of these are fetched, unless empty.
+    pub include: Vec<Glob>,
+    /// Paths matching any of these are never fetched.
+    pub exclude: Vec<Glob>,
+    /// Paths found in listings that are skipped unless an include pattern names them.
+    pub skip: Vec<Glob>,
+}
+
+/// Turns an href into a path relative to the repository root, merging duplicate slashes.
+fn normalize(href: &str) -> String {
+    href.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl Scope {
+    fn is_excluded(&self, path: &str) -> bool {
+        self.exclude.iter().any(|glob| glob.matches(path))
+    }
+
+    /// Returns true if the file at href may be written to the dump.
+    pub fn allows(&self, href: &str) -> bool {
+        let path = normalize(href);
+        !self.is_excluded(&path)
+            && (self.include.is_empty() || self.include.iter().any(|glob| glob.matches(&path)))
+    }
+
+    /// Returns true if href was found in a listing but is not worth a request.
+    pub fn skips(&self, href: &str) -> bool {
+        let path = normalize(href);
+        self.skip.iter().any(|glob| glob.matches(&path))
+            && !self.include.iter().any(|glob| glob.matches(&path))
+    }
+
+    /// Returns true if href is worth fetching, either because it is an allowed file or because it
+    /// may be a directory containing one.
+    pub fn may_fetch(&self, href: &str) -> bool {
+        let path = normalize(href);
+        if self.is_excluded(&path) {
+            return false;
+        }
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|glob| glob.matches(&path) || glob.may_match_below(&path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(include: &[&str], exclude: &[&str]) -> Scope {
+        let globs = |patterns: &[&str]| {
+            patterns
+                .iter()
+                .map(|pattern| Glob::parse(pattern).unwrap())
+                .collect()
+        };
+        Scope {
+            include: globs(include),
+            exclude: globs(exclude),
+            skip: boilerplate(),
+        }
+    }
+
+    #[test]
+    fn matches_globs() {
+        let glob = Glob::parse(".git/objects/pack/*.pack").unwrap();
+        assert!(glob.matches(".git/objects/pack/pack-1234.pack"));
+        assert!(!glob.matches(".git/objects/pack/pack-1234.idx"));
+        assert!(!glob.matches(".git/objects/pack/nested/pack-1234.pack"));
+
+        let glob = Glob::parse(".git/**/HEAD").unwrap();
+        assert!(glob.matches(".git/HEAD"));
+        assert!(glob.matches(".git/logs/refs/remotes/origin/HEAD"));
+
+        assert!(Glob::parse("*.idx")
+            .unwrap()
+            .matches(".git/objects/pack/pack-1.idx"));
+    }
+
+    #[test]
+    fn excludes_before_including() {
+        let scope = scope(&[".git/**"], &[".git/objects/pack/*.pack"]);
+        assert!(scope.allows(".git//HEAD"));
+        assert!(!scope.allows(".gitignore"));
+        assert!(!scope.allows(".git/objects/pack/pack-1.pack"));
+        assert!(scope.may_fetch(".git"));
+    }
+
+    #[test]
+    fn fetches_directories_that_may_contain_matches() {
+        let scope = scope(&[".git/refs/**"], &[]);
+        assert!(scope.may_fetch(".git"));
+        assert!(scope.may_fetch(".git/refs/heads"));
+        assert!(!scope.may_fetch(".git/objects"));
+        assert!(!scope.allows(".git/HEAD"));
+    }
+
+    #[test]
+    fn skips_boilerplate_unless_included() {
+        let scope = scope(&[], &[]);
+        assert!(scope.skips(".git//hooks/pre-commit.sample"));
+        assert!(scope.skips(".git/.COMMIT_EDITMSG.swp"));
+        assert!(!scope.skips(".git/hooks/pre-commit"));
+        assert!(!scope.skips(".git/info/refs"));
+
+        let scope = self::scope(&[".git/hooks/**"], &[]);
+        assert!(!scope.skips(".git/hooks/pre-commit.sample"));
+    }
+}
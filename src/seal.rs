@@ -0,0 +1,124 @@
+use color_eyre::{eyre::bail, Result};
+use reqwest::{header::CONTENT_TYPE, StatusCode};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+use walkdir::WalkDir;
+
+/// Lists the SHA-256 of every file in the dump, in the format `sha256sum -c` verifies.
+pub const SEAL_MANIFEST: &str = "SEAL.sha256";
+
+/// RFC 3161 timestamp request for the digest of [`SEAL_MANIFEST`].
+pub const TIMESTAMP_QUERY: &str = "SEAL.tsq";
+
+/// Timestamp token returned by the timestamping authority.
+pub const TIMESTAMP_REPLY: &str = "SEAL.tsr";
+
+/// DER encoding of the SHA-256 AlgorithmIdentifier, OID 2.16.840.1.101.3.4.2.1 with NULL parameters.
+const SHA256_ALGORITHM: &[u8] = &[
+    0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00,
+];
+
+/// The outcome of sealing a dump.
+pub struct Seal {
+    /// Number of files listed in the manifest.
+    pub files: usize,
+    /// SHA-256 of the manifest, which covers every file of the dump.
+    pub digest: String,
+}
+
+/// Encodes a DER element with a short or long form length.
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        element.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&byte| byte == 0)
+            .collect();
+        element.push(0x80 | bytes.len() as u8);
+        element.extend(bytes);
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+/// Builds a DER encoded RFC 3161 TimeStampReq for a SHA-256 digest, asking for the signing
+/// certificate to be included in the token.
+fn timestamp_query(digest: &[u8]) -> Vec<u8> {
+    let mut imprint = SHA256_ALGORITHM.to_vec();
+    imprint.extend(der(0x04, digest));
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default();
+    // DER integers use the fewest bytes, with a leading zero only to keep the nonce positive.
+    let mut nonce: Vec<u8> = nanos
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|&byte| byte == 0)
+        .collect();
+    if nonce.first().is_none_or(|&byte| byte & 0x80 != 0) {
+        nonce.insert(0, 0);
+    }
+
+    let mut request = der(0x02, &[1]);
+    request.extend(der(0x30, &imprint));
+    request.extend(der(0x02, &nonce));
+    request.extend(der(0x01, &[0xff]));
+    der(0x30, &request)
+}
+
+/// Hashes every file below the current directory into [`SEAL_MANIFEST`] and writes a timestamp
+/// request for its digest to [`TIMESTAMP_QUERY`].
+pub fn seal() -> Result<Seal> {
+    let mut files: Vec<_> = WalkDir::new(".")
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let path = entry.path().strip_prefix(".").ok()?.to_path_buf();
+            let is_seal = path.to_str().is_some_and(|path| path.starts_with("SEAL."));
+            (!is_seal).then_some(path)
+        })
+        .collect();
+    files.sort();
+
+    let mut manifest = String::new();
+    for path in &files {
+        let hash = Sha256::digest(std::fs::read(path)?);
+        manifest.push_str(&format!("{}  {}\n", hex::encode(hash), path.display()));
+    }
+    std::fs::write(SEAL_MANIFEST, &manifest)?;
+
+    let digest = Sha256::digest(manifest.as_bytes());
+    std::fs::write(TIMESTAMP_QUERY, timestamp_query(&digest))?;
+    Ok(Seal {
+        files: files.len(),
+        digest: hex::encode(digest),
+    })
+}
+
+/// Sends the timestamp request to a timestamping authority and stores its reply in [`TIMESTAMP_REPLY`].
+///
+/// The authority is not the target, so the request goes out without the headers, cookies and
+/// proxy the dump was configured with.
+pub async fn timestamp(tsa: &Url) -> Result<()> {
+    let query = std::fs::read(TIMESTAMP_QUERY)?;
+    let response = reqwest::Client::new()
+        .post(tsa.clone())
+        .header(CONTENT_TYPE, "application/timestamp-query")
+        .body(query)
+        .send()
+        .await?;
+    let status = response.status();
+    if status != StatusCode::OK {
+        bail!("{tsa} responded with status code {status}");
+    }
+    std::fs::write(TIMESTAMP_REPLY, response.bytes().await?)?;
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use crate::{object::Kind, repository::Repository};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Blobs larger than this are assumed to be data rather than source or configuration.
+const MAX_BLOB_SIZE: usize = 1 << 20;
+
+lazy_static! {
+    /// Patterns for secrets commonly committed by accident, along with their names.
+    static ref PATTERNS: Vec<(&'static str, Regex)> = vec![
+        ("aws-access-key", Regex::new(r"\b(AKIA|ASIA)[0-9A-Z]{16}\b").unwrap()),
+        ("private-key", Regex::new(r"-----BEGIN ((RSA|DSA|EC|OPENSSH|PGP) )?PRIVATE KEY( BLOCK)?-----").unwrap()),
+        ("github-token", Regex::new(r"\bgh[pousr]_[A-Za-z0-9]{36,}\b").unwrap()),
+        ("slack-token", Regex::new(r"\bxox[abposr]-[A-Za-z0-9-]{10,}").unwrap()),
+        ("google-api-key", Regex::new(r"\bAIza[0-9A-Za-z_\-]{35}").unwrap()),
+        ("password-assignment", Regex::new(r#"(?i)\b(password|passwd|secret|api_?key|access_?token)\b\s*[:=]\s*["']?[^\s"']{6,}"#).unwrap()),
+    ];
+}
+
+/// A line of a committed file that looks like it holds a secret.
+#[derive(Serialize)]
+pub struct Secret {
+    /// Name of the pattern that matched.
+    pub rule: &'static str,
+    /// Path of the file in `commit`.
+    pub path: String,
+    /// The newest commit containing this version of the file.
+    pub commit: String,
+    /// One-based line number of the match.
+    pub line: usize,
+    pub value: String,
+}
+
+/// Searches a blob for secrets, recording every matching line.
+fn scan_blob(content: &[u8], path: &str, commit: &str, secrets: &mut Vec<Secret>) {
+    if content.len() > MAX_BLOB_SIZE || content.contains(&0) {
+        return;
+    }
+    let text = String::from_utf8_lossy(content);
+    for (index, line) in text.lines().enumerate() {
+        for (rule, pattern) in PATTERNS.iter() {
+            if let Some(found) = pattern.find(line) {
+                secrets.push(Secret {
+                    rule,
+                    path: path.to_string(),
+                    commit: commit.to_string(),
+                    line: index + 1,
+                    value: found.as_str().to_string(),
+                });
+            }
+        }
+    }
+}
+
+/// Scans every blob below a tree that has not been seen yet. Objects missing from a partial dump are skipped.
+fn scan_tree(
+    repo: &Repository,
+    tree: &str,
+    prefix: &str,
+    commit: &str,
+    seen: &mut HashSet<String>,
+    secrets: &mut Vec<Secret>,
+) {
+    let Ok(entries) = repo.tree(tree) else {
+        return;
+    };
+    for entry in entries {
+        if !seen.insert(entry.id.clone()) {
+            continue;
+        }
+        let path = format!("{prefix}{}", entry.name());
+        match entry.kind() {
+            Some(Kind::Tree) => {
+                scan_tree(repo, &entry.id, &format!("{path}/"), commit, seen, secrets)
+            }
+            Some(Kind::Blob) => {
+                if let Ok(content) = repo.blob(&entry.id) {
+                    scan_blob(&content, &path, commit, secrets);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Searches every blob in the history of the repository for secrets, reporting each blob once.
+pub fn scan(repo: &Repository) -> Vec<Secret> {
+    let mut seen = HashSet::new();
+    let mut secrets = Vec::new();
+    for (id, commit) in repo.commits() {
+        scan_tree(repo, &commit.tree, "", &id, &mut seen, &mut secrets);
+    }
+    secrets
+}
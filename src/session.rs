@@ -0,0 +1,513 @@
+use crate::{
+    download::Downloader,
+    middleware::{Middleware, Next},
+    transport::{Request, Response},
+};
+use chrono::DateTime;
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use log::{info, warn};
+use reqwest::{
+    header::{CONTENT_TYPE, COOKIE, SET_COOKIE},
+    Method,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// A cookie set by the target.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Host that set the cookie, or the domain it was set for.
+    pub domain: String,
+    /// Whether the cookie is only sent to the host that set it, not to its subdomains.
+    pub host_only: bool,
+    pub path: String,
+    /// Whether the cookie is only sent over HTTPS.
+    pub secure: bool,
+    /// When the cookie expires in seconds since the epoch, None if it lasts for the session.
+    pub expires: Option<i64>,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Suffixes below which anyone may register a name, besides the top level domains. Not the whole
+/// public suffix list, only the suffixes most hosts are found under.
+const PUBLIC_SUFFIXES: &[&str] = &[
+    "ac.uk",
+    "co.uk",
+    "gov.uk",
+    "org.uk",
+    "com.au",
+    "net.au",
+    "org.au",
+    "co.jp",
+    "co.nz",
+    "co.in",
+    "co.za",
+    "com.br",
+    "com.cn",
+    "com.mx",
+    "com.tr",
+    "appspot.com",
+    "azurewebsites.net",
+    "blogspot.com",
+    "cloudfront.net",
+    "github.io",
+    "gitlab.io",
+    "herokuapp.com",
+    "netlify.app",
+    "pages.dev",
+    "vercel.app",
+];
+
+/// Returns true if domain is a public suffix, which no cookie may be set for since it would be
+/// sent to every site registered below it.
+fn is_public_suffix(domain: &str) -> bool {
+    !domain.contains('.') || PUBLIC_SUFFIXES.contains(&domain)
+}
+
+/// Returns true if host is the domain or one of its subdomains.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Returns true if a cookie for the path is sent with requests for request_path.
+fn path_matches(request_path: &str, path: &str) -> bool {
+    match request_path.strip_prefix(path) {
+        Some(rest) => rest.is_empty() || path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Returns the path a cookie without a Path attribute applies to: the directory of the URL.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => url.path()[..end].to_string(),
+    }
+}
+
+/// Parses the date of an Expires attribute, written either with spaces or with dashes.
+fn parse_date(value: &str) -> Option<i64> {
+    DateTime::parse_from_rfc2822(value)
+        .or_else(|_| DateTime::parse_from_rfc2822(&value.replace('-', " ")))
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+impl Cookie {
+    /// Parses a Set-Cookie header of a response to url. Returns None if the header is malformed
+    /// or sets the cookie for a domain the host does not belong to.
+    pub fn parse(url: &Url, header: &str, now: i64) -> Option<Self> {
+        let mut attributes = header.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in attributes {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if domain.is_empty() {
+                        continue;
+                    }
+                    // An IP address has no subdomains, and a public suffix only stands for
+                    // itself, if it is a host at all.
+                    let is_ip = !matches!(url.host(), Some(url::Host::Domain(_)));
+                    if is_ip || is_public_suffix(&domain) {
+                        if domain != host {
+                            return None;
+                        }
+                        continue;
+                    }
+                    if !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                "expires" => cookie.expires = parse_date(value),
+                _ => {}
+            }
+        }
+        // Max-Age takes precedence over Expires.
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(now.saturating_add(max_age));
+        }
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Returns true if the cookie is sent with a request for url.
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str().map(str::to_ascii_lowercase) else {
+            return false;
+        };
+        let domain = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+        domain && path_matches(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// The cookies set by the target, sent back with every later request that they apply to.
+#[derive(Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+    /// File the cookies were loaded from and are saved to whenever they change.
+    file: Option<PathBuf>,
+}
+
+/// Adds a cookie to cookies, replacing the one with the same name, domain and path, or removing
+/// it if the new one has already expired. Returns true if the cookies changed.
+fn add(cookies: &mut Vec<Cookie>, cookie: Cookie, now: i64) -> bool {
+    let position = cookies.iter().position(|existing| {
+        (&existing.name, &existing.domain, &existing.path)
+            == (&cookie.name, &cookie.domain, &cookie.path)
+    });
+    match position {
+        Some(i) if cookies[i] == cookie => false,
+        Some(i) if cookie.is_expired(now) => {
+            cookies.remove(i);
+            true
+        }
+        Some(i) => {
+            cookies[i] = cookie;
+            true
+        }
+        None if cookie.is_expired(now) => false,
+        None => {
+            cookies.push(cookie);
+            true
+        }
+    }
+}
+
+/// Writes data to file, creating it with permissions for its owner only.
+fn write_private(file: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(file)?.write_all(data)
+}
+
+impl CookieJar {
+    /// Opens the jar kept in file, which is created once the first cookie is set. Cookies that
+    /// last for the session are kept too, so that a later run continues the same session.
+    pub fn open(file: PathBuf) -> Self {
+        let cookies: Vec<Cookie> = match std::fs::read(&file) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("Ignoring malformed cookie jar {}: {e}", file.display());
+                Vec::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                warn!("Ignoring unreadable cookie jar {}: {e}", file.display());
+                Vec::new()
+            }
+        };
+        let now = now();
+        CookieJar {
+            cookies: Mutex::new(cookies.into_iter().filter(|c| !c.is_expired(now)).collect()),
+            file: Some(file),
+        }
+    }
+
+    /// Writes the cookies to the file of the jar, if it has one. The file is created readable by
+    /// its owner only, since the cookies may log anyone in.
+    fn save(&self, cookies: &[Cookie]) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let written = serde_json::to_vec_pretty(cookies)
+            .map_err(std::io::Error::from)
+            .and_then(|data| write_private(file, &data));
+        if let Err(e) = written {
+            warn!("Failed to save the cookies to {}: {e}", file.display());
+        }
+    }
+
+    /// Adds a cookie, replacing the one with the same name, domain and path, or removing it if
+    /// the new one has already expired.
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        if add(&mut cookies, cookie, now()) {
+            self.save(&cookies);
+        }
+    }
+
+    /// Stores the cookies set by a response to url.
+    pub fn store(&self, url: &Url, response: &Response) {
+        let now = now();
+        let mut cookies = self.cookies.lock().unwrap();
+        let mut changed = false;
+        for header in response.headers().get_all(SET_COOKIE) {
+            match header
+                .to_str()
+                .ok()
+                .and_then(|h| Cookie::parse(url, h, now))
+            {
+                Some(cookie) => changed |= add(&mut cookies, cookie, now),
+                None => warn!("Ignoring the cookie {header:?} set by {url}"),
+            }
+        }
+        if changed {
+            self.save(&cookies);
+        }
+    }
+
+    /// Adds the cookies of a Cookie header given on the command line, scoped to the whole host of
+    /// url, so that the cookies of the target are sent along with them instead of replacing them.
+    pub fn seed(&self, url: &Url, header: &str) {
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        for pair in header.split(';') {
+            if let Some((name, value)) = pair.split_once('=') {
+                self.insert(Cookie {
+                    name: name.trim().to_string(),
+                    value: value.trim().to_string(),
+                    domain: host.to_ascii_lowercase(),
+                    host_only: true,
+                    path: "/".to_string(),
+                    secure: false,
+                    expires: None,
+                });
+            }
+        }
+    }
+
+    /// Returns the value of the Cookie header to send with a request for url, if any cookie
+    /// applies to it. Cookies with longer paths come first.
+    pub fn header(&self, url: &Url) -> Option<String> {
+        let now = now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| !cookie.is_expired(now));
+        let mut matching: Vec<_> = cookies.iter().filter(|c| c.matches(url)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let pairs: Vec<_> = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.lock().unwrap().len()
+    }
+}
+
+impl Middleware for Arc<CookieJar> {
+    fn handle<'a>(
+        &'a self,
+        mut request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            if let Some(cookies) = self.header(&request.url) {
+                request = request.header(COOKIE.as_str(), cookies);
+            }
+            let url = request.url.clone();
+            let response = next.run(request).await?;
+            self.store(&url, &response);
+            Ok(response)
+        })
+    }
+}
+
+/// A request made before the dump begins, e.g. to log in and obtain a session cookie.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreRequest {
+    pub method: Method,
+    pub url: Url,
+    /// Form-encoded body, such as "user=admin&password=admin".
+    pub body: Option<String>,
+}
+
+impl PreRequest {
+    /// Parses a URL to fetch, optionally preceded by a method and followed by a body, such as
+    /// "POST https://example.com/login user=admin&password=admin".
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let arg = arg.trim();
+        let (method, rest) = match arg.split_once(char::is_whitespace) {
+            Some((method, rest))
+                if !method.is_empty() && method.bytes().all(|b| b.is_ascii_uppercase()) =>
+            {
+                let method = Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+                (method, rest.trim_start())
+            }
+            _ => (Method::GET, arg),
+        };
+        let (url, body) = match rest.split_once(char::is_whitespace) {
+            Some((url, body)) => (url, Some(body.trim().to_string())),
+            None => (rest, None),
+        };
+        let url = Url::parse(url).map_err(|e| format!("{url:?} is not a URL: {e}"))?;
+        Ok(PreRequest {
+            method,
+            url,
+            body: body.filter(|body| !body.is_empty()),
+        })
+    }
+
+    fn request(&self) -> Request {
+        let mut request = Request {
+            method: self.method.clone(),
+            ..Request::get(self.url.clone())
+        };
+        if let Some(body) = &self.body {
+            request.body = Some(body.clone().into_bytes());
+            request = request.header(CONTENT_TYPE.as_str(), "application/x-www-form-urlencoded");
+        }
+        request
+    }
+}
+
+impl fmt::Display for PreRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.url)
+    }
+}
+
+/// Sends the requests that establish a session before the dump, in order and with the same
+/// client, so that the cookies they obtain are sent with every later request.
+pub async fn establish(download: &Downloader, pre_requests: &[PreRequest]) {
+    for pre_request in pre_requests {
+        match download.send(pre_request.request()).await {
+            Ok(response) => info!(
+                "{pre_request} answered {}, {} cookies held",
+                response.status(),
+                download.cookies.len()
+            ),
+            Err(e) => warn!("Failed to establish a session with {pre_request}: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_cookies_where_they_apply() {
+        let login = Url::parse("https://app.example.com/account/login").unwrap();
+        let now = 1_700_000_000;
+        let session = Cookie::parse(&login, "session=abc; Path=/; Secure; HttpOnly", now).unwrap();
+        assert_eq!(session.path, "/");
+        assert!(session.host_only && session.secure);
+        let scoped = Cookie::parse(&login, "csrf=xyz; Domain=.example.com", now).unwrap();
+        assert_eq!(scoped.path, "/account");
+        assert!(!scoped.host_only);
+        assert!(Cookie::parse(&login, "stolen=1; Domain=example.org", now).is_none());
+        assert!(Cookie::parse(&login, "shared=1; Domain=com", now).is_none());
+        let hosted = Url::parse("https://app.github.io/").unwrap();
+        assert!(Cookie::parse(&hosted, "shared=1; Domain=github.io", now).is_none());
+        let ip = Url::parse("http://10.0.0.1/").unwrap();
+        assert!(Cookie::parse(&ip, "shared=1; Domain=0.1", now).is_none());
+        assert_eq!(
+            Cookie::parse(
+                &login,
+                "a=b; Max-Age=60; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+                now
+            )
+            .unwrap()
+            .expires,
+            Some(now + 60)
+        );
+        assert_eq!(
+            Cookie::parse(&login, "a=b; Expires=Wed, 21-Oct-2015 07:28:00 GMT", now)
+                .unwrap()
+                .expires,
+            Some(1_445_412_480)
+        );
+
+        let jar = CookieJar::default();
+        jar.insert(session);
+        jar.insert(scoped);
+        let head = Url::parse("https://app.example.com/.git/HEAD").unwrap();
+        assert_eq!(jar.header(&head).as_deref(), Some("session=abc"));
+        let account = Url::parse("https://app.example.com/account/settings").unwrap();
+        assert_eq!(
+            jar.header(&account).as_deref(),
+            Some("csrf=xyz; session=abc")
+        );
+        let plain = Url::parse("http://app.example.com/.git/HEAD").unwrap();
+        assert_eq!(jar.header(&plain), None);
+
+        let logout = Cookie::parse(&login, "session=; Path=/; Max-Age=0", super::now()).unwrap();
+        jar.insert(logout);
+        assert_eq!(jar.header(&head), None);
+    }
+
+    #[test]
+    fn keeps_cookies_across_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("cookies.json");
+        let url = Url::parse("http://example.com/.git/HEAD").unwrap();
+        let jar = CookieJar::open(file.clone());
+        assert!(!file.exists());
+        jar.seed(&url, "sid=abc");
+        let gone = Cookie::parse(&url, "old=1; Max-Age=0", super::now()).unwrap();
+        jar.insert(gone);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let reopened = CookieJar::open(file);
+        assert_eq!(reopened.header(&url).as_deref(), Some("sid=abc"));
+    }
+
+    #[test]
+    fn parses_pre_requests() {
+        let get = PreRequest::parse("https://example.com/").unwrap();
+        assert_eq!((get.method, get.body), (Method::GET, None));
+        let post = PreRequest::parse("POST https://example.com/login user=a&password=b").unwrap();
+        assert_eq!(post.method, Method::POST);
+        assert_eq!(post.body.as_deref(), Some("user=a&password=b"));
+        assert!(PreRequest::parse("POST login").is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    constants::SENSITIVE_SIBLINGS, download::Downloader, extract, listing, response::ResponseExt,
+    scope::Glob,
+};
+use color_eyre::Result;
+use log::{debug, info, warn};
+use reqwest::StatusCode;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// Directory inside the dump that items found next to the .git directory are mirrored to.
+pub const SIBLINGS_DIR: &str = "opendotgit-siblings";
+
+/// File inside [`SIBLINGS_DIR`] listing every mirrored item.
+pub const MANIFEST: &str = "manifest.json";
+
+/// Stops mirroring after this many files so a huge backup directory cannot fill the disk.
+const MAX_FILES: usize = 1000;
+
+/// A file mirrored from the web root.
+#[derive(Serialize)]
+pub struct Sibling {
+    pub url: String,
+    /// Path of the copy relative to [`SIBLINGS_DIR`].
+    pub path: String,
+    pub size: usize,
+    pub sha256: String,
+}
+
+/// Returns true if an entry of the web root listing is commonly sensitive.
+fn is_sensitive(name: &str) -> bool {
+    let name = name.trim_end_matches('/');
+    name != ".git"
+        && SENSITIVE_SIBLINGS
+            .iter()
+            .filter_map(|pattern| Glob::parse(pattern).ok())
+            .any(|glob| glob.matches(name))
+}
+
+/// Mirrors the sensitive items listed in the web root next to the .git directory into
+/// [`SIBLINGS_DIR`], descending into directories, and writes a manifest of what was saved.
+pub async fn grab(download: &Downloader) -> Result<Vec<Sibling>> {
+    let root = download.fetch("").await?;
+    if root.status() != StatusCode::OK || !root.is_html() {
+        info!("The web root is not listed, no siblings to grab");
+        return Ok(Vec::new());
+    }
+    let mut queue: VecDeque<String> =
+        listing::list(&root, download.normalize_url("")?.path(), download.parser)
+            .into_iter()
+            .filter(|name| is_sensitive(name))
+            .collect();
+
+    let mut siblings = Vec::new();
+    while let Some(href) = queue.pop_front() {
+        if download.is_interrupted() || siblings.len() >= MAX_FILES {
+            break;
+        }
+        let href = href.trim_end_matches('/').to_string();
+        if !download.scope.may_fetch(&href) {
+            debug!("Skipping {href}, it is out of scope");
+            continue;
+        }
+        let response = download.fetch(&href).await?;
+        match response.status() {
+            // Directories redirect to their listing.
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => {
+                if download.is_soft_404(&href, &response, response.bytes()) {
+                    continue;
+                }
+                let listing = download.fetch(&format!("{href}/")).await?;
+                if listing.status() == StatusCode::OK && listing.is_html() {
+                    queue.extend(
+                        listing::list(
+                            &listing,
+                            download.normalize_url(&href)?.path(),
+                            download.parser,
+                        )
+                        .into_iter()
+                        .map(|child| format!("{href}/{child}")),
+                    );
+                }
+            }
+            StatusCode::OK if response.is_html() => {
+                debug!("{href} responded with HTML, skipping");
+            }
+            StatusCode::OK => {
+                let body = response.bytes();
+                if download.is_soft_404(&href, &response, body) || !download.scope.allows(&href) {
+                    continue;
+                }
+                let Some(path) = extract::relative(Path::new(&href)) else {
+                    continue;
+                };
+                let target = Path::new(SIBLINGS_DIR).join(&path);
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&target, body)?;
+                siblings.push(Sibling {
+                    url: download.normalize_url(&href)?.to_string(),
+                    path: path.display().to_string(),
+                    size: body.len(),
+                    sha256: hex::encode(Sha256::digest(body)),
+                });
+            }
+            status => warn!("{href} responded with status code {status}"),
+        }
+    }
+    if siblings.len() >= MAX_FILES {
+        warn!("Stopped after mirroring {MAX_FILES} siblings");
+    }
+    if !siblings.is_empty() {
+        std::fs::write(
+            Path::new(SIBLINGS_DIR).join(MANIFEST),
+            serde_json::to_vec_pretty(&siblings)?,
+        )?;
+    }
+    Ok(siblings)
+}
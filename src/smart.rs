@@ -0,0 +1,305 @@
+use crate::{download::Downloader, transport::Request};
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use log::debug;
+use pathbuf::pathbuf;
+use reqwest::{header::CONTENT_TYPE, StatusCode};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Content type of a ref advertisement answered by `git http-backend` and its kin.
+const ADVERTISEMENT_TYPE: &str = "application/x-git-upload-pack-advertisement";
+
+/// Capabilities asked for when negotiating a pack, if the server offers them.
+const WANTED_CAPABILITIES: [&str; 2] = ["side-band-64k", "ofs-delta"];
+
+/// Refs and capabilities a server speaking the smart HTTP protocol advertised.
+#[derive(Debug, Default, PartialEq)]
+pub struct Advertisement {
+    /// Object IDs and names of the advertised refs, including peeled tags ending in `^{}`.
+    pub refs: Vec<(String, String)>,
+    pub capabilities: Vec<String>,
+}
+
+impl Advertisement {
+    /// Returns the ref HEAD points to, if the server said.
+    pub fn head_target(&self) -> Option<&str> {
+        self.capabilities
+            .iter()
+            .find_map(|capability| capability.strip_prefix("symref=HEAD:"))
+    }
+
+    fn has(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Returns the contents of a packed-refs file listing every advertised ref but HEAD.
+    pub fn packed_refs(&self) -> String {
+        let mut text = String::from("# pack-refs with: peeled fully-peeled sorted \n");
+        for (id, name) in &self.refs {
+            if name == "HEAD" {
+                continue;
+            }
+            match name.strip_suffix("^{}") {
+                Some(_) => text.push_str(&format!("^{id}\n")),
+                None => text.push_str(&format!("{id} {name}\n")),
+            }
+        }
+        text
+    }
+}
+
+/// Packet lines with their length prefix removed, None standing for a flush packet.
+type PktLines<'a> = Vec<Option<&'a [u8]>>;
+
+/// Splits data into pkt-lines, returning them along with an unframed pack following them.
+fn pkt_lines(mut data: &[u8]) -> Result<(PktLines<'_>, &[u8])> {
+    let mut lines = Vec::new();
+    while data.len() >= 4 {
+        let len = std::str::from_utf8(&data[..4])
+            .ok()
+            .and_then(|len| usize::from_str_radix(len, 16).ok())
+            .ok_or_else(|| eyre!("invalid pkt-line length"))?;
+        match len {
+            0 => lines.push(None),
+            1..=3 => bail!("invalid pkt-line length {len}"),
+            _ => {
+                let line = data
+                    .get(4..len)
+                    .ok_or_else(|| eyre!("truncated pkt-line"))?;
+                lines.push(Some(line));
+                // The pack follows the acknowledgement unframed without side-band.
+                if line.starts_with(b"NAK") || line.starts_with(b"ACK") {
+                    data = &data[len..];
+                    if !data.starts_with(b"PACK") {
+                        continue;
+                    }
+                    return Ok((lines, data));
+                }
+            }
+        }
+        data = &data[len.max(4)..];
+    }
+    Ok((lines, data))
+}
+
+/// Appends a pkt-line holding line to buffer.
+fn push_pkt_line(buffer: &mut Vec<u8>, line: &str) {
+    buffer.extend(format!("{:04x}", line.len() + 4).as_bytes());
+    buffer.extend(line.as_bytes());
+}
+
+/// Parses the body of `info/refs?service=git-upload-pack`.
+pub fn parse_advertisement(body: &[u8]) -> Result<Advertisement> {
+    let (lines, _) = pkt_lines(body)?;
+    let mut lines = lines.into_iter().peekable();
+    // HTTP servers announce the service first, followed by a flush.
+    if lines
+        .peek()
+        .is_some_and(|line| line.is_some_and(|line| line.starts_with(b"# service=")))
+    {
+        lines.next();
+        lines.next();
+    }
+
+    let mut advertisement = Advertisement::default();
+    for line in lines.flatten() {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\n');
+        let (line, capabilities) = line.split_once('\0').unwrap_or((line, ""));
+        if !capabilities.is_empty() {
+            advertisement.capabilities = capabilities.split(' ').map(str::to_string).collect();
+        }
+        let (id, name) = line
+            .split_once(' ')
+            .ok_or_else(|| eyre!("invalid ref advertisement {line:?}"))?;
+        // An empty repository advertises only its capabilities.
+        if name != "capabilities^{}" {
+            advertisement.refs.push((id.to_string(), name.to_string()));
+        }
+    }
+    Ok(advertisement)
+}
+
+/// Asks the server for its refs over the smart HTTP protocol, returning None if it does not speak it.
+pub async fn probe(download: &Downloader) -> Result<Option<Advertisement>> {
+    let url = download.normalize_url(".git/info/refs?service=git-upload-pack")?;
+    let response = download.send(Request::get(url)).await?;
+    let smart = response.status() == StatusCode::OK
+        && response
+            .headers()
+            .get(CONTENT_TYPE)
+            .is_some_and(|value| value.as_bytes().starts_with(ADVERTISEMENT_TYPE.as_bytes()));
+    if !smart {
+        return Ok(None);
+    }
+    parse_advertisement(response.bytes()).map(Some)
+}
+
+/// Builds the upload-pack request wanting every object the advertised refs reach.
+fn upload_request(advertisement: &Advertisement) -> Result<Vec<u8>> {
+    let ids: BTreeSet<&str> = advertisement
+        .refs
+        .iter()
+        .map(|(id, _)| id.as_str())
+        .collect();
+    if ids.is_empty() {
+        bail!("the server advertised no refs");
+    }
+    let capabilities: Vec<&str> = WANTED_CAPABILITIES
+        .into_iter()
+        .filter(|capability| advertisement.has(capability))
+        .chain(["agent=opendotgit"])
+        .collect();
+
+    let mut body = Vec::new();
+    for (i, id) in ids.into_iter().enumerate() {
+        if i == 0 {
+            push_pkt_line(
+                &mut body,
+                &format!("want {id} {}\n", capabilities.join(" ")),
+            );
+        } else {
+            push_pkt_line(&mut body, &format!("want {id}\n"));
+        }
+    }
+    body.extend(b"0000");
+    push_pkt_line(&mut body, "done\n");
+    Ok(body)
+}
+
+/// Extracts the pack from an upload-pack response, demultiplexing the side-band if used.
+pub fn parse_pack(body: &[u8]) -> Result<Vec<u8>> {
+    let (lines, rest) = pkt_lines(body)?;
+    let mut pack = Vec::new();
+    for line in lines.into_iter().flatten() {
+        match line.split_first() {
+            Some((1, data)) => pack.extend(data),
+            Some((2, message)) => debug!("remote: {}", String::from_utf8_lossy(message).trim()),
+            Some((3, message)) => {
+                bail!("remote error: {}", String::from_utf8_lossy(message).trim())
+            }
+            _ => {}
+        }
+    }
+    pack.extend(rest);
+    if !pack.starts_with(b"PACK") {
+        bail!("the response holds no pack");
+    }
+    Ok(pack)
+}
+
+/// Negotiates a single pack holding every object the advertised refs reach.
+pub async fn fetch_pack(download: &Downloader, advertisement: &Advertisement) -> Result<Vec<u8>> {
+    let url = download.normalize_url(".git/git-upload-pack")?;
+    let request = Request::post(url, upload_request(advertisement)?)
+        .header(CONTENT_TYPE, "application/x-git-upload-pack-request")
+        .header("accept", "application/x-git-upload-pack-result");
+    let response = download.send(request).await?;
+    if response.status() != StatusCode::OK {
+        bail!("upload-pack responded with {}", response.status());
+    }
+    parse_pack(response.bytes())
+}
+
+/// Writes the pack and the advertised refs into the dumped .git directory, keeping refs
+/// already dumped, and returns the path of the pack.
+pub fn store(advertisement: &Advertisement, pack: &[u8]) -> Result<PathBuf> {
+    let pack_dir = pathbuf![".git", "objects", "pack"];
+    std::fs::create_dir_all(&pack_dir)?;
+    let path = write_pack(&pack_dir, pack).wrap_err("Failed to store the negotiated pack")?;
+
+    let packed_refs = pathbuf![".git", "packed-refs"];
+    if !packed_refs.exists() {
+        std::fs::write(packed_refs, advertisement.packed_refs())?;
+    }
+    let head = pathbuf![".git", "HEAD"];
+    if !head.exists() {
+        let contents = match advertisement.head_target() {
+            Some(target) => format!("ref: {target}\n"),
+            None => match advertisement.refs.iter().find(|(_, name)| name == "HEAD") {
+                Some((id, _)) => format!("{id}\n"),
+                None => return Ok(path),
+            },
+        };
+        std::fs::write(head, contents)?;
+    }
+    Ok(path)
+}
+
+/// Writes the pack along with its index, which libgit2 builds.
+#[cfg(feature = "git2")]
+fn write_pack(pack_dir: &std::path::Path, pack: &[u8]) -> Result<PathBuf> {
+    use std::io::Write;
+
+    // Creating the indexer alone does not initialize libgit2, opening an object database does.
+    let odb = git2::Odb::new()?;
+    let mut indexer = git2::Indexer::new(Some(&odb), pack_dir, 0o644, true)?;
+    indexer.write_all(pack)?;
+    let name = indexer.commit()?;
+    Ok(pack_dir.join(format!("pack-{name}.pack")))
+}
+
+/// Writes the pack alone, named after its checksum as git would.
+#[cfg(not(feature = "git2"))]
+fn write_pack(pack_dir: &std::path::Path, pack: &[u8]) -> Result<PathBuf> {
+    if !crate::validate::pack_checksum(pack) {
+        bail!("the pack checksum does not match");
+    }
+    let name = hex::encode(&pack[pack.len() - 20..]);
+    let path = pack_dir.join(format!("pack-{name}.pack"));
+    std::fs::write(&path, pack)?;
+    log::warn!(
+        "Built without git2, run git index-pack {} before using the dump",
+        path.display()
+    );
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_advertisement_and_pack() {
+        let mut body = Vec::new();
+        push_pkt_line(&mut body, "# service=git-upload-pack\n");
+        body.extend(b"0000");
+        let id = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+        push_pkt_line(
+            &mut body,
+            &format!("{id} HEAD\0side-band-64k ofs-delta symref=HEAD:refs/heads/main\n"),
+        );
+        push_pkt_line(&mut body, &format!("{id} refs/heads/main\n"));
+        body.extend(b"0000");
+        let advertisement = parse_advertisement(&body).unwrap();
+        assert_eq!(advertisement.head_target(), Some("refs/heads/main"));
+        assert_eq!(advertisement.refs.len(), 2);
+        assert!(advertisement
+            .packed_refs()
+            .ends_with(&format!("{id} refs/heads/main\n")));
+
+        let mut request = Vec::new();
+        push_pkt_line(
+            &mut request,
+            &format!("want {id} side-band-64k ofs-delta agent=opendotgit\n"),
+        );
+        request.extend(b"00000009done\n");
+        assert_eq!(upload_request(&advertisement).unwrap(), request);
+
+        let mut response = Vec::new();
+        push_pkt_line(&mut response, "NAK\n");
+        push_pkt_line(&mut response, "\u{2}Counting objects: 1\n");
+        push_pkt_line(&mut response, "\u{1}PACK");
+        push_pkt_line(&mut response, "\u{1}rest");
+        response.extend(b"0000");
+        assert_eq!(parse_pack(&response).unwrap(), b"PACKrest");
+
+        let mut raw = Vec::new();
+        push_pkt_line(&mut raw, "NAK\n");
+        raw.extend(b"PACKraw");
+        assert_eq!(parse_pack(&raw).unwrap(), b"PACKraw");
+    }
+}
@@ -0,0 +1,85 @@
+use crate::transport::Response;
+use reqwest::{header::LOCATION, StatusCode};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The fingerprint of the response a server gives for files that do not exist, whether it serves
+/// an error page or redirects to one.
+#[derive(PartialEq)]
+pub struct Fingerprint {
+    status: StatusCode,
+    /// Where the response redirects to, with the requested file name removed.
+    location: Option<String>,
+    hash: Vec<u8>,
+}
+
+/// Removes every mention of the requested file name, since many error pages and redirects echo
+/// the path they could not find.
+fn strip(href: &str, text: &str) -> String {
+    let name = href.rsplit('/').next().unwrap_or(href);
+    if name.is_empty() {
+        text.to_string()
+    } else {
+        text.replace(name, "")
+    }
+}
+
+impl Fingerprint {
+    pub fn new(href: &str, response: &Response) -> Self {
+        Self::of(href, response, response.bytes())
+    }
+
+    /// Fingerprints a response whose body was decoded or resumed into body.
+    fn of(href: &str, response: &Response, body: &[u8]) -> Self {
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .map(|location| strip(href, location));
+        let text = strip(href, &String::from_utf8_lossy(body));
+        Fingerprint {
+            status: response.status(),
+            location,
+            hash: Sha256::digest(text.as_bytes()).to_vec(),
+        }
+    }
+
+    /// Returns true if the response served for href, with its body, is the same error page or
+    /// redirect as the fingerprinted one.
+    pub fn matches(&self, href: &str, response: &Response, body: &[u8]) -> bool {
+        *self == Self::of(href, response, body)
+    }
+}
+
+/// Returns the path of a file under `.git` that is certain not to exist.
+pub fn probe_href() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    format!(".git/opendotgit-{nanos:x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    fn redirect(location: &str) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert(LOCATION, HeaderValue::from_str(location).unwrap());
+        Response::new(StatusCode::FOUND, headers, Vec::new())
+    }
+
+    #[test]
+    fn tells_redirects_and_statuses_apart() {
+        let probe = ".git/opendotgit-1";
+        let fingerprint = Fingerprint::new(probe, &redirect("/404.html?from=opendotgit-1"));
+        let missing = redirect("/404.html?from=HEAD");
+        assert!(fingerprint.matches(".git/HEAD", &missing, missing.bytes()));
+        let directory = redirect("/.git/objects/");
+        assert!(!fingerprint.matches(".git/objects", &directory, directory.bytes()));
+        let served = Response::new(StatusCode::OK, HeaderMap::new(), Vec::new());
+        assert!(!fingerprint.matches(".git/HEAD", &served, served.bytes()));
+    }
+}
@@ -0,0 +1,104 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// The name of the file in the output directory that lets an interrupted run resume.
+pub const STATE_FILE: &str = "opendotgit-state.json";
+
+/// The most paths remembered as missing, so the state file stays small for huge targets.
+const MAX_MISSING: usize = 100_000;
+
+/// Progress of a dump that survives across runs.
+#[derive(Serialize, Deserialize, Default)]
+pub struct State {
+    /// Files that were downloaded and written to disk completely.
+    pub completed: BTreeSet<String>,
+    /// URL of the target the missing paths were recorded for.
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Paths the target answered with 404 or 410, which are not requested again.
+    #[serde(default)]
+    pub missing: BTreeSet<String>,
+}
+
+impl State {
+    /// Loads the state left behind by a previous run in the current directory, if any.
+    pub fn load() -> Self {
+        if !Path::new(STATE_FILE).exists() {
+            return State::default();
+        }
+        match std::fs::read(STATE_FILE).map(|data| serde_json::from_slice(&data)) {
+            Ok(Ok(state)) => state,
+            Ok(Err(e)) => {
+                warn!("Ignoring malformed {STATE_FILE}: {e}");
+                State::default()
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable {STATE_FILE}: {e}");
+                State::default()
+            }
+        }
+    }
+
+    /// Ties the state to a target, forgetting the paths known to be missing if they were recorded
+    /// for another target or the cache of missing paths is not to be trusted.
+    pub fn bind(&mut self, target: &str, use_cache: bool) {
+        if !use_cache || self.target.as_deref() != Some(target) {
+            self.missing.clear();
+        }
+        self.target = Some(target.to_string());
+    }
+
+    /// Remembers that a path does not exist on the target, until the cache is full.
+    pub fn record_missing(&mut self, href: &str) {
+        if self.missing.len() < MAX_MISSING {
+            self.missing.insert(href.to_string());
+        }
+    }
+
+    /// Forgets the completed files whose path, as returned by path_of, no longer exists, so that
+    /// files removed since the previous run are downloaded again. Returns how many were forgotten.
+    pub fn forget_removed(&mut self, path_of: impl Fn(&str) -> PathBuf) -> usize {
+        let before = self.completed.len();
+        self.completed.retain(|href| path_of(href).exists());
+        before - self.completed.len()
+    }
+
+    /// Writes the state to the current directory.
+    pub fn save(&self) -> std::io::Result<()> {
+        std::fs::write(STATE_FILE, serde_json::to_vec(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgets_missing_paths_of_other_targets() {
+        let mut state = State::default();
+        state.bind("http://example.com/", true);
+        state.record_missing(".git/ORIG_HEAD");
+        state.bind("http://example.com/", true);
+        assert!(state.missing.contains(".git/ORIG_HEAD"));
+        state.bind("http://example.com/", false);
+        assert!(state.missing.is_empty());
+
+        state.record_missing(".git/ORIG_HEAD");
+        state.bind("http://example.org/", true);
+        assert!(state.missing.is_empty());
+    }
+
+    #[test]
+    fn forgets_removed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("HEAD"), "ref: refs/heads/master\n").unwrap();
+        let mut state = State::default();
+        state
+            .completed
+            .extend(["HEAD".to_string(), "ORIG_HEAD".to_string()]);
+        assert_eq!(state.forget_removed(|href| dir.path().join(href)), 1);
+        assert!(state.completed.contains("HEAD"));
+    }
+}
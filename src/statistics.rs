@@ -0,0 +1,229 @@
+use crate::progress::Phase;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Requests made during one phase of a dump.
+#[derive(Serialize, Default, Clone, Debug, PartialEq)]
+pub struct PhaseStatistics {
+    pub phase: &'static str,
+    /// Number of responses received for each status code.
+    pub statuses: BTreeMap<u16, usize>,
+    /// Total size of the response bodies.
+    pub bytes: u64,
+    /// Attempts repeated after a failed request.
+    pub retries: usize,
+    /// Requests that did not complete within the timeout.
+    pub timeouts: usize,
+    /// Requests that failed without a response, after retrying.
+    pub errors: usize,
+    /// Files the server compressed on top of their stored form, which were decoded before writing.
+    pub decoded: usize,
+    /// Boilerplate files found in listings that were not requested.
+    pub skipped: usize,
+    /// Files whose content lacked the format of their path, such as error pages, which were not written.
+    pub rejected: usize,
+}
+
+/// Time spent in one phase of a dump and the pace of its requests.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PhaseTiming {
+    pub phase: &'static str,
+    pub seconds: f64,
+    /// Number of responses received during the phase.
+    pub requests: usize,
+}
+
+impl PhaseTiming {
+    pub fn requests_per_second(&self) -> f64 {
+        if self.seconds > 0.0 {
+            self.requests as f64 / self.seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Pairs the time spent in every phase with the requests it made.
+pub fn timings(durations: &[(Phase, Duration)], phases: &[PhaseStatistics]) -> Vec<PhaseTiming> {
+    durations
+        .iter()
+        .map(|(phase, duration)| PhaseTiming {
+            phase: phase.name(),
+            seconds: duration.as_secs_f64(),
+            requests: phases
+                .iter()
+                .find(|statistics| statistics.phase == phase.name())
+                .map(|statistics| statistics.statuses.values().sum())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Counts the outcome of every request made by a downloader, per phase.
+#[derive(Default)]
+pub struct Statistics {
+    phases: Mutex<BTreeMap<Phase, PhaseStatistics>>,
+}
+
+impl Statistics {
+    fn update(&self, phase: Phase, update: impl FnOnce(&mut PhaseStatistics)) {
+        let mut phases = self.phases.lock().unwrap();
+        let statistics = phases.entry(phase).or_insert_with(|| PhaseStatistics {
+            phase: phase.name(),
+            ..Default::default()
+        });
+        update(statistics);
+    }
+
+    /// Records a response with a body of the given size.
+    pub fn response(&self, phase: Phase, status: StatusCode, bytes: usize) {
+        self.update(phase, |statistics| {
+            *statistics.statuses.entry(status.as_u16()).or_default() += 1;
+            statistics.bytes += bytes as u64;
+        });
+    }
+
+    /// Records that a request was attempted again after failing.
+    pub fn retries(&self, phase: Phase, count: usize) {
+        self.update(phase, |statistics| statistics.retries += count);
+    }
+
+    /// Records a request that ran out of time.
+    pub fn timeout(&self, phase: Phase) {
+        self.update(phase, |statistics| statistics.timeouts += 1);
+    }
+
+    /// Records a request that failed without a response.
+    pub fn error(&self, phase: Phase) {
+        self.update(phase, |statistics| statistics.errors += 1);
+    }
+
+    /// Records a file that had to be decoded because the server compressed it in transit.
+    pub fn decoded(&self, phase: Phase) {
+        self.update(phase, |statistics| statistics.decoded += 1);
+    }
+
+    /// Records a file found in a listing that was skipped as boilerplate.
+    pub fn skipped(&self, phase: Phase) {
+        self.update(phase, |statistics| statistics.skipped += 1);
+    }
+
+    /// Records a file that was not written because its content lacked the format of its path.
+    pub fn rejected(&self, phase: Phase) {
+        self.update(phase, |statistics| statistics.rejected += 1);
+    }
+
+    /// Returns the statistics of every phase that made requests, in the order of the phases.
+    pub fn phases(&self) -> Vec<PhaseStatistics> {
+        self.phases.lock().unwrap().values().cloned().collect()
+    }
+}
+
+/// Formats the statistics as a table with a row per phase.
+pub fn table(phases: &[PhaseStatistics]) -> String {
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "{:<17}  {:>6}  {:>6}  {:>6}  {:>6}  {:>7}  {:>8}  {:>6}  {:>7}  {:>7}  {:>8}  {:>10}",
+        "PHASE",
+        "200",
+        "404",
+        "403",
+        "OTHER",
+        "RETRIES",
+        "TIMEOUTS",
+        "ERRORS",
+        "DECODED",
+        "SKIPPED",
+        "REJECTED",
+        "BYTES"
+    );
+    for phase in phases {
+        let count = |code: u16| phase.statuses.get(&code).copied().unwrap_or_default();
+        let other = phase.statuses.values().sum::<usize>() - count(200) - count(404) - count(403);
+        let _ = writeln!(
+            text,
+            "{:<17}  {:>6}  {:>6}  {:>6}  {:>6}  {:>7}  {:>8}  {:>6}  {:>7}  {:>7}  {:>8}  {:>10}",
+            phase.phase,
+            count(200),
+            count(404),
+            count(403),
+            other,
+            phase.retries,
+            phase.timeouts,
+            phase.errors,
+            phase.decoded,
+            phase.skipped,
+            phase.rejected,
+            phase.bytes
+        );
+    }
+    text
+}
+
+/// Formats the timings as a table with a row per phase and the share of the whole run.
+pub fn timing_table(timings: &[PhaseTiming]) -> String {
+    let total: f64 = timings.iter().map(|timing| timing.seconds).sum();
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "{:<17}  {:>9}  {:>5}  {:>8}  {:>8}",
+        "PHASE", "SECONDS", "SHARE", "REQUESTS", "REQ/S"
+    );
+    for timing in timings {
+        let share = if total > 0.0 {
+            timing.seconds / total * 100.0
+        } else {
+            0.0
+        };
+        let _ = writeln!(
+            text,
+            "{:<17}  {:>9.2}  {:>4.0}%  {:>8}  {:>8.1}",
+            timing.phase,
+            timing.seconds,
+            share,
+            timing.requests,
+            timing.requests_per_second()
+        );
+    }
+    let _ = writeln!(text, "{:<17}  {:>9.2}", "TOTAL", total);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_per_phase() {
+        let statistics = Statistics::default();
+        statistics.response(Phase::Objects, StatusCode::OK, 10);
+        statistics.response(Phase::Head, StatusCode::OK, 41);
+        statistics.response(Phase::Objects, StatusCode::NOT_FOUND, 5);
+        statistics.retries(Phase::Objects, 2);
+        statistics.timeout(Phase::Objects);
+
+        let phases = statistics.phases();
+        assert_eq!(phases.len(), 2);
+        assert_eq!(phases[0].phase, "HEAD check");
+        let objects = &phases[1];
+        assert_eq!(objects.statuses, BTreeMap::from([(200, 1), (404, 1)]));
+        assert_eq!(objects.bytes, 15);
+        assert_eq!((objects.retries, objects.timeouts), (2, 1));
+
+        let durations = [
+            (Phase::Head, Duration::from_millis(500)),
+            (Phase::Objects, Duration::from_secs(2)),
+            (Phase::Checkout, Duration::from_secs(1)),
+        ];
+        let timings = timings(&durations, &phases);
+        assert_eq!(timings[1].requests, 2);
+        assert_eq!(timings[1].requests_per_second(), 1.0);
+        assert_eq!(timings[2].requests, 0);
+        assert!(timing_table(&timings).ends_with("TOTAL                   3.50\n"));
+    }
+}
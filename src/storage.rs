@@ -0,0 +1,55 @@
+use crate::report::Report;
+use color_eyre::Result;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The outcome of dumping a single target, as stored in the results database.
+pub struct Outcome<'a> {
+    pub report: &'a Report,
+    pub started_at: SystemTime,
+    pub duration: Duration,
+}
+
+/// Appends the outcome of a run to the SQLite database at path, creating the database if needed.
+pub fn record<P: AsRef<Path>>(path: P, outcome: &Outcome) -> Result<()> {
+    let connection = Connection::open(path)?;
+    connection.execute(
+        "CREATE TABLE IF NOT EXISTS targets (
+            id INTEGER PRIMARY KEY,
+            url TEXT NOT NULL,
+            started_at INTEGER NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            success INTEGER NOT NULL,
+            error TEXT,
+            refs INTEGER NOT NULL,
+            credentials INTEGER NOT NULL,
+            lfs_pointers INTEGER NOT NULL,
+            report TEXT NOT NULL
+        )",
+        (),
+    )?;
+
+    let report = outcome.report;
+    let started_at = outcome
+        .started_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    connection.execute(
+        "INSERT INTO targets (url, started_at, duration_ms, success, error, refs, credentials, lfs_pointers, report)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            report.url,
+            started_at,
+            outcome.duration.as_millis() as u64,
+            report.error.is_none(),
+            report.error,
+            report.refs.len(),
+            report.credentials.len(),
+            report.lfs_pointers.len(),
+            serde_json::to_string(report)?,
+        ],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use crate::{object::Signature, repository::Repository};
+use chrono::DateTime;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Someone who authored or committed in the repository.
+#[derive(Serialize)]
+pub struct Contributor {
+    pub name: String,
+    pub email: String,
+    /// Number of commits attributed to them.
+    pub commits: usize,
+    /// Unix timestamp of their earliest commit.
+    pub first: i64,
+    /// Unix timestamp of their latest commit.
+    pub last: i64,
+}
+
+/// Who worked on the repository and when, gathered from the recovered commits.
+#[derive(Serialize, Default)]
+pub struct Summary {
+    pub commits: usize,
+    /// Unix timestamp of the earliest commit.
+    pub first: Option<i64>,
+    /// Unix timestamp of the latest commit.
+    pub last: Option<i64>,
+    /// Authors, most commits first.
+    pub authors: Vec<Contributor>,
+    /// Committers, most commits first.
+    pub committers: Vec<Contributor>,
+}
+
+/// Counts a commit towards the contributor behind signature.
+fn tally(contributors: &mut HashMap<(String, String), Contributor>, signature: &Signature) {
+    let time = signature.time;
+    let contributor = contributors
+        .entry((signature.name.clone(), signature.email.clone()))
+        .or_insert(Contributor {
+            name: signature.name.clone(),
+            email: signature.email.clone(),
+            commits: 0,
+            first: time,
+            last: time,
+        });
+    contributor.commits += 1;
+    contributor.first = contributor.first.min(time);
+    contributor.last = contributor.last.max(time);
+}
+
+/// Sorts contributors by the number of commits, breaking ties by name.
+fn ranked(contributors: HashMap<(String, String), Contributor>) -> Vec<Contributor> {
+    let mut contributors: Vec<_> = contributors.into_values().collect();
+    contributors.sort_by(|a, b| b.commits.cmp(&a.commits).then(a.name.cmp(&b.name)));
+    contributors
+}
+
+/// Summarizes the authors, committers and dates of every commit reachable from a reference.
+pub fn summarize(repo: &Repository) -> Summary {
+    let mut summary = Summary::default();
+    let mut authors = HashMap::new();
+    let mut committers = HashMap::new();
+    for (_, commit) in repo.commits() {
+        tally(&mut authors, &commit.author);
+        tally(&mut committers, &commit.committer);
+        let time = commit.committer.time;
+        summary.commits += 1;
+        summary.first = Some(summary.first.map_or(time, |first| first.min(time)));
+        summary.last = Some(summary.last.map_or(time, |last| last.max(time)));
+    }
+    summary.authors = ranked(authors);
+    summary.committers = ranked(committers);
+    summary
+}
+
+/// Formats a Unix timestamp as a UTC date.
+fn date(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// Renders the summary as a plain text table of authors.
+pub fn table(summary: &Summary) -> String {
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "{} commits by {} authors between {} and {}",
+        summary.commits,
+        summary.authors.len(),
+        summary.first.map(date).unwrap_or_default(),
+        summary.last.map(date).unwrap_or_default(),
+    );
+    let _ = writeln!(
+        text,
+        "{:>7}  {:<10}  {:<10}  AUTHOR",
+        "COMMITS", "FIRST", "LAST"
+    );
+    for author in &summary.authors {
+        let _ = writeln!(
+            text,
+            "{:>7}  {:<10}  {:<10}  {} <{}>",
+            author.commits,
+            date(author.first),
+            date(author.last),
+            author.name,
+            author.email
+        );
+    }
+    text
+}
@@ -0,0 +1,129 @@
+use crate::{
+    args::{self, NetworkArgs},
+    throttle::Delay,
+};
+use log::warn;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use url::Url;
+
+/// A target of a scan, with the settings it overrides for its own requests.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Target {
+    pub url: Url,
+    /// Headers sent with every request to this target, replacing those of the same name.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub proxy: Option<Url>,
+    pub user_agent: Option<String>,
+    /// Maximum number of requests per second.
+    pub rate: Option<f64>,
+    /// Pause before each download, e.g. "200ms-800ms".
+    pub delay: Option<String>,
+}
+
+impl Target {
+    /// Returns a target using the settings shared by every target.
+    pub fn new(url: Url) -> Self {
+        Target {
+            url,
+            headers: BTreeMap::new(),
+            proxy: None,
+            user_agent: None,
+            rate: None,
+            delay: None,
+        }
+    }
+
+    /// Parses a line of an input file: either a URL, or a JSON object with the URL and the
+    /// settings to override, such as `{"url": "...", "headers": {"Authorization": "..."}}`.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        if !line.starts_with('{') {
+            return Url::parse(line).map(Target::new).map_err(|e| e.to_string());
+        }
+        let target: Target = serde_json::from_str(line).map_err(|e| e.to_string())?;
+        for (name, value) in &target.headers {
+            args::parse_header(&format!("{name}: {value}"))?;
+        }
+        if let Some(delay) = &target.delay {
+            Delay::parse(delay)?;
+        }
+        if target
+            .rate
+            .is_some_and(|rate| !(rate > 0.0 && rate.is_finite()))
+        {
+            return Err("the rate must be a positive number of requests per second".to_string());
+        }
+        Ok(target)
+    }
+
+    /// Returns the network settings for this target: the shared ones with its overrides applied.
+    pub fn network(&self, shared: &NetworkArgs) -> NetworkArgs {
+        let mut network = shared.clone();
+        // Later headers replace earlier ones of the same name.
+        network.headers.extend(
+            self.headers
+                .iter()
+                .map(|(name, value)| format!("{name}: {value}")),
+        );
+        network.proxy = self.proxy.clone().or(network.proxy);
+        network.user_agent = self.user_agent.clone().or(network.user_agent);
+        network.rate = self.rate.or(network.rate);
+        // The delay was validated when parsing the target.
+        if let Some(delay) = self.delay.as_deref().and_then(|d| Delay::parse(d).ok()) {
+            network.delay = Some(delay);
+        }
+        network
+    }
+}
+
+/// Parses the targets of an input file, one per line, skipping blank lines, comments starting
+/// with '#' and invalid targets.
+pub fn parse(text: &str) -> Vec<Target> {
+    let mut targets = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match Target::parse(line) {
+            Ok(target) => targets.push(target),
+            Err(e) => warn!("Skipping invalid target {line}: {e}"),
+        }
+    }
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{Cli, Command};
+    use clap::Parser;
+
+    #[test]
+    fn overrides_settings_per_target() {
+        let targets = parse(concat!(
+            "# staging needs a token\n",
+            "http://a.example.com/.git/\n",
+            "\n",
+            r#"{"url": "http://b.example.com/.git/", "headers": {"Authorization": "Bearer t"}, "proxy": "http://127.0.0.1:8080", "rate": 2}"#,
+            "\n",
+            r#"{"url": "http://c.example.com/.git/", "delay": "soon"}"#,
+            "\n",
+            r#"{"url": "http://d.example.com/.git/", "retries": 9}"#,
+            "\n",
+        ));
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0], Target::new(targets[0].url.clone()));
+
+        let cli = Cli::try_parse_from(["opendotgit", "scan", "out", "-H", "X-Team: red"]).unwrap();
+        let Some(Command::Scan(args)) = cli.command else {
+            panic!("expected the scan command");
+        };
+        let network = targets[1].network(&args.options.network);
+        assert_eq!(network.headers, ["X-Team: red", "Authorization: Bearer t"]);
+        assert_eq!(network.proxy.unwrap().as_str(), "http://127.0.0.1:8080/");
+        assert_eq!(network.rate, Some(2.0));
+        assert_eq!(targets[0].network(&args.options.network).rate, None);
+    }
+}
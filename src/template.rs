@@ -0,0 +1,83 @@
+/// The placeholder of a path template that stands for the path of a file of the repository.
+const PLACEHOLDER: &str = "%s";
+
+/// How the path of a file of the repository maps to the path it is requested at, for targets that
+/// serve the .git directory under another path or only with a suffix, such as "static/%s" or
+/// "%s.txt".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathTemplate {
+    prefix: String,
+    suffix: String,
+}
+
+impl PathTemplate {
+    /// Parses a template holding the placeholder `%s` exactly once.
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        match arg.split_once(PLACEHOLDER) {
+            Some((prefix, suffix)) if !suffix.contains(PLACEHOLDER) => Ok(PathTemplate {
+                prefix: prefix.trim_start_matches('/').to_string(),
+                suffix: suffix.to_string(),
+            }),
+            _ => Err(format!(
+                "expected a template holding {PLACEHOLDER} once, such as \"static/{PLACEHOLDER}\", got {arg:?}"
+            )),
+        }
+    }
+
+    /// Returns the path href is requested at, relative to the target. The query of href is kept
+    /// after the suffix.
+    pub fn apply(&self, href: &str) -> String {
+        let (path, query) = match href.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (href, None),
+        };
+        let mut applied = format!("{}{path}{}", self.prefix, self.suffix);
+        if let Some(query) = query {
+            applied.push('?');
+            applied.push_str(query);
+        }
+        applied
+    }
+
+    /// Returns the href a path requested relative to the target stands for, the inverse of
+    /// [`PathTemplate::apply`], or None if the template does not produce it.
+    pub fn strip(&self, requested: &str) -> Option<String> {
+        let (path, query) = match requested.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (requested, None),
+        };
+        let path = path
+            .strip_prefix(self.prefix.as_str())?
+            .strip_suffix(self.suffix.as_str())?;
+        Some(match query {
+            Some(query) => format!("{path}?{query}"),
+            None => path.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_paths() {
+        let template = PathTemplate::parse("/static/%s").unwrap();
+        assert_eq!(template.apply(".git/HEAD"), "static/.git/HEAD");
+        assert_eq!(
+            template.strip("static/.git/HEAD").as_deref(),
+            Some(".git/HEAD")
+        );
+        assert_eq!(template.strip(".git/HEAD"), None);
+
+        let template = PathTemplate::parse("%s.txt").unwrap();
+        assert_eq!(template.apply(".git/?C=M"), ".git/.txt?C=M");
+        assert_eq!(
+            template.strip(".git/.txt?C=M").as_deref(),
+            Some(".git/?C=M")
+        );
+
+        assert!(PathTemplate::parse("static/").is_err());
+        assert!(PathTemplate::parse("%s/%s").is_err());
+    }
+}
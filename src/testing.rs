@@ -0,0 +1,293 @@
+//! Fixtures and a minimal HTTP server for end-to-end tests. Fixtures are built with libgit2, so
+//! the tests need the `git2` feature.
+
+use crate::{
+    args::{Cli, Command, NetworkArgs},
+    download::Downloader,
+};
+use clap::Parser;
+use std::path::{Component, Path, PathBuf};
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, MutexGuard};
+use url::Url;
+
+/// The files of the last commit of the fixture repository.
+pub const FILES: &[(&str, &str)] = &[
+    ("README.md", "# fixture\n\nServed by the test server.\n"),
+    ("src/main.rs", "fn main() {\n    println!(\"hello\");\n}\n"),
+];
+
+/// Sensitive files placed next to the .git directory of the fixture.
+pub const SIBLINGS: &[(&str, &str)] = &[
+    (".env", "DB_PASSWORD=hunter2\n"),
+    ("backup/db.sql", "INSERT INTO users VALUES ('admin');\n"),
+];
+
+/// Dumps change the current directory, which is shared by every test in the process.
+static CURRENT_DIR: Mutex<()> = Mutex::const_new(());
+
+/// Serializes the tests that change the current directory.
+pub async fn lock_current_dir() -> MutexGuard<'static, ()> {
+    CURRENT_DIR.lock().await
+}
+
+/// Creates a repository in dir with two commits on master, the last one containing [`FILES`],
+/// and places the untracked [`SIBLINGS`] and an index.html next to it.
+pub fn fixture(dir: &Path) {
+    let repo = git2::Repository::init(dir).unwrap();
+    let signature = git2::Signature::now("Fixture", "fixture@example.com").unwrap();
+    let mut parent: Option<git2::Oid> = None;
+    for (message, files) in [("Initial commit", &FILES[..1]), ("Add main", FILES)] {
+        let mut index = repo.index().unwrap();
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            index.add_path(path.strip_prefix(dir).unwrap()).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parents: Vec<_> = parent
+            .iter()
+            .map(|id| repo.find_commit(*id).unwrap())
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        parent = Some(
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap(),
+        );
+    }
+    for (path, content) in SIBLINGS.iter().chain([&("index.html", "<html></html>\n")]) {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+}
+
+/// Writes a directory index linking to every entry of dir, like a web server with autoindex on.
+fn listing(dir: &Path) -> String {
+    let mut html = String::from("<html><body>\n");
+    let mut entries: Vec<_> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let mut name = entry.file_name().to_string_lossy().to_string();
+        if entry.path().is_dir() {
+            name.push('/');
+        }
+        html.push_str(&format!("<a href=\"{name}\">{name}</a>\n"));
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// Decodes the percent escapes of a segment of a request path into a file name.
+fn decode(segment: &str) -> std::ffi::OsString {
+    let bytes = segment.as_bytes();
+    let mut name = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                name.push(byte);
+                i += 3;
+            }
+            None => {
+                name.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    #[cfg(unix)]
+    return std::os::unix::ffi::OsStringExt::from_vec(name);
+    #[cfg(not(unix))]
+    return String::from_utf8_lossy(&name).into_owned().into();
+}
+
+/// Answers a single request for a file below root.
+async fn respond(mut stream: TcpStream, root: &Path, listed: fn(&Path) -> bool) {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => head.extend_from_slice(&buffer[..n]),
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let target = head.split(' ').nth(1).unwrap_or("/");
+    let target = target.split('?').next().unwrap_or_default();
+
+    // Duplicate slashes are merged and percent escapes decoded like most web servers do.
+    let relative: PathBuf = target
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(decode)
+        .collect();
+    let path = root.join(&relative);
+    let escapes = relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)));
+
+    // Only open ended ranges are supported, as sent when resuming a download.
+    let range_start = head.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        let start = value.trim().strip_prefix("bytes=")?.strip_suffix('-')?;
+        name.eq_ignore_ascii_case("range")
+            .then(|| start.parse::<usize>().ok())?
+    });
+    let mut content_range = None;
+
+    let (status, content_type, location, body) = if escapes || !path.exists() {
+        ("404 Not Found", "text/plain", None, b"not found".to_vec())
+    } else if path.is_dir() {
+        match (listed(&relative), target.ends_with('/')) {
+            (false, _) => ("404 Not Found", "text/plain", None, b"not found".to_vec()),
+            // Like Apache, with a page linking to the new location of the directory.
+            (true, false) => (
+                "301 Moved Permanently",
+                "text/html",
+                Some(format!("{target}/")),
+                format!("<html><body><a href=\"{target}/\">Moved Permanently</a></body></html>\n")
+                    .into_bytes(),
+            ),
+            (true, true) => ("200 OK", "text/html", None, listing(&path).into_bytes()),
+        }
+    } else {
+        let body = std::fs::read(&path).unwrap_or_default();
+        match range_start {
+            Some(start) if start < body.len() => {
+                content_range = Some(format!("bytes {start}-{}/{}", body.len() - 1, body.len()));
+                let body = body[start..].to_vec();
+                (
+                    "206 Partial Content",
+                    "application/octet-stream",
+                    None,
+                    body,
+                )
+            }
+            _ => ("200 OK", "application/octet-stream", None, body),
+        }
+    };
+
+    let mut response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        body.len()
+    );
+    if let Some(location) = location {
+        response.push_str(&format!("Location: {location}\r\n"));
+    }
+    if let Some(content_range) = content_range {
+        response.push_str(&format!("Content-Range: {content_range}\r\n"));
+    }
+    response.push_str("\r\n");
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.write_all(&body).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Serves the files below root over HTTP until the runtime shuts down and returns the base URL.
+/// Directories are listed when listing is true and answer with 404 otherwise.
+pub async fn serve(root: &Path, listing: bool) -> Url {
+    serve_listing(root, if listing { |_| true } else { |_| false }).await
+}
+
+/// Like [`serve`], listing only the directories whose path relative to root listed accepts.
+pub async fn serve_listing(root: &Path, listed: fn(&Path) -> bool) -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let root = root.to_path_buf();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let root = root.clone();
+            tokio::spawn(async move { respond(stream, &root, listed).await });
+        }
+    });
+    format!("http://{address}/").parse().unwrap()
+}
+
+/// Answers every request with body, announcing its full length but closing the connection after
+/// its first sent bytes, like a download cut short. Returns the base URL.
+pub async fn serve_truncated(body: Vec<u8>, sent: usize) -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buffer = [0u8; 1024];
+            let _ = stream.read(&mut buffer).await;
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(&body[..sent]).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+    format!("http://{address}/").parse().unwrap()
+}
+
+/// A site served from a temporary directory and a downloader for it, writing to another temporary
+/// directory made the current one until this is dropped.
+pub struct Served {
+    pub network: NetworkArgs,
+    pub download: Downloader,
+    current_dir: PathBuf,
+    _site: TempDir,
+    _output: TempDir,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Drop for Served {
+    fn drop(&mut self) {
+        std::env::set_current_dir(&self.current_dir).unwrap();
+    }
+}
+
+/// Serves files, pairs of paths and contents, without listing directories and creates a
+/// downloader for them with the network options of `opendotgit check` and extra_args.
+pub async fn serve_files(files: &[(&str, &[u8])], extra_args: &[&str]) -> Served {
+    let guard = lock_current_dir().await;
+    let site = tempfile::tempdir().unwrap();
+    for (path, content) in files {
+        let path = site.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    let url = serve(site.path(), false).await;
+
+    let output = tempfile::tempdir().unwrap();
+    let current_dir = std::env::current_dir().unwrap();
+    std::env::set_current_dir(output.path()).unwrap();
+    let mut cli_args = vec!["opendotgit", "check"];
+    cli_args.extend(extra_args);
+    cli_args.push(url.as_str());
+    let cli = Cli::try_parse_from(cli_args).unwrap();
+    let Some(Command::Check(args)) = cli.command else {
+        panic!("expected the check command");
+    };
+    Served {
+        download: Downloader::new(&url, &args.network),
+        network: args.network,
+        current_dir,
+        _site: site,
+        _output: output,
+        _guard: guard,
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    middleware::{Middleware, Next},
+    transport::{Request, Response},
+};
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_retry::strategy::jitter;
+
+/// Spaces requests out evenly so that no more than a given number are made per second.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: f64) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / per_second),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the next request may be made.
+    pub async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            sleep_until(*next).await;
+        }
+        *next = (*next).max(now) + self.interval;
+    }
+}
+
+impl Middleware for RateLimiter {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            self.wait().await;
+            next.run(request).await
+        })
+    }
+}
+
+/// A fixed pause, or one picked at random from a range, taken before each download.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Delay {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+/// Parses a duration with a unit, such as "200ms" or "1.5s".
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let arg = arg.trim();
+    let (number, scale) = if let Some(number) = arg.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = arg.strip_suffix('s') {
+        (number, 1.0)
+    } else {
+        return Err(format!("expected a unit of ms or s in {arg:?}"));
+    };
+    match number.trim().parse::<f64>() {
+        Ok(value) if value >= 0.0 && value.is_finite() => {
+            Ok(Duration::from_secs_f64(value * scale))
+        }
+        _ => Err(format!("expected a duration such as 200ms, got {arg:?}")),
+    }
+}
+
+impl Delay {
+    /// Parses a fixed delay such as "200ms" or a range such as "200ms-800ms".
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let (min, max) = match arg.split_once('-') {
+            Some((min, max)) => (parse_duration(min)?, parse_duration(max)?),
+            None => {
+                let delay = parse_duration(arg)?;
+                (delay, delay)
+            }
+        };
+        if min > max {
+            return Err(format!("the range {arg:?} ends before it starts"));
+        }
+        Ok(Delay { min, max })
+    }
+
+    /// Returns how long to wait before the next request.
+    pub fn next(&self) -> Duration {
+        self.min + jitter(self.max - self.min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_delays() {
+        let fixed = Delay::parse("200ms").unwrap();
+        assert_eq!(fixed.next(), Duration::from_millis(200));
+        let range = Delay::parse("200ms-1.5s").unwrap();
+        assert_eq!(
+            (range.min, range.max),
+            (Duration::from_millis(200), Duration::from_millis(1500))
+        );
+        assert!((range.min..=range.max).contains(&range.next()));
+        assert!(Delay::parse("200").is_err());
+        assert!(Delay::parse("1s-200ms").is_err());
+    }
+}
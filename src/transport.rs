@@ -0,0 +1,259 @@
+use crate::{
+    args::NetworkArgs, hosts::PinnedResolver, listing::STREAMED_LEN, response::MAX_CONTENT_LENGTH,
+};
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use log::error;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, USER_AGENT},
+    redirect::Policy,
+    tls::Version,
+    Client, Method, Proxy, StatusCode,
+};
+use std::{path::PathBuf, sync::Arc};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::mpsc};
+use url::Url;
+
+/// A request made through a [`Transport`].
+#[derive(Clone)]
+pub struct Request {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+    /// File the body of a successful response is written to as it arrives, appended to for
+    /// partial content, so that an interrupted download can be resumed.
+    pub spill: Option<PathBuf>,
+    /// Where the body of a successful response longer than [`STREAMED_LEN`] is sent as it
+    /// arrives instead of being held in the response, whose body is then left empty.
+    pub stream: Option<mpsc::Sender<Chunk>>,
+}
+
+/// A piece of a body sent through [`Request::stream`].
+#[derive(Debug)]
+pub enum Chunk {
+    /// Another attempt at the request started sending its body, the chunks before are void.
+    Restart,
+    Data(Vec<u8>),
+}
+
+impl Request {
+    pub fn get(url: Url) -> Self {
+        Request {
+            method: Method::GET,
+            url,
+            headers: HeaderMap::new(),
+            body: None,
+            spill: None,
+            stream: None,
+        }
+    }
+
+    pub fn post(url: Url, body: Vec<u8>) -> Self {
+        Request {
+            method: Method::POST,
+            body: Some(body),
+            ..Request::get(url)
+        }
+    }
+
+    /// Adds a header to the request, ignoring names and values that are not valid in HTTP.
+    pub fn header<K: AsRef<str>, V: AsRef<str>>(mut self, name: K, value: V) -> Self {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_ref().as_bytes()),
+            HeaderValue::from_str(value.as_ref()),
+        ) {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    /// Writes the body to path as it arrives, see [`Request::spill`].
+    pub fn spill(mut self, path: PathBuf) -> Self {
+        self.spill = Some(path);
+        self
+    }
+
+    /// Sends a long body to stream as it arrives, see [`Request::stream`].
+    pub fn stream(mut self, stream: mpsc::Sender<Chunk>) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+}
+
+/// A response whose body has been read in full.
+pub struct Response {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: StatusCode, headers: HeaderMap, body: Vec<u8>) -> Self {
+        Response {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the body as text, replacing invalid UTF-8.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Sends requests on behalf of the [`Downloader`](crate::download::Downloader).
+///
+/// Retries, rate limiting and timeouts are applied by the downloader, so a transport only has to
+/// deliver a single request. Redirects must not be followed, the downloader treats them as
+/// directories. Transports that cannot stream a body may ignore [`Request::spill`], downloads
+/// through them are then not resumed, and [`Request::stream`], the body is then in the response.
+pub trait Transport: Send + Sync {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response>>;
+}
+
+/// The default transport, making requests over HTTP(S) with reqwest.
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+/// Returns the headers configured by network to send with every request: those of the evasion
+/// profile in the browser's order, then the user agent and the headers given explicitly.
+pub fn default_headers(network: &NetworkArgs) -> HeaderMap {
+    let mut headers = network
+        .evasion
+        .map(|profile| profile.header_map())
+        .unwrap_or_default();
+    if let Some(user_agent) = network
+        .user_agent
+        .as_deref()
+        .and_then(|user_agent| HeaderValue::from_str(user_agent).ok())
+    {
+        headers.insert(USER_AGENT, user_agent);
+    }
+    for header in &network.headers {
+        // Headers are validated while parsing the arguments.
+        if let Some((name, value)) = header.split_once(':') {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.trim().as_bytes()),
+                HeaderValue::from_str(value.trim()),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+    }
+    headers
+}
+
+impl ReqwestTransport {
+    /// Creates a client with the headers, user agent and proxy configured by network.
+    pub fn new(network: &NetworkArgs) -> Self {
+        let mut builder = Client::builder()
+            .redirect(Policy::none())
+            .default_headers(default_headers(network));
+        if network.evasion.is_some() {
+            // Browsers capitalize HTTP/1.1 header names and no longer offer TLS 1.0 or 1.1.
+            builder = builder
+                .http1_title_case_headers()
+                .min_tls_version(Version::TLS_1_2);
+        }
+        match &network.proxy {
+            Some(proxy) => match Proxy::all(proxy.clone()) {
+                Ok(proxy) => builder = builder.proxy(proxy),
+                Err(e) => error!("Ignoring proxy {proxy}: {e}"),
+            },
+            // Connections only go to the addresses the scope allows, however a domain resolves
+            // by the time they are made.
+            None if !network.scope.is_empty() => {
+                builder =
+                    builder.dns_resolver(Arc::new(PinnedResolver::new(network.scope.clone())));
+            }
+            None => {}
+        }
+        ReqwestTransport {
+            client: builder.build().unwrap(),
+        }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move {
+            let mut builder = self
+                .client
+                .request(request.method, request.url)
+                .headers(request.headers);
+            if let Some(body) = request.body {
+                builder = builder.body(body);
+            }
+            let mut response = builder.send().await?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            // A body announced beyond any file of the repository is left unread rather than held
+            // in memory, the Content-Length left for the caller to reject the response by.
+            let body = if response
+                .content_length()
+                .is_some_and(|length| length > MAX_CONTENT_LENGTH)
+            {
+                Vec::new()
+            } else if let Some(spill) = request.spill.filter(|_| {
+                matches!(status, StatusCode::OK | StatusCode::PARTIAL_CONTENT)
+                    && !headers.contains_key(CONTENT_ENCODING)
+            }) {
+                if let Some(parent) = spill.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                let partial = status == StatusCode::PARTIAL_CONTENT;
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(partial)
+                    .truncate(!partial)
+                    .open(&spill)
+                    .await?;
+                let mut body = Vec::new();
+                // Flushed chunk by chunk, whatever arrived is on disk when the connection drops.
+                while let Some(chunk) = response.chunk().await? {
+                    file.write_all(&chunk).await?;
+                    file.flush().await?;
+                    body.extend_from_slice(&chunk);
+                }
+                body
+            } else if let Some(stream) = request.stream.filter(|_| status == StatusCode::OK) {
+                // Short bodies are held like any other, so that the middlewares still see them.
+                let mut body = Vec::new();
+                let mut streaming = false;
+                while let Some(chunk) = response.chunk().await? {
+                    if !streaming {
+                        body.extend_from_slice(&chunk);
+                        if body.len() <= STREAMED_LEN {
+                            continue;
+                        }
+                        streaming = true;
+                        stream.send(Chunk::Restart).await?;
+                        stream.send(Chunk::Data(std::mem::take(&mut body))).await?;
+                    } else {
+                        stream.send(Chunk::Data(chunk.to_vec())).await?;
+                    }
+                }
+                body
+            } else {
+                response.bytes().await?.to_vec()
+            };
+            Ok(Response::new(status, headers, body))
+        })
+    }
+}
@@ -0,0 +1,179 @@
+use crate::progress::{Phase, Progress};
+use color_eyre::Result;
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        cursor::{Hide, Show},
+        execute,
+        terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, Gauge, List, Paragraph},
+    Frame, Terminal,
+};
+use std::io::Stdout;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often the dashboard is redrawn.
+const TICK: Duration = Duration::from_millis(250);
+
+/// A dashboard drawn on the alternate screen until it is stopped or dropped, so that the
+/// terminal is restored even when the command panics.
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    /// The thread drawing the dashboard, taken once it is stopped.
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Formats a number of bytes with a binary unit.
+pub fn human_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn draw(frame: &mut Frame, progress: &Progress, throughput: f64) {
+    let [phases_area, gauge_area, throughput_area, messages_area] = Layout::vertical([
+        Constraint::Length(Phase::ALL.len() as u16 + 2),
+        Constraint::Length(3),
+        Constraint::Length(3),
+        Constraint::Min(3),
+    ])
+    .areas(frame.size());
+
+    let current = progress.phase();
+    let phases: Vec<Line> = Phase::ALL
+        .iter()
+        .map(|&phase| {
+            let (marker, color) = if phase < current {
+                ("✔", Color::Green)
+            } else if phase == current {
+                ("▶", Color::Yellow)
+            } else {
+                (" ", Color::DarkGray)
+            };
+            Line::styled(format!("{marker} {}", phase.name()), Style::new().fg(color))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(phases).block(Block::new().borders(Borders::ALL).title("Phases")),
+        phases_area,
+    );
+
+    let (finished, queued) = progress.counts();
+    let ratio = if queued == 0 {
+        0.0
+    } else {
+        finished as f64 / queued as f64
+    };
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::new().borders(Borders::ALL).title(current.name()))
+            .gauge_style(Style::new().fg(Color::Cyan))
+            .ratio(ratio.min(1.0))
+            .label(format!("{finished}/{queued} requests")),
+        gauge_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "{}/s, {} received",
+            human_bytes(throughput),
+            human_bytes(progress.bytes() as f64)
+        ))
+        .block(Block::new().borders(Borders::ALL).title("Throughput")),
+        throughput_area,
+    );
+
+    // Only the most recent messages that fit inside the borders are shown, so the pane scrolls.
+    let visible = messages_area.height.saturating_sub(2) as usize;
+    let messages: Vec<Line> = progress
+        .recent_messages(visible)
+        .into_iter()
+        .map(Line::from)
+        .collect();
+    frame.render_widget(
+        List::new(messages).block(Block::new().borders(Borders::ALL).title("Errors")),
+        messages_area,
+    );
+}
+
+fn render_loop(
+    mut terminal: Terminal<CrosstermBackend<Stdout>>,
+    progress: Arc<Progress>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut last_bytes = progress.bytes();
+    let mut last_tick = Instant::now();
+    let mut throughput = 0.0;
+    while !stop.load(Ordering::Relaxed) {
+        let bytes = progress.bytes();
+        let elapsed = last_tick.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            throughput = (bytes - last_bytes) as f64 / elapsed;
+        }
+        last_bytes = bytes;
+        last_tick = Instant::now();
+
+        if terminal
+            .draw(|frame| draw(frame, &progress, throughput))
+            .is_err()
+        {
+            break;
+        }
+        std::thread::sleep(TICK);
+    }
+}
+
+/// Switches to the alternate screen and redraws the dashboard until stopped.
+/// Raw mode is left off so that Ctrl-C still interrupts the dump.
+pub fn start(progress: Arc<Progress>) -> Result<Dashboard> {
+    let mut stdout = std::io::stdout();
+    execute!(stdout, EnterAlternateScreen, Hide)?;
+    let terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handle = {
+        let stop = stop.clone();
+        std::thread::spawn(move || render_loop(terminal, progress, stop))
+    };
+    Ok(Dashboard {
+        stop,
+        handle: Some(handle),
+    })
+}
+
+impl Dashboard {
+    /// Stops redrawing and restores the terminal.
+    pub fn stop(mut self) -> Result<()> {
+        self.restore()
+    }
+
+    fn restore(&mut self) -> Result<()> {
+        let Some(handle) = self.handle.take() else {
+            return Ok(());
+        };
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = handle.join();
+        execute!(std::io::stdout(), LeaveAlternateScreen, Show)?;
+        Ok(())
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
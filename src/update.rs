@@ -0,0 +1,215 @@
+use crate::args::SelfUpdateArgs;
+use color_eyre::{
+    eyre::{bail, eyre, Result, WrapErr},
+    Section,
+};
+use log::info;
+use minisign_verify::{PublicKey, Signature};
+use reqwest::header::{ACCEPT, USER_AGENT};
+use serde::Deserialize;
+use std::path::Path;
+
+/// The GitHub API endpoint describing the latest release.
+const LATEST_RELEASE: &str = "https://api.github.com/repos/lavafroth/opendotgit/releases/latest";
+
+/// The minisign public key the release binaries are signed with, embedded by release builds.
+const PUBLIC_KEY: Option<&str> = option_env!("OPENDOTGIT_UPDATE_KEY");
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Returns the name of the release binary for this platform, such as "opendotgit-x86_64-linux".
+fn asset_name() -> String {
+    format!(
+        "opendotgit-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// Parses a version such as "v0.4.3" into its numeric components.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Returns true if the release is newer than the running version.
+fn is_newer(release: &str, current: &str) -> bool {
+    match (parse_version(release), parse_version(current)) {
+        (Some(release), Some(current)) => release > current,
+        _ => false,
+    }
+}
+
+/// Returns the value of a `key:value` field of the trusted comment of a signature, such as the
+/// `file` minisign records by default.
+fn comment_field<'a>(comment: &'a str, key: &str) -> Option<&'a str> {
+    comment
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix(key)?.strip_prefix(':'))
+}
+
+/// Checks the signature of a downloaded binary against the minisign public key, and that its
+/// trusted comment names the asset and the release tag, so that a validly signed binary cannot
+/// be served for another platform or an older release.
+fn verify(public_key: &str, binary: &[u8], signature: &str, name: &str, tag: &str) -> Result<()> {
+    let public_key = PublicKey::from_base64(public_key.trim())
+        .or_else(|_| PublicKey::decode(public_key))
+        .map_err(|e| eyre!("Invalid public key: {e}"))?;
+    let signature = Signature::decode(signature).map_err(|e| eyre!("Invalid signature: {e}"))?;
+    public_key
+        .verify(binary, &signature, false)
+        .map_err(|e| eyre!("The signature does not match the binary: {e}"))?;
+    let comment = signature.trusted_comment();
+    if comment_field(comment, "file") != Some(name) {
+        bail!("The signature is for another file than {name}: {comment}");
+    }
+    let version = comment_field(comment, "version").map(|version| version.trim_start_matches('v'));
+    if version != Some(tag.trim_start_matches('v')) {
+        bail!("The signature is for another release than {tag}: {comment}");
+    }
+    Ok(())
+}
+
+async fn get(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .wrap_err(format!("Failed to request {url}"))?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("{url} responded with status code {status}");
+    }
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Replaces the executable at path with binary, keeping its permissions. The new binary is
+/// written next to it first so that a failed write never leaves a broken executable behind.
+fn replace(path: &Path, binary: &[u8]) -> Result<()> {
+    let staged = path.with_extension("new");
+    std::fs::write(&staged, binary).wrap_err(format!("Failed to write {}", staged.display()))?;
+    std::fs::set_permissions(&staged, std::fs::metadata(path)?.permissions())?;
+    // Windows refuses to overwrite a running executable but lets it be renamed.
+    if cfg!(windows) {
+        let old = path.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(path, &old)?;
+    }
+    std::fs::rename(&staged, path).wrap_err(format!("Failed to replace {}", path.display()))
+}
+
+/// Replaces the running binary with the one from the latest release, once its signature checks out.
+pub async fn run(args: SelfUpdateArgs) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let client = reqwest::Client::builder()
+        .default_headers(
+            [
+                (USER_AGENT, format!("opendotgit/{current}").parse()?),
+                (ACCEPT, "application/vnd.github+json".parse()?),
+            ]
+            .into_iter()
+            .collect(),
+        )
+        .build()?;
+    let release: Release = serde_json::from_slice(&get(&client, LATEST_RELEASE).await?)
+        .wrap_err("Failed to parse the latest release")?;
+    if !is_newer(&release.tag_name, current) {
+        info!(
+            "opendotgit {current} is up to date, the latest release is {}",
+            release.tag_name
+        );
+        return Ok(());
+    }
+    info!(
+        "opendotgit {} is available, running {current}",
+        release.tag_name
+    );
+    if args.check {
+        return Ok(());
+    }
+
+    let public_key = args.public_key.as_deref().or(PUBLIC_KEY).ok_or_else(|| {
+        eyre!("This build has no public key to verify the release with")
+            .suggestion("Pass the minisign public key of the releases with --public-key")
+    })?;
+    let name = asset_name();
+    let find = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .map(|asset| asset.browser_download_url.as_str())
+            .ok_or_else(|| eyre!("Release {} has no {name}", release.tag_name))
+    };
+    let binary_url = find(&name)?;
+    let signature_url = find(&format!("{name}.minisig"))?;
+
+    info!("Downloading {name}");
+    let binary = get(&client, binary_url).await?;
+    let signature = String::from_utf8(get(&client, signature_url).await?)
+        .map_err(|_| eyre!("The signature of {name} is not text"))?;
+    verify(public_key, &binary, &signature, &name, &release.tag_name)?;
+
+    let path = std::env::current_exe()?;
+    replace(&path, &binary)?;
+    info!("Updated {} to {}", path.display(), release.tag_name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions() {
+        assert!(is_newer("v0.5.0", "0.4.3"));
+        assert!(is_newer("0.4.10", "0.4.3"));
+        assert!(!is_newer("v0.4.3", "0.4.3"));
+        assert!(!is_newer("nightly", "0.4.3"));
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        let public_key = "RWQBAgMEBQYHCAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4";
+        let signature = concat!(
+            "untrusted comment: signature from minisign secret key\n",
+            "RUQBAgMEBQYHCCh+tSKPb9Irwb0I9AxwlupJlA45uiMoxNEFr8di2o6rBDeO38O0ZJsQ41iNV9uDzmMzH6AJrZkYdq6wloOrJAI=\n",
+            "trusted comment: timestamp:1700000000\tfile:opendotgit-x86_64-linux\tversion:v0.5.0\n",
+            "z/Y7ZRcwE39WAZFzoscLqQRxTNZbo1h4sDHzphp1ayqfEw/c7EqSwdtvwfoTo8OFSVbQK7Udi1ivHq9pM5VEDw==\n",
+        );
+        let binary = b"opendotgit release binary\n";
+        let name = "opendotgit-x86_64-linux";
+        assert!(verify(public_key, binary, signature, name, "v0.5.0").is_ok());
+        assert!(verify(
+            public_key,
+            b"tampered release binary\n",
+            signature,
+            name,
+            "v0.5.0"
+        )
+        .is_err());
+        assert!(verify(
+            public_key,
+            binary,
+            signature,
+            "opendotgit-aarch64-macos",
+            "v0.5.0"
+        )
+        .is_err());
+        assert!(verify(public_key, binary, signature, name, "v0.6.0").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{encoding, object::Object, pack};
+use color_eyre::{eyre::bail, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+lazy_static! {
+    /// A loose reference file holds a single object hash or a symbolic reference.
+    static ref LOOSE_REF: Regex = Regex::new(r"^(ref: \S+|[0-9a-f]{40}|[0-9a-f]{64})$").unwrap();
+
+    /// A reflog line starts with the old and new object hashes.
+    static ref REFLOG_LINE: Regex = Regex::new(r"^([0-9a-f]{40}|[0-9a-f]{64}) ([0-9a-f]{40}|[0-9a-f]{64}) ").unwrap();
+
+    /// A packed-refs line is a comment, a hash with a reference name or a peeled tag hash.
+    static ref PACKED_REFS_LINE: Regex = Regex::new(r"^(#.*|([0-9a-f]{40}|[0-9a-f]{64}) \S+|\^([0-9a-f]{40}|[0-9a-f]{64}))$").unwrap();
+
+    /// Lines in info/refs and FETCH_HEAD start with a hash followed by a tab.
+    static ref TAB_SEPARATED_LINE: Regex = Regex::new(r"^([0-9a-f]{40}|[0-9a-f]{64})\t").unwrap();
+}
+
+/// Returns true if every non-empty line of the text matches the expression.
+fn all_lines_match(expression: &Regex, text: &str) -> bool {
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .all(|line| expression.is_match(line))
+}
+
+/// Checks that the content of a reference-bearing file at href has the format git writes for it,
+/// so that soft-404 pages served with 200 OK never end up in `.git/refs` or the object discovery.
+/// Files with no known format are always accepted.
+pub fn ref_file(href: &str, body: &[u8]) -> bool {
+    let segments: Vec<&str> = href.split('/').filter(|s| !s.is_empty()).collect();
+    let path = match segments.iter().position(|&segment| segment == ".git") {
+        Some(index) => &segments[index + 1..],
+        None => return true,
+    };
+
+    let text = String::from_utf8_lossy(body);
+    match path {
+        ["HEAD" | "ORIG_HEAD"] | ["refs", ..] => LOOSE_REF.is_match(text.trim()),
+        ["logs", ..] => all_lines_match(&REFLOG_LINE, &text),
+        ["packed-refs"] => all_lines_match(&PACKED_REFS_LINE, &text),
+        ["info", "refs"] | ["FETCH_HEAD"] => all_lines_match(&TAB_SEPARATED_LINE, &text),
+        ["config"] => !text.trim_start().starts_with('<'),
+        _ => true,
+    }
+}
+
+/// Checks that a loose object at href inflates and hashes to the ID in its path, so corrupted or
+/// substituted objects are never written. Other files are always accepted.
+pub fn loose_object(href: &str, body: &[u8]) -> Result<()> {
+    let segments: Vec<&str> = href.split('/').filter(|s| !s.is_empty()).collect();
+    match segments.as_slice() {
+        [.., "objects", prefix, rest]
+            if prefix.len() == 2
+                && matches!(rest.len(), 38 | 62)
+                && prefix
+                    .bytes()
+                    .chain(rest.bytes())
+                    .all(|b| b.is_ascii_hexdigit()) =>
+        {
+            if !encoding::is_zlib(body) {
+                bail!("it does not start with a zlib header");
+            }
+            Object::verify(body, &format!("{prefix}{rest}"))?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Signatures the binary files of a .git directory start with, by the end of their path.
+const SIGNATURES: &[(&str, &[u8; 4])] = &[
+    (".pack", b"PACK"),
+    (".rev", b"RIDX"),
+    (".bitmap", b"BITM"),
+    ("/multi-pack-index", b"MIDX"),
+    ("/commit-graph", b"CGPH"),
+    (".graph", b"CGPH"),
+    (".git/index", b"DIRC"),
+];
+
+/// Checks that a binary file at href starts with the signature git writes for it, and that a pack
+/// index parses, since version 1 indexes have no signature. Text files and loose objects are left
+/// to [`ref_file`] and [`loose_object`], other files are always accepted.
+pub fn binary_file(href: &str, body: &[u8]) -> Result<()> {
+    let path = href.split(['?', '#']).next().unwrap_or(href);
+    if path.ends_with(".idx") {
+        pack::index_entries(body)?;
+        return Ok(());
+    }
+    match SIGNATURES.iter().find(|(end, _)| path.ends_with(end)) {
+        Some((_, signature)) if !body.starts_with(*signature) => bail!(
+            "it does not start with the signature {}",
+            String::from_utf8_lossy(*signature)
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a pack ends with the SHA-1 or SHA-256 checksum of its contents, as git writes it.
+pub fn pack_checksum(body: &[u8]) -> bool {
+    let matches = |len: usize, digest: &dyn Fn(&[u8]) -> Vec<u8>| {
+        body.len() > len && digest(&body[..body.len() - len]) == body[body.len() - len..]
+    };
+    matches(20, &|content| Sha1::digest(content).to_vec())
+        || matches(32, &|content| Sha256::digest(content).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_signatures() {
+        let html = b"<html><body>Not Found</body></html>";
+        assert!(binary_file(".git/objects/pack/pack-1234.pack", b"PACK\0\0\0\x02").is_ok());
+        assert!(binary_file(".git/objects/pack/pack-1234.pack", html).is_err());
+        assert!(binary_file(".git/objects/pack/pack-1234.idx", html).is_err());
+        assert!(binary_file(".git/index", b"DIRC\0\0\0\x02").is_ok());
+        assert!(binary_file(".git/index", html).is_err());
+        assert!(binary_file(".git/objects/info/commit-graph", html).is_err());
+        assert!(binary_file(".git/description", html).is_ok());
+    }
+}
@@ -0,0 +1,89 @@
+use color_eyre::Result;
+use rusqlite::Connection;
+use std::collections::HashSet;
+
+/// Above this many object IDs the set moves to a temporary database on disk.
+const MAX_IN_MEMORY: usize = 1_000_000;
+/// How many inserts into the database share one transaction.
+const BATCH_LEN: usize = 10_000;
+
+/// The object IDs seen so far, kept in memory for ordinary repositories and spilled to disk for
+/// huge ones so discovery never holds millions of IDs at once.
+#[derive(Default)]
+pub struct Visited {
+    memory: HashSet<Vec<u8>>,
+    /// A temporary database that SQLite removes once the connection is closed.
+    disk: Option<Connection>,
+    /// The inserts made in the open transaction, committed once there are BATCH_LEN of them. The
+    /// database is thrown away with the connection, so the last batch is never committed.
+    pending: usize,
+}
+
+impl Visited {
+    /// Adds a hex encoded object ID, returning true if it was not seen before.
+    pub fn insert(&mut self, id: &str) -> Result<bool> {
+        let id = hex::decode(id)?;
+        if let Some(disk) = &self.disk {
+            if self.pending == 0 {
+                disk.execute_batch("BEGIN")?;
+            }
+            let inserted = disk
+                .prepare_cached("INSERT OR IGNORE INTO visited (id) VALUES (?1)")?
+                .execute([&id])?;
+            self.pending += 1;
+            if self.pending == BATCH_LEN {
+                disk.execute_batch("COMMIT")?;
+                self.pending = 0;
+            }
+            return Ok(inserted == 1);
+        }
+        if self.memory.contains(&id) {
+            return Ok(false);
+        }
+        if self.memory.len() >= MAX_IN_MEMORY {
+            self.spill()?;
+            return self.insert(&hex::encode(id));
+        }
+        self.memory.insert(id);
+        Ok(true)
+    }
+
+    /// Moves the IDs held in memory to a temporary database.
+    fn spill(&mut self) -> Result<()> {
+        // An empty path opens a private database in a temporary file.
+        let mut disk = Connection::open("")?;
+        disk.execute(
+            "CREATE TABLE visited (id BLOB PRIMARY KEY) WITHOUT ROWID",
+            (),
+        )?;
+        let transaction = disk.transaction()?;
+        {
+            let mut insert = transaction.prepare("INSERT INTO visited (id) VALUES (?1)")?;
+            for id in self.memory.drain() {
+                insert.execute([id])?;
+            }
+        }
+        transaction.commit()?;
+        self.memory.shrink_to_fit();
+        self.disk = Some(disk);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_ids_after_spilling() {
+        let first = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+        let second = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+        let mut visited = Visited::default();
+        assert!(visited.insert(first).unwrap());
+        assert!(!visited.insert(first).unwrap());
+        visited.spill().unwrap();
+        assert!(!visited.insert(first).unwrap());
+        assert!(visited.insert(second).unwrap());
+        assert!(!visited.insert(second).unwrap());
+    }
+}
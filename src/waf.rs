@@ -0,0 +1,188 @@
+use crate::{
+    middleware::{Middleware, Next},
+    transport::{Request, Response},
+};
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use log::warn;
+use reqwest::{header::SERVER, StatusCode};
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use tokio::time::{sleep, Duration};
+
+/// Delay added before every request once a block page was seen, when slowing down is enabled.
+const INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest delay slowing down ever waits before a request.
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// A web application firewall recognized from its block page.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Waf {
+    Cloudflare,
+    Akamai,
+    Imperva,
+    Sucuri,
+    Aws,
+    ModSecurity,
+}
+
+impl Waf {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Waf::Cloudflare => "Cloudflare",
+            Waf::Akamai => "Akamai",
+            Waf::Imperva => "Imperva Incapsula",
+            Waf::Sucuri => "Sucuri",
+            Waf::Aws => "AWS WAF",
+            Waf::ModSecurity => "ModSecurity",
+        }
+    }
+}
+
+/// Returns the firewall whose block page the response is, if any.
+///
+/// Headers only identify the firewall when the request was refused, a site merely served through
+/// a CDN sends them with every response. Challenge pages served with 200 OK are recognized by
+/// their body.
+pub fn detect(response: &Response) -> Option<Waf> {
+    let status = response.status();
+    let refused = matches!(
+        status,
+        StatusCode::FORBIDDEN
+            | StatusCode::NOT_ACCEPTABLE
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::SERVICE_UNAVAILABLE
+    );
+    // Block pages are small, only their start is searched.
+    let head = &response.bytes()[..response.bytes().len().min(16 * 1024)];
+    let body = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let by_body = if body.contains("cf-error-details")
+        || body.contains("attention required! | cloudflare")
+        || body.contains("/cdn-cgi/challenge-platform/")
+    {
+        Some(Waf::Cloudflare)
+    } else if body.contains("access denied") && body.contains("reference&#32;#") {
+        Some(Waf::Akamai)
+    } else if body.contains("incapsula incident id") {
+        Some(Waf::Imperva)
+    } else if body.contains("sucuri website firewall") {
+        Some(Waf::Sucuri)
+    } else if body.contains("mod_security") || body.contains("modsecurity") {
+        Some(Waf::ModSecurity)
+    } else {
+        None
+    };
+    if !refused {
+        return by_body;
+    }
+
+    let headers = response.headers();
+    let server = headers
+        .get(SERVER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    by_body.or(
+        if headers.contains_key("cf-ray") || server == "cloudflare" {
+            Some(Waf::Cloudflare)
+        } else if server.contains("akamaighost") {
+            Some(Waf::Akamai)
+        } else if headers.contains_key("x-iinfo") {
+            Some(Waf::Imperva)
+        } else if headers.contains_key("x-sucuri-id") {
+            Some(Waf::Sucuri)
+        } else if server == "awselb/2.0" || headers.contains_key("x-amzn-waf-action") {
+            Some(Waf::Aws)
+        } else if status == StatusCode::NOT_ACCEPTABLE && body.contains("not acceptable!") {
+            Some(Waf::ModSecurity)
+        } else {
+            None
+        },
+    )
+}
+
+/// Reports every firewall that blocks a request once, and with slowing down enabled waits
+/// before each request, doubling the delay with every block page and halving it again while
+/// requests get through.
+pub struct WafGuard {
+    slow_down: bool,
+    seen: Mutex<BTreeSet<Waf>>,
+    delay: Mutex<Duration>,
+}
+
+impl WafGuard {
+    pub fn new(slow_down: bool) -> Self {
+        WafGuard {
+            slow_down,
+            seen: Mutex::default(),
+            delay: Mutex::default(),
+        }
+    }
+
+    fn record(&self, url: &str, waf: Option<Waf>) {
+        let mut delay = self.delay.lock().unwrap();
+        let Some(waf) = waf else {
+            *delay /= 2;
+            if *delay < INITIAL_DELAY {
+                *delay = Duration::ZERO;
+            }
+            return;
+        };
+        if self.slow_down {
+            *delay = (*delay * 2).clamp(INITIAL_DELAY, MAX_DELAY);
+        }
+        if self.seen.lock().unwrap().insert(waf) {
+            if self.slow_down {
+                warn!("{} blocked {url}, slowing down", waf.name());
+            } else {
+                warn!(
+                    "{} blocked {url}, consider a browser --user-agent, a lower --rate or --waf-slowdown",
+                    waf.name()
+                );
+            }
+        }
+    }
+}
+
+impl Middleware for WafGuard {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            let delay = *self.delay.lock().unwrap();
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+            let url = request.url.to_string();
+            let response = next.run(request).await?;
+            self.record(&url, detect(&response));
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    #[test]
+    fn detects_block_pages() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cf-ray", HeaderValue::from_static("8a1b2c3d4e5f-AMS"));
+        let body = b"<title>Attention Required! | Cloudflare</title>".to_vec();
+        let blocked = Response::new(StatusCode::FORBIDDEN, headers.clone(), body.clone());
+        assert_eq!(detect(&blocked), Some(Waf::Cloudflare));
+        let served = Response::new(
+            StatusCode::OK,
+            headers.clone(),
+            b"ref: refs/heads/master\n".to_vec(),
+        );
+        assert_eq!(detect(&served), None);
+        let challenge = Response::new(StatusCode::OK, headers, body);
+        assert_eq!(detect(&challenge), Some(Waf::Cloudflare));
+
+        let body = b"<h1>Not Acceptable!</h1><p>An appropriate representation of the requested resource could not be found. This error was generated by Mod_Security.</p>";
+        let blocked = Response::new(StatusCode::NOT_ACCEPTABLE, HeaderMap::new(), body.to_vec());
+        assert_eq!(detect(&blocked), Some(Waf::ModSecurity));
+    }
+}
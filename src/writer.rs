@@ -0,0 +1,223 @@
+use crate::{
+    download::partial_path,
+    events::{Event, Events},
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::{
+    fs,
+    sync::{mpsc, watch},
+};
+
+/// Number of tasks writing files at once.
+pub const WORKERS: usize = 4;
+
+/// Number of files that may wait to be written before the downloads pause.
+pub const QUEUE_LEN: usize = 256;
+
+/// Most files a worker takes from the queue at once, creating their directories together.
+const BATCH: usize = 32;
+
+/// A file waiting to be written.
+struct Job {
+    /// The href the file was downloaded from.
+    href: String,
+    /// Where the file is written, relative to the output directory.
+    local: String,
+    path: PathBuf,
+    body: Vec<u8>,
+}
+
+/// A file that could not be written.
+pub struct Failure {
+    pub href: String,
+    pub local: String,
+    pub source: std::io::Error,
+}
+
+/// State shared by the writer and its workers.
+struct Shared {
+    /// Files queued and not yet written.
+    pending: watch::Sender<usize>,
+    failures: Mutex<Vec<Failure>>,
+    events: Arc<Events>,
+    /// Directories created for the files written so far, which are not created again.
+    directories: Mutex<HashSet<PathBuf>>,
+    /// Locks of the files being written. Different hrefs may be written to the same path, such
+    /// as a ref found in both packed-refs and a listing, and two workers writing its temporary
+    /// file at once would leave it interleaved.
+    locks: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl Shared {
+    /// Returns the lock of path, shared with every other job writing to it.
+    fn lock(&self, path: &Path) -> Arc<tokio::sync::Mutex<()>> {
+        self.locks
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone()
+    }
+
+    /// Forgets the lock of path once no other job holds it.
+    fn unlock(&self, path: &Path, lock: Arc<tokio::sync::Mutex<()>>) {
+        let mut locks = self.locks.lock().unwrap();
+        // One reference is the map's and one is ours.
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(path);
+        }
+    }
+}
+
+/// Writes downloaded files on a pool of tasks, so that slow disks and network filesystems do not
+/// hold up the downloads. Files are queued with [`Writer::write`] and are only sure to be on disk
+/// once [`Writer::flush`] returns.
+pub struct Writer {
+    /// Started on the first write, since writers are created outside of the runtime.
+    sender: OnceLock<mpsc::Sender<Job>>,
+    shared: Arc<Shared>,
+}
+
+impl Writer {
+    pub fn new(events: Arc<Events>) -> Self {
+        Writer {
+            sender: OnceLock::new(),
+            shared: Arc::new(Shared {
+                pending: watch::channel(0).0,
+                failures: Mutex::default(),
+                events,
+                directories: Mutex::default(),
+                locks: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Returns the sender of the queue, starting the workers if they are not running yet.
+    fn sender(&self) -> &mpsc::Sender<Job> {
+        self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel(QUEUE_LEN);
+            let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+            for _ in 0..WORKERS {
+                tokio::spawn(work(receiver.clone(), self.shared.clone()));
+            }
+            sender
+        })
+    }
+
+    /// Queues body to be written to path, the local name of href, waiting while the queue is full.
+    pub async fn write(&self, href: &str, local: String, path: PathBuf, body: Vec<u8>) {
+        self.shared.pending.send_modify(|pending| *pending += 1);
+        let job = Job {
+            href: href.to_string(),
+            local,
+            path,
+            body,
+        };
+        // The workers only stop once the writer is dropped.
+        if self.sender().send(job).await.is_err() {
+            self.shared.pending.send_modify(|pending| *pending -= 1);
+        }
+    }
+
+    /// Waits until every queued file is written, returning those that could not be.
+    pub async fn flush(&self) -> Vec<Failure> {
+        let mut pending = self.shared.pending.subscribe();
+        let _ = pending.wait_for(|&pending| pending == 0).await;
+        std::mem::take(&mut *self.shared.failures.lock().unwrap())
+    }
+}
+
+/// Writes the files of the queue until the writer is dropped.
+async fn work(receiver: Arc<tokio::sync::Mutex<mpsc::Receiver<Job>>>, shared: Arc<Shared>) {
+    loop {
+        let mut jobs = Vec::with_capacity(BATCH);
+        {
+            let mut receiver = receiver.lock().await;
+            let Some(job) = receiver.recv().await else {
+                return;
+            };
+            jobs.push(job);
+            while jobs.len() < BATCH {
+                match receiver.try_recv() {
+                    Ok(job) => jobs.push(job),
+                    Err(_) => break,
+                }
+            }
+        }
+        let count = jobs.len();
+        write_batch(jobs, &shared).await;
+        shared.pending.send_modify(|pending| *pending -= count);
+    }
+}
+
+/// Creates the directories of a batch of files that were not created before, then writes the
+/// files. Each file is written to a temporary file first and renamed into place, so a crash
+/// mid-write never leaves a truncated file at the final path.
+async fn write_batch(jobs: Vec<Job>, shared: &Shared) {
+    let parents: BTreeSet<PathBuf> = {
+        let directories = shared.directories.lock().unwrap();
+        jobs.iter()
+            .filter_map(|job| job.path.parent())
+            .filter(|parent| !directories.contains(*parent))
+            .map(PathBuf::from)
+            .collect()
+    };
+    for parent in parents {
+        // A directory that cannot be created fails the writes of its files below.
+        if fs::create_dir_all(&parent).await.is_ok() {
+            shared.directories.lock().unwrap().insert(parent);
+        }
+    }
+    for job in jobs {
+        let lock = shared.lock(&job.path);
+        let write = async {
+            if job.path.parent().is_none() {
+                return Err(std::io::Error::other("parent directory unavailable"));
+            }
+            let _guard = lock.lock().await;
+            let part = partial_path(&job.path);
+            fs::write(&part, &job.body).await?;
+            fs::rename(&part, &job.path).await
+        };
+        let written = write.await;
+        shared.unlock(&job.path, lock);
+        match written {
+            Ok(()) => shared.events.emit(Event::Written {
+                path: job.local,
+                bytes: job.body.len(),
+            }),
+            Err(source) => shared.failures.lock().unwrap().push(Failure {
+                href: job.href,
+                local: job.local,
+                source,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn writes_same_path_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("packed-refs");
+        let writer = Writer::new(Arc::default());
+        let bodies: Vec<Vec<u8>> = (1..=64)
+            .map(|len| vec![b'a' + len as u8 % 26; len * 1024])
+            .collect();
+        for (i, body) in bodies.iter().enumerate() {
+            let href = format!(".git/packed-refs?{i}");
+            writer
+                .write(&href, href.clone(), path.clone(), body.clone())
+                .await;
+        }
+        assert!(writer.flush().await.is_empty());
+        let written = std::fs::read(&path).unwrap();
+        assert!(bodies.contains(&written));
+        assert!(writer.shared.locks.lock().unwrap().is_empty());
+    }
+}